@@ -0,0 +1,47 @@
+/* Generated with cbindgen from src/ffi.rs (see cbindgen.toml), do not edit by hand */
+
+#ifndef WOWLOGS_PARSER_H
+#define WOWLOGS_PARSER_H
+
+#include <stdbool.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/**
+ * Opaque parser handle
+ */
+typedef struct WowlogsParser WowlogsParser;
+
+/**
+ * Opens a combat log file. Returns null if the file can't be opened.
+ */
+WowlogsParser *wowlogs_parser_open(const char *path);
+
+/**
+ * Creates a parser fed with `wowlogs_parser_push`
+ */
+WowlogsParser *wowlogs_parser_new(void);
+
+/**
+ * Adds text to a parser made with `wowlogs_parser_new`. Complete lines become available from `wowlogs_parser_next_event_json`.
+ * Returns false if the parser reads from a file, or the text isn't valid UTF-8.
+ */
+bool wowlogs_parser_push(WowlogsParser *parser, const char *chunk);
+
+/**
+ * The next event as JSON, `{"event": ...}` or `{"error": ...}` for lines which failed to parse.
+ * Returns null once there are no more lines, for now. Free the string with `wowlogs_string_free`.
+ */
+char *wowlogs_parser_next_event_json(WowlogsParser *parser);
+
+void wowlogs_parser_free(WowlogsParser *parser);
+
+void wowlogs_string_free(char *s);
+
+#ifdef __cplusplus
+}  /* extern "C" */
+#endif
+
+#endif  /* WOWLOGS_PARSER_H */
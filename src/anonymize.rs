@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+
+/// Rewrites player names, realms & GUIDs to pseudonyms, eg. `"Sønike-Ysondre"` to `"Player1-Realm1"`.
+/// The same player gets the same pseudonym everywhere in the log, so it still parses & reads the same.
+#[derive(Debug)]
+pub struct Anonymizer {
+    /// A player GUID followed by their name, as in the source & target fields
+    named_guid: Regex,
+    guid: Regex,
+    quoted: Regex,
+    guids: HashMap<String, String>,
+    servers: HashMap<String, usize>,
+    names: HashMap<String, String>,
+    realms: HashMap<String, usize>,
+}
+
+impl Anonymizer {
+    pub fn new() -> Self {
+        Self {
+            named_guid: Regex::new(r#"(Player-\d+-[0-9A-F]+),"([^"]+)""#).unwrap(),
+            guid: Regex::new(r"Player-(\d+)-([0-9A-F]+)").unwrap(),
+            quoted: Regex::new(r#""([^"]*)""#).unwrap(),
+            guids: HashMap::new(),
+            servers: HashMap::new(),
+            names: HashMap::new(),
+            realms: HashMap::new(),
+        }
+    }
+
+    /// Assigns pseudonyms to the players on a line
+    pub fn learn(&mut self, line: &str) {
+        for caps in self.guid.captures_iter(line) {
+            if self.guids.contains_key(&caps[0]) { continue; }
+            let next = self.servers.len() + 1;
+            let server = *self.servers.entry(caps[1].to_string()).or_insert(next);
+            let guid = format!("Player-{}-{:08X}", server, self.guids.len() + 1);
+            self.guids.insert(caps[0].to_string(), guid);
+        }
+
+        for caps in self.named_guid.captures_iter(line) {
+            let name = &caps[2];
+            if self.names.contains_key(name) { continue; }
+            let pseudonym = match name.split_once('-') {
+                Some((_, realm)) => {
+                    let next = self.realms.len() + 1;
+                    let realm = *self.realms.entry(realm.to_string()).or_insert(next);
+                    format!("Player{}-Realm{}", self.names.len() + 1, realm)
+                }
+                None => format!("Player{}", self.names.len() + 1),
+            };
+            self.names.insert(name.to_string(), pseudonym);
+        }
+    }
+
+    /// Replaces every known player GUID & name on a line
+    pub fn rewrite(&self, line: &str) -> String {
+        let line = self.guid.replace_all(line, |caps: &Captures| {
+            self.guids.get(&caps[0]).cloned().unwrap_or_else(|| caps[0].to_string())
+        });
+        self.quoted.replace_all(&line, |caps: &Captures| match self.names.get(&caps[1]) {
+            Some(name) => format!("\"{}\"", name),
+            None => caps[0].to_string(),
+        }).into_owned()
+    }
+}
+
+/// Writes an anonymized copy of the log. Players are learnt up front so that names appearing before
+/// the player's first event are replaced too.
+pub fn anonymize(log: &Path, out_path: &Path) -> Result<usize> {
+    let open = || File::open(log).with_context(|| format!("Failed to open file: {:?}", log));
+
+    let mut anonymizer = Anonymizer::new();
+    for line in BufReader::new(open()?).lines() {
+        anonymizer.learn(&line?);
+    }
+
+    let mut out = BufWriter::new(File::create(out_path)
+        .with_context(|| format!("Failed to create file: {:?}", out_path))?);
+    for line in BufReader::new(open()?).lines() {
+        writeln!(out, "{}", anonymizer.rewrite(&line?))?;
+    }
+    out.flush()?;
+
+    Ok(anonymizer.names.len())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::anonymize::Anonymizer;
+    use crate::parser::EventParser;
+
+    #[test]
+    fn anonymize() {
+        let log = "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:02:08.000  SPELL_HEAL,Player-1335-0A264B4D,\"Healer-Ysondre\",0x514,0x0,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,774,\"Rejuvenation\",0x8,Player-1335-0A264B4C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2549,0.0000,72,1000,1000,0,0,nil\n\
+4/6 14:02:09.000  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Player-1402-0A1B2C3D,\"Other-Kazzak\",0x514,0x0,0\n";
+
+        let mut anonymizer = Anonymizer::new();
+        log.lines().for_each(|l| anonymizer.learn(l));
+        let rewritten = log.lines().map(|l| anonymizer.rewrite(l)).collect::<Vec<_>>();
+
+        assert_eq!(rewritten[0], log.lines().next().unwrap());
+        assert_eq!(rewritten[1], "4/6 14:02:07.362  SWING_MISSED,Player-1-00000001,\"Player1-Realm1\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1");
+        assert!(rewritten[2].contains("Player-1-00000002,\"Player2-Realm1\",0x514,0x0,Player-1-00000001,\"Player1-Realm1\""));
+        assert!(rewritten[3].contains("Player-2-00000003,\"Player3-Realm2\""));
+
+        let text = rewritten.join("\n");
+        for secret in ["Sønike", "Ysondre", "Kazzak", "0A264B4C", "1335"] {
+            assert!(!text.contains(secret), "{} leaked", secret);
+        }
+        assert!(EventParser::new(text.as_bytes()).all(|e| e.is_ok()));
+    }
+}
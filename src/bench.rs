@@ -0,0 +1,68 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{ensure, Result};
+
+use crate::parser::EventParser;
+
+/// A small, representative slice of a retail raid log
+const REFERENCE_LOG: &str = include_str!("../resources/bench_reference.txt");
+
+#[derive(Debug)]
+pub struct BenchResult {
+    pub lines: usize,
+    pub failures: usize,
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    pub fn lines_per_sec(&self) -> f64 {
+        self.lines as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Parses the bundled reference log `repeats` times over and measures throughput
+pub fn run(repeats: usize) -> BenchResult {
+    let log = REFERENCE_LOG.repeat(repeats);
+
+    let start = Instant::now();
+    let (lines, failures) = EventParser::new(log.as_bytes())
+        .fold((0, 0), |(lines, failures), e| (lines + 1, failures + e.is_err() as usize));
+    let elapsed = start.elapsed();
+
+    BenchResult { lines, failures, elapsed }
+}
+
+/// Runs the benchmark, failing if throughput is below the given lines/sec
+pub fn assert_throughput(repeats: usize, min_throughput: Option<f64>) -> Result<BenchResult> {
+    let result = run(repeats);
+
+    println!("Parsed {} lines ({} failed) in {:.3}s: {:.0} lines/sec",
+             result.lines, result.failures, result.elapsed.as_secs_f64(), result.lines_per_sec());
+
+    ensure!(result.failures == 0, "Reference log should parse cleanly, got {} failures", result.failures);
+    if let Some(min) = min_throughput {
+        ensure!(result.lines_per_sec() >= min,
+            "Throughput regression: {:.0} lines/sec is below the minimum of {:.0}", result.lines_per_sec(), min);
+    }
+
+    Ok(result)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::bench::{assert_throughput, run};
+
+    #[test]
+    fn reference_log_parses() {
+        let result = run(2);
+        assert_eq!(result.failures, 0);
+        assert_eq!(result.lines, 2 * super::REFERENCE_LOG.lines().count());
+    }
+
+    #[test]
+    fn impossible_threshold_fails() {
+        assert!(assert_throughput(1, Some(f64::MAX)).is_err());
+        assert!(assert_throughput(1, None).is_ok());
+    }
+}
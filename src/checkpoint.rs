@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::components::format::LogFormat;
+use crate::index::first_line;
+
+/// How far through a log a run got
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    /// Used to spot when the log has been replaced by a different one
+    pub first_line: String,
+    /// Start of the first line not yet read
+    pub byte_offset: u64,
+    pub last_timestamp: Option<NaiveDateTime>,
+    /// Layout of the log at the offset, as the header is behind it
+    pub format: LogFormat,
+}
+
+/// How far through each log previous runs got, so a restarted run picks up where the last one stopped
+/// rather than reading the log again & writing its events out twice. Kept as a JSON file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Checkpoints {
+    #[serde(skip)]
+    path: PathBuf,
+    logs: BTreeMap<PathBuf, Position>,
+}
+
+fn key(log: &Path) -> PathBuf {
+    log.canonicalize().unwrap_or_else(|_| log.to_path_buf())
+}
+
+impl Checkpoints {
+    /// Reads the checkpoint file, or starts a new one if it doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut checkpoints = match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str::<Self>(&json)
+                .with_context(|| format!("Failed to read checkpoints: {:?}", path))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to open file: {:?}", path)),
+        };
+        checkpoints.path = path.to_path_buf();
+        Ok(checkpoints)
+    }
+
+    /// Where to pick the log up from, unless it's new, or has been replaced or truncated since
+    pub fn resume(&self, log: &Path) -> Option<&Position> {
+        let position = self.logs.get(&key(log))?;
+        let len = log.metadata().ok()?.len();
+        let first = first_line(log).ok()?;
+        (position.byte_offset <= len && position.first_line == first).then_some(position)
+    }
+
+    /// Records how far through the log has been read, keeping the last timestamp if nothing new was read
+    pub fn record(&mut self, log: &Path, byte_offset: u64, last_timestamp: Option<NaiveDateTime>, format: LogFormat) -> Result<()> {
+        let first_line = first_line(log)
+            .with_context(|| format!("Failed to open file: {:?}", log))?;
+        let previous = self.logs.get(&key(log)).filter(|p| p.first_line == first_line);
+        let last_timestamp = last_timestamp.or(previous.and_then(|p| p.last_timestamp));
+
+        self.logs.insert(key(log), Position { first_line, byte_offset, last_timestamp, format });
+        Ok(())
+    }
+
+    /// Writes the checkpoint file, replacing it whole so a crash mid-write doesn't lose it
+    pub fn save(&self) -> Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write file: {:?}", tmp))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to write file: {:?}", self.path))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::checkpoint::Checkpoints;
+    use crate::components::format::{LogFormat, PROJECT_WRATH_CLASSIC};
+
+    #[test]
+    fn resume() {
+        let dir = std::env::temp_dir().join("wowlogs_checkpoints");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("WoWCombatLog.txt");
+        let path = dir.join("checkpoints.json");
+        std::fs::write(&log, "first line\nsecond line\n").unwrap();
+
+        let format = LogFormat::new(9, PROJECT_WRATH_CLASSIC, true);
+        let mut checkpoints = Checkpoints::load(&path).unwrap();
+        assert!(checkpoints.resume(&log).is_none());
+        checkpoints.record(&log, 11, None, format).unwrap();
+        checkpoints.save().unwrap();
+
+        let checkpoints = Checkpoints::load(&path).unwrap();
+        let position = checkpoints.resume(&log).unwrap();
+        assert_eq!((position.byte_offset, position.format), (11, format));
+
+        // A new log with the same name starts again
+        std::fs::write(&log, "other line\n").unwrap();
+        assert!(checkpoints.resume(&log).is_none());
+    }
+}
@@ -1,19 +1,158 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDateTime;
 use clap::{Parser, Subcommand, ValueEnum};
 
+use crate::components::format::GameVersion;
+use crate::consumers::PeriodicMode;
+use crate::consumers::timeseries::SeriesFormat;
+use crate::schema::SchemaFormat;
+use crate::summary::SummaryFormat;
+
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None, subcommand_value_name = "OUTPUT_MODE", subcommand_help_heading = "Output modes")]
+#[command(version, about, long_about = None, subcommand_value_name = "OUTPUT_MODE", subcommand_help_heading = "Output modes", subcommand_negates_reqs = true)]
 pub struct Cli {
-    /// Path to wow log file
-    pub wowlog_path: PathBuf,
+    /// Path to wow log file, or a glob of them like `Logs/WoWCombatLog-*.txt`.
+    /// Process mode takes several logs, or a Logs directory for all of them, reporting on each log & on all of them together.
+    /// In watch mode this can be the Logs directory to follow the newest log
+    #[arg(required = true, num_args = 1..)]
+    pub wowlog_paths: Vec<PathBuf>,
+
+    #[arg(value_enum, required = true)]
+    pub read_mode: Option<ReadMode>,
+
+    /// Trackers to run over the events
+    #[arg(long = "tracker", value_enum, default_values_t = [Tracker::Damage])]
+    pub trackers: Vec<Tracker>,
+
+    /// Spell breakdowns: merge periodic ticks into their spell, or list them separately
+    #[arg(long, value_enum, default_value_t = PeriodicMode::Merge)]
+    pub periodic: PeriodicMode,
+
+    /// Damage & healing trackers: reference data for percentile estimates, as `encounter_id,spec_id,metric,percentile,value` lines.
+    /// Parses are only shown when this is given
+    #[arg(long)]
+    pub percentile_reference: Option<PathBuf>,
+
+    /// Damage tracker: NPC IDs whose damage is reported as priority damage, eg. "210231,211904"
+    #[arg(long = "priority-target", value_delimiter = ',')]
+    pub priority_targets: Vec<u64>,
+
+    /// Deaths tracker: TOML file of mechanic names by spell ID, used to classify deaths
+    #[arg(long)]
+    pub mechanic_rules: Option<PathBuf>,
+
+    /// Avoidable tracker: TOML file of avoidable spell IDs per raid tier
+    #[arg(long)]
+    pub avoidable_rules: Option<PathBuf>,
+
+    /// Cooldowns tracker: TOML file of cooldown spell IDs & their cooldowns
+    #[arg(long)]
+    pub cooldown_rules: Option<PathBuf>,
+
+    /// TOML file of the boss casts & deaths starting each encounter's phases, for per-phase breakdowns
+    #[arg(long)]
+    pub phase_rules: Option<PathBuf>,
+
+    /// Gear tracker: file of `item_id,name[,set_id[,sockets]]` lines used to name items & count tier set pieces
+    #[arg(long)]
+    pub item_db: Option<PathBuf>,
+
+    /// Mythic+ tracker: timer penalty per death in seconds, instead of the one for the log's game version
+    #[arg(long)]
+    pub death_penalty_secs: Option<i64>,
+
+    /// Add each completed Mythic+ key to this file, for the mplus-history report
+    #[arg(long)]
+    pub key_history: Option<PathBuf>,
+
+    /// Process mode & queries: only parse the selected encounter, by pull number (from 1) or boss name.
+    /// An index of encounters is saved next to the log to speed up later runs, see the index output mode
+    #[arg(long)]
+    pub encounter: Option<String>,
+
+    /// Pick each log up where the last run with this checkpoint file stopped, saving how far it got as lines are read.
+    /// Stops a restarted run reading a log again & writing its events out twice. Once the checkpoint file exists,
+    /// json & sqlite outs are added to rather than replaced
+    #[arg(long)]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Parse the log with the layout of this game version, instead of detecting it from the log's header
+    #[arg(long, value_enum)]
+    pub game_version: Option<GameVersion>,
+
+    /// Keep events with unknown types, eg. ones added by a new patch, instead of failing them
+    #[arg(long)]
+    pub lenient: bool,
+
+    /// Fail events with more or fewer fields than expected, to spot events whose layout changed in a patch
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Keep events cut short, eg. by the game crashing mid write, with whatever of them could be parsed
+    #[arg(long)]
+    pub recover: bool,
+
+    /// Only pass events of these players & their pets to the trackers, eg. "Sønike" or "Sønike-Ysondre,Stillnixx"
+    #[arg(long = "player", value_delimiter = ',')]
+    pub players: Vec<String>,
+
+    /// Split combat outside of encounters (eg. dungeon trash) into pulls, each ending after this many seconds without damage
+    #[arg(long)]
+    pub combat_gap_secs: Option<i64>,
+
+    /// Watch mode: forget actors not seen for this many minutes (outside of encounters)
+    #[arg(long)]
+    pub prune_idle_minutes: Option<i64>,
 
-    #[arg(value_enum)]
-    pub read_mode: ReadMode,
+    /// Watch mode: maximum number of actors held across all trackers (outside of encounters)
+    #[arg(long)]
+    pub max_tracked_actors: Option<usize>,
+
+    /// Watch mode: shell command run when a pull starts, with the encounter details in WOW_* environment variables
+    #[arg(long)]
+    pub on_pull_start: Option<String>,
+
+    /// Watch mode: shell command run when a pull ends, with the encounter details & result in WOW_* environment variables
+    #[arg(long)]
+    pub on_pull_end: Option<String>,
+
+    /// Watch mode: http:// or https:// URL sent a JSON POST when a pull starts or ends
+    #[arg(long)]
+    pub pull_webhook: Option<String>,
+
+    /// TOML file of alert rules, eg. a tank hit hard with no defensive up. Watch mode fires them as they happen
+    /// with a message, sound, command or webhook, otherwise they're listed in the summary
+    #[arg(long)]
+    pub alert_rules: Option<PathBuf>,
+
+    /// Show a single, continuously updated status line instead of the full tracker tables
+    #[arg(long)]
+    pub status_line: bool,
+
+    /// Watch mode: show the trackers at most once every this many seconds, eg. 0.5, instead of after every write to the log
+    #[arg(long, value_parser = parse_secs)]
+    pub refresh_interval: Option<Duration>,
+
+    /// Watch mode: clear the terminal before showing the trackers, so they update in place like a meter instead of scrolling
+    #[arg(long)]
+    pub redraw: bool,
+
+    /// Format of the tracker results
+    #[arg(long, value_enum, default_value_t = SummaryFormat::Plain)]
+    pub summary_format: SummaryFormat,
+
+    /// Where the parsed events go, alongside or instead of the output mode. Can be given several times, eg.
+    /// `--out std --out json:events.jsonl --out sqlite:raid.db`. One of std, none, json:PATH, sqlite:PATH, html:PATH
+    /// or file:GOOD_PATH,FAILED_PATH
+    #[arg(long = "out", value_parser = Sink::parse)]
+    pub outs: Vec<Sink>,
 
     /// Output mode
     #[command(subcommand)]
-    pub output_mode: OutputMode,
+    pub output_mode: Option<OutputMode>,
 
 }
 
@@ -25,6 +164,96 @@ pub enum ReadMode {
     Process,
 }
 
+#[derive(Debug, ValueEnum, Clone, Copy, PartialEq)]
+pub enum Tracker {
+    /// Total damage & DPS per player
+    Damage,
+    /// Healing per player & spell
+    Healing,
+    /// Damage per player & ability, with hits, crit rate, average hit & share of the player's damage
+    Abilities,
+    /// Each ability's min, max & mean hit, crit / glancing / crushing rates & resisted, blocked & absorbed damage
+    Hits,
+    /// Overhealing, shield absorbs & shields that expired unused per healer, ranked by effective healing
+    Overheal,
+    /// Player deaths per boss, classified by mechanic
+    Deaths,
+    /// When each actor first & last appeared, and pet summons
+    Timeline,
+    /// Add spawn waves per pull
+    Adds,
+    /// Mythic+ runs & the time lost to deaths
+    MythicPlus,
+    /// Deaths, battle res use & charges left over each Mythic+ key & raid boss pull
+    BattleRes,
+    /// Distance moved, time spent moving vs standing & average range to the boss per player, per pull
+    Movement,
+    /// Estimated threat table per enemy (Classic)
+    Threat,
+    /// Avoidable damage & friendly fire taken per player, per pull
+    Avoidable,
+    /// Uptime of each player's totems, guardians & other summons
+    Summons,
+    /// Resources generated, wasted by overcapping, spent, drained & leeched, per player & power type
+    Energize,
+    /// Each player's item level, tier set pieces & missing enchants / gems
+    Gear,
+    /// Arena match results, ratings & damage per player
+    Pvp,
+    /// Events per type & per actor, parse failures & the time span covered, as a first look at a log
+    Stats,
+    /// Raid warnings emoted by bosses, timed from the start of each pull
+    Emotes,
+    /// Uses of major cooldowns per pull, flagging ones never used or used fewer times than possible
+    Cooldowns,
+    /// Bursts of damage taken by tanks & the defensives they had up, flagging unmitigated tank busters
+    TankSpikes,
+    /// Damage the Augmentation Evoker's buffs added to other players, credited back to the Evoker for true DPS
+    Support,
+}
+
+fn parse_secs(s: &str) -> Result<Duration> {
+    s.parse::<f64>().ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .with_context(|| format!("Expected a number of seconds, eg. 0.5, got {:?}", s))
+}
+
+/// An output which events are streamed to, given with `--out`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sink {
+    /// Prints to stdout, failures to stderr
+    Std,
+    None,
+    /// Each event or failure as a JSON line
+    Json(PathBuf),
+    /// Events & failures as rows of an SQLite database, replacing any from before unless resuming with `--checkpoint`
+    Sqlite(PathBuf),
+    Html(PathBuf),
+    File { good_path: PathBuf, failed_path: PathBuf },
+}
+
+impl Sink {
+    pub fn parse(s: &str) -> Result<Self> {
+        let (kind, path) = s.split_once(':').map_or((s, None), |(kind, path)| (kind, Some(PathBuf::from(path))));
+        let path = || path.clone().with_context(|| format!("Expected a path, eg. \"{}:out.txt\"", kind));
+
+        Ok(match kind {
+            "std" => Self::Std,
+            "none" => Self::None,
+            "json" => Self::Json(path()?),
+            "sqlite" => Self::Sqlite(path()?),
+            "html" => Self::Html(path()?),
+            "file" => {
+                let paths = path()?;
+                let (good, failed) = paths.to_str().and_then(|p| p.split_once(','))
+                    .context("Expected a good & a failed path, eg. \"file:good.txt,failed.txt\"")?;
+                Self::File { good_path: good.into(), failed_path: failed.into() }
+            }
+            _ => bail!("Unknown sink {:?}, expected one of std, none, json, sqlite, html or file", kind),
+        })
+    }
+}
+
 #[derive(Debug, Subcommand)]
 pub enum OutputMode {
     /// Prints to stdin / stdout
@@ -40,14 +269,173 @@ pub enum OutputMode {
 
     /// Do nothing
     None,
+
+    /// Write a standalone HTML report with charts for each encounter
+    Html {
+        /// HTML file to write
+        path: PathBuf,
+    },
+
+    /// Export per player damage & healing for each second of each encounter, for plotting
+    Timeseries {
+        /// File to write
+        path: PathBuf,
+        #[arg(long, value_enum, default_value_t = SeriesFormat::Csv)]
+        format: SeriesFormat,
+    },
+
+    /// Write each pull's lines to its own combat log file, named by boss & start time, eg. to share a single pull
+    SplitFiles {
+        /// Directory to write the files to
+        dir: PathBuf,
+        /// Also write each pull's parsed events as JSON lines
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Copy the raw lines between two times, of a boss's pulls, and/or involving a player to a new combat log
+    Extract {
+        /// File to write the extracted log to
+        output: PathBuf,
+        /// Keep lines from this time on, as written in the log eg. "4/6 14:01:05"
+        #[arg(long, value_parser = crate::extract::parse_log_time)]
+        from: Option<NaiveDateTime>,
+        /// Keep lines up to this time
+        #[arg(long, value_parser = crate::extract::parse_log_time)]
+        to: Option<NaiveDateTime>,
+        /// Keep only pulls of this boss, or the Nth pull
+        #[arg(long)]
+        boss: Option<String>,
+        /// Keep only events this player is the source or target of, eg. "Sønike" or "Sønike-Ysondre"
+        #[arg(long)]
+        player: Option<String>,
+    },
+
+    /// Write a copy of the log with player names, realms & GUIDs replaced by consistent pseudonyms, eg. to share it publicly
+    Anonymize {
+        /// File to write the anonymized log to
+        output: PathBuf,
+    },
+
+    /// Answer a SQL-like query over the log's damage, healing or events table, eg.
+    /// "SELECT source, SUM(amount) FROM damage WHERE target = 'Fyrakk' GROUP BY source ORDER BY SUM(amount) DESC"
+    Query {
+        /// SELECT .. FROM damage|healing|events [WHERE ..] [GROUP BY ..] [ORDER BY .. [DESC]] [LIMIT n]
+        sql: String,
+    },
+
+    /// Index the log's pulls, zone changes & headers by byte offset & list them. The index is saved next to the log,
+    /// for --encounter, query, extract & split-files to seek straight to pulls. Later runs only index newly written lines
+    Index,
+
+    /// Parse the log & group its failures by event type & problem, eg. to spot events changed by a new build.
+    /// Use --strict to also catch events which gained or lost fields
+    Diagnose,
+
+    /// Compare the pulls of each boss: boss health left, duration & phase reached per pull, the best pull,
+    /// & each player's DPS over the pulls. Phases need --phase-rules
+    Compare,
+
+    /// Keep text files of the pull's DPS leaderboard, a player's DPS & the boss's health up to date, for OBS text sources
+    Obs {
+        /// Directory to write the files to
+        dir: PathBuf,
+        /// Player to write personal.txt for, eg. "Sønike" or "Sønike-Ysondre"
+        #[arg(long)]
+        player: Option<String>,
+        /// Number of players on the leaderboard
+        #[arg(long, default_value_t = 5)]
+        top: usize,
+        /// Leaderboard line, with {rank}, {name}, {dps} & {damage}
+        #[arg(long, default_value = "{rank}. {name} {dps}")]
+        row_template: String,
+        /// personal.txt, with {name}, {dps} & {damage}
+        #[arg(long, default_value = "{name}: {dps} DPS")]
+        personal_template: String,
+        /// boss.txt, with {boss} & {health}
+        #[arg(long, default_value = "{boss} {health}%")]
+        boss_template: String,
+    },
+
+    /// Upload the log to a report service as gzipped chunks, eg. a self-hosted log analyser
+    Upload {
+        /// Endpoint to POST each chunk to. A token is only sent over https://, or plain http:// to this machine
+        url: String,
+        /// API token, sent as a bearer token
+        #[arg(long)]
+        token: Option<String>,
+        /// Size of each chunk before compression, in MB
+        #[arg(long, default_value_t = 16)]
+        chunk_mb: usize,
+    },
+
+    /// Broadcast events & tracker snapshots as JSON to WebSocket clients, eg. stream overlays
+    Serve {
+        /// Port to listen on
+        #[arg(long, default_value_t = 9001)]
+        port: u16,
+    },
+
+    /// Serve per encounter stats as a JSON HTTP API, eg. for external dashboards
+    ServeHttp {
+        /// Port to listen on
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+    },
+
+    /// Benchmark parsing throughput on a bundled reference log
+    Bench {
+        /// Number of times to repeat the reference log
+        #[arg(long, default_value_t = 5000)]
+        repeats: usize,
+        /// Exit with a failure if throughput is below this many lines/sec
+        #[arg(long)]
+        assert_min_throughput: Option<f64>,
+    },
+
+    /// Convert a Warcraft Logs API event export into a combat log
+    WclImport {
+        /// JSON export of a report's events
+        input: PathBuf,
+        /// Combat log file to write
+        output: PathBuf,
+    },
+
+    /// Print a reference of every supported event & its fields
+    Schema {
+        #[arg(long, value_enum, default_value_t = SchemaFormat::Markdown)]
+        format: SchemaFormat,
+    },
+
+    /// Estimate each character's Mythic+ score over the season from a key history file
+    MplusHistory {
+        /// Key history file, written with --key-history
+        path: PathBuf,
+    },
+
+    /// Re-parse the failed lines file written by the file output mode, reporting which lines now parse
+    Retry {
+        /// Failed lines file
+        failed_path: PathBuf,
+        /// Write the lines which still fail here, in the same format
+        #[arg(long)]
+        remaining: Option<PathBuf>,
+    },
 }
 
 
 #[cfg(test)]
 mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
     use clap::Parser;
 
-    use crate::cli::Cli;
+    use crate::cli::{Cli, OutputMode, ReadMode, Sink, Tracker};
+    use crate::components::format::GameVersion;
+    use crate::consumers::timeseries::SeriesFormat;
+    use crate::schema::SchemaFormat;
+    use crate::summary::SummaryFormat;
 
     #[test]
     fn test_help() {
@@ -66,4 +454,225 @@ mod tests {
         let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "watch", "file", "good.txt", "bad.txt"]);
         println!("{:?}", args);
     }
+
+    #[test]
+    fn test_combat_gap() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--combat-gap-secs", "8", "none"]);
+        assert_eq!(args.combat_gap_secs, Some(8));
+    }
+
+    #[test]
+    fn test_game_version() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--game-version", "classic-era", "none"]);
+        assert_eq!(args.game_version, Some(GameVersion::ClassicEra));
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--game-version", "11.0", "none"]);
+        assert_eq!(args.game_version, Some(GameVersion::TheWarWithin));
+        assert!(!args.lenient);
+    }
+
+    #[test]
+    fn test_lenient() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--lenient", "none"]);
+        assert!(args.lenient && !args.strict);
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--strict", "none"]);
+        assert!(args.strict && !args.recover);
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--recover", "none"]);
+        assert!(args.recover);
+    }
+
+    #[test]
+    fn test_split_files() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "split-files", "pulls", "--json"]);
+        assert!(matches!(args.output_mode, Some(OutputMode::SplitFiles { dir, json: true }) if dir.to_str() == Some("pulls")));
+    }
+
+    #[test]
+    fn test_extract() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "extract", "out.txt", "--boss", "Gnarlroot", "--from", "4/6 14:01:05"]);
+        assert!(matches!(args.output_mode, Some(OutputMode::Extract { boss: Some(b), from: Some(_), to: None, .. }) if b == "Gnarlroot"));
+        assert!(Cli::try_parse_from(vec!["wowlogs.exe", "logs.txt", "process", "extract", "out.txt", "--to", "soon"]).is_err());
+    }
+
+    #[test]
+    fn test_query() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "query", "SELECT COUNT(*) FROM damage"]);
+        assert!(matches!(args.output_mode, Some(OutputMode::Query { sql }) if sql == "SELECT COUNT(*) FROM damage"));
+    }
+
+    #[test]
+    fn test_priority_targets() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--priority-target", "210231,211904", "none"]);
+        assert_eq!(args.priority_targets, [210231, 211904]);
+    }
+
+    #[test]
+    fn test_players() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--player", "Sønike,Stillnixx-Hyjal", "extract", "out.txt", "--player", "Sønike"]);
+        assert_eq!(args.players, ["Sønike", "Stillnixx-Hyjal"]);
+        assert!(matches!(args.output_mode, Some(OutputMode::Extract { player: Some(_), .. })));
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--strict", "diagnose"]);
+        assert!(matches!(args.output_mode, Some(OutputMode::Diagnose)));
+    }
+
+    #[test]
+    fn test_compare() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--phase-rules", "phases.toml", "compare"]);
+        assert!(matches!(args.output_mode, Some(OutputMode::Compare)));
+        assert_eq!(args.phase_rules, Some(PathBuf::from("phases.toml")));
+    }
+
+    #[test]
+    fn test_index() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "index"]);
+        assert!(matches!(args.output_mode, Some(OutputMode::Index)));
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--encounter", "Gnarlroot", "query", "SELECT COUNT(*) FROM damage"]);
+        assert_eq!(args.encounter.as_deref(), Some("Gnarlroot"));
+    }
+
+    #[test]
+    fn test_alert_rules() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "watch", "--alert-rules", "alerts.toml", "none"]);
+        assert_eq!(args.alert_rules, Some(PathBuf::from("alerts.toml")));
+    }
+
+    #[test]
+    fn test_pull_hooks() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "watch", "--on-pull-start", "obs-cli recording start", "--pull-webhook", "http://localhost:8123/pull", "none"]);
+        assert_eq!(args.on_pull_start.as_deref(), Some("obs-cli recording start"));
+        assert_eq!(args.on_pull_end, None);
+        assert_eq!(args.pull_webhook.as_deref(), Some("http://localhost:8123/pull"));
+    }
+
+    #[test]
+    fn test_trackers() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--tracker", "damage", "--tracker", "healing", "none"]);
+        assert_eq!(args.trackers, vec![Tracker::Damage, Tracker::Healing]);
+    }
+
+    #[test]
+    fn test_sinks() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--out", "std", "--out", "json:events.jsonl", "--out", "sqlite:raid.db", "--out", "file:good.txt,bad.txt"]);
+        assert!(args.output_mode.is_none());
+        assert_eq!(args.outs, [
+            Sink::Std,
+            Sink::Json(PathBuf::from("events.jsonl")),
+            Sink::Sqlite(PathBuf::from("raid.db")),
+            Sink::File { good_path: PathBuf::from("good.txt"), failed_path: PathBuf::from("bad.txt") },
+        ]);
+        // Alongside an output mode
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--out", "html:report.html", "none"]);
+        assert!(matches!(args.output_mode, Some(OutputMode::None)));
+
+        assert!(Cli::try_parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--out", "json"]).is_err());
+        assert!(Cli::try_parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--out", "file:good.txt"]).is_err());
+        assert!(Cli::try_parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--out", "parquet:x"]).is_err());
+    }
+
+    #[test]
+    fn test_mythic_plus() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--tracker", "mythic-plus", "--death-penalty-secs", "5", "none"]);
+        assert_eq!(args.trackers, vec![Tracker::MythicPlus]);
+        assert_eq!(args.death_penalty_secs, Some(5));
+    }
+
+    #[test]
+    fn test_avoidable() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--tracker", "avoidable", "--avoidable-rules", "rules.toml", "none"]);
+        assert_eq!(args.trackers, vec![Tracker::Avoidable]);
+        assert!(args.avoidable_rules.is_some());
+    }
+
+    #[test]
+    fn test_status_line() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "watch", "--status-line", "none"]);
+        assert!(args.status_line);
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "watch", "--refresh-interval", "0.5", "--redraw", "none"]);
+        assert_eq!(args.refresh_interval, Some(Duration::from_millis(500)));
+        assert!(args.redraw && !args.status_line);
+        assert!(Cli::try_parse_from(vec!["wowlogs.exe", "logs.txt", "watch", "--refresh-interval", "-1", "none"]).is_err());
+    }
+
+    #[test]
+    fn test_html() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "html", "report.html"]);
+        assert!(matches!(args.output_mode, Some(OutputMode::Html { .. })));
+    }
+
+    #[test]
+    fn test_summary_format() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--summary-format", "markdown", "none"]);
+        assert_eq!(args.summary_format, SummaryFormat::Markdown);
+
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "--summary-format", "discord", "none"]);
+        assert_eq!(args.summary_format, SummaryFormat::Discord);
+    }
+
+    #[test]
+    fn test_timeseries() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "timeseries", "series.json", "--format", "json"]);
+        assert!(matches!(args.output_mode, Some(OutputMode::Timeseries { format: SeriesFormat::Json, .. })));
+    }
+
+    #[test]
+    fn test_obs() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "watch", "obs", "overlay", "--player", "Sønike", "--row-template", "{name}: {dps}"]);
+        assert!(matches!(args.output_mode, Some(OutputMode::Obs { top: 5, ref player, ref row_template, .. })
+            if player.as_deref() == Some("Sønike") && row_template == "{name}: {dps}"));
+    }
+
+    #[test]
+    fn test_upload() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "upload", "http://localhost:8000/logs", "--token", "abc"]);
+        assert!(matches!(args.output_mode, Some(OutputMode::Upload { chunk_mb: 16, ref token, .. }) if token.as_deref() == Some("abc")));
+    }
+
+    #[test]
+    fn test_serve() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "watch", "serve", "--port", "9100"]);
+        assert!(matches!(args.output_mode, Some(OutputMode::Serve { port: 9100 })));
+    }
+
+    #[test]
+    fn test_multiple_logs() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "a.txt", "b.txt", "process", "--tracker", "healing", "std"]);
+        assert_eq!(args.wowlog_paths, [PathBuf::from("a.txt"), PathBuf::from("b.txt")]);
+        assert!(matches!(args.read_mode, Some(ReadMode::Process)));
+    }
+
+    #[test]
+    fn test_serve_http() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "logs.txt", "process", "serve-http"]);
+        assert!(matches!(args.output_mode, Some(OutputMode::ServeHttp { port: 8080 })));
+    }
+
+    #[test]
+    fn test_bench() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "bench", "--assert-min-throughput", "1000"]);
+        assert!(args.wowlog_paths.is_empty());
+        println!("{:?}", args);
+    }
+
+    #[test]
+    fn test_schema() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "schema", "--format", "json"]);
+        assert!(matches!(args.output_mode, Some(OutputMode::Schema { format: SchemaFormat::Json })));
+    }
+
+    #[test]
+    fn test_retry() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "retry", "bad.txt", "--remaining", "bad2.txt"]);
+        assert!(args.wowlog_paths.is_empty());
+        assert!(matches!(args.output_mode, Some(OutputMode::Retry { remaining: Some(_), .. })));
+    }
+
+    #[test]
+    fn test_wcl_import() {
+        let args = Cli::parse_from(vec!["wowlogs.exe", "wcl-import", "events.json", "WoWCombatLog.txt"]);
+        assert!(args.wowlog_paths.is_empty());
+        println!("{:?}", args);
+    }
 }
\ No newline at end of file
@@ -1,9 +1,17 @@
 pub mod advanced;
+pub mod combatant;
 pub mod common;
+pub mod emote;
 pub mod enums;
 pub mod events;
+pub mod flags;
+pub mod format;
 pub mod guid;
+pub mod names;
 pub mod prefixes;
+pub mod registry;
 pub mod special;
 pub mod suffixes;
-mod combatant;
\ No newline at end of file
+
+#[cfg(test)]
+mod round_trip;
@@ -1,11 +1,13 @@
-use anyhow::Result;
 use itertools::izip;
+use serde::{Deserialize, Serialize};
 
 use crate::components::enums::PowerType;
-use crate::components::guid::GUID;
-use crate::utils::parse_num;
+use crate::components::format::LogFormat;
+use crate::components::guid::{guid_field, GUID};
+use crate::error::{malformed, Result};
+use crate::utils::{Fields, parse_num};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PowerInfo {
     pub power_type: Option<PowerType>,
     pub current_power: u64,
@@ -24,16 +26,27 @@ impl PowerInfo {
             line[3].split('|')
         )
             .map(|(t, cur, max, cost)| Ok(PowerInfo {
-                power_type: PowerType::parse(t)?,
-                current_power: parse_num(cur)?,
-                max_power: parse_num(max)?,
-                power_cost: parse_num(cost)?,
+                power_type: PowerType::parse(t).map_err(|e| e.in_field(0))?,
+                current_power: parse_num(cur).map_err(|e| e.in_field(1))?,
+                max_power: parse_num(max).map_err(|e| e.in_field(2))?,
+                power_cost: parse_num(cost).map_err(|e| e.in_field(3))?,
             }))
             .collect::<Result<Vec<_>>>()
     }
+
+    /// The 4 fields, with multiple power types joined by `|`
+    fn to_log_fields(powers: &[Self]) -> Vec<String> {
+        let join = |f: fn(&Self) -> String| powers.iter().map(f).collect::<Vec<_>>().join("|");
+        vec![
+            join(|p| p.power_type.map_or("-1".to_string(), |t| (t as i8).to_string())),
+            join(|p| p.current_power.to_string()),
+            join(|p| p.max_power.to_string()),
+            join(|p| p.power_cost.to_string()),
+        ]
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Position {
     pub x: f32,
     pub y: f32,
@@ -41,18 +54,19 @@ pub struct Position {
 }
 
 impl Position {
-    fn parse(line_xy: &[&str], line_facing: &str) -> Result<Self> {
-        assert_eq!(line_xy.len(), 2);
+    /// From the x, y, map ID & facing fields
+    fn parse(line: &[&str]) -> Result<Self> {
+        assert_eq!(line.len(), 4);
 
         Ok(Self {
-            x: parse_num(line_xy[0])?,
-            y: parse_num(line_xy[1])?,
-            facing: parse_num(line_facing)?,
+            x: line.num(0)?,
+            y: line.num(1)?,
+            facing: line.num(3)?,
         })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AdvancedParams {
     pub info_guid: Option<GUID>,
     pub owner_guid: Option<GUID>,
@@ -69,24 +83,54 @@ pub struct AdvancedParams {
 }
 
 impl AdvancedParams {
+    /// Retail logs have 17 fields, classic logs have 16 (no absorb)
     pub(crate) fn parse(line: &[&str]) -> Result<Self> {
-        assert_eq!(line.len(), 17);
+        let (absorb, start) = match line.len() {
+            17 => (line.num(7)?, 8),
+            16 => (0, 7),
+            n => malformed!("Bad number of advanced params: expected 16 or 17, got {}", n)
+        };
 
         Ok(Self {
-            info_guid: GUID::parse(line[0])?,
-            owner_guid: GUID::parse(line[1])?,
-            current_hp: parse_num(line[2])?,
-            max_hp: parse_num(line[3])?,
-            attack_power: parse_num(line[4])?,
-            spell_power: parse_num(line[5])?,
-            armor: parse_num(line[6])?,
-            absorb: parse_num(line[7])?,
-            power_info: PowerInfo::parse(&line[8..12])?,
-            position: Position::parse(&line[12..14], line[15])?,
-            ui_map_id: parse_num(line[14])?,
-            level_or_ilvl: parse_num(line[16])?,
+            info_guid: GUID::parse_or_unknown(line[0]),
+            owner_guid: GUID::parse_or_unknown(line[1]),
+            current_hp: line.num(2)?,
+            max_hp: line.num(3)?,
+            attack_power: line.num(4)?,
+            spell_power: line.num(5)?,
+            armor: line.num(6)?,
+            absorb,
+            power_info: PowerInfo::parse(&line[start..start + 4]).map_err(|e| e.offset(start))?,
+            position: Position::parse(&line[start + 4..start + 8]).map_err(|e| e.offset(start + 4))?,
+            ui_map_id: line.num(start + 6)?,
+            level_or_ilvl: line.num(start + 8)?,
         })
     }
+
+    /// Classic logs don't have the absorb field
+    pub fn to_log_fields(&self, format: &LogFormat) -> Vec<String> {
+        let mut fields = vec![
+            guid_field(&self.info_guid),
+            guid_field(&self.owner_guid),
+            self.current_hp.to_string(),
+            self.max_hp.to_string(),
+            self.attack_power.to_string(),
+            self.spell_power.to_string(),
+            self.armor.to_string(),
+        ];
+        if format.has_advanced_absorb() {
+            fields.push(self.absorb.to_string());
+        }
+        fields.extend(PowerInfo::to_log_fields(&self.power_info));
+        fields.extend([
+            format!("{:.2}", self.position.x),
+            format!("{:.2}", self.position.y),
+            self.ui_map_id.to_string(),
+            format!("{:.4}", self.position.facing),
+            self.level_or_ilvl.to_string(),
+        ]);
+        fields
+    }
 }
 
 #[cfg(test)]
@@ -110,7 +154,7 @@ mod tests {
 
     #[test]
     fn parse_position() {
-        let parsed = Position::parse(&["3295.44", "13209.11"], "3.4506");
+        let parsed = Position::parse(&["3295.44", "13209.11", "2232", "3.4506"]);
         println!("{:?}", parsed);
     }
 
@@ -128,4 +172,13 @@ mod tests {
         let parsed = AdvancedParams::parse(&line);
         println!("{:?}", parsed);
     }
+
+    #[test]
+    fn parse_classic() {
+        let line = vec!["Creature-0-4395-615-22-30452-00001A2B3C", "0000000000000000", "3487744", "3500000", "0", "0", "0", "-1", "0", "0", "0", "3262.87", "532.15", "0", "1.6829", "83"];
+        let parsed = AdvancedParams::parse(&line).unwrap();
+        assert_eq!(parsed.absorb, 0);
+        assert_eq!(parsed.ui_map_id, 0);
+        assert_eq!(parsed.level_or_ilvl, 83);
+    }
 }
\ No newline at end of file
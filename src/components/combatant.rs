@@ -1,11 +1,13 @@
-use anyhow::{bail, Context, ensure, Result};
 use itertools::Itertools;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 
-use crate::components::guid::GUID;
-use crate::utils::{match_replace_all, parse_num};
+use crate::components::format::LogFormat;
+use crate::components::guid::{guid_field, GUID};
+use crate::error::{Context, malformed, Result};
+use crate::utils::{Fields, match_replace_all, parse_num};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CharacterStats {
     strength: u64,
     agility: u64,
@@ -33,32 +35,41 @@ pub struct CharacterStats {
 impl CharacterStats {
     pub fn parse(line: &[&str]) -> Result<Self> {
         Ok(Self {
-            strength: parse_num(line[0])?,
-            agility: parse_num(line[1])?,
-            stamina: parse_num(line[2])?,
-            intelligence: parse_num(line[3])?,
-            dodge: parse_num(line[4])?,
-            parry: parse_num(line[5])?,
-            block: parse_num(line[6])?,
-            crit_melee: parse_num(line[7])?,
-            crit_ranged: parse_num(line[8])?,
-            crit_spell: parse_num(line[9])?,
-            speed: parse_num(line[10])?,
-            leech: parse_num(line[11])?,
-            haste_melee: parse_num(line[12])?,
-            haste_range: parse_num(line[13])?,
-            haste_spell: parse_num(line[14])?,
-            avoidance: parse_num(line[15])?,
-            mastery: parse_num(line[16])?,
-            versatility_damage_done: parse_num(line[17])?,
-            versatility_healing_done: parse_num(line[18])?,
-            versatility_damage_taken: parse_num(line[19])?,
-            armor: parse_num(line[20])?,
+            strength: line.num(0)?,
+            agility: line.num(1)?,
+            stamina: line.num(2)?,
+            intelligence: line.num(3)?,
+            dodge: line.num(4)?,
+            parry: line.num(5)?,
+            block: line.num(6)?,
+            crit_melee: line.num(7)?,
+            crit_ranged: line.num(8)?,
+            crit_spell: line.num(9)?,
+            speed: line.num(10)?,
+            leech: line.num(11)?,
+            haste_melee: line.num(12)?,
+            haste_range: line.num(13)?,
+            haste_spell: line.num(14)?,
+            avoidance: line.num(15)?,
+            mastery: line.num(16)?,
+            versatility_damage_done: line.num(17)?,
+            versatility_healing_done: line.num(18)?,
+            versatility_damage_taken: line.num(19)?,
+            armor: line.num(20)?,
         })
     }
+
+    fn to_log_fields(&self) -> Vec<String> {
+        [
+            self.strength, self.agility, self.stamina, self.intelligence, self.dodge, self.parry, self.block,
+            self.crit_melee, self.crit_ranged, self.crit_spell, self.speed, self.leech, self.haste_melee,
+            self.haste_range, self.haste_spell, self.avoidance, self.mastery, self.versatility_damage_done,
+            self.versatility_healing_done, self.versatility_damage_taken, self.armor,
+        ].map(|s| s.to_string()).to_vec()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PVPStats {
     honor_level: u64,
     season: u64,
@@ -69,15 +80,19 @@ pub struct PVPStats {
 impl PVPStats {
     pub fn parse(line: &[&str]) -> Result<Self> {
         Ok(Self {
-            honor_level: parse_num(line[0])?,
-            season: parse_num(line[1])?,
-            rating: parse_num(line[2])?,
-            tier: parse_num(line[3])?,
+            honor_level: line.num(0)?,
+            season: line.num(1)?,
+            rating: line.num(2)?,
+            tier: line.num(3)?,
         })
     }
+
+    fn to_log_fields(&self) -> Vec<String> {
+        [self.honor_level, self.season, self.rating, self.tier].map(|s| s.to_string()).to_vec()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum Faction {
     Horde,
     Alliance,
@@ -89,9 +104,21 @@ impl Faction {
         match s {
             "0" => Ok(Self::Horde),
             "1" => Ok(Self::Alliance),
-            _ => bail!(format!("Failed to parse Faction: {:?}", s))
+            _ => malformed!("Failed to parse Faction: {:?}", s)
         }
     }
+
+    fn to_log_field(&self) -> String {
+        match self {
+            Self::Horde => "0",
+            Self::Alliance => "1",
+        }.to_string()
+    }
+}
+
+/// eg. `(1,2,3)`
+fn tuple(ids: impl IntoIterator<Item=impl ToString>) -> String {
+    format!("({})", ids.into_iter().map(|i| i.to_string()).join(","))
 }
 
 pub type PVPTalents = [u64; 4];
@@ -110,13 +137,14 @@ impl PrimitiveParse<PVPTalents> for PVPTalents {
             // Vec -> [u64]
             .as_slice()
             .try_into()
+            .ok()
             .with_context(|| format!("Incorrect number of ids: {}", s))?;
 
         Ok(ids)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ClassTalent {
     // https://wago.tools/db2/TraitNodeXTraitNodeEntry
     node_id: u64,
@@ -132,7 +160,7 @@ impl ClassTalent {
             .map(parse_num)
             .collect::<Result<Vec<_>>>()?;
 
-        ensure!(parsed.len() == 3, "incorrect numer of values, expected 3, got {}", parsed.len());
+        if parsed.len() != 3 { malformed!("incorrect numer of values, expected 3, got {}", parsed.len()); }
 
 
         Ok(Self {
@@ -142,9 +170,13 @@ impl ClassTalent {
         })
     }
 
+    fn to_log_field(talents: &[Self]) -> String {
+        format!("[{}]", talents.iter().map(|t| tuple([t.node_id, t.entry_id, t.rank])).join(","))
+    }
+
     pub fn parse_vec(s: &str) -> Result<Vec<Self>> {
         // s: "[(a,b,c),...]"
-        let re = Regex::new(r"\(((?:\d+,?)+)\)")?;
+        let re = Regex::new(r"\(((?:\d+,?)+)\)").unwrap();
 
         re.find_iter(s)
             .map(|m| Self::parse(m.as_str()))
@@ -152,11 +184,11 @@ impl ClassTalent {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Enchant {
-    permanent_id: u64,
-    temp_id: u64,
-    on_use_id: u64,
+    pub permanent_id: u64,
+    pub temp_id: u64,
+    pub on_use_id: u64,
 }
 
 impl Enchant {
@@ -169,26 +201,49 @@ impl Enchant {
             .collect::<Vec<_>>();
 
         Ok(Some(Self {
-            permanent_id: parse_num(parts[0])?,
-            temp_id: parse_num(parts[1])?,
-            on_use_id: parse_num(parts[2])?,
+            permanent_id: parts.num(0)?,
+            temp_id: parts.num(1)?,
+            on_use_id: parts.num(2)?,
         }))
     }
 }
 
 
-#[derive(Debug)]
+/// Equipment slots, in the order items are logged
+pub const SLOTS: [&str; 19] = [
+    "Head", "Neck", "Shoulder", "Shirt", "Chest", "Waist", "Legs", "Feet", "Wrist", "Hands",
+    "Finger 1", "Finger 2", "Trinket 1", "Trinket 2", "Back", "Main Hand", "Off Hand", "Ranged", "Tabard",
+];
+
+/// Slots which can be enchanted in the current expansion
+pub const ENCHANTABLE_SLOTS: [usize; 8] = [4, 6, 7, 8, 10, 11, 14, 15];
+
+/// Cosmetic slots, which don't count towards item level
+pub const COSMETIC_SLOTS: [usize; 2] = [3, 18];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EquippedItem {
-    item_id: u64,
-    ilvl: u64,
-    enchant: Option<Enchant>,
-    bonus_ids: Vec<u64>,
-    gem_ids: Vec<u64>,
+    /// Index into SLOTS
+    pub slot: usize,
+    pub item_id: u64,
+    pub ilvl: u64,
+    pub enchant: Option<Enchant>,
+    pub bonus_ids: Vec<u64>,
+    /// Empty sockets are logged as 0
+    pub gem_ids: Vec<u64>,
 }
 
 impl EquippedItem {
-    fn parse(parts: Vec<&str>) -> Result<Option<Self>> {
-        ensure!(parts.len() == 5, "Not enough sections: expected 5, got: {}", parts.len());
+    pub fn slot_name(&self) -> &'static str {
+        SLOTS.get(self.slot).copied().unwrap_or("Unknown")
+    }
+
+    pub fn is_enchanted(&self) -> bool {
+        self.enchant.as_ref().is_some_and(|e| e.permanent_id != 0)
+    }
+
+    fn parse(slot: usize, parts: Vec<&str>) -> Result<Option<Self>> {
+        if parts.len() != 5 { malformed!("Not enough sections: expected 5, got: {}", parts.len()); }
 
         if parts[0] == "0" { return Ok(None); };
 
@@ -211,19 +266,38 @@ impl EquippedItem {
         };
 
         Ok(Some(Self {
-            item_id: parse_num(parts[0])?,
-            ilvl: parse_num(parts[1])?,
-            enchant: Enchant::parse(parts[2])?,
+            slot,
+            item_id: parts.num(0)?,
+            ilvl: parts.num(1)?,
+            enchant: parts.parse_with(2, Enchant::parse)?,
             bonus_ids,
             gem_ids,
         }))
     }
 
+    /// Empty slots before the last item are filled back in, ones after it can't be
+    fn to_log_field(items: &[Self]) -> String {
+        let slots = items.iter().map(|i| i.slot + 1).max().unwrap_or(0);
+        let items = (0..slots)
+            .map(|slot| match items.iter().find(|i| i.slot == slot) {
+                Some(item) => format!("({},{},{},{},{})",
+                                      item.item_id,
+                                      item.ilvl,
+                                      item.enchant.as_ref().map_or("()".to_string(), |e| tuple([e.permanent_id, e.temp_id, e.on_use_id])),
+                                      tuple(&item.bonus_ids),
+                                      tuple(&item.gem_ids)),
+                None => "(0,0,(),(),())".to_string(),
+            })
+            .join(",");
+        format!("[{}]", items)
+    }
+
     pub fn parse_vec(s: &str) -> Result<Vec<Self>> {
         let re = Regex::new(r"(\d+),(\d+),(\(.*?\),?)(\(.*?\),?)(\(.*?\),?)").unwrap();
 
         let items = re.captures_iter(s)
-            .map(|c| {
+            .enumerate()
+            .map(|(slot, c)| {
                 let parts = c.iter()
                     .skip(1)
                     .collect::<Option<Vec<_>>>()
@@ -231,7 +305,7 @@ impl EquippedItem {
                     .iter().map(|m| m.as_str())
                     .collect::<Vec<_>>();
 
-                Self::parse(parts)
+                Self::parse(slot, parts)
             })
             .collect::<Result<Vec<_>>>()?
             // Filter out empty slots
@@ -242,7 +316,7 @@ impl EquippedItem {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct InterestingAura {
     caster: Option<GUID>,
     aura_id: u64,
@@ -250,14 +324,18 @@ pub struct InterestingAura {
 
 impl InterestingAura {
     fn parse(parts: &[&str]) -> Result<InterestingAura> {
-        ensure!(parts.len() == 2, "Not enough parts for InterstingAura: expected 2, got {}", parts.len());
+        if parts.len() != 2 { malformed!("Not enough parts for InterstingAura: expected 2, got {}", parts.len()); }
 
         Ok(Self {
-            caster: GUID::parse(parts[0])?,
-            aura_id: parse_num(parts[1])?,
+            caster: parts.parse_with(0, GUID::parse)?,
+            aura_id: parts.num(1)?,
         })
     }
 
+    fn to_log_field(auras: &[Self]) -> String {
+        format!("[{}]", auras.iter().map(|a| format!("{},{}", guid_field(&a.caster), a.aura_id)).join(","))
+    }
+
     pub fn parse_vec(s: &str) -> Result<Vec<Self>> {
         if s == "[]," { return Ok(vec![]); }
 
@@ -272,47 +350,111 @@ impl InterestingAura {
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CombatantInfo {
-    guid: GUID,
+    pub guid: GUID,
     faction: Faction,
     stats: CharacterStats,
+    /// Not logged by classic flavours
+    pub spec_id: Option<u64>,
     class_talents: Vec<ClassTalent>,
-    pvp_talents: PVPTalents,
+    pvp_talents: Option<PVPTalents>,
     // artifact_traits: todo!(),
-    equipped_items: Vec<EquippedItem>,
+    pub equipped_items: Vec<EquippedItem>,
     interesting_auras: Vec<InterestingAura>,
-    pvp_stats: PVPStats,
+    pvp_stats: Option<PVPStats>,
+    /// Fields after the PvP stats added by newer log versions, kept as they are
+    extra_fields: Vec<String>,
 }
 
 impl CombatantInfo {
-    pub fn parse(line: &[&str]) -> Result<Self> {
-        let line2 = line.join(",");
+    /// Classic flavours omit the class talents, pvp talents & pvp stats sections
+    pub fn parse(line: &[&str], format: &LogFormat) -> Result<Self> {
+        // Trailing comma so a [...] section at the end of the line (classic) matches like the rest
+        let line2 = line.join(",") + ",";
 
         // Pull out square brackets (class talents, equipped items, interesting auras
         let re = Regex::new(r"(\[.*?]),").unwrap();
-        let (matches, line3) = match_replace_all(&re, &line2);
-        ensure!(matches.len() == 3, "incorrect number of [...] sections found. Expected 3, found {}", matches.len());
+        let (mut matches, line3) = match_replace_all(&re, &line2);
+        if matches.len() != 3 && matches.len() != 2 { malformed!("incorrect number of [...] sections found. Expected 2 or 3, found {}", matches.len()); }
+        if matches.len() == 2 { matches.insert(0, "[]".to_string()); }
 
 
         // Pull out remaining round brackets (pvp talents)
         let re = Regex::new(r"\([\d,?]+\),").unwrap();
         let (matches_pvp, line4) = match_replace_all(&re, &line3);
-        ensure!(matches_pvp.len() == 1, "incorrect number of (...) sections found. Expected 1, found {}", matches_pvp.len());
+        if matches_pvp.len() > 1 { malformed!("incorrect number of (...) sections found. Expected 0 or 1, found {}", matches_pvp.len()); }
 
         // Re-split todo: use csv to make sure we escape properly
-        let line5 = line4.split(',').collect::<Vec<_>>();
+        let line5 = line4.trim_end_matches(',').split(',').collect::<Vec<_>>();
+        if line5.len() < 23 { malformed!("Not enough stat fields: expected 23, got {}", line5.len()); }
 
 
         Ok(Self {
-            guid: GUID::parse(line5[0])?.unwrap(),
-            faction: Faction::parse(line5[1])?,
+            guid: line5.parse_with(0, GUID::parse)?.unwrap(),
+            faction: line5.parse_with(1, Faction::parse)?,
             stats: CharacterStats::parse(&line5[2..23])?,
+            spec_id: line5.get(23).map(|s| parse_num(s)).transpose()?,
             class_talents: ClassTalent::parse_vec(matches[0].as_str())?,
-            pvp_talents: PVPTalents::parse(matches_pvp[0].as_str())?,
+            pvp_talents: matches_pvp.first()
+                .map(|m| PVPTalents::parse(m.as_str()))
+                .transpose()?,
             equipped_items: EquippedItem::parse_vec(matches[1].as_str())?,
             interesting_auras: InterestingAura::parse_vec(matches[2].as_str())?,
-            pvp_stats: PVPStats::parse(&line5[23..])?,
+            pvp_stats: if line5.len() >= 28 { Some(PVPStats::parse(&line5[24..])?) } else { None },
+            extra_fields: match format.has_combatant_extras() {
+                true => line5.iter().skip(28).map(|s| s.to_string()).collect(),
+                false => vec![],
+            },
         })
     }
-}
\ No newline at end of file
+
+    /// The bracketed sections are single fields, as they contain commas
+    pub fn to_log_fields(&self) -> Vec<String> {
+        let mut fields = vec![self.guid.to_string(), self.faction.to_log_field()];
+        fields.extend(self.stats.to_log_fields());
+        if let Some(spec_id) = self.spec_id {
+            fields.extend([spec_id.to_string(), ClassTalent::to_log_field(&self.class_talents)]);
+        }
+        if let Some(pvp_talents) = self.pvp_talents {
+            fields.push(tuple(pvp_talents));
+        }
+        fields.extend([
+            EquippedItem::to_log_field(&self.equipped_items),
+            InterestingAura::to_log_field(&self.interesting_auras),
+        ]);
+        if let Some(pvp_stats) = &self.pvp_stats {
+            fields.extend(pvp_stats.to_log_fields());
+        }
+        fields.extend(self.extra_fields.iter().cloned());
+        fields
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::components::combatant::CombatantInfo;
+    use crate::components::format::LogFormat;
+
+    #[test]
+    fn parse_classic() {
+        let line = vec!["Player-4395-01C5EEA6", "0", "180", "1200", "900", "150", "0", "0", "0", "1500", "1500", "1500", "0", "0", "300", "300", "300", "0", "0", "0", "0", "0", "4500", "[(40432", "278", "(3817", "0", "0)", "()", "())", "(0", "0", "()", "()", "())]", "[Player-4395-01C5EEA6", "48162]"];
+        let parsed = CombatantInfo::parse(&line, &LogFormat::default()).unwrap();
+        assert!(parsed.class_talents.is_empty());
+        assert!(parsed.pvp_talents.is_none());
+        assert!(parsed.pvp_stats.is_none());
+        assert!(parsed.spec_id.is_none());
+        assert_eq!(parsed.equipped_items.len(), 1);
+        assert_eq!(parsed.equipped_items[0].slot_name(), "Head");
+        assert!(parsed.equipped_items[0].is_enchanted());
+    }
+
+    #[test]
+    fn parse_retail_spec() {
+        let line = vec!["Player-1098-0500B8C6", "1", "12648", "1734", "52761", "1128", "0", "0", "0", "3511", "3511", "3511", "900", "0", "4692", "4692", "4692", "443", "6741", "533", "533", "533", "11302", "251", "[(76034", "96162", "1)]", "(1", "204080", "199719", "233396)", "[(207200", "489", "()", "()", "())]", "[Player-1098-0500B8C6", "396092]", "145", "0", "0", "0"];
+        let parsed = CombatantInfo::parse(&line, &LogFormat::default()).unwrap();
+        assert_eq!(parsed.spec_id, Some(251));
+        assert_eq!(parsed.pvp_stats.unwrap().honor_level, 145);
+    }
+}
@@ -1,21 +1,23 @@
-use std::u64;
+use std::sync::Arc;
 
-use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 
 use crate::components::{
-    enums::SpellSchool,
+    enums::SpellSchoolSet,
+    flags::{Controller, RaidMarker, Reaction, UnitFlags},
     guid::GUID,
 };
-use crate::utils::{parse_hex, parse_num};
+use crate::error::{ComponentError, Result};
+use crate::utils::{Fields, intern, quote};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SpellInfo {
     pub spell_id: u64,
-    pub spell_name: String,
-    pub spell_school: Vec<SpellSchool>,
+    pub spell_name: Arc<str>,
+    pub spell_school: SpellSchoolSet,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Actor {
     pub guid: GUID,
     pub name: String,
@@ -27,27 +29,31 @@ impl SpellInfo {
     pub fn parse(line: &[&str]) -> Result<Self> {
         assert_eq!(line.len(), 3);
 
-        let spell_school = SpellSchool::parse(line[2])?
-            .with_context(|| format!("Error parsing spell school: {}", line[2]))?;
+        let spell_school = line.parse_with(2, SpellSchoolSet::parse)?
+            .ok_or_else(|| ComponentError::bad_field(line[2], "spell school").in_field(2))?;
 
         Ok(Self {
-            spell_id: parse_num(line[0])?,
-            spell_name: line[1].to_string(),
+            spell_id: line.num(0)?,
+            spell_name: intern(line[1]),
             spell_school,
         })
     }
+
+    pub fn to_log_fields(&self) -> Vec<String> {
+        vec![self.spell_id.to_string(), quote(&self.spell_name), format!("{:#x}", self.spell_school.bits())]
+    }
 }
 
 impl Actor {
     pub fn parse(line: &[&str]) -> Result<Option<Self>> {
-        let guid = GUID::parse(line[0])?;
+        let guid = line.parse_with(0, GUID::parse)?;
         let guid = if let Some(g) = guid { g } else { return Ok(None); };
 
-        let flags = parse_hex(line[2]).context("Error parsing target flags")?;
+        let flags = line.hex(2)?;
 
         let raid_flags = match line[3] {
             "nil" => None,
-            x => Some(parse_hex(x).context("Error parsing target raid flags")?)
+            _ => Some(line.hex(3)?)
         };
 
         Ok(Some(Self {
@@ -58,6 +64,49 @@ impl Actor {
 
         }))
     }
+
+    pub fn to_log_fields(&self) -> Vec<String> {
+        vec![
+            self.guid.to_string(),
+            quote(&self.name),
+            format!("{:#x}", self.flags),
+            self.raid_flags.map_or("nil".to_string(), |f| format!("{:#x}", f)),
+        ]
+    }
+
+    pub fn unit_flags(&self) -> UnitFlags {
+        UnitFlags::decode(self.flags)
+    }
+
+    /// Raid target icon on the unit, if any
+    pub fn raid_marker(&self) -> Option<RaidMarker> {
+        self.raid_flags.and_then(RaidMarker::decode)
+    }
+
+    pub fn is_hostile(&self) -> bool {
+        self.unit_flags().reaction == Some(Reaction::Hostile)
+    }
+
+    pub fn is_friendly(&self) -> bool {
+        self.unit_flags().reaction == Some(Reaction::Friendly)
+    }
+
+    pub fn is_player_controlled(&self) -> bool {
+        self.unit_flags().controller == Some(Controller::Player)
+    }
+
+    /// In the log writer's party or raid, including themselves
+    pub fn in_group(&self) -> bool {
+        self.unit_flags().in_group()
+    }
+}
+
+/// An actor's fields as written in the log, or the "nobody" placeholder
+pub fn actor_fields(actor: &Option<Actor>) -> Vec<String> {
+    match actor {
+        Some(actor) => actor.to_log_fields(),
+        None => ["0000000000000000", "nil", "0x80000000", "0x80000000"].map(String::from).to_vec(),
+    }
 }
 
 
@@ -0,0 +1,90 @@
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+/// The text of an EMOTE, with the UI escape sequences boss warnings are written with picked out.
+/// eg. `|TInterface\\Icons\\Spell_Fire_Fire.blp:20|t Fyrakk begins to cast |cFFFF0000|Hspell:423717|h[Wildfire]|h|r!`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmoteText {
+    /// As shown in game: escapes removed, links replaced by their text
+    pub plain: String,
+    /// Path of the first icon, eg. `Interface\\Icons\\Spell_Fire_Fire.blp`
+    pub icon: Option<String>,
+    /// First spell linked
+    pub spell_id: Option<u64>,
+}
+
+impl EmoteText {
+    /// Unknown escapes are kept as they are
+    pub fn parse(text: &str) -> Self {
+        let mut plain = String::with_capacity(text.len());
+        let mut icon = None;
+        let mut spell_id = None;
+
+        let mut rest = text;
+        while let Some(i) = rest.find('|') {
+            plain.push_str(&rest[..i]);
+            let escape = &rest[i + 1..];
+
+            rest = match escape.chars().next() {
+                Some('|') => {
+                    plain.push('|');
+                    &escape[1..]
+                }
+                Some('n') => {
+                    plain.push(' ');
+                    &escape[1..]
+                }
+                // Ends a colour, link or texture
+                Some('r' | 'h' | 't') => &escape[1..],
+                // Colour, eg. `|cFFFF0000`
+                Some('c') => escape.get(9..).unwrap_or_default(),
+                // Texture, eg. `|TInterface\\Icons\\Spell_Fire_Fire.blp:20|t`
+                Some('T') => {
+                    let end = escape.find("|t").unwrap_or(escape.len());
+                    let path = escape[1..end].split(':').next().unwrap_or_default();
+                    icon.get_or_insert_with(|| path.to_string());
+                    escape.get(end + 2..).unwrap_or_default()
+                }
+                // Start of a link, eg. `|Hspell:423717|h`. Its text follows, up to a closing `|h`
+                Some('H') => {
+                    let end = escape.find("|h").unwrap_or(escape.len());
+                    if let Some(("spell", id)) = escape[1..end].split(':').next_tuple::<(_, _)>() {
+                        if let Ok(id) = id.parse() { spell_id.get_or_insert(id); }
+                    }
+                    escape.get(end + 2..).unwrap_or_default()
+                }
+                _ => {
+                    plain.push('|');
+                    escape
+                }
+            };
+        }
+        plain.push_str(rest);
+
+        Self { plain: plain.split_whitespace().join(" "), icon, spell_id }
+    }
+
+    /// Raid warnings carry an icon or a spell link, flavour text doesn't
+    pub fn is_warning(&self) -> bool {
+        self.icon.is_some() || self.spell_id.is_some()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::components::emote::EmoteText;
+
+    #[test]
+    fn parse() {
+        let text = EmoteText::parse("|TInterface\\Icons\\Spell_Fire_Fire.blp:20|t Fyrakk begins to cast |cFFFF0000|Hspell:423717:0|h[Wildfire]|h|r!");
+        assert_eq!(text.plain, "Fyrakk begins to cast [Wildfire]!");
+        assert_eq!(text.icon.as_deref(), Some("Interface\\Icons\\Spell_Fire_Fire.blp"));
+        assert_eq!(text.spell_id, Some(423717));
+        assert!(text.is_warning());
+
+        let text = EmoteText::parse("%s laughs.|nHealth || mana");
+        assert_eq!(text.plain, "%s laughs. Health | mana");
+        assert!(!text.is_warning());
+    }
+}
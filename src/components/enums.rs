@@ -1,14 +1,16 @@
-use std::i8;
 use std::str::FromStr;
 
-use anyhow::{Context, Result};
+use bitflags::bitflags;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use strum::{EnumIter, EnumString, IntoEnumIterator};
 
+use crate::error::{ComponentError, Result};
 use crate::traits::ToCamel;
 use crate::utils::parse_num;
 
 /// https://warcraft.wiki.gg/wiki/COMBAT_LOG_EVENT#Spell_School
-#[derive(Debug, EnumIter, PartialEq, Copy, Clone)]
+#[derive(Debug, EnumIter, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum SpellSchool {
     Physical = 1,
     Holy = 2,
@@ -19,25 +21,106 @@ pub enum SpellSchool {
     Arcane = 64,
 }
 
-impl SpellSchool {
-    /// Hex bitmask to vector of schools
-    pub(crate) fn parse(s: &str) -> Result<Option<Vec<SpellSchool>>> {
+bitflags! {
+    /// One or more spell schools, as the bitmask in the log. Multi-school spells have their own names, eg. Shadowflame.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+    #[serde(from = "Vec<SpellSchool>", into = "Vec<SpellSchool>")]
+    pub struct SpellSchoolSet: u8 {
+        const PHYSICAL = SpellSchool::Physical as u8;
+        const HOLY = SpellSchool::Holy as u8;
+        const FIRE = SpellSchool::Fire as u8;
+        const NATURE = SpellSchool::Nature as u8;
+        const FROST = SpellSchool::Frost as u8;
+        const SHADOW = SpellSchool::Shadow as u8;
+        const ARCANE = SpellSchool::Arcane as u8;
+    }
+}
+
+/// Names of multi-school combinations
+/// https://warcraft.wiki.gg/wiki/COMBAT_LOG_EVENT#Spell_School
+const COMPOSITE_SCHOOLS: [(u8, &str); 25] = [
+    (3, "Holystrike"),
+    (5, "Flamestrike"),
+    (6, "Radiant"),
+    (9, "Stormstrike"),
+    (10, "Holystorm"),
+    (12, "Volcanic"),
+    (17, "Froststrike"),
+    (18, "Holyfrost"),
+    (20, "Frostfire"),
+    (24, "Froststorm"),
+    (28, "Elemental"),
+    (33, "Shadowstrike"),
+    (34, "Twilight"),
+    (36, "Shadowflame"),
+    (40, "Plague"),
+    (48, "Shadowfrost"),
+    (65, "Spellstrike"),
+    (66, "Divine"),
+    (68, "Spellfire"),
+    (72, "Astral"),
+    (80, "Spellfrost"),
+    (96, "Spellshadow"),
+    (124, "Chromatic"),
+    (126, "Magic"),
+    (127, "Chaos"),
+];
+
+impl SpellSchoolSet {
+    /// Hex bitmask to set of schools
+    pub(crate) fn parse(s: &str) -> Result<Option<Self>> {
         if s == "-1" { return Ok(None); }
 
         let s = if s.starts_with("0x") {
             u8::from_str_radix(s.trim_start_matches("0x"), 16)
         } else {
             u8::from_str(s)
-        }.with_context(|| format!("Could not parse spell school as u8: {s}"))?;
+        }.map_err(|_| ComponentError::bad_field(s, "spell school"))?;
+
+        Ok(Some(Self::from_bits_truncate(s)))
+    }
 
-        Ok(Some(Self::iter()
-            .filter(|&e| (e as u8) & s != 0)
-            .collect()))
+    /// The individual schools, in bit order
+    pub fn schools(&self) -> Vec<SpellSchool> {
+        SpellSchool::iter()
+            .filter(|&e| self.contains(e.into()))
+            .collect()
+    }
+}
+
+impl From<SpellSchool> for SpellSchoolSet {
+    fn from(school: SpellSchool) -> Self {
+        Self::from_bits_truncate(school as u8)
+    }
+}
+
+impl From<Vec<SpellSchool>> for SpellSchoolSet {
+    fn from(schools: Vec<SpellSchool>) -> Self {
+        schools.into_iter().map(Self::from).collect()
+    }
+}
+
+impl From<SpellSchoolSet> for Vec<SpellSchool> {
+    fn from(set: SpellSchoolSet) -> Self {
+        set.schools()
+    }
+}
+
+/// eg. `Fire`, `Shadowflame`, or `Holy+Nature+Shadow+Arcane` for combinations without a name
+impl std::fmt::Display for SpellSchoolSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some((_, name)) = COMPOSITE_SCHOOLS.iter().find(|(bits, _)| *bits == self.bits()) {
+            return write!(f, "{}", name);
+        }
+        match self.schools().as_slice() {
+            [] => write!(f, "None"),
+            schools => write!(f, "{}", schools.iter().map(|s| format!("{:?}", s)).join("+")),
+        }
     }
 }
 
 /// https://warcraft.wiki.gg/wiki/COMBAT_LOG_EVENT#Power_Type
-#[derive(Debug, Copy, Clone, EnumIter, PartialEq)]
+#[derive(Debug, Copy, Clone, EnumIter, PartialEq, Serialize, Deserialize)]
 pub enum PowerType {
     Health = -2,
     Mana = 0,
@@ -72,17 +155,17 @@ impl PowerType {
     pub(crate) fn parse(s: &str) -> Result<Option<PowerType>> {
         if s == "-1" { return Ok(None); };
 
-        let s = parse_num(s)?;
+        let id: i8 = parse_num(s)?;
 
-        let matched = Self::iter().find(|&e| e as i8 == s)
-            .with_context(|| format!("Failed to find matching PowerType: {s}"))?;
+        let matched = Self::iter().find(|&e| e as i8 == id)
+            .ok_or_else(|| ComponentError::bad_field(s, "power type"))?;
 
         Ok(Some(matched))
     }
 }
 
 /// https://warcraft.wiki.gg/wiki/COMBAT_LOG_EVENT#Miss_Type
-#[derive(Debug, EnumString, PartialEq)]
+#[derive(Debug, EnumString, PartialEq, Serialize, Deserialize)]
 pub enum MissType {
     Absorb,
     Block,
@@ -99,12 +182,36 @@ pub enum MissType {
 impl MissType {
     pub fn parse(s: &str) -> Result<Self> {
         MissType::from_str(&s.to_camel_case())
-            .with_context(|| format!("Failed to parse MissType: {}", s))
+            .map_err(|_| ComponentError::bad_field(s, "miss type"))
+    }
+}
+
+/// Whether a hit was single target or AoE, logged from COMBAT_LOG_VERSION 21
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DamageKind {
+    SingleTarget,
+    Aoe,
+}
+
+impl DamageKind {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "ST" => Ok(Self::SingleTarget),
+            "AOE" => Ok(Self::Aoe),
+            _ => Err(ComponentError::bad_field(s, "damage kind")),
+        }
+    }
+
+    pub fn to_log_field(&self) -> String {
+        match self {
+            Self::SingleTarget => "ST",
+            Self::Aoe => "AOE",
+        }.to_string()
     }
 }
 
 /// https://warcraft.wiki.gg/wiki/COMBAT_LOG_EVENT#Aura_Type
-#[derive(Debug, EnumString)]
+#[derive(Debug, EnumString, Serialize, Deserialize)]
 pub enum AuraType {
     Buff,
     Debuff,
@@ -113,12 +220,12 @@ pub enum AuraType {
 impl AuraType {
     pub fn parse(s: &str) -> Result<Self> {
         AuraType::from_str(&s.to_camel_case())
-            .with_context(|| format!("Failed to parse AuraType: {}", s))
+            .map_err(|_| ComponentError::bad_field(s, "aura type"))
     }
 }
 
 /// https://warcraft.wiki.gg/wiki/COMBAT_LOG_EVENT#Environmental_Type
-#[derive(Debug, EnumString)]
+#[derive(Debug, EnumString, Serialize, Deserialize)]
 pub enum EnvironmentalType {
     Drowning,
     Falling,
@@ -131,21 +238,32 @@ pub enum EnvironmentalType {
 impl EnvironmentalType {
     pub fn parse(s: &str) -> Result<Self> {
         EnvironmentalType::from_str(&s.to_camel_case())
-            .with_context(|| format!("Error parsing Environmental prefix: {}", s))
+            .map_err(|_| ComponentError::bad_field(s, "environmental type"))
     }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::components::enums::{MissType, PowerType, SpellSchool};
-    use crate::components::enums::SpellSchool::{Arcane, Holy, Nature, Shadow};
+    use crate::components::enums::{MissType, PowerType, SpellSchoolSet};
+    use crate::components::enums::SpellSchool::{Arcane, Fire, Holy, Nature, Shadow};
 
     #[test]
     fn parse_spell_school() {
-        assert_eq!(SpellSchool::parse("0x2").unwrap(), Some(vec![Holy]));
-        assert_eq!(SpellSchool::parse("0x6A").unwrap(), Some(vec![Holy, Nature, Shadow, Arcane]));
-        assert!(SpellSchool::parse("-1").unwrap().is_none());
+        assert_eq!(SpellSchoolSet::parse("0x2").unwrap(), Some(SpellSchoolSet::HOLY));
+        let parsed = SpellSchoolSet::parse("0x6A").unwrap().unwrap();
+        assert_eq!(parsed.schools(), vec![Holy, Nature, Shadow, Arcane]);
+        assert!(SpellSchoolSet::parse("-1").unwrap().is_none());
+
+        assert!(parsed.contains(Shadow.into()) && !parsed.contains(Fire.into()));
+        assert_eq!(parsed.to_string(), "Holy+Nature+Shadow+Arcane");
+        assert_eq!(SpellSchoolSet::parse("36").unwrap().unwrap().to_string(), "Shadowflame");
+        assert_eq!((SpellSchoolSet::FIRE | SpellSchoolSet::FROST).to_string(), "Frostfire");
+        assert_eq!(SpellSchoolSet::FIRE.to_string(), "Fire");
+
+        // Serialised as the list of schools, as before
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), r#"["Holy","Nature","Shadow","Arcane"]"#);
+        assert_eq!(serde_json::from_str::<SpellSchoolSet>(r#"["Fire","Shadow"]"#).unwrap(), SpellSchoolSet::FIRE | SpellSchoolSet::SHADOW);
     }
 
     #[test]
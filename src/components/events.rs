@@ -1,121 +1,331 @@
-use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::Arc;
 
-use anyhow::{Context, Result};
 use chrono::NaiveDateTime;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
 use crate::components::{
     advanced::AdvancedParams,
-    common::Actor,
+    common::{actor_fields, Actor, SpellInfo},
+    enums::{EnvironmentalType, SpellSchoolSet},
+    format::LogFormat,
+    guid::GUID,
+    names::{EventName, PrefixKind, SuffixKind},
     prefixes::Prefix,
     special,
     suffixes::Suffix,
 };
+use crate::context::SessionContext;
+use crate::error::{ComponentError, Context, ParseError, Result};
+use crate::utils::{Fields, intern};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum EventType {
     Special {
-        name: String,
+        name: Arc<str>,
         details: special::Special,
     },
     Standard {
-        name: String,
+        name: Arc<str>,
         source: Option<Actor>,
         target: Option<Actor>,
-        prefix: Prefix,
-        advanced_params: Option<AdvancedParams>,
+        prefix: Box<Prefix>,
+        advanced_params: Option<Box<AdvancedParams>>,
         suffix: Suffix,
+        /// The Augmentation Evoker whose buff part of the damage or healing came from, on _SUPPORT events
+        supporter: Option<Box<GUID>>,
     },
+    /// ENVIRONMENTAL_DAMAGE, eg. falling or standing in lava. Kept apart from standard events as its advanced params
+    /// come before the environment type, where every other event has its spell info first.
+    EnvironmentalDamage {
+        source: Option<Actor>,
+        target: Option<Actor>,
+        advanced_params: Option<Box<AdvancedParams>>,
+        env_type: EnvironmentalType,
+        suffix: Suffix,
+    },
+    /// An event the parser doesn't know about, kept as it is in lenient mode
+    Unknown {
+        name: Arc<str>,
+        /// The fields after the event type as they were written, quoted ones with their quotes
+        raw_fields: Vec<String>,
+    },
+    /// A known event whose line was cut short, kept in recovery mode
+    Partial(PartialEvent),
+}
+
+/// What could be parsed of a line that ends early, eg. the last one written before the game crashed
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PartialEvent {
+    pub name: Arc<str>,
+    /// The actors & spell, for events which have them & got that far
+    pub source: Option<Actor>,
+    pub target: Option<Actor>,
+    pub spell: Option<SpellInfo>,
+    /// The fields after the event type as they were written, quoted ones with their quotes
+    pub raw_fields: Vec<String>,
+}
+
+impl PartialEvent {
+    /// `raw` is the same fields as `line`, as they were written
+    fn parse(name: &str, line: &[&str], raw: &[&str]) -> Self {
+        let actor = |fields: Option<&[&str]>| fields.and_then(|f| Actor::parse(f).ok().flatten());
+        let spell = EventName::parse(name).ok()
+            .filter(|n| n.prefix.log_fields() == 3)
+            .and_then(|_| SpellInfo::parse(line.get(8..11)?).ok());
+
+        Self {
+            name: intern(name),
+            source: actor(line.get(..4)),
+            target: actor(line.get(4..8)),
+            spell,
+            raw_fields: raw.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+}
+
+/// Advanced params as written in the log, when they're logged at all
+fn advanced_fields(advanced_params: &Option<Box<AdvancedParams>>, format: &LogFormat) -> Vec<String> {
+    match (advanced_params, format.advanced_params_len()) {
+        (Some(a), 1..) => a.to_log_fields(format),
+        _ => vec![],
+    }
+}
+
+/// Whether a SPELL_ABSORBED(_SUPPORT) line has the spell which was absorbed. Melee absorbs don't, so the absorb
+/// caster's GUID follows the target rather than a spell ID. The nil GUID is all digits, so isn't taken as one.
+fn absorb_has_spell_info(line: &[&str]) -> bool {
+    line.get(8).is_some_and(|field| *field != "0000000000000000" && u64::from_str(field).is_ok())
 }
 
 impl EventType {
-    fn parse(event_type: &str, line: &[&str]) -> Result<Self> {
+    fn parse(event_type: &str, line: &[&str], format: &LogFormat) -> Result<Self> {
+        if !format.rules().has_event(event_type) {
+            return Err(ComponentError::UnknownEventType(event_type.to_string()));
+        }
+
         // Match against any special events
-        let special = special::Special::parse(event_type, line)?;
+        let special = special::Special::parse(event_type, line, format)?;
         match special {
             // No match
             special::Special::NoneSentinel => {}
             // Valid match
             s => return Ok(Self::Special {
-                name: event_type.to_string(),
+                name: intern(event_type),
                 details: s,
             }),
         }
 
 
-        // match against standard but specially named events
-        let specially_named_events = HashMap::from([
-            ("DAMAGE_SPLIT", "SPELL_DAMAGE"),
-            ("DAMAGE_SHIELD", "SPELL_DAMAGE"),
-            ("DAMAGE_SHIELD_MISSED", "SPELL_MISSED"),
-            ("SWING_DAMAGE_LANDED_SUPPORT", "SPELL_DAMAGE_SUPPORT"),
-        ]);
-
-        let (name, event_type) = match specially_named_events.get(&event_type) {
-            None => (event_type, event_type),
-            Some(&val) => (event_type, val)
+        // Fallback to standard one
+        let event_name = EventName::parse(event_type)?;
+        let advanced_len = format.advanced_params_len();
+        let to_consume = match (event_name.prefix, event_name.suffix) {
+            // Special case: ABSORB may or may not contain spell info
+            (PrefixKind::Spell, SuffixKind::Absorbed | SuffixKind::AbsorbedSupport)
+            if !absorb_has_spell_info(line) => 0,
+            (prefix, _) => prefix.log_fields()
         };
 
-        // Fallback to standard one
+        let advanced_fields = if event_name.suffix.has_advanced_params() { advanced_len } else { 0 };
+        let expected = 8 + to_consume + advanced_fields + event_name.suffix.min_log_fields();
+        if line.len() < expected {
+            return Err(ComponentError::TooFewFields { event_type: event_type.to_string(), expected: expected + 1, got: line.len() + 1 });
+        }
+
         let source = Actor::parse(&line[..4])?;
-        let target = Actor::parse(&line[4..8])?;
+        let target = Actor::parse(&line[4..8]).map_err(|e| e.offset(4))?;
+
+        if event_name.prefix == PrefixKind::Environmental {
+            let advanced_end = 8 + advanced_len;
+            return Ok(Self::EnvironmentalDamage {
+                source,
+                target,
+                advanced_params: match advanced_len {
+                    0 => None,
+                    _ => Some(Box::new(AdvancedParams::parse(&line[8..advanced_end]).map_err(|e| e.offset(8))?)),
+                },
+                env_type: line.parse_with(advanced_end, EnvironmentalType::parse)?,
+                suffix: Suffix::parse(event_name.suffix, &line[advanced_end + 1..], format).map_err(|e| e.offset(advanced_end + 1))?,
+            });
+        }
 
-        let (prefix, advanced, offset) = if name == "ENVIRONMENTAL_DAMAGE" {
-            // ENVIRONMENTAL_DAMAGE has spellinfo & advanced params flipped order /facepalm/
-            let prefix = Prefix::parse(event_type, &line[25..26])?;
-            let advanced = Some(AdvancedParams::parse(&line[8..25])?);
+        let prefix = Prefix::parse(event_name.prefix, &line[8..8 + to_consume]).map_err(|e| e.offset(8))?;
+        let mut offset = 8 + to_consume;
 
-            (prefix, advanced, 26)
+        let advanced = if advanced_fields > 0 {
+            let a = AdvancedParams::parse(&line[offset..offset + advanced_len]).map_err(|e| e.offset(offset))?;
+            offset += advanced_len;
+            Some(Box::new(a))
         } else {
-            let to_consume = match event_type {
-                // Special case: ABSORB may or may not contain spell info
-                // we have no way to tell without attempting to parse and catching fails
-                e if (e == "SPELL_ABSORBED"
-                    || e == "SPELL_ABSORBED_SUPPORT")
-                    && u64::from_str(line[8]).is_err() => 0,
-                _ => Prefix::entries_to_consume(event_type)?
-            };
-
-            let prefix = Prefix::parse(event_type, &line[8..8 + to_consume])?;
-            let mut offset = 8 + to_consume;
-
-            let advanced = if Suffix::has_advanced_params(event_type)? {
-                let a = AdvancedParams::parse(&line[offset..offset + 17])?;
-                offset += 17;
-                Some(a)
-            } else {
-                None
-            };
-
-            (prefix, advanced, offset)
+            None
         };
 
 
-        let suffixes = Suffix::parse(event_type, &line[offset..])?;
+        let mut suffix_fields = line[offset..].to_vec();
+        let supporter_field = event_name.suffix.supporter_field().filter(|&i| i < suffix_fields.len());
+        let supporter = match supporter_field {
+            Some(i) => Some(Box::new(GUID::parse(suffix_fields.remove(i)).map_err(|e| e.in_field(offset + i))?
+                .context("Supporter GUID cannot be none")?)),
+            None => None,
+        };
+        let suffixes = Suffix::parse(event_name.suffix, &suffix_fields, format)
+            .map_err(|e| e.skipping(supporter_field).offset(offset))?;
 
         Ok(Self::Standard {
-            name: name.to_string(),
+            name: intern(event_type),
             source,
             target,
-            prefix,
+            prefix: Box::new(prefix),
             advanced_params: advanced,
             suffix: suffixes,
+            supporter,
         })
     }
+
+    /// As written in the log, eg. `SPELL_DAMAGE`
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Special { name, .. } | Self::Standard { name, .. } | Self::Unknown { name, .. } => name,
+            Self::Partial(partial) => &partial.name,
+            Self::EnvironmentalDamage { .. } => "ENVIRONMENTAL_DAMAGE",
+        }
+    }
+
+    /// The event name & its fields, inverse of `parse`
+    fn to_log_fields(&self, format: &LogFormat) -> Vec<String> {
+        match self {
+            Self::Special { name, details } => [vec![name.to_string()], details.to_log_fields()].concat(),
+            Self::Standard { name, source, target, prefix, advanced_params, suffix, supporter } => {
+                let mut suffix_fields = suffix.to_log_fields();
+                if let Some(supporter) = supporter {
+                    let i = EventName::parse(name).ok().and_then(|n| n.suffix.supporter_field()).unwrap_or(suffix_fields.len());
+                    suffix_fields.insert(i.min(suffix_fields.len()), supporter.to_string());
+                }
+
+                [vec![name.to_string()], actor_fields(source), actor_fields(target), prefix.to_log_fields(), advanced_fields(advanced_params, format), suffix_fields].concat()
+            }
+            Self::EnvironmentalDamage { source, target, advanced_params, env_type, suffix } => [
+                vec![self.name().to_string()],
+                actor_fields(source),
+                actor_fields(target),
+                advanced_fields(advanced_params, format),
+                vec![format!("{:?}", env_type)],
+                suffix.to_log_fields(),
+            ].concat(),
+            Self::Unknown { name, raw_fields } | Self::Partial(PartialEvent { name, raw_fields, .. }) =>
+                [vec![name.to_string()], raw_fields.clone()].concat(),
+        }
+    }
 }
 
 
-#[derive(Debug)]
+/// The schools an event carries: the spell's own school from the prefix, and the school of the hit from the suffix
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Schools {
+    pub spell: Option<SpellSchoolSet>,
+    pub hit: Option<SpellSchoolSet>,
+}
+
+impl Schools {
+    /// School the hit actually landed as, falling back to the spell's school
+    pub fn effective(&self) -> Option<SpellSchoolSet> {
+        self.hit.or(self.spell)
+    }
+
+    /// Whether the hit landed as a different school to the spell's, eg. through a conversion effect
+    pub fn mismatched(&self) -> bool {
+        match (self.spell, self.hit) {
+            (Some(spell), Some(hit)) => spell != hit,
+            _ => false,
+        }
+    }
+}
+
+impl EventType {
+    /// Both schools of a standard event. Environmental damage only has the hit's, & special events have none
+    pub fn schools(&self) -> Schools {
+        match self {
+            Self::Standard { prefix, suffix, .. } => Schools {
+                spell: prefix.spell_info().map(|s| s.spell_school),
+                hit: suffix.school(),
+            },
+            Self::EnvironmentalDamage { suffix, .. } => Schools { spell: None, hit: suffix.school() },
+            Self::Partial(partial) => Schools { spell: partial.spell.as_ref().map(|s| s.spell_school), hit: None },
+            Self::Special { .. } | Self::Unknown { .. } => Schools { spell: None, hit: None },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Event {
     pub timestamp: NaiveDateTime,
     pub event_type: EventType,
+    /// Zone, encounter etc. as of this event, attached by the parser
+    #[serde(skip)]
+    pub context: Arc<SessionContext>,
 }
 
 impl Event {
-    pub(crate) fn parse(line: &[&str]) -> Result<Self> {
-        // Split timestamp & event type
-        let (timestamp, event_type) = if line[0] == "COMBAT_LOG_VERSION" {
+    /// Parses a line from a current retail log
+    pub(crate) fn parse(line: &[&str]) -> Result<Self, ParseError> {
+        Self::parse_with_format(line, &LogFormat::default())
+    }
+
+    pub(crate) fn parse_with_format(line: &[&str], format: &LogFormat) -> Result<Self, ParseError> {
+        let (timestamp, event_type) = Self::split_timestamp(line)?;
+
+        Ok(Self {
+            timestamp,
+            event_type: EventType::parse(event_type, &line[1..], format)
+                .map_err(|e| ParseError::from_component(e.offset(1), line))?,
+            context: SessionContext::empty(),
+        })
+    }
+
+    /// Keeps the fields of a line with an unknown event type as they are, so `line` should be the fields as written
+    pub(crate) fn parse_unknown(line: &[&str]) -> Result<Self, ParseError> {
+        let (timestamp, event_type) = Self::split_timestamp(line)?;
+
+        Ok(Self {
+            timestamp,
+            event_type: EventType::Unknown {
+                name: intern(event_type),
+                raw_fields: line[1..].iter().map(|f| f.to_string()).collect(),
+            },
+            context: SessionContext::empty(),
+        })
+    }
+
+    /// Keeps what can be parsed of a known event whose line ends early, along with its fields as written in `raw`
+    pub(crate) fn parse_partial(line: &[&str], raw: &[&str]) -> Result<Self, ParseError> {
+        let (timestamp, event_type) = Self::split_timestamp(line)?;
+
+        Ok(Self {
+            timestamp,
+            event_type: EventType::Partial(PartialEvent::parse(event_type, &line[1..], &raw[1..])),
+            context: SessionContext::empty(),
+        })
+    }
+
+    /// Fails the event if parsing it didn't use exactly all of the line's fields.
+    /// Serialising writes every field the event was parsed from, so the count is checked against that.
+    pub(crate) fn check_field_count(&self, line: &[&str], format: &LogFormat) -> Result<(), ParseError> {
+        let expected = self.event_type.to_log_fields(format).len();
+        if expected == line.len() { return Ok(()); }
+
+        Err(ParseError::FieldCount {
+            event_type: self.event_type.name().to_string(),
+            expected,
+            got: line.len(),
+            raw: line.join(","),
+        })
+    }
+
+    /// Splits the timestamp & event type from the first field
+    fn split_timestamp<'a>(line: &[&'a str]) -> Result<(NaiveDateTime, &'a str), ParseError> {
+        Ok(if line[0] == "COMBAT_LOG_VERSION" {
             (
                 NaiveDateTime::parse_from_str("2024/01/01 00:00:00.000", "%Y/%_m/%d %H:%M:%S%.3f").unwrap(),
                 line[0]
@@ -123,49 +333,69 @@ impl Event {
         } else {
             let (date, event_type) = line[0].splitn(2, "  ")
                 .collect_tuple()
-                .with_context(|| format!("Error splitting date & event type: {}", line[0]))?;
+                .ok_or_else(|| ParseError::Malformed {
+                    reason: "Error splitting date & event type".to_string(),
+                    raw: line.join(","),
+                })?;
 
             // todo: horrible hacky way of date parsing
-            let date = ["2024/ ", date].join("");
-            let datetime = NaiveDateTime::parse_from_str(date.as_str(), "%Y/%_m/%d %H:%M:%S%.3f")
-                .with_context(|| "Failed to parse date.")?;
+            let datetime = NaiveDateTime::parse_from_str(["2024/ ", date].join("").as_str(), "%Y/%_m/%d %H:%M:%S%.3f")
+                .map_err(|_| ParseError::BadTimestamp { got: date.to_string(), raw: line.join(",") })?;
 
             (datetime, event_type)
-        };
-
-        Ok(Self {
-            timestamp,
-            event_type: EventType::parse(event_type, &line[1..])
-                .with_context(|| format!("Error parsing line: {:?}", line))?,
         })
     }
+
+    /// Serialises back to a line of a current retail log, without the newline
+    pub fn to_log_line(&self) -> String {
+        self.to_log_line_with_format(&LogFormat::default())
+    }
+
+    /// Serialises back to a log line. This is the original text for everything the parser keeps, but
+    /// fields it skips are written with placeholder values, eg. the amounts of non-absorb misses.
+    pub fn to_log_line_with_format(&self, format: &LogFormat) -> String {
+        format!("{}  {}", self.timestamp.format("%-m/%-d %H:%M:%S%.3f"), self.event_type.to_log_fields(format).join(","))
+    }
+}
+
+/// The event as a retail log line
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_log_line())
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::components::events::{Event, EventType};
+    use crate::components::enums::{EnvironmentalType, SpellSchoolSet};
+    use crate::components::events::{absorb_has_spell_info, Event, EventType};
+    use crate::components::format::{LogFormat, PROJECT_WRATH_CLASSIC};
+    use crate::components::guid::GUID;
+    use crate::components::names::{EventName, PrefixKind, SuffixKind};
+    use crate::components::suffixes::Suffix;
+    use crate::parser::EventParser;
 
     #[test]
     fn parse_event_type() {
         let event_type = "COMBAT_LOG_VERSION";
         let line = vec!["20", "ADVANCED_LOG_ENABLED", "1", "BUILD_VERSION", "10.2.6", "PROJECT_ID", "1"];
-        let parsed = EventType::parse(event_type, &line);
+        let parsed = EventType::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
 
         let event_type = "SPELL_PERIODIC_HEAL";
         let line = vec!["Player-1393-077C088C", "Mubaku-BronzeDragonflight", "0x514", "0x0", "Creature-0-1469-2549-12530-210177-000011428F", "Tormented Ancient", "0xa18", "0x0", "8936", "Regrowth", "0x8", "Creature-0-1469-2549-12530-210177-000011428F", "0000000000000000", "5927873", "7468728", "0", "0", "5043", "0", "1", "0", "0", "0", "3295.44", "13209.11", "2232", "3.4506", "72", "2557", "2557", "0", "0", "nil"];
-        let parsed = EventType::parse(event_type, &line);
+        let parsed = EventType::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
 
         let event_type = "SPELL_CAST_SUCCESS";
         let line = vec!["Player-1329-09AF0ACF", "Adamthebash-Ravencrest", "0x511", "0x0", "0000000000000000", "nil", "0x80000000", "0x80000000", "1850", "Dash", "0x1", "Player-1329-09AF0ACF", "0000000000000000", "846460", "846460", "16429", "15797", "5313", "94077", "3", "100", "100", "0", "3110.69", "13146.01", "2232", "0.7478", "486"];
-        let parsed = EventType::parse(event_type, &line);
+        let parsed = EventType::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
 
         let event_type = "SPELL_AURA_REMOVED";
         let line = vec!["Player-1084-0934CD1D", "Neversman-TarrenMill", "0x514", "0x0", "Player-1379-0814BAB7", "Kuro-Zul'jin", "0x40512", "0x4", "6673", "Battle Shout", "0x1", "BUFF"];
-        let parsed = EventType::parse(event_type, &line);
+        let parsed = EventType::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
     }
 
@@ -188,6 +418,26 @@ mod tests {
         println!("{:?}", parsed.unwrap());
     }
 
+    #[test]
+    fn schools() {
+        // Shadowflame spell landing as pure shadow
+        let line = vec!["4/11 23:52:57.070  SPELL_DAMAGE", "Creature-0-1469-2549-12091-204931-0000186743", "Fyrakk", "0x10a48", "0x0", "Player-1390-0C4E032E", "Stillnixx-Hyjal", "0x514", "0x0", "423720", "Blazing Seed", "0x24", "Player-1390-0C4E032E", "0000000000000000", "306419", "834740", "2104", "22733", "3088", "0", "0", "196960", "250000", "0", "-2159.06", "7174.82", "2238", "4.5667", "481", "14260", "14260", "-1", "32", "0", "0", "0", "nil", "nil", "nil"];
+        let event = Event::parse(&line).unwrap();
+        let schools = event.event_type.schools();
+        assert_eq!(schools.spell, Some(SpellSchoolSet::FIRE | SpellSchoolSet::SHADOW));
+        assert_eq!(schools.hit, Some(SpellSchoolSet::SHADOW));
+        assert_eq!(schools.effective(), Some(SpellSchoolSet::SHADOW));
+        assert!(schools.mismatched());
+
+        // Melee only has the hit's school
+        let line = vec!["4/6 14:01:12.000  SWING_DAMAGE", "Player-1335-0A264B4C", "Sønike-Ysondre", "0x514", "0x0", "Creature-0-1469-2549-12530-209333-000011428A", "Gnarlroot", "0x10a48", "0x0", "Player-1335-0A264B4C", "0000000000000000", "0", "100", "0", "0", "0", "0", "0", "0", "0", "0", "0.00", "0.00", "2232", "0.0000", "70", "6000", "6000", "-1", "1", "0", "0", "0", "nil", "nil", "nil"];
+        let event = Event::parse(&line).unwrap();
+        let schools = event.event_type.schools();
+        assert_eq!(schools.spell, None);
+        assert_eq!(schools.effective(), Some(SpellSchoolSet::PHYSICAL));
+        assert!(!schools.mismatched());
+    }
+
     #[test]
     fn parse_emote_player() {
         let line = vec!["4/11 22:19:57.499  EMOTE", "Creature-0-1465-2444-137-194909-00009853CD", "Feather-Ruffling Duck", "0000000000000000", "nil", "Take control of the Feather Ruffling Duck!"];
@@ -205,8 +455,12 @@ mod tests {
     #[test]
     fn parse_env_damage() {
         let line = vec!["4/11 22:42:01.100  ENVIRONMENTAL_DAMAGE", "0000000000000000", "nil", "0x80000000", "0x80000000", "Player-1329-070EBCFC", "Naladrem-Ravencrest", "0x518", "0x0", "Player-1329-070EBCFC", "0000000000000000", "815216", "866544", "14879", "1421", "5217", "0", "17", "109", "120", "0", "-931.46", "2546.12", "2133", "4.8479", "484", "Falling", "51328", "51328", "0", "1", "0", "0", "0", "nil", "nil", "nil"];
-        let parsed = Event::parse(&line);
-        println!("{:?}", parsed.unwrap());
+        let parsed = Event::parse(&line).unwrap();
+        let EventType::EnvironmentalDamage { target: Some(target), advanced_params: Some(_), env_type: EnvironmentalType::Falling, suffix: Suffix::Damage { amount: 51328, .. }, .. } = &parsed.event_type
+            else { panic!("{:?}", parsed) };
+        assert_eq!(target.name, "Naladrem-Ravencrest");
+        assert_eq!(parsed.event_type.name(), "ENVIRONMENTAL_DAMAGE");
+        parsed.check_field_count(&line, &LogFormat::default()).unwrap();
     }
 
     #[test]
@@ -216,6 +470,15 @@ mod tests {
         println!("{:?}", parsed.unwrap());
     }
 
+    #[test]
+    fn parse_bres_bad_owner() {
+        // Same as above, but with a cut off owner GUID in the advanced params
+        let line = vec!["4/11 22:38:54.708  SPELL_CAST_SUCCESS", "Player-1329-09AF0ACF", "Adamthebash-Ravencrest", "0x511", "0x0", "Corpse-0-1465-2454-103-0-000018584E", "Unknown", "0x4228", "0x0", "20484", "Rebirth", "0x8", "Player-1329-09AF0ACF", "Corpse-0-1465", "732698", "846460", "16347", "15718", "5632", "0", "0", "250000", "250000", "5000", "66.53", "3330.43", "2133", "4.7368", "486"];
+        let parsed = Event::parse(&line).unwrap();
+        let EventType::Standard { advanced_params: Some(advanced), .. } = parsed.event_type else { panic!("{:?}", parsed) };
+        assert_eq!(advanced.owner_guid, Some(GUID::Unknown("Corpse-0-1465".to_string())));
+    }
+
     #[test]
     fn parse_spell_negative() {
         let line = vec!["4/11 23:46:16.867  SPELL_DAMAGE", "Player-604-0A77B54A", "Sangrenar-Thrall", "0x514", "0x0", "Creature-0-1469-2549-12091-204931-0000186743", "Fyrakk", "0x10a48", "0x0", "203796", "Demon Blades", "0x20", "Creature-0-1469-2549-12091-204931-0000186743", "0000000000000000", "758517319", "770131200", "0", "-2435", "5043", "0", "3", "11", "100", "0", "-2161.04", "7142.32", "2238", "0.5034", "73", "16857", "6079", "-1", "127", "0", "0", "0", "1", "nil", "nil"];
@@ -240,8 +503,10 @@ mod tests {
     #[test]
     fn parse_spell_dam_support() {
         let line = vec!["2/15 20:32:16.706  SPELL_DAMAGE_SUPPORT", "Player-1329-0A00AB32", "Twigsneak-Ravencrest", "0x514", "0x0", "Creature-0-4233-2549-14868-200927-00004E626C", "Smolderon", "0x10a48", "0x0", "410089", "Prescience", "0x40", "Creature-0-4233-2549-14868-200927-00004E626C", "0000000000000000", "1439613911", "1442829510", "0", "0", "5043", "0", "3", "3", "100", "0", "4043.26", "13109.35", "2233", "2.9862", "73", "163", "73", "-1", "8", "0", "0", "0", "1", "nil", "nil", "Player-1329-09E79FE9"];
-        let parsed = Event::parse(&line);
-        println!("{:?}", parsed.unwrap());
+        let parsed = Event::parse(&line).unwrap();
+        let EventType::Standard { supporter, .. } = &parsed.event_type else { panic!("{:?}", parsed) };
+        assert_eq!(supporter.as_deref(), GUID::parse("Player-1329-09E79FE9").unwrap().as_ref());
+        parsed.check_field_count(&line, &LogFormat::default()).unwrap();
     }
 
     #[test]
@@ -299,4 +564,91 @@ mod tests {
         let parsed = Event::parse(&line);
         println!("{:?}", parsed.unwrap());
     }
+
+    #[test]
+    fn absorb_layouts() {
+        let target = ["Creature-0-1469-2549-12530-209333-000011428A", "Gnarlroot", "0x10a48", "0x0", "Player-1329-0A0800FA", "Foxgates-Ravencrest", "0x514", "0x0"];
+        let spell = ["421971", "Controlled Burn", "0x4"];
+        let absorb = ["Player-1329-0A0800FA", "Foxgates-Ravencrest", "0x514", "0x0", "108366", "Soul Leech", "0x20", "202", "4000", "nil"];
+        let nil_caster = ["0000000000000000", "nil", "0x80000000", "0x80000000", "108366", "Soul Leech", "0x20", "202", "4000", "nil"];
+
+        for (spell, absorb) in [(&spell[..], absorb), (&[], absorb), (&spell[..], nil_caster), (&[], nil_caster)] {
+            for support in [None, Some("Player-1329-09E79FE9")] {
+                let line = [&target[..], spell, &absorb, support.as_slice()].concat();
+                assert_eq!(absorb_has_spell_info(&line), !spell.is_empty(), "{:?}", line);
+
+                let name = if support.is_some() { "SPELL_ABSORBED_SUPPORT" } else { "SPELL_ABSORBED" };
+                let event_type = EventType::parse(name, &line, &LogFormat::default());
+                if absorb == nil_caster {
+                    // Not a spell ID, but there's no absorb caster either
+                    assert!(event_type.is_err());
+                    continue;
+                }
+                let EventType::Standard { prefix, .. } = &event_type.unwrap() else { panic!("{:?}", line) };
+                assert_eq!(prefix.spell_info().is_some(), !spell.is_empty(), "{:?}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn parse_periodic_and_building() {
+        let absorbed = vec!["1/21 19:36:18.613  SPELL_PERIODIC_ABSORBED", "Creature-0-1469-2549-12530-209333-000011428A", "Gnarlroot", "0x10a48", "0x0", "Player-1329-0A0800FA", "Foxgates-Ravencrest", "0x514", "0x0", "421971", "Controlled Burn", "0x4", "Player-1329-0A0800FA", "Foxgates-Ravencrest", "0x514", "0x0", "108366", "Soul Leech", "0x20", "202", "4000", "nil"];
+        let cast = vec!["4/11 22:38:54.708  SPELL_PERIODIC_CAST_SUCCESS", "Player-1329-09AF0ACF", "Adamthebash-Ravencrest", "0x511", "0x0", "0000000000000000", "nil", "0x80000000", "0x80000000", "1850", "Dash", "0x1", "Player-1329-09AF0ACF", "0000000000000000", "846460", "846460", "16429", "15797", "5313", "94077", "3", "100", "100", "0", "3110.69", "13146.01", "2232", "0.7478", "486"];
+        let building = vec!["4/6 14:02:07.362  SPELL_BUILDING_DAMAGE", "Player-1335-0A264B4C", "Sønike-Ysondre", "0x514", "0x0", "Vehicle-0-1469-2549-12530-28094-000011428A", "Demolisher", "0xa48", "0x0", "8921", "Moonfire", "0x40", "Vehicle-0-1469-2549-12530-28094-000011428A", "0000000000000000", "100", "1000", "0", "0", "0", "0", "0", "0", "0", "0", "0.00", "0.00", "2232", "0.0000", "72", "5000", "5000", "-1", "64", "0", "0", "0", "nil", "nil", "nil"];
+
+        for (line, prefix, suffix) in [
+            (absorbed, PrefixKind::SpellPeriodic, SuffixKind::Absorbed),
+            (cast, PrefixKind::SpellPeriodic, SuffixKind::CastSuccess),
+            (building, PrefixKind::SpellBuilding, SuffixKind::Damage),
+        ] {
+            let event = Event::parse(&line).unwrap();
+            let EventType::Standard { name, prefix: p, suffix: s, .. } = &event.event_type else { panic!("{:?}", event) };
+            let parsed = EventName::parse(name).unwrap();
+            assert_eq!((parsed.prefix, parsed.suffix), (prefix, suffix), "{}", name);
+            assert_eq!(p.spell_info().unwrap().spell_id.to_string(), line[9], "{}", name);
+            assert!(s.school().is_some() || suffix != SuffixKind::Damage);
+            event.check_field_count(&line, &LogFormat::default()).unwrap();
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let log = r#"4/6 14:00:00.000  COMBAT_LOG_VERSION,20,ADVANCED_LOG_ENABLED,1,BUILD_VERSION,10.2.6,PROJECT_ID,1
+4/6 14:00:01.000  ZONE_CHANGE,2549,"Amirdrassil, the Dream's Hope",16
+4/6 14:00:02.000  MAP_CHANGE,2232,"Amirdrassil",3800.000000,3000.000000,13725.000000,12525.000000
+4/6 14:01:05.000  ENCOUNTER_START,2820,"Gnarlroot",14,19,2549
+4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,"Sønike-Ysondre",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,"Gnarlroot",0x10a48,0x0,MISS,1
+4/6 14:09:44.867  SPELL_PERIODIC_HEAL,Player-1393-077C088C,"Mubaku-BronzeDragonflight",0x514,0x0,Creature-0-1469-2549-12530-210177-000011428F,"Tormented Ancient",0xa18,0x0,8936,"Regrowth",0x8,Creature-0-1469-2549-12530-210177-000011428F,0000000000000000,5927873,7468728,0,0,5043,0,1,0,0,0,3295.44,13209.11,2232,3.4506,72,2557,2557,0,0,nil
+4/6 14:09:45.000  SPELL_CAST_SUCCESS,Player-1335-0A264B4C,"Sønike-Ysondre",0x514,0x0,0000000000000000,nil,0x80000000,0x80000000,1850,"Dash",0x1,Player-1335-0A264B4C,0000000000000000,621960,621960,12071,1488,4067,0,3|4,43|6,300|6,25|6,3471.75,13115.98,2232,0.4119,455
+4/6 14:09:45.100  SPELL_PERIODIC_ENERGIZE,Player-1393-077C088C,"Mubaku-BronzeDragonflight",0x514,0x0,Player-1393-077C088C,"Mubaku-BronzeDragonflight",0x514,0x0,8936,"Regrowth",0x8,Player-1393-077C088C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,1.0000,0.0000,5,6
+4/6 14:09:45.200  SPELL_AURA_REMOVED,Player-1084-0934CD1D,"Neversman-TarrenMill",0x514,0x0,Player-1379-0814BAB7,"Kuro-Zul'jin",0x40512,0x4,6673,"Battle Shout",0x1,BUFF
+4/6 14:09:45.300  SPELL_AURA_APPLIED_DOSE,Player-1084-0934CD1D,"Neversman-TarrenMill",0x514,0x0,Player-1379-0814BAB7,"Kuro-Zul'jin",0x40512,0x4,6673,"Battle Shout",0x1,DEBUFF,3
+4/6 14:09:45.400  SPELL_CAST_FAILED,Player-1084-0934CD1D,"Neversman-TarrenMill",0x514,0x0,0000000000000000,nil,0x80000000,0x80000000,6673,"Battle Shout",0x1,"Not yet recovered"
+4/11 22:42:01.100  ENVIRONMENTAL_DAMAGE,0000000000000000,nil,0x80000000,0x80000000,Player-1329-070EBCFC,"Naladrem-Ravencrest",0x518,0x0,Player-1329-070EBCFC,0000000000000000,815216,866544,14879,1421,5217,0,17,109,120,0,-931.46,2546.12,2133,4.8479,484,Falling,51328,51328,0,1,0,0,0,nil,nil,nil
+4/11 23:52:57.070  SPELL_DAMAGE,Creature-0-1469-2549-12091-204931-0000186743,"Fyrakk",0x10a48,0x0,Player-1390-0C4E032E,"Stillnixx-Hyjal",0x514,0x0,423720,"Blazing Seed",0x24,Player-1390-0C4E032E,0000000000000000,306419,834740,2104,22733,3088,0,0,196960,250000,0,-2159.06,7174.82,2238,4.5667,481,-14260,144372,-1,36,0,0,85562,nil,nil,nil
+2/15 20:32:16.706  SPELL_DAMAGE_SUPPORT,Player-1329-0A00AB32,"Twigsneak-Ravencrest",0x514,0x0,Creature-0-4233-2549-14868-200927-00004E626C,"Smolderon",0x10a48,0x0,410089,"Prescience",0x40,Creature-0-4233-2549-14868-200927-00004E626C,0000000000000000,1439613911,1442829510,0,0,5043,0,3,3,100,0,4043.26,13109.35,2233,2.9862,73,163,73,-1,8,0,0,0,1,nil,nil,Player-1329-09E79FE9
+2/15 20:33:05.904  SPELL_ABSORBED_SUPPORT,Creature-0-4233-2549-14868-200927-00004E626C,"Smolderon",0x10a48,0x0,Player-1329-0A0800FA,"Foxgates-Ravencrest",0x512,0x0,422578,"Searing Aftermath",0x4,Player-1329-0A0800FA,"Foxgates-Ravencrest",0x512,0x0,413984,"Shifting Sands",0x40,1284,37144,nil,Player-1329-09E79FE9
+1/21 19:36:18.613  SPELL_ABSORBED,Player-1329-0A0800FA,"Foxgates-Ravencrest",0x514,0x0,Pet-0-1461-2548-10089-17252-01040EF8F7,"Khil'arad",0x1114,0x0,Player-1329-0A0800FA,"Foxgates-Ravencrest",0x514,0x0,108366,"Soul Leech",0x20,202,0,nil
+1/21 19:36:18.700  SPELL_HEAL_ABSORBED,Player-1329-0A0800FA,"Foxgates-Ravencrest",0x514,0x0,Player-1329-0A0800FA,"Foxgates-Ravencrest",0x514,0x0,422382,"Wild Growth",0x8,Creature-0-4233-2549-14868-54983-00004E66CB,"Treant",0x2114,0x0,422382,"Wild Growth",0x8,2585,2585
+4/11 23:57:17.207  COMBATANT_INFO,Player-1098-0500B8C6,1,12648,1734,52761,1128,0,0,0,3511,3511,3511,900,0,4692,4692,4692,443,6741,533,533,533,11302,251,[(76034,96162,1)],(1,204080,199719,233396),[(207200,489,(7052,0,0),(40,9513),(192961,415)),(0,0,(),(),()),(137311,483,(),(),())],[Player-1098-0500B8C6,396092,Player-1303-0B0DF865,389684],145,0,0,0
+1/31 23:26:12.705  CHALLENGE_MODE_START,"Black Rook Hold",1501,199,18,[9,134,11]
+4/12 20:00:00.000  EMOTE,Creature-0-4233-2549-14868-200927-00004E8C97,"Smolderon",0000000000000000,nil,"Emberscar attempts to devour you!"
+4/12 20:00:01.000  STAGGER_CLEAR,Player-1403-0A1B2C3D,48213.238281
+4/6 14:05:00.000  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Player-1390-0C4E032E,"Stillnixx-Hyjal",0x514,0x0,0
+4/6 14:12:00.000  ENCOUNTER_END,2820,"Gnarlroot",14,19,1,162742"#;
+
+        assert_eq!(EventParser::new(log.as_bytes()).count(), log.lines().count());
+        for (event, line) in EventParser::new(log.as_bytes()).zip(log.lines()) {
+            let event = event.unwrap();
+            assert_eq!(event.to_log_line(), line);
+            assert_eq!(event.to_string(), line);
+        }
+
+        // Classic logs don't have the absorb field
+        let line = "1/12 20:00:03.000  SPELL_HEAL,Player-4395-01C5EEAA,\"Priest-Whitemane\",0x514,0x0,Player-4395-01C5EEA8,\"Tank-Whitemane\",0x514,0x0,2060,\"Greater Heal\",0x2,Player-4395-01C5EEA8,0000000000000000,100,100,0,0,0,0,0,0,0,0.00,0.00,0,0.0000,60,2000,1800,200,0,nil";
+        let format = LogFormat::new(9, PROJECT_WRATH_CLASSIC, true);
+        let event = EventParser::with_format(line.as_bytes(), format).next().unwrap().unwrap();
+        assert_eq!(event.to_log_line_with_format(&format), line);
+    }
+
 }
\ No newline at end of file
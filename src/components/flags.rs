@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+
+/// COMBATLOG_OBJECT_AFFILIATION_*, relative to the player writing the log
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Affiliation {
+    Mine = 0x1,
+    Party = 0x2,
+    Raid = 0x4,
+    Outsider = 0x8,
+}
+
+/// COMBATLOG_OBJECT_REACTION_*, relative to the player writing the log
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Reaction {
+    Friendly = 0x10,
+    Neutral = 0x20,
+    Hostile = 0x40,
+}
+
+/// COMBATLOG_OBJECT_CONTROL_*
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Controller {
+    Player = 0x100,
+    Npc = 0x200,
+}
+
+/// COMBATLOG_OBJECT_TYPE_*
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnitType {
+    Player = 0x400,
+    Npc = 0x800,
+    Pet = 0x1000,
+    Guardian = 0x2000,
+    Object = 0x4000,
+}
+
+/// COMBATLOG_OBJECT_RAIDTARGET*, the raid target icon on a unit
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RaidMarker {
+    Star = 0x1,
+    Circle = 0x2,
+    Diamond = 0x4,
+    Triangle = 0x8,
+    Moon = 0x10,
+    Square = 0x20,
+    Cross = 0x40,
+    Skull = 0x80,
+}
+
+/// Finds the variant whose bit is set. Only one of each group is ever set by the game.
+fn decode<T: Copy>(flags: u64, variants: &[(T, u64)]) -> Option<T> {
+    variants.iter().find(|(_, bit)| flags & bit != 0).map(|(v, _)| *v)
+}
+
+/// Unit flags, decoded.
+/// https://warcraft.wiki.gg/wiki/UnitFlag
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct UnitFlags {
+    pub affiliation: Option<Affiliation>,
+    pub reaction: Option<Reaction>,
+    pub controller: Option<Controller>,
+    pub unit_type: Option<UnitType>,
+    /// The player's target, focus, or a main tank / assist
+    pub target: bool,
+    pub focus: bool,
+    pub main_tank: bool,
+    pub main_assist: bool,
+}
+
+impl UnitFlags {
+    pub fn decode(flags: u64) -> Self {
+        use Affiliation as A;
+        use Reaction as R;
+        use Controller as C;
+        use UnitType as U;
+
+        Self {
+            affiliation: decode(flags, &[A::Mine, A::Party, A::Raid, A::Outsider].map(|v| (v, v as u64))),
+            reaction: decode(flags, &[R::Friendly, R::Neutral, R::Hostile].map(|v| (v, v as u64))),
+            controller: decode(flags, &[C::Player, C::Npc].map(|v| (v, v as u64))),
+            unit_type: decode(flags, &[U::Player, U::Npc, U::Pet, U::Guardian, U::Object].map(|v| (v, v as u64))),
+            target: flags & 0x10000 != 0,
+            focus: flags & 0x20000 != 0,
+            main_tank: flags & 0x40000 != 0,
+            main_assist: flags & 0x80000 != 0,
+        }
+    }
+
+    /// In the log writer's party or raid, including themselves
+    pub fn in_group(&self) -> bool {
+        matches!(self.affiliation, Some(Affiliation::Mine | Affiliation::Party | Affiliation::Raid))
+    }
+}
+
+impl RaidMarker {
+    pub fn decode(raid_flags: u64) -> Option<Self> {
+        use RaidMarker as M;
+        decode(raid_flags, &[M::Star, M::Circle, M::Diamond, M::Triangle, M::Moon, M::Square, M::Cross, M::Skull].map(|v| (v, v as u64)))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::components::flags::{Affiliation, Controller, RaidMarker, Reaction, UnitFlags, UnitType};
+
+    #[test]
+    fn decode() {
+        let flags = UnitFlags::decode(0x514);
+        assert_eq!(flags, UnitFlags {
+            affiliation: Some(Affiliation::Raid),
+            reaction: Some(Reaction::Friendly),
+            controller: Some(Controller::Player),
+            unit_type: Some(UnitType::Player),
+            ..Default::default()
+        });
+        assert!(flags.in_group());
+
+        let flags = UnitFlags::decode(0x10a48);
+        assert_eq!((flags.reaction, flags.controller, flags.unit_type), (Some(Reaction::Hostile), Some(Controller::Npc), Some(UnitType::Npc)));
+        assert!(flags.target && !flags.in_group());
+
+        assert_eq!(UnitFlags::decode(0x80000000), UnitFlags::default());
+
+        assert_eq!(RaidMarker::decode(0x80), Some(RaidMarker::Skull));
+        assert_eq!(RaidMarker::decode(0x40), Some(RaidMarker::Cross));
+        assert_eq!(RaidMarker::decode(0x0), None);
+        // The "no object" sentinel has every bit set
+        assert_eq!(RaidMarker::decode(0x80000000), None);
+    }
+}
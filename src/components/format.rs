@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+
+use crate::components::names::{EventName, SuffixKind};
+
+/// PROJECT_ID values of the different game flavours
+pub const PROJECT_RETAIL: u64 = 1;
+pub const PROJECT_CLASSIC_ERA: u64 = 2;
+pub const PROJECT_WRATH_CLASSIC: u64 = 11;
+pub const PROJECT_CATA_CLASSIC: u64 = 14;
+
+/// How one game version lays out its events, & which events it has at all
+#[derive(Debug, PartialEq)]
+pub struct RuleSet {
+    pub name: &'static str,
+    /// The COMBAT_LOG_VERSION header the version writes
+    pub format: LogFormat,
+    /// Fields the advanced params take up, when advanced logging is enabled
+    pub advanced_params_len: usize,
+    /// Advanced params include the absorb field
+    pub advanced_absorb: bool,
+    /// Damage events end with whether the hit was single target or AoE
+    pub damage_kind: bool,
+    /// COMBATANT_INFO can have fields after the PvP stats, which are kept as they are
+    pub combatant_extras: bool,
+    /// Special events the version doesn't log
+    pub missing_specials: &'static [&'static str],
+    /// Standard event suffixes the version doesn't log
+    pub missing_suffixes: &'static [SuffixKind],
+}
+
+impl RuleSet {
+    /// Whether the version logs the event at all
+    pub fn has_event(&self, event_type: &str) -> bool {
+        !self.missing_specials.contains(&event_type)
+            && (self.missing_suffixes.is_empty()
+                || EventName::parse(event_type).map_or(true, |name| !self.missing_suffixes.contains(&name.suffix)))
+    }
+}
+
+/// Classic flavours (Era / Wrath / Cata): no absorb in the advanced params, and no Mythic+, monks or evokers
+pub const CLASSIC: RuleSet = RuleSet {
+    name: "classic-era",
+    format: LogFormat { log_version: 9, project_id: PROJECT_CLASSIC_ERA, advanced_log_enabled: true },
+    advanced_params_len: 16,
+    advanced_absorb: false,
+    damage_kind: false,
+    combatant_extras: false,
+    missing_specials: &["CHALLENGE_MODE_START", "CHALLENGE_MODE_END", "STAGGER_CLEAR", "STAGGER_PREVENTED"],
+    missing_suffixes: &[
+        SuffixKind::DamageSupport, SuffixKind::DamageLandedSupport, SuffixKind::HealSupport, SuffixKind::AbsorbedSupport,
+        SuffixKind::EmpowerStart, SuffixKind::EmpowerEnd, SuffixKind::EmpowerInterrupt,
+    ],
+};
+
+/// Retail up to COMBAT_LOG_VERSION 20
+pub const DRAGONFLIGHT: RuleSet = RuleSet {
+    name: "10.2",
+    format: LogFormat { log_version: 20, project_id: PROJECT_RETAIL, advanced_log_enabled: true },
+    advanced_params_len: 17,
+    advanced_absorb: true,
+    damage_kind: false,
+    combatant_extras: false,
+    missing_specials: &[],
+    missing_suffixes: &[],
+};
+
+/// Retail from COMBAT_LOG_VERSION 21
+pub const THE_WAR_WITHIN: RuleSet = RuleSet {
+    name: "11.0",
+    format: LogFormat { log_version: 21, project_id: PROJECT_RETAIL, advanced_log_enabled: true },
+    damage_kind: true,
+    combatant_extras: true,
+    ..DRAGONFLIGHT
+};
+
+/// Layout of the log, derived from the COMBAT_LOG_VERSION header.
+/// Classic flavours (Era / Wrath / Cata) lay out some fields differently to retail.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LogFormat {
+    pub log_version: u64,
+    pub project_id: u64,
+    pub advanced_log_enabled: bool,
+}
+
+impl Default for LogFormat {
+    /// Logs without a header are assumed to be current retail
+    fn default() -> Self {
+        Self { log_version: 20, project_id: PROJECT_RETAIL, advanced_log_enabled: true }
+    }
+}
+
+impl LogFormat {
+    pub fn new(log_version: u64, project_id: u64, advanced_log_enabled: bool) -> Self {
+        Self { log_version, project_id, advanced_log_enabled }
+    }
+
+    pub fn is_classic(&self) -> bool {
+        self.project_id != PROJECT_RETAIL
+    }
+
+    /// The rules of the game version which writes this header
+    pub fn rules(&self) -> &'static RuleSet {
+        match (self.is_classic(), self.log_version) {
+            (true, _) => &CLASSIC,
+            (false, 21..) => &THE_WAR_WITHIN,
+            (false, _) => &DRAGONFLIGHT,
+        }
+    }
+
+    pub fn has_advanced_absorb(&self) -> bool {
+        self.rules().advanced_absorb
+    }
+
+    /// Number of fields the advanced params take up, 0 if advanced logging is disabled
+    pub fn advanced_params_len(&self) -> usize {
+        if self.advanced_log_enabled { self.rules().advanced_params_len } else { 0 }
+    }
+
+    pub fn has_damage_kind(&self) -> bool {
+        self.rules().damage_kind
+    }
+
+    pub fn has_combatant_extras(&self) -> bool {
+        self.rules().combatant_extras
+    }
+}
+
+/// Game versions whose layout can be forced, for logs with a missing or wrong COMBAT_LOG_VERSION header
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum GameVersion {
+    /// Dragonflight
+    #[cfg_attr(feature = "cli", value(name = "10.2"))]
+    Dragonflight,
+    /// The War Within
+    #[cfg_attr(feature = "cli", value(name = "11.0"))]
+    TheWarWithin,
+    #[cfg_attr(feature = "cli", value(name = "classic-era"))]
+    ClassicEra,
+}
+
+impl GameVersion {
+    pub fn rules(&self) -> &'static RuleSet {
+        match self {
+            Self::Dragonflight => &DRAGONFLIGHT,
+            Self::TheWarWithin => &THE_WAR_WITHIN,
+            Self::ClassicEra => &CLASSIC,
+        }
+    }
+
+    pub fn format(&self) -> LogFormat {
+        self.rules().format
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::components::enums::DamageKind;
+    use crate::components::events::EventType;
+    use crate::components::format::{GameVersion, LogFormat, PROJECT_RETAIL, PROJECT_WRATH_CLASSIC, THE_WAR_WITHIN};
+    use crate::components::suffixes::Suffix;
+    use crate::error::ParseError;
+    use crate::parser::EventParser;
+
+    #[test]
+    fn advanced_params_len() {
+        assert_eq!(LogFormat::default().advanced_params_len(), 17);
+        assert_eq!(LogFormat::new(9, PROJECT_WRATH_CLASSIC, true).advanced_params_len(), 16);
+        assert_eq!(LogFormat::new(20, PROJECT_RETAIL, false).advanced_params_len(), 0);
+    }
+
+    #[test]
+    fn advanced_log_disabled() {
+        let log = "4/6 14:09:40.000  COMBAT_LOG_VERSION,20,ADVANCED_LOG_ENABLED,0,BUILD_VERSION,10.2.6,PROJECT_ID,1\n\
+4/6 14:09:45.200  SPELL_DAMAGE,Player-604-0A77B54A,\"Sangrenar-Thrall\",0x514,0x0,Creature-0-1469-2549-12091-204931-0000186743,\"Fyrakk\",0x10a48,0x0,203796,\"Demon Blades\",0x20,16857,6079,-1,127,0,0,0,1,nil,nil\n\
+4/6 14:09:45.800  ENVIRONMENTAL_DAMAGE,0000000000000000,nil,0x80000000,0x80000000,Player-1329-070EBCFC,\"Naladrem-Ravencrest\",0x518,0x0,Falling,51328,51328,0,1,0,0,0,nil,nil,nil\n";
+
+        let events = EventParser::new(log.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        for event in &events[1..] {
+            assert!(matches!(event.event_type,
+                EventType::Standard { advanced_params: None, .. } | EventType::EnvironmentalDamage { advanced_params: None, .. }));
+        }
+    }
+
+    #[test]
+    fn classic_log() {
+        let log = "9/28 20:15:41.000  COMBAT_LOG_VERSION,9,ADVANCED_LOG_ENABLED,1,BUILD_VERSION,3.4.3,PROJECT_ID,11\n\
+9/28 20:15:41.735  SPELL_DAMAGE,Player-4395-01C5EEA6,\"Xyz-Whitemane\",0x512,0x0,Creature-0-4395-615-22-30452-00001A2B3C,\"Tenebron\",0x10a48,0x0,57591,\"Vengeance\",0x2,Creature-0-4395-615-22-30452-00001A2B3C,0000000000000000,3487744,3500000,0,0,0,-1,0,0,0,3262.87,532.15,0,1.6829,83,12256,12255,-1,2,0,0,0,nil,nil,nil\n";
+
+        let mut parser = EventParser::new(log.as_bytes());
+        parser.next().unwrap().unwrap();
+        assert!(parser.log_format().is_classic());
+        parser.next().unwrap().unwrap();
+    }
+
+    #[test]
+    fn forced_game_version() {
+        // A classic fragment, without its header
+        let log = "9/28 20:15:41.735  SPELL_DAMAGE,Player-4395-01C5EEA6,\"Xyz-Whitemane\",0x512,0x0,Creature-0-4395-615-22-30452-00001A2B3C,\"Tenebron\",0x10a48,0x0,57591,\"Vengeance\",0x2,Creature-0-4395-615-22-30452-00001A2B3C,0000000000000000,3487744,3500000,0,0,0,-1,0,0,0,3262.87,532.15,0,1.6829,83,12256,12255,-1,2,0,0,0,nil,nil,nil\n";
+        assert!(EventParser::new(log.as_bytes()).next().unwrap().is_err());
+
+        let mut parser = EventParser::new(log.as_bytes()).with_game_version(Some(GameVersion::ClassicEra));
+        parser.next().unwrap().unwrap();
+
+        // A forced version isn't switched away from by a header
+        let log = format!("9/28 20:15:41.000  COMBAT_LOG_VERSION,20,ADVANCED_LOG_ENABLED,1,BUILD_VERSION,10.2.6,PROJECT_ID,1\n{}", log);
+        let mut parser = EventParser::new(log.as_bytes()).with_game_version(Some(GameVersion::ClassicEra));
+        assert!(parser.all(|e| e.is_ok()));
+    }
+
+    #[test]
+    fn rule_sets() {
+        assert_eq!(LogFormat::new(21, PROJECT_RETAIL, true).rules(), &THE_WAR_WITHIN);
+        assert_eq!(LogFormat::new(9, PROJECT_WRATH_CLASSIC, true).rules().name, "classic-era");
+        for version in [GameVersion::Dragonflight, GameVersion::TheWarWithin, GameVersion::ClassicEra] {
+            assert_eq!(version.format().rules(), version.rules());
+        }
+        assert_ne!(GameVersion::Dragonflight.rules(), GameVersion::TheWarWithin.rules());
+
+        // Events which came after classic are unknown to it
+        let log = "1/31 23:26:12.705  CHALLENGE_MODE_START,\"Black Rook Hold\",1501,199,18,[9,134,11]\n\
+1/31 23:26:13.000  SPELL_EMPOWER_START,Player-1329-0A00AB32,\"Twigsneak-Ravencrest\",0x514,0x0,0000000000000000,nil,0x80000000,0x80000000,357208,\"Fire Breath\",0x4\n";
+        assert!(EventParser::new(log.as_bytes()).with_game_version(Some(GameVersion::Dragonflight)).all(|e| e.is_ok()));
+        assert!(EventParser::new(log.as_bytes()).with_game_version(Some(GameVersion::ClassicEra))
+            .all(|e| e.is_err_and(|f| matches!(f.source, ParseError::UnknownEvent { .. }))));
+    }
+
+    #[test]
+    fn war_within_log() {
+        let damage = "8/27 19:00:01.000  SPELL_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,8921,\"Moonfire\",0x40,Creature-0-1469-2549-12530-209333-000011428A,0000000000000000,100,1000,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,5000,5000,-1,64,0,0,0,nil,nil,nil";
+        let header = |version: u64| format!("8/27 19:00:00.000  COMBAT_LOG_VERSION,{},ADVANCED_LOG_ENABLED,1,BUILD_VERSION,11.0.2,PROJECT_ID,1", version);
+
+        let log = format!("{}\n{},AOE\n", header(21), damage);
+        let mut parser = EventParser::new(log.as_bytes());
+        parser.next().unwrap().unwrap();
+        let event = parser.next().unwrap().unwrap();
+        assert!(matches!(event.event_type, EventType::Standard { suffix: Suffix::Damage { damage_kind: Some(DamageKind::Aoe), .. }, .. }));
+        assert!(event.to_log_line_with_format(parser.log_format()).ends_with(",nil,nil,nil,AOE"));
+
+        // Required from 21, not read before it
+        let log = format!("{}\n{}\n", header(21), damage);
+        assert!(EventParser::new(log.as_bytes()).nth(1).unwrap().is_err());
+        let log = format!("{}\n{},AOE\n", header(20), damage);
+        let event = EventParser::new(log.as_bytes()).nth(1).unwrap().unwrap();
+        assert!(matches!(event.event_type, EventType::Standard { suffix: Suffix::Damage { damage_kind: None, .. }, .. }));
+    }
+}
@@ -1,12 +1,12 @@
 use std::str::FromStr;
 
-use anyhow::{bail, Context};
-use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use strum::EnumString;
 
+use crate::error::{ComponentError, Result};
 use crate::utils::parse_num;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CastType {
     Local = 2,
     Active = 3,
@@ -15,7 +15,7 @@ pub enum CastType {
     TickB = 16,
 }
 
-#[derive(Debug, EnumString)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EnumString, Serialize, Deserialize)]
 pub enum CreatureType {
     Creature,
     Pet,
@@ -26,12 +26,32 @@ pub enum CreatureType {
 
 impl CreatureType {
     pub fn parse(s: &str) -> Result<Self> {
-        CreatureType::from_str(s).with_context(|| format!("Error parsing CreatureType: {}", s))
+        CreatureType::from_str(s).map_err(|_| ComponentError::bad_field(s, "creature type"))
+    }
+}
+
+/// When a creature spawned, packed into the 40 bit spawn UID at the end of its GUID.
+/// Respawns of the same NPC ID get a new one, so this tells apart eg. each wave of an add.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SpawnInfo {
+    /// Server time of the spawn in seconds, wrapping every 2^23 (~97 days). Lower 23 bits
+    pub epoch: u32,
+    /// Counts up for units spawned by the server. Upper 17 bits
+    pub counter: u32,
+}
+
+impl SpawnInfo {
+    pub fn parse(spawn_uid: &str) -> Result<Self> {
+        let uid = u64::from_str_radix(spawn_uid, 16).ok()
+            .filter(|uid| uid >> 40 == 0)
+            .ok_or_else(|| ComponentError::bad_field(spawn_uid, "40 bit hex spawn UID"))?;
+
+        Ok(Self { epoch: (uid & 0x7FFFFF) as u32, counter: (uid >> 23) as u32 })
     }
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[allow(clippy::upper_case_acronyms)]
 pub enum GUID {
     BattlePet {
@@ -77,40 +97,86 @@ pub enum GUID {
         zone_uid: u64,
         spawn_uid: u64,
     },
+    /// A GUID that couldn't be parsed, kept as it was written
+    Unknown(String),
 }
 
 impl GUID {
-    pub(crate) fn parse(s: &str) -> Result<Option<Self>> {
+    pub fn parse(s: &str) -> Result<Option<Self>> {
         if s == "0000000000000000" { return Ok(None); }
 
+        // Whatever's wrong with it, the field as a whole is a bad GUID
+        let bad = || ComponentError::bad_field(s, "GUID");
         let parts = s.split('-').collect::<Vec<_>>();
+        let part = |i: usize| parts.get(i).copied().ok_or_else(bad);
+        let num = |i: usize| parse_num(part(i)?).map_err(|_| bad());
 
         let matched = match parts[0] {
             "Player" =>
                 Self::Player {
-                    server_id: parse_num(parts[1])?,
-                    player_uid: parts[2].to_string(),
+                    server_id: num(1)?,
+                    player_uid: part(2)?.to_string(),
                 },
             "Pet" | "Creature" | "GameObject" | "Vehicle" | "Corpse" => 
                 Self::Creature {
-                    unit_type: CreatureType::parse(parts[0])?,
-                    server_id: parse_num(parts[2])?,
-                    instance_id: parse_num(parts[3])?,
-                    zone_uid: parse_num(parts[4])?,
-                    id: parse_num(parts[5])?,
-                    spawn_uid: parts[6].to_string(),
+                    unit_type: CreatureType::parse(parts[0]).map_err(|_| bad())?,
+                    server_id: num(2)?,
+                    instance_id: num(3)?,
+                    zone_uid: num(4)?,
+                    id: num(5)?,
+                    spawn_uid: part(6)?.to_string(),
                 },
-            _ => bail!("GUID type not found: {}", parts[0])
+            _ => return Err(bad()),
         };
 
         Ok(Some(matched))
     }
+
+    /// Spawn time & counter of creatures, pets, objects & vehicles
+    pub fn spawn_info(&self) -> Option<SpawnInfo> {
+        match self {
+            Self::Creature { spawn_uid, .. } => SpawnInfo::parse(spawn_uid).ok(),
+            _ => None,
+        }
+    }
+
+    /// Like `parse`, but malformed GUIDs are kept raw as `Unknown` rather than failing.
+    /// For fields that are nice to have but shouldn't lose the whole event.
+    pub(crate) fn parse_or_unknown(s: &str) -> Option<Self> {
+        Self::parse(s).unwrap_or_else(|_| Some(Self::Unknown(s.to_string())))
+    }
+}
+
+/// As written in the log, with the all zeros GUID for none
+pub fn guid_field(guid: &Option<GUID>) -> String {
+    guid.as_ref().map_or("0000000000000000".to_string(), GUID::to_string)
+}
+
+/// As written in the log, eg. `Player-1403-0A5506C6`
+impl std::fmt::Display for GUID {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BattlePet { id } => write!(f, "BattlePet-0-{:012X}", id),
+            Self::BNetAccount { account_id } => write!(f, "BNetAccount-0-{:012X}", account_id),
+            Self::Cast { cast_type, server_id, instance_id, zone_uid, spell_id, cast_uid } =>
+                write!(f, "Cast-{}-{}-{}-{}-{}-{:010X}", cast_type.clone() as u8, server_id, instance_id, zone_uid, spell_id, cast_uid),
+            Self::ClientActor { x, y, z } => write!(f, "ClientActor-{}-{}-{}", x, y, z),
+            Self::Creature { unit_type, server_id, instance_id, zone_uid, id, spawn_uid } =>
+                write!(f, "{:?}-0-{}-{}-{}-{}-{}", unit_type, server_id, instance_id, zone_uid, id, spawn_uid),
+            Self::Follower(id) => write!(f, "Follower-0-{}", id),
+            Self::Item { server_id, spawn_uid } => write!(f, "Item-{}-0-{:016X}", server_id, spawn_uid),
+            Self::Player { server_id, player_uid } => write!(f, "Player-{}-{}", server_id, player_uid),
+            Self::Vignette { server_id, instance_id, zone_uid, spawn_uid } =>
+                write!(f, "Vignette-0-{}-{}-{}-0-{:010X}", server_id, instance_id, zone_uid, spawn_uid),
+            Self::Unknown(raw) => write!(f, "{}", raw),
+        }
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::components::guid::GUID;
+    use crate::components::guid::{SpawnInfo, GUID};
 
     #[test]
     fn parse() {
@@ -122,5 +188,27 @@ mod tests {
 
         let parsed = GUID::parse("Creature-0-1469-2549-12530-209333-000011428A");
         assert!(parsed.is_ok_and(|x| x.is_some()));
+
+        let parsed = GUID::parse("Vehicle-0-1465");
+        assert!(parsed.is_err());
+        assert_eq!(GUID::parse_or_unknown("Vehicle-0-1465"), Some(GUID::Unknown("Vehicle-0-1465".to_string())));
+    }
+
+    #[test]
+    fn display() {
+        for raw in ["Player-1403-0A5506C6", "Creature-0-1469-2549-12530-209333-000011428A", "Pet-0-1469-2549-12530-165189-0203F0B1E4"] {
+            assert_eq!(GUID::parse(raw).unwrap().unwrap().to_string(), raw);
+        }
+    }
+
+    #[test]
+    fn spawn_info() {
+        let spawn = |raw: &str| GUID::parse(raw).unwrap().unwrap().spawn_info();
+        assert_eq!(spawn("Creature-0-1469-2549-12530-209333-000011428A"), Some(SpawnInfo { epoch: 1131146, counter: 0 }));
+        assert_eq!(spawn("Pet-0-1469-2549-12530-165189-0203F0B1E4"), Some(SpawnInfo { epoch: 7385572, counter: 1031 }));
+        assert_eq!(spawn("Player-1403-0A5506C6"), None);
+
+        assert!(SpawnInfo::parse("nonsense").is_err());
+        assert!(SpawnInfo::parse("10000000000").is_err());
     }
 }
\ No newline at end of file
@@ -0,0 +1,202 @@
+use crate::error::{ComponentError, Result};
+
+/// Generates a field-less enum of the event name parts, with the name each one has in the log
+macro_rules! name_parts {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident = $log_name:literal,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant,)*
+        }
+
+        impl $name {
+            pub const ALL: &'static [Self] = &[$(Self::$variant,)*];
+
+            /// As written in the log, eg. `SPELL_PERIODIC`
+            pub fn log_name(self) -> &'static str {
+                match self {
+                    $(Self::$variant => $log_name,)*
+                }
+            }
+
+            pub fn from_log_name(name: &str) -> Option<Self> {
+                match name {
+                    $($log_name => Some(Self::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+name_parts!(
+    /// Start of a standard event's name. Longer names come first, as `SPELL` is also the start of `SPELL_PERIODIC`.
+    PrefixKind {
+        Swing = "SWING",
+        Range = "RANGE",
+        SpellPeriodic = "SPELL_PERIODIC",
+        SpellBuilding = "SPELL_BUILDING",
+        Spell = "SPELL",
+        Environmental = "ENVIRONMENTAL",
+    }
+);
+
+name_parts!(
+    /// End of a standard event's name
+    SuffixKind {
+        Damage = "DAMAGE",
+        DamageLanded = "DAMAGE_LANDED",
+        DamageSupport = "DAMAGE_SUPPORT",
+        DamageLandedSupport = "DAMAGE_LANDED_SUPPORT",
+        Missed = "MISSED",
+        Heal = "HEAL",
+        HealSupport = "HEAL_SUPPORT",
+        HealAbsorbed = "HEAL_ABSORBED",
+        Absorbed = "ABSORBED",
+        AbsorbedSupport = "ABSORBED_SUPPORT",
+        Energize = "ENERGIZE",
+        Drain = "DRAIN",
+        Leech = "LEECH",
+        Interrupt = "INTERRUPT",
+        Dispel = "DISPEL",
+        DispelFailed = "DISPEL_FAILED",
+        Stolen = "STOLEN",
+        ExtraAttacks = "EXTRA_ATTACKS",
+        AuraApplied = "AURA_APPLIED",
+        AuraRemoved = "AURA_REMOVED",
+        AuraAppliedDose = "AURA_APPLIED_DOSE",
+        AuraRemovedDose = "AURA_REMOVED_DOSE",
+        AuraRefresh = "AURA_REFRESH",
+        AuraBroken = "AURA_BROKEN",
+        AuraBrokenSpell = "AURA_BROKEN_SPELL",
+        CastStart = "CAST_START",
+        CastSuccess = "CAST_SUCCESS",
+        CastFailed = "CAST_FAILED",
+        Instakill = "INSTAKILL",
+        DurabilityDamage = "DURABILITY_DAMAGE",
+        DurabilityDamageAll = "DURABILITY_DAMAGE_ALL",
+        Create = "CREATE",
+        Summon = "SUMMON",
+        Resurrect = "RESURRECT",
+        EmpowerStart = "EMPOWER_START",
+        EmpowerEnd = "EMPOWER_END",
+        EmpowerInterrupt = "EMPOWER_INTERRUPT",
+    }
+);
+
+impl PrefixKind {
+    /// Number of log fields the prefix takes up
+    pub fn log_fields(self) -> usize {
+        match self {
+            Self::Swing => 0,
+            Self::Range | Self::SpellPeriodic | Self::SpellBuilding | Self::Spell => 3,
+            Self::Environmental => 1,
+        }
+    }
+}
+
+impl SuffixKind {
+    /// Whether the event carries advanced params, when advanced logging is on
+    pub fn has_advanced_params(self) -> bool {
+        match self {
+            Self::Damage | Self::DamageLanded | Self::DamageSupport | Self::DamageLandedSupport
+            | Self::Heal | Self::HealSupport | Self::CastSuccess | Self::Energize | Self::Drain | Self::Leech => true,
+            Self::Missed | Self::HealAbsorbed | Self::Absorbed | Self::AbsorbedSupport | Self::Interrupt | Self::Dispel
+            | Self::DispelFailed | Self::Stolen | Self::ExtraAttacks | Self::AuraApplied | Self::AuraRemoved
+            | Self::AuraAppliedDose | Self::AuraRemovedDose | Self::AuraRefresh | Self::AuraBroken | Self::AuraBrokenSpell
+            | Self::CastStart | Self::CastFailed | Self::Instakill | Self::DurabilityDamage | Self::DurabilityDamageAll
+            | Self::Create | Self::Summon | Self::Resurrect | Self::EmpowerStart | Self::EmpowerEnd
+            | Self::EmpowerInterrupt => false,
+        }
+    }
+
+    /// Fewest log fields the suffix can have, counting the supporter's GUID. Anything optional, eg. the damage kind
+    /// or a MISSED absorb's amounts, isn't counted.
+    pub fn min_log_fields(self) -> usize {
+        match self {
+            Self::DamageSupport | Self::DamageLandedSupport | Self::AbsorbedSupport => 11,
+            Self::Damage | Self::DamageLanded | Self::Absorbed => 10,
+            Self::HealAbsorbed => 9,
+            Self::HealSupport => 6,
+            Self::Heal => 5,
+            Self::Energize | Self::Drain | Self::Dispel | Self::Stolen | Self::AuraBrokenSpell => 4,
+            Self::Leech | Self::Interrupt | Self::DispelFailed => 3,
+            Self::Missed | Self::AuraAppliedDose | Self::AuraRemovedDose => 2,
+            Self::ExtraAttacks | Self::AuraApplied | Self::AuraRemoved | Self::AuraRefresh | Self::AuraBroken
+            | Self::CastFailed | Self::Instakill | Self::EmpowerEnd | Self::EmpowerInterrupt => 1,
+            Self::CastStart | Self::CastSuccess | Self::DurabilityDamage | Self::DurabilityDamageAll | Self::Create
+            | Self::Summon | Self::Resurrect | Self::EmpowerStart => 0,
+        }
+    }
+
+    /// Where the supporter's GUID sits among the suffix's fields, on _SUPPORT events
+    pub fn supporter_field(self) -> Option<usize> {
+        match self {
+            Self::DamageSupport | Self::DamageLandedSupport | Self::AbsorbedSupport => Some(10),
+            Self::HealSupport => Some(5),
+            _ => None,
+        }
+    }
+}
+
+/// A standard event's name split into its prefix & suffix, worked out once per line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EventName {
+    pub prefix: PrefixKind,
+    pub suffix: SuffixKind,
+}
+
+impl EventName {
+    pub fn parse(name: &str) -> Result<Self> {
+        // Standard events which don't follow the naming scheme
+        let standard = match name {
+            "DAMAGE_SPLIT" | "DAMAGE_SHIELD" => "SPELL_DAMAGE",
+            "DAMAGE_SHIELD_MISSED" => "SPELL_MISSED",
+            "SWING_DAMAGE_LANDED_SUPPORT" => "SPELL_DAMAGE_SUPPORT",
+            x => x,
+        };
+
+        PrefixKind::ALL.iter()
+            .find_map(|&prefix| {
+                let rest = standard.strip_prefix(prefix.log_name())?.strip_prefix('_')?;
+                Some(Self { prefix, suffix: SuffixKind::from_log_name(rest)? })
+            })
+            .ok_or_else(|| ComponentError::UnknownEventType(name.to_string()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::components::names::{EventName, PrefixKind, SuffixKind};
+
+    #[test]
+    fn split() {
+        let name = |n: &str| EventName::parse(n).map(|e| (e.prefix, e.suffix)).ok();
+
+        assert_eq!(name("SPELL_PERIODIC_DAMAGE"), Some((PrefixKind::SpellPeriodic, SuffixKind::Damage)));
+        assert_eq!(name("SWING_DAMAGE_LANDED"), Some((PrefixKind::Swing, SuffixKind::DamageLanded)));
+        assert_eq!(name("SPELL_AURA_BROKEN_SPELL"), Some((PrefixKind::Spell, SuffixKind::AuraBrokenSpell)));
+        assert_eq!(name("SPELL_DURABILITY_DAMAGE"), Some((PrefixKind::Spell, SuffixKind::DurabilityDamage)));
+        assert_eq!(name("DAMAGE_SHIELD_MISSED"), Some((PrefixKind::Spell, SuffixKind::Missed)));
+        assert_eq!(name("SPELL_SOMETHING_NEW"), None);
+        assert_eq!(name("SPELLDAMAGE"), None);
+
+        for suffix in SuffixKind::ALL {
+            assert_eq!(SuffixKind::from_log_name(suffix.log_name()), Some(*suffix));
+        }
+    }
+
+    #[test]
+    fn every_combination() {
+        // Longer prefixes are tried first, so no name is split at the wrong place
+        for prefix in PrefixKind::ALL {
+            for suffix in SuffixKind::ALL {
+                let name = format!("{}_{}", prefix.log_name(), suffix.log_name());
+                // Logged with spell info, so it's parsed as the spell event
+                if name == "SWING_DAMAGE_LANDED_SUPPORT" { continue; }
+                assert_eq!(EventName::parse(&name).unwrap(), EventName { prefix: *prefix, suffix: *suffix }, "{}", name);
+            }
+        }
+    }
+}
@@ -1,72 +1,69 @@
-use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
 
 use crate::components::common::SpellInfo;
-use crate::components::enums::EnvironmentalType;
+use crate::components::names::PrefixKind;
+use crate::error::{malformed, Result};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum Prefix {
     Swing,
     Range(SpellInfo),
     Spell(Option<SpellInfo>),
     SpellPeriodic(SpellInfo),
     SpellBuilding(SpellInfo),
-    Environmental(EnvironmentalType),
 }
 
 impl Prefix {
-    pub(crate) fn parse(event_type: &str, line: &[&str]) -> Result<Self> {
-        let matched = match event_type {
-            x if x.starts_with("SWING") => Self::Swing,
-            x if x.starts_with("RANGE") => Self::Range(SpellInfo::parse(&line[..3])?),
-            x if x.starts_with("SPELL_PERIODIC") => Self::SpellPeriodic(SpellInfo::parse(&line[..3])?),
-            x if x.starts_with("SPELL_BUILDING") => Self::SpellBuilding(SpellInfo::parse(&line[..3])?),
-            x if x.starts_with("SPELL") => Self::Spell({
+    pub(crate) fn parse(kind: PrefixKind, line: &[&str]) -> Result<Self> {
+        let matched = match kind {
+            PrefixKind::Swing => Self::Swing,
+            PrefixKind::Range => Self::Range(SpellInfo::parse(&line[..3])?),
+            PrefixKind::SpellPeriodic => Self::SpellPeriodic(SpellInfo::parse(&line[..3])?),
+            PrefixKind::SpellBuilding => Self::SpellBuilding(SpellInfo::parse(&line[..3])?),
+            PrefixKind::Spell => Self::Spell({
                 match line.len() {
                     0 => None,
                     3 => Some(SpellInfo::parse(&line[..3])?),
-                    _ => bail!("Bad number of entries for Spell")
+                    _ => malformed!("Bad number of entries for Spell")
                 }
             }),
-            x if x.starts_with("ENVIRONMENTAL") => Self::Environmental(
-                EnvironmentalType::parse(line[0])?
-            ),
-            _ => bail!("Unknown prefix: {}", event_type)
+            // Has its own event type, as its fields are in a different order
+            PrefixKind::Environmental => malformed!("ENVIRONMENTAL isn't a standard prefix"),
         };
 
         Ok(matched)
     }
 
-    pub(crate) fn entries_to_consume(event_type: &str) -> Result<usize> {
-        let matched = match event_type {
-            x if x.starts_with("SWING") => 0,
-            x if x.starts_with("RANGE") |
-                x.starts_with("SPELL_PERIODIC") |
-                x.starts_with("SPELL_BUILDING") |
-                x.starts_with("SPELL") => 3,
-            x if x.starts_with("ENVIRONMENTAL") => 1,
-            _ => bail!("Unknown prefix: {}", event_type)
-        };
+    /// Spell details, for prefixes that carry them
+    pub fn spell_info(&self) -> Option<&SpellInfo> {
+        match self {
+            Self::Range(s) | Self::Spell(Some(s)) | Self::SpellPeriodic(s) | Self::SpellBuilding(s) => Some(s),
+            Self::Swing | Self::Spell(None) => None,
+        }
+    }
 
-        Ok(matched)
+    pub fn to_log_fields(&self) -> Vec<String> {
+        match self {
+            Self::Swing | Self::Spell(None) => vec![],
+            Self::Range(s) | Self::Spell(Some(s)) | Self::SpellPeriodic(s) | Self::SpellBuilding(s) => s.to_log_fields(),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Prefix;
+    use crate::components::names::PrefixKind;
 
     #[test]
     fn parse() {
-        let event_type = "SPELL_PERIODIC_HEAL";
         let lines = vec!["8936", "Regrowth", "0x8"];
-        let _parsed = Prefix::parse(event_type, &lines);
+        let _parsed = Prefix::parse(PrefixKind::SpellPeriodic, &lines);
 
-        let event_type = "SWING_DAMAGE";
         let lines = vec![];
-        let _parsed = Prefix::parse(event_type, &lines);
+        let _parsed = Prefix::parse(PrefixKind::Swing, &lines);
 
-        let event_type = "SPELL_AURA_APPLIED";
         let lines = vec!["6673", "Battle Shout", "0x1"];
-        let _parsed = Prefix::parse(event_type, &lines);
+        let _parsed = Prefix::parse(PrefixKind::Spell, &lines);
     }
 }
@@ -0,0 +1,222 @@
+use crate::components::names::{EventName, PrefixKind, SuffixKind};
+
+/// How an event name is parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventSchema {
+    Standard(EventName),
+    /// Special event, with the `Special` variants it can parse into
+    Special(&'static [&'static str]),
+}
+
+/// An event name the parser knows about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownEvent {
+    pub name: &'static str,
+    pub schema: EventSchema,
+}
+
+impl KnownEvent {
+    pub fn prefix(&self) -> Option<PrefixKind> {
+        match self.schema {
+            EventSchema::Standard(e) => Some(e.prefix),
+            EventSchema::Special(_) => None,
+        }
+    }
+
+    pub fn suffix(&self) -> Option<SuffixKind> {
+        match self.schema {
+            EventSchema::Standard(e) => Some(e.suffix),
+            EventSchema::Special(_) => None,
+        }
+    }
+
+    /// Whether the event carries advanced params, when advanced logging is on
+    pub fn has_advanced_params(&self) -> bool {
+        self.suffix().is_some_and(|s| s.has_advanced_params())
+    }
+
+    /// `Special` variants the event can parse into, empty for standard events
+    pub fn special(&self) -> &'static [&'static str] {
+        match self.schema {
+            EventSchema::Standard(_) => &[],
+            EventSchema::Special(variants) => variants,
+        }
+    }
+}
+
+/// Builds the `KNOWN_EVENTS` table from standard events' prefix & suffix kinds, and special events' variants
+macro_rules! known_events {
+    (
+        standard { $($name:literal => $prefix:ident $suffix:ident,)* }
+        special { $($special:literal => [$($variant:literal),+],)* }
+    ) => {
+        /// Every event name the parser knows about
+        pub const KNOWN_EVENTS: &[KnownEvent] = &[
+            $(KnownEvent {
+                name: $name,
+                schema: EventSchema::Standard(EventName { prefix: PrefixKind::$prefix, suffix: SuffixKind::$suffix }),
+            },)*
+            $(KnownEvent {
+                name: $special,
+                schema: EventSchema::Special(&[$($variant),+]),
+            },)*
+        ];
+    };
+}
+
+known_events! {
+    standard {
+        "SWING_DAMAGE" => Swing Damage,
+        "SWING_DAMAGE_LANDED" => Swing DamageLanded,
+        "SWING_MISSED" => Swing Missed,
+        "RANGE_DAMAGE" => Range Damage,
+        "RANGE_DAMAGE_SUPPORT" => Range DamageSupport,
+        "RANGE_MISSED" => Range Missed,
+        "SPELL_DAMAGE" => Spell Damage,
+        "SPELL_DAMAGE_SUPPORT" => Spell DamageSupport,
+        "SPELL_MISSED" => Spell Missed,
+        "SPELL_HEAL" => Spell Heal,
+        "SPELL_HEAL_SUPPORT" => Spell HealSupport,
+        "SPELL_HEAL_ABSORBED" => Spell HealAbsorbed,
+        "SPELL_ABSORBED" => Spell Absorbed,
+        "SPELL_ABSORBED_SUPPORT" => Spell AbsorbedSupport,
+        "SPELL_ENERGIZE" => Spell Energize,
+        "SPELL_DRAIN" => Spell Drain,
+        "SPELL_LEECH" => Spell Leech,
+        "SPELL_INTERRUPT" => Spell Interrupt,
+        "SPELL_DISPEL" => Spell Dispel,
+        "SPELL_DISPEL_FAILED" => Spell DispelFailed,
+        "SPELL_STOLEN" => Spell Stolen,
+        "SPELL_EXTRA_ATTACKS" => Spell ExtraAttacks,
+        "SPELL_AURA_APPLIED" => Spell AuraApplied,
+        "SPELL_AURA_REMOVED" => Spell AuraRemoved,
+        "SPELL_AURA_APPLIED_DOSE" => Spell AuraAppliedDose,
+        "SPELL_AURA_REMOVED_DOSE" => Spell AuraRemovedDose,
+        "SPELL_AURA_REFRESH" => Spell AuraRefresh,
+        "SPELL_AURA_BROKEN" => Spell AuraBroken,
+        "SPELL_AURA_BROKEN_SPELL" => Spell AuraBrokenSpell,
+        "SPELL_CAST_START" => Spell CastStart,
+        "SPELL_CAST_SUCCESS" => Spell CastSuccess,
+        "SPELL_CAST_FAILED" => Spell CastFailed,
+        "SPELL_INSTAKILL" => Spell Instakill,
+        "SPELL_DURABILITY_DAMAGE" => Spell DurabilityDamage,
+        "SPELL_DURABILITY_DAMAGE_ALL" => Spell DurabilityDamageAll,
+        "SPELL_CREATE" => Spell Create,
+        "SPELL_SUMMON" => Spell Summon,
+        "SPELL_RESURRECT" => Spell Resurrect,
+        "SPELL_EMPOWER_START" => Spell EmpowerStart,
+        "SPELL_EMPOWER_END" => Spell EmpowerEnd,
+        "SPELL_EMPOWER_INTERRUPT" => Spell EmpowerInterrupt,
+        "SPELL_PERIODIC_DAMAGE" => SpellPeriodic Damage,
+        "SPELL_PERIODIC_DAMAGE_SUPPORT" => SpellPeriodic DamageSupport,
+        "SPELL_PERIODIC_MISSED" => SpellPeriodic Missed,
+        "SPELL_PERIODIC_HEAL" => SpellPeriodic Heal,
+        "SPELL_PERIODIC_HEAL_SUPPORT" => SpellPeriodic HealSupport,
+        "SPELL_PERIODIC_ENERGIZE" => SpellPeriodic Energize,
+        "SPELL_PERIODIC_DRAIN" => SpellPeriodic Drain,
+        "SPELL_PERIODIC_LEECH" => SpellPeriodic Leech,
+        "SPELL_PERIODIC_ABSORBED" => SpellPeriodic Absorbed,
+        "SPELL_PERIODIC_CAST_SUCCESS" => SpellPeriodic CastSuccess,
+        "SPELL_BUILDING_DAMAGE" => SpellBuilding Damage,
+        "SPELL_BUILDING_HEAL" => SpellBuilding Heal,
+        "ENVIRONMENTAL_DAMAGE" => Environmental Damage,
+        // Standard events which don't follow the naming scheme
+        "DAMAGE_SPLIT" => Spell Damage,
+        "DAMAGE_SHIELD" => Spell Damage,
+        "DAMAGE_SHIELD_MISSED" => Spell Missed,
+        "SWING_DAMAGE_LANDED_SUPPORT" => Spell DamageSupport,
+    }
+    special {
+        "COMBAT_LOG_VERSION" => ["CombatLogInfo"],
+        "ZONE_CHANGE" => ["ZoneChange"],
+        "MAP_CHANGE" => ["MapChange"],
+        "ENCOUNTER_START" => ["EncounterStart"],
+        "ENCOUNTER_END" => ["EncounterEnd"],
+        "CHALLENGE_MODE_START" => ["ChallengeModeStart"],
+        "CHALLENGE_MODE_END" => ["ChallengeModeEnd"],
+        "ARENA_MATCH_START" => ["ArenaMatchStart"],
+        "ARENA_MATCH_END" => ["ArenaMatchEnd"],
+        "COMBATANT_INFO" => ["CombatantInfo"],
+        "ENCHANT_APPLIED" => ["EnchantApplied"],
+        "ENCHANT_REMOVED" => ["EnchantRemoved"],
+        "PARTY_KILL" => ["PartyKill"],
+        "UNIT_DIED" => ["UnitDied"],
+        "UNIT_DESTROYED" => ["UnitDestroyed"],
+        "UNIT_DISSIPATES" => ["UnitDissipates"],
+        "WORLD_MARKER_PLACED" => ["WorldMarkerPlaced"],
+        "WORLD_MARKER_REMOVED" => ["WorldMarkerRemoved"],
+        "EMOTE" => ["EmoteStandard", "EmoteEnvironmental"],
+        "STAGGER_CLEAR" => ["StaggerClear"],
+        "STAGGER_PREVENTED" => ["StaggerPrevented"],
+    }
+}
+
+/// Looks up a known event by its name in the log
+pub fn lookup(name: &str) -> Option<&'static KnownEvent> {
+    KNOWN_EVENTS.iter().find(|e| e.name == name)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use itertools::Itertools;
+    use regex::Regex;
+
+    use crate::components::names::EventName;
+    use crate::components::registry::{EventSchema, KNOWN_EVENTS, lookup};
+
+    #[test]
+    fn matches_parser() {
+        assert!(KNOWN_EVENTS.iter().map(|e| e.name).all_unique());
+
+        for event in KNOWN_EVENTS {
+            match event.schema {
+                EventSchema::Standard(name) => assert_eq!(EventName::parse(event.name).unwrap(), name, "{}", event.name),
+                EventSchema::Special(_) => assert!(EventName::parse(event.name).is_err(), "{}", event.name),
+            }
+        }
+
+        let heal = lookup("SPELL_PERIODIC_HEAL").unwrap();
+        assert!(heal.has_advanced_params());
+        assert!(heal.special().is_empty());
+        assert_eq!(lookup("EMOTE").unwrap().special().len(), 2);
+        assert!(lookup("SPELL_SOMETHING_NEW").is_none());
+    }
+
+    /// Every .rs file under `dir`
+    fn sources(dir: &Path) -> Vec<PathBuf> {
+        fs::read_dir(dir).unwrap()
+            .map(|e| e.unwrap().path())
+            .flat_map(|p| match p.is_dir() {
+                true => sources(&p),
+                false => p.extension().is_some_and(|e| e == "rs").then_some(p).into_iter().collect(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn covers_test_data() {
+        // Made up events, for testing how unknown ones are handled
+        let unknown = ["SOMETHING_NEW", "SPELL_SOMETHING_NEW", "SWING_MYSTERY", "NOT_AN_EVENT"];
+
+        // The start of each fixture line in the crate's tests & the bench log, eg. `4/6 14:02:07.362  SWING_DAMAGE`
+        let re = Regex::new(r"\d+/\d+(?:/\d+)? [\d:.]+(?:[-+]\d+)?  ([A-Z][A-Z0-9_]*)").unwrap();
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut files = sources(&root.join("src"));
+        files.push(root.join("resources/bench_reference.txt"));
+
+        let mut checked = 0;
+        for file in files {
+            let text = fs::read_to_string(&file).unwrap();
+            for name in re.captures_iter(&text).map(|c| c.get(1).unwrap().as_str()) {
+                if unknown.contains(&name) { continue; }
+                assert!(lookup(name).is_some(), "{} from {} is not in the registry", name, file.display());
+                checked += 1;
+            }
+        }
+        assert!(checked > 300, "Only found {} fixture lines", checked);
+    }
+}
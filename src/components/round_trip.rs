@@ -0,0 +1,207 @@
+//! Property tests: synthetic log lines are parsed, serialised back & parsed again, which should give
+//! the same line & the same event. Catches fields being read from or written to the wrong offset.
+
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+
+use crate::components::events::Event;
+use crate::components::format::{LogFormat, PROJECT_RETAIL, PROJECT_WRATH_CLASSIC};
+use crate::parser::EventParser;
+
+type Fields = Vec<String>;
+
+fn timestamp() -> BoxedStrategy<String> {
+    (1..=12u32, 1..=28u32, 0..24u32, 0..60u32, 0..60u32, 0..1000u32)
+        .prop_map(|(mo, d, h, m, s, ms)| format!("{}/{} {:02}:{:02}:{:02}.{:03}", mo, d, h, m, s, ms))
+        .boxed()
+}
+
+fn guid() -> BoxedStrategy<String> {
+    let player = (1..5000u64, "[0-9A-F]{8}")
+        .prop_map(|(server, uid)| format!("Player-{}-{}", server, uid));
+    let creature = (prop_oneof![Just("Creature"), Just("Pet"), Just("Vehicle")], 1..5000u64, 1..3000u64, 1..30000u64, 1..300000u64, "[0-9A-F]{10}")
+        .prop_map(|(t, server, instance, zone, id, spawn)| format!("{}-0-{}-{}-{}-{}-{}", t, server, instance, zone, id, spawn));
+    prop_oneof![player, creature].boxed()
+}
+
+fn optional_guid() -> BoxedStrategy<String> {
+    prop_oneof![guid(), Just("0000000000000000".to_string())].boxed()
+}
+
+fn name() -> BoxedStrategy<String> {
+    "[A-Z][a-z']{2,11}(-[A-Z][a-z]{2,11}| [A-Z][a-z]{2,11})?".prop_map(|n| format!("\"{}\"", n)).boxed()
+}
+
+fn nil_bool() -> BoxedStrategy<String> {
+    prop_oneof![Just("nil".to_string()), Just("1".to_string())].boxed()
+}
+
+fn actor() -> BoxedStrategy<Fields> {
+    let raid_flags = prop_oneof![Just("nil".to_string()), (0..=0x80u64).prop_map(|f| format!("{:#x}", f))];
+    let some = (guid(), name(), 0..0x80000000u64, raid_flags)
+        .prop_map(|(guid, name, flags, raid_flags)| vec![guid, name, format!("{:#x}", flags), raid_flags]);
+    let none = Just(["0000000000000000", "nil", "0x80000000", "0x80000000"].map(String::from).to_vec());
+    prop_oneof![4 => some, 1 => none].boxed()
+}
+
+fn spell_info() -> BoxedStrategy<Fields> {
+    (1..500000u64, name(), 1..=127u8)
+        .prop_map(|(id, name, school)| vec![id.to_string(), name, format!("{:#x}", school)])
+        .boxed()
+}
+
+/// A coordinate with 2 decimal places, small enough to survive being read as an f32
+fn coordinate() -> BoxedStrategy<String> {
+    (-2_000_000..2_000_000i32).prop_map(|c| format!("{:.2}", c as f64 / 100.)).boxed()
+}
+
+fn advanced(classic: bool) -> BoxedStrategy<Fields> {
+    let stats = (1..10_000_000u64, 1..10_000_000u64, 0..100_000u64, -100..100_000i64, 0..100_000u64, 0..1_000_000u64);
+    let powers = proptest::collection::vec((-1..=25i8, 0..300_000u64, 0..300_000u64, 0..300_000u64), 1..=3);
+    let position = (coordinate(), coordinate(), 0..3000u64, 0..62832u32, 1..700u64);
+
+    (guid(), optional_guid(), stats, powers, position).prop_map(move |(info, owner, stats, powers, position)| {
+        let (hp, max_hp, ap, sp, armor, absorb) = stats;
+        let join = |f: fn(&(i8, u64, u64, u64)) -> String| powers.iter().map(f).collect::<Vec<_>>().join("|");
+        let (x, y, map, facing, level) = position;
+
+        let mut fields = vec![info, owner, hp.to_string(), max_hp.to_string(), ap.to_string(), sp.to_string(), armor.to_string()];
+        if !classic {
+            fields.push(absorb.to_string());
+        }
+        fields.extend([
+            join(|p| p.0.to_string()),
+            join(|p| p.1.to_string()),
+            join(|p| p.2.to_string()),
+            join(|p| p.3.to_string()),
+            x,
+            y,
+            map.to_string(),
+            format!("{:.4}", facing as f64 / 10000.),
+            level.to_string(),
+        ]);
+        fields
+    }).boxed()
+}
+
+fn damage() -> BoxedStrategy<Fields> {
+    let amounts = (-100_000..10_000_000i64, 0..10_000_000u64, prop_oneof![Just(-1), 0..1_000_000i64], prop_oneof![Just(-1), 1..=127i64]);
+    let mitigated = (0..100_000u64, 0..100_000u64, -100_000..100_000i64);
+    (amounts, mitigated, nil_bool(), nil_bool(), nil_bool())
+        .prop_map(|((amount, base, overkill, school), (resisted, blocked, absorbed), critical, glancing, crushing)| vec![
+            amount.to_string(), base.to_string(), overkill.to_string(), school.to_string(),
+            resisted.to_string(), blocked.to_string(), absorbed.to_string(), critical, glancing, crushing,
+        ])
+        .boxed()
+}
+
+fn heal() -> BoxedStrategy<Fields> {
+    (0..10_000_000u64, 0..10_000_000u64, 0..10_000_000u64, 0..100_000u64, nil_bool())
+        .prop_map(|(amount, base, overhealing, absorbed, critical)|
+            vec![amount.to_string(), base.to_string(), overhealing.to_string(), absorbed.to_string(), critical])
+        .boxed()
+}
+
+fn missed() -> BoxedStrategy<Fields> {
+    let absorb = (nil_bool(), 0..1_000_000u64, 0..1_000_000u64, nil_bool())
+        .prop_map(|(offhand, amount, base, critical)| vec!["ABSORB".to_string(), offhand, amount.to_string(), base.to_string(), critical]);
+    let other = (prop_oneof![Just("MISS"), Just("DODGE"), Just("PARRY"), Just("IMMUNE"), Just("EVADE")], nil_bool())
+        .prop_map(|(miss_type, offhand)| vec![miss_type.to_string(), offhand]);
+    prop_oneof![absorb, other].boxed()
+}
+
+/// Whole amounts, or fractional ones small enough to survive being read as an f32
+fn energize() -> BoxedStrategy<Fields> {
+    let amount = prop_oneof![
+        (0..1_000_000u32).prop_map(|a| format!("{}.0000", a)),
+        (0..1_000_000u32).prop_map(|a| format!("{:.4}", a as f64 / 10000.)),
+    ];
+    (amount.clone(), amount, 0..=25i8, 0..300_000u64)
+        .prop_map(|(amount, over, power, max)| vec![amount, over, power.to_string(), max.to_string()])
+        .boxed()
+}
+
+fn aura_type() -> BoxedStrategy<String> {
+    prop_oneof![Just("BUFF".to_string()), Just("DEBUFF".to_string())].boxed()
+}
+
+/// The event name & its fields
+fn standard(classic: bool) -> BoxedStrategy<Fields> {
+    let with_spell = prop_oneof![Just("SPELL"), Just("SPELL_PERIODIC"), Just("RANGE")];
+    let prefix = prop_oneof![
+        1 => Just(("SWING", vec![])),
+        3 => (with_spell, spell_info()).prop_map(|(p, s)| (p, s)),
+    ];
+    let suffix = prop_oneof![
+        (Just("_DAMAGE"), advanced(classic), damage()).prop_map(|(s, a, d)| (s, Some(a), d)),
+        (Just("_HEAL"), advanced(classic), heal()).prop_map(|(s, a, h)| (s, Some(a), h)),
+        (Just("_ENERGIZE"), advanced(classic), energize()).prop_map(|(s, a, e)| (s, Some(a), e)),
+        (Just("_CAST_SUCCESS"), advanced(classic)).prop_map(|(s, a)| (s, Some(a), vec![])),
+        missed().prop_map(|m| ("_MISSED", None, m)),
+        (aura_type(), proptest::option::of(0..1_000_000u64))
+            .prop_map(|(a, amount)| ("_AURA_APPLIED", None, [Some(a), amount.map(|a| a.to_string())].into_iter().flatten().collect())),
+        name().prop_map(|reason| ("_CAST_FAILED", None, vec![reason])),
+        spell_info().prop_map(|s| ("_INTERRUPT", None, s)),
+        (spell_info(), aura_type()).prop_map(|(mut s, a)| { s.push(a); ("_DISPEL", None, s) }),
+    ];
+
+    (prefix, suffix, actor(), actor()).prop_map(|((prefix, prefix_fields), (suffix, advanced, suffix_fields), source, target)| {
+        let name = format!("{}{}", prefix, suffix);
+        [vec![name], source, target, prefix_fields, advanced.unwrap_or_default(), suffix_fields].concat()
+    }).boxed()
+}
+
+/// Has the environmental type & advanced params the other way round to everything else
+fn environmental(classic: bool) -> BoxedStrategy<Fields> {
+    let env = prop_oneof![Just("Falling"), Just("Drowning"), Just("Fire"), Just("Lava")];
+    (actor(), actor(), advanced(classic), env, damage()).prop_map(|(source, target, advanced, env, damage)|
+        [vec!["ENVIRONMENTAL_DAMAGE".to_string()], source, target, advanced, vec![env.to_string()], damage].concat())
+        .boxed()
+}
+
+fn special() -> BoxedStrategy<Fields> {
+    let encounter = || (1..5000u64, name(), 1..30u64, 1..40u64);
+    prop_oneof![
+        (encounter(), 1..3000u64).prop_map(|((id, name, difficulty, size), instance)|
+            vec!["ENCOUNTER_START".to_string(), id.to_string(), name, difficulty.to_string(), size.to_string(), instance.to_string()]),
+        (encounter(), any::<bool>(), 0..1_000_000u64).prop_map(|((id, name, difficulty, size), success, time)|
+            vec!["ENCOUNTER_END".to_string(), id.to_string(), name, difficulty.to_string(), size.to_string(), (success as u8).to_string(), time.to_string()]),
+        (actor(), actor(), any::<bool>()).prop_map(|(source, target, unconscious)|
+            [vec!["UNIT_DIED".to_string()], source, target, vec![(unconscious as u8).to_string()]].concat()),
+    ].boxed()
+}
+
+fn line(classic: bool) -> BoxedStrategy<String> {
+    let fields = prop_oneof![6 => standard(classic), 1 => environmental(classic), 1 => special()];
+    (timestamp(), fields).prop_map(|(timestamp, fields)| format!("{}  {}", timestamp, fields.join(",")))
+        .boxed()
+}
+
+fn parse(line: &str, format: LogFormat) -> Result<Event, TestCaseError> {
+    EventParser::with_format(line.as_bytes(), format)
+        .next()
+        .ok_or_else(|| TestCaseError::fail("no event"))?
+        .map_err(|e| TestCaseError::fail(format!("{:?}", e)))
+}
+
+fn check(line: &str, format: LogFormat) -> Result<(), TestCaseError> {
+    let event = parse(line, format)?;
+    let written = event.to_log_line_with_format(&format);
+    prop_assert_eq!(&written, line);
+
+    let reparsed = parse(&written, format)?;
+    prop_assert_eq!(serde_json::to_value(&event).unwrap(), serde_json::to_value(&reparsed).unwrap());
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn retail(line in line(false)) {
+        check(&line, LogFormat::new(20, PROJECT_RETAIL, true))?;
+    }
+
+    #[test]
+    fn classic(line in line(true)) {
+        check(&line, LogFormat::new(9, PROJECT_WRATH_CLASSIC, true))?;
+    }
+}
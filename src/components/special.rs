@@ -1,11 +1,33 @@
-use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
 use crate::components::combatant;
-use crate::components::common::Actor;
-use crate::components::guid::GUID;
-use crate::utils::{parse_bool, parse_num};
+use crate::components::common::{actor_fields, Actor};
+use crate::components::format::LogFormat;
+use crate::components::guid::{guid_field, GUID};
+use crate::error::{ComponentError, malformed, Result};
+use crate::utils::{Fields, num_bool, parse_num, quote};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EncounterStart {
+    pub encounter_id: u64,
+    pub encounter_name: String,
+    pub difficulty_id: u64,
+    pub group_size: u64,
+    pub instance_id: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EncounterEnd {
+    pub encounter_id: u64,
+    pub encounter_name: String,
+    pub difficulty_id: u64,
+    pub group_size: u64,
+    pub success: bool,
+    /// Milliseconds
+    pub fight_time: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub enum Special {
     EnchantApplied {
         source: Option<Actor>,
@@ -60,21 +82,8 @@ pub enum Special {
         y0: f32,
         y1: f32,
     },
-    EncounterStart {
-        encounter_id: u64,
-        encounter_name: String,
-        difficulty_id: u64,
-        group_size: u64,
-        instance_id: u64,
-    },
-    EncounterEnd {
-        encounter_id: u64,
-        encounter_name: String,
-        difficulty_id: u64,
-        group_size: u64,
-        success: bool,
-        fight_time: u64,
-    },
+    EncounterStart(EncounterStart),
+    EncounterEnd(EncounterEnd),
     WorldMarkerPlaced {
         instance_id: u64,
         marker: u64,
@@ -95,7 +104,7 @@ pub enum Special {
         target_name: String,
         text: String,
     },
-    CombatantInfo(combatant::CombatantInfo),
+    CombatantInfo(Box<combatant::CombatantInfo>),
     ChallengeModeStart {
         zone_name: String,
         instance_id: u64,
@@ -109,102 +118,148 @@ pub enum Special {
         keystone_level: u64,
         total_time: u64,
     },
+    ArenaMatchStart {
+        instance_id: u64,
+        /// eg. 2v2, 3v3, Rated Solo Shuffle, Skirmish
+        match_type: String,
+        /// Team of the player writing the log, 0 or 1
+        team_id: u64,
+    },
+    ArenaMatchEnd {
+        winning_team: u64,
+        /// Seconds
+        match_duration: u64,
+        /// Matchmaking rating of team 0 & team 1 after the match
+        team_ratings: [u64; 2],
+    },
+    /// Brewmaster monk's stagger pool being wiped, eg. by Purifying Brew running out of charges or death
+    StaggerClear {
+        guid: Option<GUID>,
+        amount: f64,
+    },
+    /// Damage moved into the stagger pool instead of being taken up front
+    StaggerPrevented {
+        guid: Option<GUID>,
+        spell_id: u64,
+        amount: f64,
+    },
     NoneSentinel,
 }
 
 impl Special {
-    pub fn parse(event_type: &str, line: &[&str]) -> Result<Self> {
+    /// Fewest fields each special event can have. COMBATANT_INFO checks its own, as it's split differently.
+    pub fn min_log_fields(event_type: &str) -> usize {
+        match event_type {
+            "ENCHANT_APPLIED" | "ENCHANT_REMOVED" => 11,
+            "PARTY_KILL" | "UNIT_DIED" | "UNIT_DESTROYED" | "UNIT_DISSIPATES" => 9,
+            "COMBAT_LOG_VERSION" => 7,
+            "MAP_CHANGE" | "ENCOUNTER_END" => 6,
+            "ENCOUNTER_START" | "EMOTE" | "CHALLENGE_MODE_START" => 5,
+            "WORLD_MARKER_PLACED" | "CHALLENGE_MODE_END" | "ARENA_MATCH_START" | "ARENA_MATCH_END" => 4,
+            "ZONE_CHANGE" | "STAGGER_PREVENTED" => 3,
+            "STAGGER_CLEAR" => 2,
+            "WORLD_MARKER_REMOVED" => 1,
+            _ => 0,
+        }
+    }
+
+    pub fn parse(event_type: &str, line: &[&str], format: &LogFormat) -> Result<Self> {
+        let expected = Self::min_log_fields(event_type);
+        if line.len() < expected {
+            return Err(ComponentError::TooFewFields { event_type: event_type.to_string(), expected: expected + 1, got: line.len() + 1 });
+        }
+
         let matched = match event_type {
             "ENCHANT_APPLIED" => Self::EnchantApplied {
                 source: Actor::parse(&line[0..4])?,
-                target: Actor::parse(&line[4..8])?,
+                target: Actor::parse(&line[4..8]).map_err(|e| e.offset(4))?,
                 spell_name: line[8].to_string(),
-                item_id: parse_num(line[9])?,
+                item_id: line.num(9)?,
                 item_name: line[10].to_string(),
             },
 
             "ENCHANT_REMOVED" => Self::EnchantRemoved {
                 source: Actor::parse(&line[0..4])?,
-                target: Actor::parse(&line[4..8])?,
+                target: Actor::parse(&line[4..8]).map_err(|e| e.offset(4))?,
                 spell_name: line[8].to_string(),
-                item_id: parse_num(line[9])?,
+                item_id: line.num(9)?,
                 item_name: line[10].to_string(),
             },
 
             "PARTY_KILL" => Self::PartyKill {
                 source: Actor::parse(&line[0..4])?,
-                target: Actor::parse(&line[4..8])?,
-                unconscious_on_death: parse_bool(line[8])?,
+                target: Actor::parse(&line[4..8]).map_err(|e| e.offset(4))?,
+                unconscious_on_death: line.bool(8)?,
             },
 
             "UNIT_DIED" => Self::UnitDied {
                 source: Actor::parse(&line[0..4])?,
-                target: Actor::parse(&line[4..8])?,
-                unconscious_on_death: parse_bool(line[8])?,
+                target: Actor::parse(&line[4..8]).map_err(|e| e.offset(4))?,
+                unconscious_on_death: line.bool(8)?,
             },
 
             "UNIT_DESTROYED" => Self::UnitDestroyed {
                 source: Actor::parse(&line[0..4])?,
-                target: Actor::parse(&line[4..8])?,
-                unconscious_on_death: parse_bool(line[8])?,
+                target: Actor::parse(&line[4..8]).map_err(|e| e.offset(4))?,
+                unconscious_on_death: line.bool(8)?,
             },
 
             "UNIT_DISSIPATES" => Self::UnitDissipates {
                 source: Actor::parse(&line[0..4])?,
-                target: Actor::parse(&line[4..8])?,
-                unconscious_on_death: parse_bool(line[8])?,
+                target: Actor::parse(&line[4..8]).map_err(|e| e.offset(4))?,
+                unconscious_on_death: line.bool(8)?,
             },
 
             "COMBAT_LOG_VERSION" => Self::CombatLogInfo {
-                log_version: parse_num(line[0])?,
-                advanced_log_enabled: parse_bool(line[2])?,
+                log_version: line.num(0)?,
+                advanced_log_enabled: line.bool(2)?,
                 build_version: line[4].to_string(),
-                project_id: parse_num(line[6])?,
+                project_id: line.num(6)?,
             },
 
             "ZONE_CHANGE" => Self::ZoneChange {
-                instance_id: parse_num(line[0])?,
+                instance_id: line.num(0)?,
                 zone_name: line[1].to_string(),
-                id: parse_num(line[2])?,
+                id: line.num(2)?,
             },
 
             "MAP_CHANGE" => Self::MapChange {
-                ui_map_id: parse_num(line[0])?,
+                ui_map_id: line.num(0)?,
                 ui_map_name: line[1].to_string(),
-                x0: parse_num(line[2])?,
-                x1: parse_num(line[3])?,
-                y0: parse_num(line[4])?,
-                y1: parse_num(line[5])?,
+                x0: line.num(2)?,
+                x1: line.num(3)?,
+                y0: line.num(4)?,
+                y1: line.num(5)?,
             },
 
-            "ENCOUNTER_START" => Self::EncounterStart {
-                encounter_id: parse_num(line[0])?,
+            "ENCOUNTER_START" => Self::EncounterStart(EncounterStart {
+                encounter_id: line.num(0)?,
                 encounter_name: line[1].to_string(),
-                difficulty_id: parse_num(line[2])?,
-                group_size: parse_num(line[3])?,
-                instance_id: parse_num(line[4])?,
-            },
-            "ENCOUNTER_END" => Self::EncounterEnd {
-                encounter_id: parse_num(line[0])?,
+                difficulty_id: line.num(2)?,
+                group_size: line.num(3)?,
+                instance_id: line.num(4)?,
+            }),
+            "ENCOUNTER_END" => Self::EncounterEnd(EncounterEnd {
+                encounter_id: line.num(0)?,
                 encounter_name: line[1].to_string(),
-                difficulty_id: parse_num(line[2])?,
-                group_size: parse_num(line[3])?,
-                success: parse_bool(line[4])?,
-                fight_time: parse_num(line[5])?,
-            },
+                difficulty_id: line.num(2)?,
+                group_size: line.num(3)?,
+                success: line.bool(4)?,
+                fight_time: line.num(5)?,
+            }),
             "WORLD_MARKER_PLACED" => Self::WorldMarkerPlaced {
-                instance_id: parse_num(line[0])?,
-                marker: parse_num(line[1])?,
-                x: parse_num(line[2])?,
-                y: parse_num(line[3])?,
+                instance_id: line.num(0)?,
+                marker: line.num(1)?,
+                x: line.num(2)?,
+                y: line.num(3)?,
             },
             "WORLD_MARKER_REMOVED" => Self::WorldMarkerRemoved {
-                marker: parse_num(line[0])?,
+                marker: line.num(0)?,
             },
             "EMOTE" => {
-                match GUID::parse(line[2]) {
+                match line.parse_with(2, GUID::parse) {
                     Ok(g) => Self::EmoteEnvironmental {
-                        source_guid: GUID::parse(line[0])?,
+                        source_guid: line.parse_with(0, GUID::parse)?,
                         source_name: line[1].to_string(),
                         target_guid: g,
                         target_name: line[3].to_string(),
@@ -216,26 +271,50 @@ impl Special {
                     }
                 }
             }
-            "COMBATANT_INFO" => Self::CombatantInfo(combatant::CombatantInfo::parse(line)?),
+            "COMBATANT_INFO" => Self::CombatantInfo(Box::new(combatant::CombatantInfo::parse(line, format)?)),
             "CHALLENGE_MODE_START" => Self::ChallengeModeStart {
                 zone_name: line[0].to_string(),
-                instance_id: parse_num(line[1])?,
-                challenge_mode_id: parse_num(line[2])?,
-                keystone_level: parse_num(line[3])?,
+                instance_id: line.num(1)?,
+                challenge_mode_id: line.num(2)?,
+                keystone_level: line.num(3)?,
                 affix_ids: {
+                    // Cut off lines, eg. from a crashed client, can end before the closing bracket
                     let joined = line[4..].join(",");
+                    let Some(affixes) = joined.strip_prefix('[').and_then(|s| s.strip_suffix(']')) else {
+                        malformed!("Keystone affixes not in brackets: {:?}", joined);
+                    };
 
-                    joined[1..joined.len() - 1]
-                        .split(',')
-                        .map(parse_num)
+                    affixes.split(',')
+                        .enumerate()
+                        .filter(|(_, s)| !s.is_empty())
+                        .map(|(i, s)| parse_num(s).map_err(|e| e.in_field(4 + i)))
                         .collect::<Result<Vec<u64>>>()?
                 },
             },
             "CHALLENGE_MODE_END" => Self::ChallengeModeEnd {
-                instance_id: parse_num(line[0])?,
-                success: parse_bool(line[1])?,
-                keystone_level: parse_num(line[2])?,
-                total_time: parse_num(line[3])?,
+                instance_id: line.num(0)?,
+                success: line.bool(1)?,
+                keystone_level: line.num(2)?,
+                total_time: line.num(3)?,
+            },
+            "ARENA_MATCH_START" => Self::ArenaMatchStart {
+                instance_id: line.num(0)?,
+                match_type: line[2].to_string(),
+                team_id: line.num(3)?,
+            },
+            "ARENA_MATCH_END" => Self::ArenaMatchEnd {
+                winning_team: line.num(0)?,
+                match_duration: line.num(1)?,
+                team_ratings: [line.num(2)?, line.num(3)?],
+            },
+            "STAGGER_CLEAR" => Self::StaggerClear {
+                guid: line.parse_with(0, GUID::parse)?,
+                amount: line.num(1)?,
+            },
+            "STAGGER_PREVENTED" => Self::StaggerPrevented {
+                guid: line.parse_with(0, GUID::parse)?,
+                spell_id: line.num(1)?,
+                amount: line.num(2)?,
             },
 
             _ => Self::NoneSentinel
@@ -243,83 +322,182 @@ impl Special {
 
         Ok(matched)
     }
+
+    /// Inverse of `parse`. The fields it skips are written as 0, & the trailing ones of CHALLENGE_MODE_END are left off.
+    pub fn to_log_fields(&self) -> Vec<String> {
+        match self {
+            Self::EnchantApplied { source, target, spell_name, item_id, item_name }
+            | Self::EnchantRemoved { source, target, spell_name, item_id, item_name } =>
+                [actor_fields(source), actor_fields(target), vec![quote(spell_name), item_id.to_string(), quote(item_name)]].concat(),
+            Self::PartyKill { source, target, unconscious_on_death }
+            | Self::UnitDied { source, target, unconscious_on_death }
+            | Self::UnitDestroyed { source, target, unconscious_on_death }
+            | Self::UnitDissipates { source, target, unconscious_on_death } =>
+                [actor_fields(source), actor_fields(target), vec![num_bool(*unconscious_on_death)]].concat(),
+            Self::CombatLogInfo { log_version, advanced_log_enabled, build_version, project_id } => vec![
+                log_version.to_string(),
+                "ADVANCED_LOG_ENABLED".to_string(),
+                num_bool(*advanced_log_enabled),
+                "BUILD_VERSION".to_string(),
+                build_version.clone(),
+                "PROJECT_ID".to_string(),
+                project_id.to_string(),
+            ],
+            Self::ZoneChange { instance_id, zone_name, id } =>
+                vec![instance_id.to_string(), quote(zone_name), id.to_string()],
+            Self::MapChange { ui_map_id, ui_map_name, x0, x1, y0, y1 } =>
+                [vec![ui_map_id.to_string(), quote(ui_map_name)], [x0, x1, y0, y1].map(|c| format!("{:.6}", c)).to_vec()].concat(),
+            Self::EncounterStart(e) => vec![
+                e.encounter_id.to_string(),
+                quote(&e.encounter_name),
+                e.difficulty_id.to_string(),
+                e.group_size.to_string(),
+                e.instance_id.to_string(),
+            ],
+            Self::EncounterEnd(e) => vec![
+                e.encounter_id.to_string(),
+                quote(&e.encounter_name),
+                e.difficulty_id.to_string(),
+                e.group_size.to_string(),
+                num_bool(e.success),
+                e.fight_time.to_string(),
+            ],
+            Self::WorldMarkerPlaced { instance_id, marker, x, y } =>
+                vec![instance_id.to_string(), marker.to_string(), format!("{:.2}", x), format!("{:.2}", y)],
+            Self::WorldMarkerRemoved { marker } => vec![marker.to_string()],
+            // Only reached when the 3rd field isn't a GUID, so it was read as the actor's flags
+            Self::EmoteStandard { actor, text } => match actor {
+                Some(actor) => vec![
+                    actor.guid.to_string(),
+                    quote(&actor.name),
+                    format!("{:016X}", actor.flags),
+                    actor.raid_flags.map_or("nil".to_string(), |f| format!("{:#x}", f)),
+                    quote(text),
+                ],
+                None => [actor_fields(actor), vec![quote(text)]].concat(),
+            },
+            Self::EmoteEnvironmental { source_guid, source_name, target_guid, target_name, text } =>
+                vec![guid_field(source_guid), quote(source_name), guid_field(target_guid), quote(target_name), quote(text)],
+            Self::CombatantInfo(info) => info.to_log_fields(),
+            Self::ChallengeModeStart { zone_name, instance_id, challenge_mode_id, keystone_level, affix_ids } => vec![
+                quote(zone_name),
+                instance_id.to_string(),
+                challenge_mode_id.to_string(),
+                keystone_level.to_string(),
+                format!("[{}]", affix_ids.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(",")),
+            ],
+            Self::ChallengeModeEnd { instance_id, success, keystone_level, total_time } =>
+                vec![instance_id.to_string(), num_bool(*success), keystone_level.to_string(), total_time.to_string()],
+            Self::ArenaMatchStart { instance_id, match_type, team_id } =>
+                vec![instance_id.to_string(), "0".to_string(), match_type.clone(), team_id.to_string()],
+            Self::ArenaMatchEnd { winning_team, match_duration, team_ratings } =>
+                vec![winning_team.to_string(), match_duration.to_string(), team_ratings[0].to_string(), team_ratings[1].to_string()],
+            Self::StaggerClear { guid, amount } => vec![guid_field(guid), format!("{:.6}", amount)],
+            Self::StaggerPrevented { guid, spell_id, amount } =>
+                vec![guid_field(guid), spell_id.to_string(), format!("{:.6}", amount)],
+            Self::NoneSentinel => vec![],
+        }
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::Special;
+    use crate::components::format::LogFormat;
 
     #[test]
     fn parse() {
         let event_type = "ENCHANT_APPLIED";
         let line = vec!["0000000000000000", "nil", "0x80000000", "0x80000000", "Player-1329-09AF0ACF", "Adamthebash-Ravencrest", "0x511", "0x0", "Howling Rune", "207782", "Sickle of the White Stag"];
-        let parsed = Special::parse(event_type, &line);
+        let parsed = Special::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
 
         let event_type = "ENCHANT_REMOVED";
         let line = vec!["0000000000000000", "nil", "0x80000000", "0x80000000", "Player-1329-09AF0ACF", "Adamthebash-Ravencrest", "0x511", "0x0", "Howling Rune", "207782", "Sickle of the White Stag"];
-        let parsed = Special::parse(event_type, &line);
+        let parsed = Special::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
 
         let event_type = "PARTY_KILL";
         let line = vec!["0000000000000000", "nil", "0x80000000", "0x80000000", "Player-1329-09AF0ACF", "Adamthebash-Ravencrest", "0x511", "0x0", "0"];
-        let parsed = Special::parse(event_type, &line);
+        let parsed = Special::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
 
         let event_type = "UNIT_DIED";
         let line = vec!["0000000000000000", "nil", "0x80000000", "0x80000000", "Player-1329-09AF0ACF", "Adamthebash-Ravencrest", "0x511", "0x0", "0"];
-        let parsed = Special::parse(event_type, &line);
+        let parsed = Special::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
 
         let event_type = "UNIT_DESTROYED";
         let line = vec!["0000000000000000", "nil", "0x80000000", "0x80000000", "Player-1329-09AF0ACF", "Adamthebash-Ravencrest", "0x511", "0x0", "0"];
-        let parsed = Special::parse(event_type, &line);
+        let parsed = Special::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
 
         let event_type = "UNIT_DISSIPATES";
         let line = vec!["0000000000000000", "nil", "0x80000000", "0x80000000", "Player-1329-09AF0ACF", "Adamthebash-Ravencrest", "0x511", "0x0", "0"];
-        let parsed = Special::parse(event_type, &line);
+        let parsed = Special::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
 
         let event_type = "COMBAT_LOG_VERSION";
         let line = vec!["20", "ADVANCED_LOG_ENABLED", "1", "BUILD_VERSION", "10.2.6", "PROJECT_ID", "1"];
-        let parsed = Special::parse(event_type, &line);
+        let parsed = Special::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
 
         let event_type = "ZONE_CHANGE";
         let line = vec!["2549", "Amirdrassil, the Dream's Hope", "14"];
-        let parsed = Special::parse(event_type, &line);
+        let parsed = Special::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
 
         let event_type = "MAP_CHANGE";
         let line = vec!["2232", "Amirdrassil", "3800.000000", "3000.000000", "13725.000000", "12525.000000"];
-        let parsed = Special::parse(event_type, &line);
+        let parsed = Special::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
 
         let event_type = "ENCOUNTER_START";
         let line = vec!["2820", "Gnarlroot", "14", "19", "2549"];
-        let parsed = Special::parse(event_type, &line);
+        let parsed = Special::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
 
         let event_type = "ENCOUNTER_END";
         let line = vec!["2820", "Gnarlroot", "14", "19", "1", "162742"];
-        let parsed = Special::parse(event_type, &line);
+        let parsed = Special::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
 
         let event_type = "WORLD_MARKER_PLACED";
         let line = vec!["2549", "7", "4010.06", "13115.27"];
-        let parsed = Special::parse(event_type, &line);
+        let parsed = Special::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
 
         let event_type = "WORLD_MARKER_REMOVED";
         let line = vec!["7"];
-        let parsed = Special::parse(event_type, &line);
+        let parsed = Special::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
 
         let event_type = "EMOTE";
         let line = vec!["Creature-0-4233-2549-14868-200927-00004E8C97", "Smolderon", "0000000000000000", "nil", r"|TInterface\Icons\SPELL_FIRE_RAGNAROS_MOLTENINFERNO.BLP:20|tEmberscar attempts to |cFFFF0000|Hspell:422277|h[Devour Your Essence]|h|r!"];
-        let parsed = Special::parse(event_type, &line);
+        let parsed = Special::parse(event_type, &line, &LogFormat::default());
         println!("{:?}", parsed);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parse_arena() {
+        let line = vec!["1672", "33", "3v3", "1"];
+        let parsed = Special::parse("ARENA_MATCH_START", &line, &LogFormat::default()).unwrap();
+        assert!(matches!(parsed, Special::ArenaMatchStart { instance_id: 1672, ref match_type, team_id: 1 } if match_type == "3v3"), "{:?}", parsed);
+
+        let line = vec!["1", "193", "1587", "1579"];
+        let parsed = Special::parse("ARENA_MATCH_END", &line, &LogFormat::default()).unwrap();
+        assert!(matches!(parsed, Special::ArenaMatchEnd { winning_team: 1, match_duration: 193, team_ratings: [1587, 1579] }), "{:?}", parsed);
+    }
+
+    #[test]
+    fn parse_stagger() {
+        let line = vec!["Player-1403-0A1B2C3D", "48213.238281"];
+        let parsed = Special::parse("STAGGER_CLEAR", &line, &LogFormat::default()).unwrap();
+        assert!(matches!(parsed, Special::StaggerClear { guid: Some(_), amount } if amount == 48213.238281), "{:?}", parsed);
+
+        let line = vec!["Player-1403-0A1B2C3D", "124255", "5210.500000"];
+        let parsed = Special::parse("STAGGER_PREVENTED", &line, &LogFormat::default()).unwrap();
+        assert!(matches!(parsed, Special::StaggerPrevented { guid: Some(_), spell_id: 124255, amount } if amount == 5210.5), "{:?}", parsed);
+    }
+}
@@ -1,35 +1,41 @@
-use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
 
-use crate::components::common::{Actor, SpellInfo};
-use crate::components::enums::{AuraType, MissType, PowerType, SpellSchool};
-use crate::components::guid::GUID;
-use crate::utils::{parse_bool, parse_num};
+use crate::components::common::{actor_fields, Actor, SpellInfo};
+use crate::components::enums::{AuraType, DamageKind, MissType, PowerType, SpellSchoolSet};
+use crate::components::format::LogFormat;
+use crate::components::names::SuffixKind;
+use crate::error::{ComponentError, Context, Result};
+use crate::utils::{Fields, nil_bool, num_bool, quote};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum Suffix {
     Damage {
         amount: i64,
         base_amount: u64,
         overkill: Option<u64>,
-        school: Option<Vec<SpellSchool>>,
+        school: Option<SpellSchoolSet>,
         resisted: u64,
         blocked: u64,
         absorbed: i64,
         critical: bool,
         glancing: bool,
         crushing: bool,
+        /// From COMBAT_LOG_VERSION 21
+        damage_kind: Option<DamageKind>,
     },
     DamageLanded {
         amount: u64,
         base_amount: u64,
         overkill: Option<u64>,
-        school: Option<Vec<SpellSchool>>,
+        school: Option<SpellSchoolSet>,
         resisted: u64,
         blocked: u64,
         absorbed: u64,
         critical: bool,
         glancing: bool,
         crushing: bool,
+        /// From COMBAT_LOG_VERSION 21
+        damage_kind: Option<DamageKind>,
     },
     Missed {
         miss_type: MissType,
@@ -64,7 +70,6 @@ pub enum Suffix {
         absorbed_amount: i64,
         base_amount: u64,
         critical: bool,
-        caster: GUID,
     },
     Energize {
         amount: f32,
@@ -132,27 +137,29 @@ pub enum Suffix {
         amount: i64,
         base_amount: i64,
         overkill: Option<u64>,
-        school: Option<Vec<SpellSchool>>,
+        school: Option<SpellSchoolSet>,
         resisted: u64,
         blocked: u64,
         absorbed: i64,
         critical: bool,
         glancing: bool,
         crushing: bool,
-        caster: GUID,
+        /// From COMBAT_LOG_VERSION 21
+        damage_kind: Option<DamageKind>,
     },
     DamageLandedSupport {
         amount: u64,
         base_amount: u64,
         overkill: Option<u64>,
-        school: Option<Vec<SpellSchool>>,
+        school: Option<SpellSchoolSet>,
         resisted: u64,
         blocked: u64,
         absorbed: u64,
         critical: bool,
         glancing: bool,
         crushing: bool,
-        caster: GUID,
+        /// From COMBAT_LOG_VERSION 21
+        damage_kind: Option<DamageKind>,
     },
     HealSupport {
         amount: u64,
@@ -160,462 +167,497 @@ pub enum Suffix {
         overhealing: u64,
         absorbed: u64,
         critical: bool,
-        caster: GUID,
     },
 }
 
 impl Suffix {
-    pub fn parse(event_type: &str, line: &[&str]) -> Result<Self> {
-        let matched = match event_type {
-            x if x.ends_with("DAMAGE") => Self::Damage {
-                amount: parse_num(line[0])?,
-                base_amount: parse_num(line[1])?,
+    pub fn parse(kind: SuffixKind, line: &[&str], format: &LogFormat) -> Result<Self> {
+        let damage_kind = |i: usize| match format.has_damage_kind() {
+            true => line.parse_with(i, DamageKind::parse).map(Some),
+            false => Ok(None),
+        };
+
+        let matched = match kind {
+            SuffixKind::Damage => Self::Damage {
+                amount: line.num(0)?,
+                base_amount: line.num(1)?,
                 overkill: match line[2] {
                     "-1" => None,
-                    x => Some(parse_num(x)?)
+                    _ => Some(line.num(2)?)
                 },
-                school: SpellSchool::parse(line[3])?,
-                resisted: parse_num(line[4])?,
-                blocked: parse_num(line[5])?,
-                absorbed: parse_num(line[6])?,
-                critical: parse_bool(line[7])?,
-                glancing: parse_bool(line[8])?,
-                crushing: parse_bool(line[9])?,
+                school: line.parse_with(3, SpellSchoolSet::parse)?,
+                resisted: line.num(4)?,
+                blocked: line.num(5)?,
+                absorbed: line.num(6)?,
+                critical: line.bool(7)?,
+                glancing: line.bool(8)?,
+                crushing: line.bool(9)?,
+                damage_kind: damage_kind(10)?,
             },
-            x if x.ends_with("DAMAGE_SUPPORT") => Self::DamageSupport {
-                amount: parse_num(line[0])?,
-                base_amount: parse_num(line[1])?,
+            SuffixKind::DamageSupport => Self::DamageSupport {
+                amount: line.num(0)?,
+                base_amount: line.num(1)?,
                 overkill: match line[2] {
                     "-1" => None,
-                    x => Some(parse_num(x)?)
+                    _ => Some(line.num(2)?)
                 },
-                school: SpellSchool::parse(line[3])?,
-                resisted: parse_num(line[4])?,
-                blocked: parse_num(line[5])?,
-                absorbed: parse_num(line[6])?,
-                critical: parse_bool(line[7])?,
-                glancing: parse_bool(line[8])?,
-                crushing: parse_bool(line[9])?,
-                caster: GUID::parse(line[10])?
-                    .with_context(|| "Support caster GUID cannot be none")?,
+                school: line.parse_with(3, SpellSchoolSet::parse)?,
+                resisted: line.num(4)?,
+                blocked: line.num(5)?,
+                absorbed: line.num(6)?,
+                critical: line.bool(7)?,
+                glancing: line.bool(8)?,
+                crushing: line.bool(9)?,
+                damage_kind: damage_kind(10)?,
             },
 
-            x if x.ends_with("DAMAGE_LANDED") => Self::DamageLanded {
-                amount: parse_num(line[0])?,
-                base_amount: parse_num(line[1])?,
+            SuffixKind::DamageLanded => Self::DamageLanded {
+                amount: line.num(0)?,
+                base_amount: line.num(1)?,
                 overkill: match line[2] {
                     "-1" => None,
-                    x => Some(parse_num(x)?)
+                    _ => Some(line.num(2)?)
                 },
-                school: SpellSchool::parse(line[3])?,
-                resisted: parse_num(line[4])?,
-                blocked: parse_num(line[5])?,
-                absorbed: parse_num(line[6])?,
-                critical: parse_bool(line[7])?,
-                glancing: parse_bool(line[8])?,
-                crushing: parse_bool(line[9])?,
+                school: line.parse_with(3, SpellSchoolSet::parse)?,
+                resisted: line.num(4)?,
+                blocked: line.num(5)?,
+                absorbed: line.num(6)?,
+                critical: line.bool(7)?,
+                glancing: line.bool(8)?,
+                crushing: line.bool(9)?,
+                damage_kind: damage_kind(10)?,
             },
-            x if x.ends_with("DAMAGE_LANDED_SUPPORT") => Self::DamageLandedSupport {
-                amount: parse_num(line[0])?,
-                base_amount: parse_num(line[1])?,
+            SuffixKind::DamageLandedSupport => Self::DamageLandedSupport {
+                amount: line.num(0)?,
+                base_amount: line.num(1)?,
                 overkill: match line[2] {
                     "-1" => None,
-                    x => Some(parse_num(x)?)
+                    _ => Some(line.num(2)?)
                 },
-                school: SpellSchool::parse(line[3])?,
-                resisted: parse_num(line[4])?,
-                blocked: parse_num(line[5])?,
-                absorbed: parse_num(line[6])?,
-                critical: parse_bool(line[7])?,
-                glancing: parse_bool(line[8])?,
-                crushing: parse_bool(line[9])?,
-                caster: GUID::parse(line[10])?
-                    .with_context(|| "Support caster GUID cannot be none")?,
+                school: line.parse_with(3, SpellSchoolSet::parse)?,
+                resisted: line.num(4)?,
+                blocked: line.num(5)?,
+                absorbed: line.num(6)?,
+                critical: line.bool(7)?,
+                glancing: line.bool(8)?,
+                crushing: line.bool(9)?,
+                damage_kind: damage_kind(10)?,
             },
 
-            x if x.ends_with("MISSED") => {
-                let miss_type = MissType::parse(line[0])?;
+            SuffixKind::Missed => {
+                let miss_type = line.parse_with(0, MissType::parse)?;
 
                 let (amount_missed, base_amount, critical) = match miss_type {
                     MissType::Absorb => (
-                        parse_num(line[2])?,
-                        parse_num(line[3])?,
-                        parse_bool(line[4])?
+                        line.num(2)?,
+                        line.num(3)?,
+                        line.bool(4)?
                     ),
                     _ => (0, 0, false)
                 };
 
                 Self::Missed {
                     miss_type,
-                    offhand: parse_bool(line[1])?,
+                    offhand: line.bool(1)?,
                     amount_missed,
                     base_amount,
                     critical,
                 }
             }
 
-            x if x.ends_with("HEAL") => Self::Heal {
-                amount: parse_num(line[0])?,
-                base_amount: parse_num(line[1])?,
-                overhealing: parse_num(line[2])?,
-                absorbed: parse_num(line[3])?,
-                critical: parse_bool(line[4])?,
+            SuffixKind::Heal => Self::Heal {
+                amount: line.num(0)?,
+                base_amount: line.num(1)?,
+                overhealing: line.num(2)?,
+                absorbed: line.num(3)?,
+                critical: line.bool(4)?,
             },
-            x if x.ends_with("HEAL_SUPPORT") => Self::HealSupport {
-                amount: parse_num(line[0])?,
-                base_amount: parse_num(line[1])?,
-                overhealing: parse_num(line[2])?,
-                absorbed: parse_num(line[3])?,
-                critical: parse_bool(line[4])?,
-                caster: GUID::parse(line[5])?
-                    .with_context(|| "Support caster GUID cannot be none")?,
+            SuffixKind::HealSupport => Self::HealSupport {
+                amount: line.num(0)?,
+                base_amount: line.num(1)?,
+                overhealing: line.num(2)?,
+                absorbed: line.num(3)?,
+                critical: line.bool(4)?,
             },
 
-            x if x.ends_with("HEAL_ABSORBED") => Self::HealAbsorbed {
+            SuffixKind::HealAbsorbed => Self::HealAbsorbed {
                 actor: Actor::parse(&line[..4])?,
-                spell_info: SpellInfo::parse(&line[4..7])?,
-                absorbed_amount: parse_num(line[7])?,
-                total_amount: parse_num(line[8])?,
+                spell_info: SpellInfo::parse(&line[4..7]).map_err(|e| e.offset(4))?,
+                absorbed_amount: line.num(7)?,
+                total_amount: line.num(8)?,
             },
 
-            x if x.ends_with("ABSORBED") => Self::Absorbed {
-                absorb_caster: Actor::parse(&line[..4])?.unwrap(),
-                absorb_spell_info: SpellInfo::parse(&line[4..7])?,
-                absorbed_amount: parse_num(line[7])?,
-                base_amount: parse_num(line[8])?,
-                critical: parse_bool(line[9])?,
+            SuffixKind::Absorbed => Self::Absorbed {
+                absorb_caster: Actor::parse(&line[..4])?
+                    .with_context(|| "Absorb caster cannot be none")?,
+                absorb_spell_info: SpellInfo::parse(&line[4..7]).map_err(|e| e.offset(4))?,
+                absorbed_amount: line.num(7)?,
+                base_amount: line.num(8)?,
+                critical: line.bool(9)?,
             },
-            x if x.ends_with("ABSORBED_SUPPORT") => Self::AbsorbedSupport {
-                absorb_caster: Actor::parse(&line[..4])?.unwrap(),
-                absorb_spell_info: SpellInfo::parse(&line[4..7])?,
-                absorbed_amount: parse_num(line[7])?,
-                base_amount: parse_num(line[8])?,
-                critical: parse_bool(line[9])?,
-                caster: GUID::parse(line[10])?
-                    .with_context(|| "Support caster GUID cannot be none")?,
+            SuffixKind::AbsorbedSupport => Self::AbsorbedSupport {
+                absorb_caster: Actor::parse(&line[..4])?
+                    .with_context(|| "Absorb caster cannot be none")?,
+                absorb_spell_info: SpellInfo::parse(&line[4..7]).map_err(|e| e.offset(4))?,
+                absorbed_amount: line.num(7)?,
+                base_amount: line.num(8)?,
+                critical: line.bool(9)?,
             },
 
-            x if x.ends_with("ENERGIZE") => Self::Energize {
-                amount: parse_num(line[0])?,
-                over_energize: parse_num(line[1])?,
-                power_type: PowerType::parse(line[2])?
-                    .with_context(|| format!("Invalid power type: {}", line[2]))?,
-                max_power: parse_num(line[3])?,
+            SuffixKind::Energize => Self::Energize {
+                amount: line.num(0)?,
+                over_energize: line.num(1)?,
+                power_type: line.parse_with(2, PowerType::parse)?
+                    .ok_or_else(|| ComponentError::bad_field(line[2], "power type").in_field(2))?,
+                max_power: line.num(3)?,
             },
 
-            x if x.ends_with("DRAIN") => Self::Drain {
-                amount: parse_num(line[0])?,
-                power_type: PowerType::parse(line[1])?
-                    .with_context(|| format!("Invalid power type: {}", line[1]))?,
-                extra_amount: parse_num(line[2])?,
-                max_power: parse_num(line[3])?,
+            SuffixKind::Drain => Self::Drain {
+                amount: line.num(0)?,
+                power_type: line.parse_with(1, PowerType::parse)?
+                    .ok_or_else(|| ComponentError::bad_field(line[1], "power type").in_field(1))?,
+                extra_amount: line.num(2)?,
+                max_power: line.num(3)?,
             },
 
-            x if x.ends_with("LEECH") => Self::Leech {
-                amount: parse_num(line[0])?,
-                power_type: PowerType::parse(line[1])?
-                    .with_context(|| format!("Invalid power type: {}", line[1]))?,
-                extra_amount: parse_num(line[2])?,
+            SuffixKind::Leech => Self::Leech {
+                amount: line.num(0)?,
+                power_type: line.parse_with(1, PowerType::parse)?
+                    .ok_or_else(|| ComponentError::bad_field(line[1], "power type").in_field(1))?,
+                extra_amount: line.num(2)?,
             },
 
-            x if x.ends_with("EMPOWER_INTERRUPT") => Self::EmpowerInterrupt {
-                empowered_rank: parse_num(line[0])?
+            SuffixKind::EmpowerInterrupt => Self::EmpowerInterrupt {
+                empowered_rank: line.num(0)?
             },
 
-            x if x.ends_with("INTERRUPT") => Self::Interrupt {
+            SuffixKind::Interrupt => Self::Interrupt {
                 spell_info: SpellInfo::parse(&line[..3])?,
             },
 
-            x if x.ends_with("DISPEL") => Self::Dispel {
+            SuffixKind::Dispel => Self::Dispel {
                 spell_info: SpellInfo::parse(&line[..3])?,
-                aura_type: AuraType::parse(line[3])?,
+                aura_type: line.parse_with(3, AuraType::parse)?,
             },
 
-            x if x.ends_with("DISPEL_FAILED") => Self::DispelFailed {
+            SuffixKind::DispelFailed => Self::DispelFailed {
                 spell_info: SpellInfo::parse(&line[..3])?,
             },
 
-            x if x.ends_with("STOLEN") => Self::Stolen {
+            SuffixKind::Stolen => Self::Stolen {
                 spell_info: SpellInfo::parse(&line[..3])?,
-                aura_type: AuraType::parse(line[3])?,
+                aura_type: line.parse_with(3, AuraType::parse)?,
             },
 
-            x if x.ends_with("EXTRA_ATTACKS") => Self::ExtraAttacks {
-                amount: parse_num(line[0])?
+            SuffixKind::ExtraAttacks => Self::ExtraAttacks {
+                amount: line.num(0)?
             },
 
-            x if x.ends_with("AURA_APPLIED") => {
-                let amount = if line.len() < 2 { None } else { Some(parse_num(line[1])?) };
+            SuffixKind::AuraApplied => {
+                let amount = if line.len() < 2 { None } else { Some(line.num(1)?) };
 
                 Self::AuraApplied {
-                    aura_type: AuraType::parse(line[0])?,
+                    aura_type: line.parse_with(0, AuraType::parse)?,
                     amount,
                 }
             }
 
-            x if x.ends_with("AURA_REMOVED") => {
-                let amount = if line.len() < 2 { None } else { Some(parse_num(line[1])?) };
+            SuffixKind::AuraRemoved => {
+                let amount = if line.len() < 2 { None } else { Some(line.num(1)?) };
 
                 Self::AuraRemoved {
-                    aura_type: AuraType::parse(line[0])?,
+                    aura_type: line.parse_with(0, AuraType::parse)?,
                     amount,
                 }
             }
 
-            x if x.ends_with("AURA_APPLIED_DOSE") => Self::AuraAppliedDose {
-                aura_type: AuraType::parse(line[0])?,
-                amount: parse_num(line[1])?,
+            SuffixKind::AuraAppliedDose => Self::AuraAppliedDose {
+                aura_type: line.parse_with(0, AuraType::parse)?,
+                amount: line.num(1)?,
             },
 
-            x if x.ends_with("AURA_REMOVED_DOSE") => Self::AuraRemovedDose {
-                aura_type: AuraType::parse(line[0])?,
-                amount: parse_num(line[1])?,
+            SuffixKind::AuraRemovedDose => Self::AuraRemovedDose {
+                aura_type: line.parse_with(0, AuraType::parse)?,
+                amount: line.num(1)?,
             },
 
-            x if x.ends_with("AURA_REFRESH") => Self::AuraRefresh {
-                aura_type: AuraType::parse(line[0])?,
+            SuffixKind::AuraRefresh => Self::AuraRefresh {
+                aura_type: line.parse_with(0, AuraType::parse)?,
             },
 
-            x if x.ends_with("AURA_BROKEN") => Self::AuraBroken {
-                aura_type: AuraType::parse(line[0])?,
+            SuffixKind::AuraBroken => Self::AuraBroken {
+                aura_type: line.parse_with(0, AuraType::parse)?,
             },
 
-            x if x.ends_with("AURA_BROKEN_SPELL") => Self::AuraBrokenSpell {
+            SuffixKind::AuraBrokenSpell => Self::AuraBrokenSpell {
                 spell_info: SpellInfo::parse(&line[..3])?,
-                aura_type: AuraType::parse(line[3])?,
+                aura_type: line.parse_with(3, AuraType::parse)?,
             },
 
-            x if x.ends_with("CAST_START") => Self::CastStart,
+            SuffixKind::CastStart => Self::CastStart,
 
-            x if x.ends_with("CAST_SUCCESS") => Self::CastSuccess,
+            SuffixKind::CastSuccess => Self::CastSuccess,
 
-            x if x.ends_with("CAST_FAILED") => Self::CastFailed {
+            SuffixKind::CastFailed => Self::CastFailed {
                 failed_type: line[0].to_string(),
             },
 
-            x if x.ends_with("INSTAKILL") => Self::Instakill {
-                unconscious_on_death: parse_bool(line[0])?,
+            SuffixKind::Instakill => Self::Instakill {
+                unconscious_on_death: line.bool(0)?,
             },
 
-            x if x.ends_with("DURABILITY_DAMAGE") => Self::DurabilityDamage,
+            SuffixKind::DurabilityDamage => Self::DurabilityDamage,
 
-            x if x.ends_with("DURABILITY_DAMAGE_ALL") => Self::DurabilityDamageAll,
+            SuffixKind::DurabilityDamageAll => Self::DurabilityDamageAll,
 
-            x if x.ends_with("CREATE") => Self::Create,
+            SuffixKind::Create => Self::Create,
 
-            x if x.ends_with("SUMMON") => Self::Summon,
+            SuffixKind::Summon => Self::Summon,
 
-            x if x.ends_with("RESURRECT") => Self::Resurrect,
+            SuffixKind::Resurrect => Self::Resurrect,
 
-            x if x.ends_with("EMPOWER_START") => Self::EmpowerStart,
+            SuffixKind::EmpowerStart => Self::EmpowerStart,
 
-            x if x.ends_with("EMPOWER_END") => Self::EmpowerEnd {
-                empowered_rank: parse_num(line[0])?,
+            SuffixKind::EmpowerEnd => Self::EmpowerEnd {
+                empowered_rank: line.num(0)?,
             },
-
-            _ => bail!("Unknown suffix: {}", event_type)
         };
 
         Ok(matched)
     }
 
-    pub fn has_advanced_params(event_type: &str) -> Result<bool> {
-        // todo: fill these in - surely a better way to do this
-        let advanced_suffixes = [
-            "DAMAGE",
-            "DAMAGE_LANDED",
-            "HEAL",
-            "DAMAGE_SUPPORT",
-            "DAMAGE_LANDED_SUPPORT",
-            "HEAL_SUPPORT",
-            "CAST_SUCCESS",
-            "ENERGIZE",
-            "DRAIN",
-            "LEECH",
-            "CAST_SUCCESS",
-        ];
-        let non_advanced_suffixes = [
-            "STOLEN",
-            "AURA_APPLIED",
-            "AURA_REMOVED",
-            "MISSED",
-            "HEAL_ABSORBED",
-            "ABSORBED",
-            "ABSORBED_SUPPORT",
-            "EMPOWER_INTERRUPT",
-            "INTERRUPT",
-            "DISPEL_FAILED",
-            "EXTRA_ATTACKS",
-            "AURA_APPLIED_DOSE",
-            "AURA_REMOVED_DOSE",
-            "AURA_REFRESH",
-            "AURA_BROKEN",
-            "AURA_BROKEN_SPELL",
-            "CAST_START",
-            "CAST_FAILED",
-            "INSTAKILL",
-            "DURABILITY_DAMAGE",
-            "DURABILITY_DAMAGE_ALL",
-            "CREATE",
-            "SUMMON",
-            "RESURRECT",
-            "EMPOWER_START",
-            "EMPOWER_END",
-            "DISPEL",
-        ];
-
-        let matched = match event_type {
-            x if advanced_suffixes.iter().any(|s| x.ends_with(s)) => true,
-            x if non_advanced_suffixes.iter().any(|s| x.ends_with(s)) => false,
-            _ => bail!("Unknown suffix: {}", event_type)
-        };
+    /// Inverse of `parse`. Missed amounts are only written for absorbs, as that's all that's parsed.
+    pub fn to_log_fields(&self) -> Vec<String> {
+        #[allow(clippy::too_many_arguments)]
+        fn hit(amount: String, base_amount: String, overkill: &Option<u64>, school: &Option<SpellSchoolSet>, resisted: u64,
+               blocked: u64, absorbed: String, critical: bool, glancing: bool, crushing: bool) -> Vec<String> {
+            vec![
+                amount,
+                base_amount,
+                overkill.map_or("-1".to_string(), |o| o.to_string()),
+                school.map_or("-1".to_string(), |s| s.bits().to_string()),
+                resisted.to_string(),
+                blocked.to_string(),
+                absorbed,
+                nil_bool(critical),
+                nil_bool(glancing),
+                nil_bool(crushing),
+            ]
+        }
+        let with = |mut fields: Vec<String>, extra: Vec<String>| { fields.extend(extra); fields };
+        let aura = |aura_type: &AuraType| format!("{:?}", aura_type).to_uppercase();
+        let kind = |damage_kind: &Option<DamageKind>| damage_kind.iter().map(|k| k.to_log_field()).collect::<Vec<_>>();
+
+        match self {
+            Self::Damage { amount, base_amount, overkill, school, resisted, blocked, absorbed, critical, glancing, crushing, damage_kind } =>
+                with(hit(amount.to_string(), base_amount.to_string(), overkill, school, *resisted, *blocked, absorbed.to_string(), *critical, *glancing, *crushing), kind(damage_kind)),
+            Self::DamageLanded { amount, base_amount, overkill, school, resisted, blocked, absorbed, critical, glancing, crushing, damage_kind } =>
+                with(hit(amount.to_string(), base_amount.to_string(), overkill, school, *resisted, *blocked, absorbed.to_string(), *critical, *glancing, *crushing), kind(damage_kind)),
+            Self::DamageSupport { amount, base_amount, overkill, school, resisted, blocked, absorbed, critical, glancing, crushing, damage_kind } =>
+                with(hit(amount.to_string(), base_amount.to_string(), overkill, school, *resisted, *blocked, absorbed.to_string(), *critical, *glancing, *crushing), kind(damage_kind)),
+            Self::DamageLandedSupport { amount, base_amount, overkill, school, resisted, blocked, absorbed, critical, glancing, crushing, damage_kind } =>
+                with(hit(amount.to_string(), base_amount.to_string(), overkill, school, *resisted, *blocked, absorbed.to_string(), *critical, *glancing, *crushing), kind(damage_kind)),
+            Self::Missed { miss_type, offhand, amount_missed, base_amount, critical } => {
+                let mut fields = vec![format!("{:?}", miss_type).to_uppercase(), nil_bool(*offhand)];
+                if *miss_type == MissType::Absorb {
+                    fields.extend([amount_missed.to_string(), base_amount.to_string(), nil_bool(*critical)]);
+                }
+                fields
+            }
+            Self::Heal { amount, base_amount, overhealing, absorbed, critical } =>
+                vec![amount.to_string(), base_amount.to_string(), overhealing.to_string(), absorbed.to_string(), nil_bool(*critical)],
+            Self::HealSupport { amount, base_amount, overhealing, absorbed, critical } =>
+                vec![amount.to_string(), base_amount.to_string(), overhealing.to_string(), absorbed.to_string(), nil_bool(*critical)],
+            Self::HealAbsorbed { actor, spell_info, absorbed_amount, total_amount } =>
+                [actor_fields(actor), spell_info.to_log_fields(), vec![absorbed_amount.to_string(), total_amount.to_string()]].concat(),
+            Self::Absorbed { absorb_caster, absorb_spell_info, absorbed_amount, base_amount, critical } =>
+                [absorb_caster.to_log_fields(), absorb_spell_info.to_log_fields(), vec![absorbed_amount.to_string(), base_amount.to_string(), nil_bool(*critical)]].concat(),
+            Self::AbsorbedSupport { absorb_caster, absorb_spell_info, absorbed_amount, base_amount, critical } =>
+                [absorb_caster.to_log_fields(), absorb_spell_info.to_log_fields(), vec![absorbed_amount.to_string(), base_amount.to_string(), nil_bool(*critical)]].concat(),
+            Self::Energize { amount, over_energize, power_type, max_power } =>
+                vec![format!("{:.4}", amount), format!("{:.4}", over_energize), (*power_type as i8).to_string(), max_power.to_string()],
+            Self::Drain { amount, power_type, extra_amount, max_power } =>
+                vec![amount.to_string(), (*power_type as i8).to_string(), extra_amount.to_string(), max_power.to_string()],
+            Self::Leech { amount, power_type, extra_amount } =>
+                vec![amount.to_string(), (*power_type as i8).to_string(), extra_amount.to_string()],
+            Self::Interrupt { spell_info } | Self::DispelFailed { spell_info } => spell_info.to_log_fields(),
+            Self::Dispel { spell_info, aura_type }
+            | Self::Stolen { spell_info, aura_type }
+            | Self::AuraBrokenSpell { spell_info, aura_type } => with(spell_info.to_log_fields(), vec![aura(aura_type)]),
+            Self::ExtraAttacks { amount } => vec![amount.to_string()],
+            Self::AuraApplied { aura_type, amount } | Self::AuraRemoved { aura_type, amount } =>
+                [Some(aura(aura_type)), amount.map(|a| a.to_string())].into_iter().flatten().collect(),
+            Self::AuraAppliedDose { aura_type, amount } | Self::AuraRemovedDose { aura_type, amount } =>
+                vec![aura(aura_type), amount.to_string()],
+            Self::AuraRefresh { aura_type } | Self::AuraBroken { aura_type } => vec![aura(aura_type)],
+            Self::CastFailed { failed_type } => vec![quote(failed_type)],
+            Self::Instakill { unconscious_on_death } => vec![num_bool(*unconscious_on_death)],
+            Self::EmpowerEnd { empowered_rank } | Self::EmpowerInterrupt { empowered_rank } => vec![empowered_rank.to_string()],
+            Self::CastStart | Self::CastSuccess | Self::DurabilityDamage | Self::DurabilityDamageAll
+            | Self::Create | Self::Summon | Self::Resurrect | Self::EmpowerStart => vec![],
+        }
+    }
 
-        Ok(matched)
+    /// School of the hit itself. This can differ from the spell's school, eg. when an effect converts the damage
+    pub fn school(&self) -> Option<SpellSchoolSet> {
+        match self {
+            Self::Damage { school, .. }
+            | Self::DamageLanded { school, .. }
+            | Self::DamageSupport { school, .. }
+            | Self::DamageLandedSupport { school, .. } => *school,
+            _ => None,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Suffix;
+    use crate::components::format::LogFormat;
+    use crate::components::names::EventName;
+
+    fn parse_named(event_type: &str, line: &[&str]) -> crate::error::Result<Suffix> {
+        Suffix::parse(EventName::parse(event_type)?.suffix, line, &LogFormat::default())
+    }
 
     #[test]
     fn parse() {
         let event_type = "SPELL_DAMAGE";
         let line = vec!["23134", "23133", "-1", "2", "0", "0", "0", "nil", "nil", "nil"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_DAMAGE";
         let line = vec!["22844", "26082", "-1", "4", "0", "0", "-2025", "nil", "nil", "nil"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_PERIODIC_MISSED";
         let line = vec!["ABSORB", "nil", "9478", "11175", "nil"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_HEAL";
         let line = vec!["2621", "2621", "0", "0", "1"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_ABSORBED";
         let line = vec!["Player-1587-0F81497D", "Huisarts-Arathor", "0x514", "0x0", "47753", "Divine Aegis", "0x2", "983", "56699", "nil"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_ABSORBED";
         let line = vec!["Player-1329-0A0800FA", "Foxgates-Ravencrest", "0x512", "0x0", "386124", "Fel Armor", "0x20", "-2900", "48673", "nil"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_PERIODIC_ENERGIZE";
         let line = vec!["1.0000", "0.0000", "5", "6"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_DRAIN";
         let line = vec!["25", "3", "0", "160"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_EMPOWER_INTERRUPT";
         let line = vec!["0"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_AURA_APPLIED";
         let line = vec!["DEBUFF"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let line = vec!["DEBUFF", "123"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_AURA_REMOVED";
         let line = vec!["DEBUFF"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let line = vec!["DEBUFF", "123"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_AURA_APPLIED_DOSE";
         let line = vec!["DEBUFF", "123"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_AURA_REMOVED_DOSE";
         let line = vec!["DEBUFF", "123"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_AURA_REFRESH";
         let line = vec!["DEBUFF"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_AURA_BROKEN";
         let line = vec!["DEBUFF"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_AURA_BROKEN_SPELL";
         let line = vec!["360194", "Deathmark", "1", "DEBUFF"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_CAST_START";
         let line = vec![];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_CAST_SUCCESS";
         let line = vec![];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_CAST_FAILED";
         let line = vec!["Not yet recovered"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_SUMMON";
         let line = vec![];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_RESURRECT";
         let line = vec![];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_EMPOWER_START";
         let line = vec![];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_EMPOWER_END";
         let line = vec!["1"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SWING_DAMAGE_LANDED";
         let line = vec!["16898", "12070", "-1", "1", "0", "0", "0", "1", "nil", "nil"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_HEAL_ABSORBED";
         let line = vec!["Creature-0-4233-2549-14868-54983-00004E66CB", "Treant", "0x2114", "0x0", "422382", "Wild Growth", "0x8", "2585", "2585"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
 
         let event_type = "SPELL_HEAL_ABSORBED";
         let line = vec!["0000000000000000", "Unknown", "0x80000000", "0x80000000", "422382", "Wild Growth", "0x8", "2438", "2438"];
-        let parsed = Suffix::parse(event_type, &line);
+        let parsed = parse_named(event_type, &line);
         println!("{:?}", parsed);
     }
 }
\ No newline at end of file
@@ -1,25 +1,130 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
-use std::fs::File;
-use std::io::Write;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::PathBuf;
 
 use anyhow::{Context, Result};
 use chrono::NaiveDateTime;
+use clap::ValueEnum;
 use itertools::Itertools;
 
-use crate::components::common::Actor;
 use crate::components::events::{Event, EventType};
 use crate::components::guid::GUID;
-use crate::components::special;
+use crate::components::special::{EncounterEnd, EncounterStart, Special};
 use crate::components::suffixes::Suffix;
+use crate::consumers::actors::ActorIds;
+use crate::consumers::ownership::OwnershipResolver;
+use crate::consumers::percentiles::{format_percentile, Metric, Percentiles};
+use crate::consumers::phases::PhaseStart;
+use crate::consumers::pruning::LastSeen;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, SummaryFormat, Table};
+
+pub mod abilities;
+pub mod alerts;
+pub mod actors;
+pub mod auras;
+pub mod avoidable;
+pub mod battleres;
+pub mod compare;
+pub mod cooldowns;
+pub mod deaths;
+pub mod emotes;
+pub mod encounters;
+pub mod energize;
+pub mod gear;
+pub mod healing;
+pub mod hits;
+pub mod hooks;
+pub mod keystones;
+pub mod lag;
+pub mod movement;
+pub mod mythic;
+pub mod obs;
+pub mod overheal;
+pub mod ownership;
+pub mod percentiles;
+pub mod phases;
+pub mod pruning;
+pub mod pvp;
+pub mod registry;
+pub mod report;
+pub mod scope;
+pub mod segments;
+pub mod serve;
+pub mod spawns;
+pub mod spikes;
+pub mod sqlite;
+pub mod stats;
+pub mod status;
+pub mod summons;
+pub mod support;
+pub mod threat;
+pub mod timeline;
+pub mod timeseries;
 
 pub trait EventHandler {
-    fn handle(&mut self, event: &Result<Event>);
+    fn handle(&mut self, event: &Result<Event, ParseFailure>);
+
+    /// Plain text results, rendered from the summary unless a handler has its own
+    fn display(&self) -> Option<String> {
+        self.summary().map(|s| s.render(SummaryFormat::Plain))
+    }
+
+    /// Results as typed tables, so every output format can be rendered without changes to the handler
+    fn summary(&self) -> Option<Summary> { None }
+
+    /// Called once before any events are handled
+    fn on_start(&mut self) {}
+
+    /// Called before the ENCOUNTER_START event is handled
+    fn on_encounter_start(&mut self, _encounter: &EncounterStart) {}
+
+    /// Called after the ENCOUNTER_END event is handled
+    fn on_encounter_end(&mut self, _encounter: &EncounterEnd) {}
+
+    /// Called after the event starting a phase is handled, for encounters with phase rules. Phase 1 starts with the encounter
+    fn on_phase_start(&mut self, _phase: &PhaseStart) {}
 
-    fn display(&self) -> Option<String>;
+    /// Called once after the last event has been handled
+    fn on_finish(&mut self) {}
+
+    /// Drops any per-actor state for actors not seen since the cutoff
+    fn prune(&mut self, _cutoff: NaiveDateTime) {}
+
+    /// Number of actors this handler is holding state for
+    fn tracked_actors(&self) -> usize { 0 }
+}
+
+
+/// How spell breakdowns treat the periodic (DoT / HoT) part of a spell
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum PeriodicMode {
+    /// Count periodic ticks under the same spell as its direct hits
+    #[default]
+    Merge,
+    /// Report periodic ticks as a separate line to direct hits
+    Separate,
 }
 
+/// Passes an event to every handler, firing the encounter lifecycle hooks around it
+pub fn dispatch(handlers: &mut [Box<dyn EventHandler>], event: &Result<Event, ParseFailure>) {
+    let details = match event {
+        Ok(Event { event_type: EventType::Special { details, .. }, .. }) => Some(details),
+        _ => None
+    };
+
+    if let Some(Special::EncounterStart(encounter)) = details {
+        handlers.iter_mut().for_each(|h| h.on_encounter_start(encounter));
+    }
+
+    handlers.iter_mut().for_each(|h| h.handle(event));
+
+    if let Some(Special::EncounterEnd(encounter)) = details {
+        handlers.iter_mut().for_each(|h| h.on_encounter_end(encounter));
+    }
+}
 
 /// Logs out successfully & failed parsed events to stdout & stderr.
 pub struct StdLogger;
@@ -29,7 +134,7 @@ impl StdLogger {
 }
 
 impl EventHandler for StdLogger {
-    fn handle(&mut self, event: &Result<Event>) {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
         match event {
             Ok(x) => println!("{:?}", x),
             Err(x) => eprintln!("{}", x)
@@ -59,13 +164,14 @@ impl FileLogger {
 }
 
 impl EventHandler for FileLogger {
-    fn handle(&mut self, event: &Result<Event>) {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
         match event {
             Ok(x) => {
                 let _ = self.good_file.write(format!("{:?}\n", x).as_bytes());
             }
             Err(x) => {
-                let _ = self.bad_file.write(format!("{:?}\n", x).as_bytes());
+                // Where the line came from as a comment, then the raw line so the bad file can be fed back through the parser
+                let _ = self.bad_file.write(format!("# {}\n{}\n", x.location(), x.raw).as_bytes());
             }
         };
     }
@@ -75,72 +181,326 @@ impl EventHandler for FileLogger {
     }
 }
 
-/// A simple damage tracker
+/// Writes each event, or why its line failed, to a file as a JSON line, with short IDs of the event's source & target
+pub struct JsonLogger {
+    file: BufWriter<File>,
+    ids: ActorIds,
+}
+
+impl JsonLogger {
+    pub(crate) fn new(path: &PathBuf) -> Result<Self> {
+        Ok(Self {
+            file: BufWriter::new(File::create(path)
+                .with_context(|| format!("Failed to create file: {:?}", path))?),
+            ids: ActorIds::new(),
+        })
+    }
+
+    /// Adds to an earlier export rather than replacing it, carrying on with its short IDs
+    pub(crate) fn append(path: &PathBuf) -> Result<Self> {
+        let mut ids = ActorIds::new();
+        match File::open(path) {
+            Ok(file) => for (i, line) in BufReader::new(file).lines().enumerate() {
+                let row: serde_json::Value = serde_json::from_str(&line?)
+                    .with_context(|| format!("Failed to read line {} of {:?}", i + 1, path))?;
+                for (guid, id) in [("source_guid", "source_id"), ("target_guid", "target_id")] {
+                    if let (Some(guid), Some(id)) = (row[guid].as_str(), row[id].as_str()) {
+                        ids.restore(guid, id)?;
+                    }
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).with_context(|| format!("Failed to open file: {:?}", path)),
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)
+            .with_context(|| format!("Failed to open file: {:?}", path))?;
+        Ok(Self { file: BufWriter::new(file), ids })
+    }
+}
+
+impl EventHandler for JsonLogger {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        if let Ok(json) = serde_json::to_string(&self.ids.identify(event)) {
+            let _ = writeln!(self.file, "{}", json);
+        }
+    }
+
+    /// Keeps up with the checkpoint, which is also saved here
+    fn on_encounter_end(&mut self, _encounter: &EncounterEnd) {
+        let _ = self.file.flush();
+    }
+
+    fn on_finish(&mut self) {
+        let _ = self.file.flush();
+    }
+
+    fn display(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Player, target NPC ID & whether it was during an encounter
+type TargetKey = (String, u64, bool);
+
+/// What an NPC that took damage was, in the order of the by target columns
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TargetKind {
+    Boss,
+    Add,
+    Trash,
+}
+
+/// Damage per player in a phase of the encounter
+#[derive(Debug)]
+struct PhaseDamage {
+    name: String,
+    start: NaiveDateTime,
+    damage: HashMap<String, i64>,
+}
+
+/// A simple damage tracker. Pet & guardian damage is attributed to the owning player.
+/// DPS is ranked against reference percentiles when there's data for the boss & player's spec.
+/// Damage to NPCs is also split into boss, adds & trash (outside of encounters), plus priority targets if given.
+/// The boss is taken to be the hostile NPC with the most health. Encounters with phase rules are also split by phase.
 #[derive(Debug)]
 pub struct DamageTracker {
     accumulated: HashMap<String, i64>,
     start_time: Option<NaiveDateTime>,
     latest_time: Option<NaiveDateTime>,
+    owners: OwnershipResolver,
+    last_seen: LastSeen<String>,
+    percentiles: Percentiles,
+    encounter_id: Option<u64>,
+    specs: HashMap<GUID, u64>,
+    guids: HashMap<String, GUID>,
+    targets: HashMap<TargetKey, i64>,
+    npc_names: HashMap<u64, String>,
+    /// NPC ID & max health
+    boss: Option<(u64, u64)>,
+    in_encounter: bool,
+    priority_targets: HashSet<u64>,
+    phases: Vec<PhaseDamage>,
 }
 
 impl DamageTracker {
     pub(crate) fn new() -> Self {
-        Self { accumulated: HashMap::new(), start_time: None, latest_time: None }
+        Self {
+            accumulated: HashMap::new(),
+            start_time: None,
+            latest_time: None,
+            owners: OwnershipResolver::new(),
+            last_seen: LastSeen::default(),
+            percentiles: Percentiles::default(),
+            encounter_id: None,
+            specs: HashMap::new(),
+            guids: HashMap::new(),
+            targets: HashMap::new(),
+            npc_names: HashMap::new(),
+            boss: None,
+            in_encounter: false,
+            priority_targets: HashSet::new(),
+            phases: vec![],
+        }
+    }
+
+    /// NPC IDs whose damage is reported as priority damage
+    pub(crate) fn with_priority_targets(mut self, npc_ids: &[u64]) -> Self {
+        self.priority_targets = npc_ids.iter().copied().collect();
+        self
+    }
+
+    pub(crate) fn with_percentiles(mut self, percentiles: Percentiles) -> Self {
+        self.percentiles = percentiles;
+        self
     }
 
     fn reset(&mut self) {
         self.accumulated.clear();
+        self.last_seen.clear();
+        self.specs.clear();
+        self.guids.clear();
+        self.targets.clear();
+        self.npc_names.clear();
+        self.boss = None;
         self.start_time = None;
         self.latest_time = None;
+        self.phases.clear();
+    }
+
+    /// Damage & DPS by each player in each phase, each phase lasting until the next
+    fn phase_table(&self) -> Table {
+        let mut table = Table::new(&[("Phase", 20), ("Player", 30), ("Damage", 10), ("DPS", 10)]).with_title("By phase");
+        for (i, phase) in self.phases.iter().enumerate() {
+            let end = self.phases.get(i + 1).map(|p| p.start).or(self.latest_time).unwrap_or(phase.start);
+            let secs = ((end - phase.start).num_milliseconds() as f64 / 1000.).max(1.);
+            phase.damage.iter()
+                .sorted_by_key(|(player, &damage)| (-damage, (*player).clone()))
+                .for_each(|(player, &damage)| table.push(vec![
+                    phase.name.as_str().into(), player.as_str().into(), Cell::Int(damage), Cell::Float(damage as f64 / secs, 0),
+                ]));
+        }
+        table
+    }
+
+    fn target_kind(&self, npc_id: u64, in_encounter: bool) -> TargetKind {
+        match (in_encounter, self.boss) {
+            (false, _) => TargetKind::Trash,
+            (true, Some((boss, _))) if boss == npc_id => TargetKind::Boss,
+            (true, _) => TargetKind::Add,
+        }
+    }
+
+    /// Damage by each player to the boss, adds, trash & priority targets
+    fn target_table(&self) -> Table {
+        let mut split: HashMap<&str, [i64; 4]> = HashMap::new();
+        for ((player, npc_id, in_encounter), amount) in &self.targets {
+            let totals = split.entry(player).or_default();
+            totals[self.target_kind(*npc_id, *in_encounter) as usize] += amount;
+            if self.priority_targets.contains(npc_id) { totals[3] += amount; }
+        }
+
+        let mut table = Table::new(&[("Player", 30), ("Boss", 10), ("Adds", 10), ("Trash", 10), ("Priority", 10)])
+            .with_title("By target");
+        split.iter()
+            .sorted_by_key(|(player, totals)| (-totals[..3].iter().sum::<i64>(), **player))
+            .for_each(|(player, totals)| {
+                table.push([vec![(*player).into()], totals.iter().map(|t| Cell::Int(*t)).collect()].concat());
+            });
+        table
+    }
+
+    /// Damage to each target NPC
+    fn npc_table(&self) -> Table {
+        let mut npcs: HashMap<(u64, bool), i64> = HashMap::new();
+        self.targets.iter()
+            .for_each(|((_, npc_id, in_encounter), amount)| *npcs.entry((*npc_id, *in_encounter)).or_default() += amount);
+
+        let mut table = Table::new(&[("Target", 30), ("NPC ID", 8), ("Kind", 8), ("Damage", 10)]).with_title("Targets");
+        npcs.iter()
+            .sorted_by_key(|((npc_id, _), amount)| (-**amount, *npc_id))
+            .for_each(|((npc_id, in_encounter), amount)| {
+                let kind = self.target_kind(*npc_id, *in_encounter);
+                let kind = if self.priority_targets.contains(npc_id) { format!("{:?} *", kind) } else { format!("{:?}", kind) };
+                let name = self.npc_names.get(npc_id).map_or("", String::as_str);
+                table.push(vec![name.into(), (*npc_id).into(), kind.as_str().into(), Cell::Int(*amount)]);
+            });
+        table
+    }
+
+    fn percentile(&self, name: &str, dps: f64) -> Option<f64> {
+        let spec = self.guids.get(name).and_then(|g| self.specs.get(g))?;
+        self.percentiles.estimate(self.encounter_id?, *spec, Metric::Dps, dps)
     }
 }
 
 
 impl EventHandler for DamageTracker {
-    fn handle(&mut self, event: &Result<Event>) {
-        match event {
-            Ok(Event {
-                   timestamp: time,
-                   event_type: EventType::Standard {
-                       source: Some(Actor { name, guid: GUID::Player { .. }, .. }),
-                       suffix: Suffix::Damage { amount: dmg, .. },
-                       ..
-                   },
-                   ..
-               }) => {
-                if self.accumulated.is_empty() { self.start_time = Some(*time) }
-                self.latest_time = Some(*time);
-
-                if let Some(total) = self.accumulated.get_mut(name) {
-                    *total += dmg;
-                } else {
-                    self.accumulated.insert(name.clone(), *dmg);
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        if let Ok(e) = event { self.owners.update(e); }
+
+        if let Ok(Event { event_type: EventType::Special { details: Special::CombatantInfo(info), .. }, .. }) = event {
+            if let Some(spec) = info.spec_id { self.specs.insert(info.guid.clone(), spec); }
+        }
+
+        if let Ok(Event {
+                      timestamp: time,
+                      event_type: EventType::Standard {
+                          source: Some(source),
+                          target,
+                          advanced_params,
+                          suffix: Suffix::Damage { amount: dmg, .. },
+                          ..
+                      },
+                      ..
+                  }) = event {
+            let Some((guid, name)) = self.owners.resolve_player(source) else { return; };
+
+            if let Some(target) = target.as_ref().filter(|t| t.is_hostile() && !t.is_player_controlled()) {
+                if let GUID::Creature { id: npc_id, .. } = target.guid {
+                    *self.targets.entry((name.clone(), npc_id, self.in_encounter)).or_default() += dmg;
+                    self.npc_names.entry(npc_id).or_insert_with(|| target.name.clone());
+
+                    let max_hp = advanced_params.as_ref()
+                        .filter(|p| p.info_guid.as_ref() == Some(&target.guid))
+                        .map(|p| p.max_hp);
+                    if let (true, Some(max_hp)) = (self.in_encounter, max_hp) {
+                        if self.boss.is_none_or(|(_, boss_hp)| max_hp > boss_hp) { self.boss = Some((npc_id, max_hp)); }
+                    }
                 }
             }
 
-            // Reset on encounter start
-            Ok(Event {
-                   event_type: EventType::Special {
-                       details: special::Special::EncounterStart { .. }, ..
-                   }, ..
-               }) => {
-                self.reset();
+            if !self.guids.contains_key(&name) { self.guids.insert(name.clone(), guid); }
+            if let Some(phase) = self.phases.last_mut().filter(|_| self.in_encounter) {
+                *phase.damage.entry(name.clone()).or_default() += dmg;
+            }
+
+            if self.accumulated.is_empty() { self.start_time = Some(*time) }
+            self.latest_time = Some(*time);
+            self.last_seen.touch(&name, *time);
+
+            if let Some(total) = self.accumulated.get_mut(&name) {
+                *total += dmg;
+            } else {
+                self.accumulated.insert(name, *dmg);
             }
-            _ => {}
         }
     }
 
-    fn display(&self) -> Option<String> {
+    fn on_encounter_start(&mut self, encounter: &EncounterStart) {
+        self.reset();
+        self.encounter_id = Some(encounter.encounter_id);
+        self.in_encounter = true;
+    }
+
+    fn on_encounter_end(&mut self, _encounter: &EncounterEnd) {
+        self.in_encounter = false;
+    }
+
+    fn on_phase_start(&mut self, phase: &PhaseStart) {
+        self.phases.push(PhaseDamage { name: phase.name.clone(), start: phase.time, damage: HashMap::new() });
+    }
+
+    fn summary(&self) -> Option<Summary> {
         let duration = if let (Some(start), Some(end)) = (self.start_time, self.latest_time) {
             (end - start).num_seconds() + 1
         } else { 1 };
 
-        let s = self.accumulated.iter()
+        // Parses are only shown when there's reference data to rank against
+        let ranked = !self.percentiles.is_empty();
+        let columns = [("Player", 30), ("Damage", 10), ("DPS", 10), ("Parse", 10)];
+        let mut table = Table::new(if ranked { &columns } else { &columns[..3] });
+        self.accumulated.iter()
             .sorted_by_key(|(_, &v)| v).rev()
-            .map(|(k, v)| format!("{:>30}:{:>10}|{:>10.0}{:>10}", k, v, (*v as f64) / (duration as f64), "💯"))
-            .join("\n");
+            .for_each(|(k, v)| {
+                let dps = (*v as f64) / (duration as f64);
+                let mut row = vec![k.as_str().into(), Cell::Int(*v), Cell::Float(dps, 0)];
+                if ranked { row.push(format_percentile(self.percentile(k, dps)).into()); }
+                table.push(row);
+            });
+
+        let mut summary = Summary::new("Damage").with_table(table);
+        if !self.targets.is_empty() {
+            summary = summary.with_table(self.target_table()).with_table(self.npc_table());
+        }
+        if !self.phases.is_empty() {
+            summary = summary.with_table(self.phase_table());
+        }
+        Some(summary)
+    }
+
+    fn prune(&mut self, cutoff: NaiveDateTime) {
+        self.last_seen.expire(cutoff).iter()
+            .for_each(|name| {
+                self.accumulated.remove(name);
+                self.targets.retain(|(player, _, _), _| player != name);
+                if let Some(guid) = self.guids.remove(name) { self.specs.remove(&guid); }
+            });
+        self.owners.prune(cutoff);
+    }
 
-        Some(format!("8=================D~~~~~{:~>0}~{:~>10}~{:~>10}~{:~>10}\n{}", "Player", "Damage", "DPS", "Parse", s))
+    fn tracked_actors(&self) -> usize {
+        self.accumulated.len() + self.owners.len()
     }
 }
 
@@ -148,7 +508,141 @@ impl EventHandler for DamageTracker {
 pub struct NulLogger;
 
 impl EventHandler for NulLogger {
-    fn handle(&mut self, _event: &Result<Event>) {}
+    fn handle(&mut self, _event: &Result<Event, ParseFailure>) {}
 
     fn display(&self) -> Option<String> { None }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use anyhow::Result;
+
+    use crate::components::events::Event;
+    use crate::components::special::{EncounterEnd, EncounterStart};
+    use crate::consumers::{DamageTracker, dispatch, EventHandler};
+    use crate::consumers::percentiles::Percentiles;
+    use crate::consumers::segments::CombatSegmenter;
+    use crate::error::ParseFailure;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    struct HookRecorder {
+        calls: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl EventHandler for HookRecorder {
+        fn handle(&mut self, _event: &Result<Event, ParseFailure>) {
+            self.calls.borrow_mut().push("handle".to_string());
+        }
+
+        fn display(&self) -> Option<String> { None }
+
+        fn on_encounter_start(&mut self, encounter: &EncounterStart) {
+            self.calls.borrow_mut().push(format!("start {}", encounter.encounter_name));
+        }
+
+        fn on_encounter_end(&mut self, encounter: &EncounterEnd) {
+            self.calls.borrow_mut().push(format!("end {}", encounter.success));
+        }
+    }
+
+    #[test]
+    fn encounter_hooks() {
+        let log = "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 14:12:00.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,1,162742\n";
+
+        let calls = Rc::new(RefCell::new(vec![]));
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(HookRecorder { calls: calls.clone() })];
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        assert_eq!(*calls.borrow(), vec!["start Gnarlroot", "handle", "handle", "end true"]);
+    }
+
+    #[test]
+    fn damage_percentile() {
+        let log = "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 14:01:05.100  COMBATANT_INFO,Player-1335-0A264B4C,1,12648,1734,52761,1128,0,0,0,3511,3511,3511,900,0,4692,4692,4692,443,6741,533,533,533,11302,251,[(76034,96162,1)],(1,204080,199719,233396),[(207200,489,(),(),())],[Player-1335-0A264B4C,396092],145,0,0,0\n\
+4/6 14:01:06.000  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,500,500,-1,1,0,0,0,nil,nil,nil\n";
+
+        let mut percentiles = Percentiles::default();
+        percentiles.extend_from_str("2820,251,dps,50,250\n2820,251,dps,100,1000").unwrap();
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(DamageTracker::new().with_percentiles(percentiles))];
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        assert!(handlers[0].display().unwrap().contains("~67th"), "{}", handlers[0].display().unwrap());
+
+        // Without reference data there's nothing to rank against
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(DamageTracker::new())];
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+        let summary = handlers[0].summary().unwrap();
+        assert!(summary.tables[0].columns.iter().all(|c| c.name != "Parse"));
+        assert_eq!(summary.tables[0].rows[0].len(), 3);
+    }
+
+    #[test]
+    fn damage_by_target() {
+        let hit = |time: &str, target: &str, name: &str, max_hp: u64, amount: u64| format!("4/6 14:01:{}  SPELL_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,{},\"{}\",0x10a48,0x0,8921,\"Moonfire\",0x40,{},0000000000000000,100,{},0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,{},{},-1,64,0,0,0,nil,nil,nil\n", time, target, name, target, max_hp, amount, amount);
+        let boss = "Creature-0-1469-2549-12530-209333-000011428A";
+        let add = "Creature-0-1469-2549-12530-210231-000011428B";
+        let log = [
+            hit("00.000", add, "Tainted Treant", 5000, 50),
+            "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n".to_string(),
+            hit("06.000", add, "Tainted Treant", 5000, 300),
+            hit("07.000", boss, "Gnarlroot", 1000000, 1000),
+            hit("08.000", add, "Tainted Treant", 5000, 200),
+            "4/6 14:01:10.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,1,5000\n".to_string(),
+            hit("12.000", add, "Tainted Treant", 5000, 70),
+        ].concat();
+
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(DamageTracker::new().with_priority_targets(&[210231]))];
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        let summary = handlers[0].summary().unwrap();
+        let split = summary.table("By target").unwrap().find("Sønike-Ysondre").unwrap();
+        assert_eq!(split[1..], [Cell::Int(1000), Cell::Int(500), Cell::Int(70), Cell::Int(570)]);
+
+        let targets = &summary.table("Targets").unwrap().rows;
+        assert_eq!(targets[0][..3], [Cell::from("Gnarlroot"), 209333u64.into(), "Boss".into()]);
+        assert_eq!(targets[1][2..], [Cell::from("Add *"), Cell::Int(500)]);
+    }
+
+    #[test]
+    fn pet_damage_to_owner() {
+        // The warlock only shows up as the imp's owner
+        let log = "4/11 22:40:01.000  SPELL_DAMAGE,Pet-0-1461-2548-10089-17252-01040EF8F7,\"Khil'arad\",0x1114,0x0,Creature-0-1469-2549-12091-204931-0000186743,\"Fyrakk\",0x10a48,0x0,54049,\"Shadow Bite\",0x20,Pet-0-1461-2548-10089-17252-01040EF8F7,Player-1329-0A0800FA,100,100,0,0,0,0,-1,0,0,0,0.00,0.00,2238,0.0000,70,500,500,-1,32,0,0,0,nil,nil,nil\n\
+4/11 22:40:02.000  SPELL_DAMAGE,Pet-0-1461-2548-10089-17252-01040EF8F7,\"Khil'arad\",0x1114,0x0,Creature-0-1469-2549-12091-204931-0000186743,\"Fyrakk\",0x10a48,0x0,54049,\"Shadow Bite\",0x20,Pet-0-1461-2548-10089-17252-01040EF8F7,Player-1329-0A0800FA,100,100,0,0,0,0,-1,0,0,0,0.00,0.00,2238,0.0000,70,700,700,-1,32,0,0,0,nil,nil,nil\n";
+
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(DamageTracker::new())];
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        let summary = handlers[0].summary().unwrap();
+        let row = summary.tables[0].find("Player-1329-0A0800FA").unwrap();
+        assert_eq!(row[1], Cell::Int(1200));
+    }
+
+    #[test]
+    fn damage_by_phase() {
+        let hit = |time: &str, amount: u64| format!("4/6 14:01:{}  SPELL_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,8921,\"Moonfire\",0x40,Creature-0-1469-2549-12530-209333-000011428A,0000000000000000,100,1000000,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,{},{},-1,64,0,0,0,nil,nil,nil\n", time, amount, amount);
+        let log = [
+            "4/6 14:01:00.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n".to_string(),
+            hit("02.000", 1000),
+            "4/6 14:01:10.000  SPELL_CAST_START,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,0000000000000000,nil,0x80000000,0x80000000,421898,\"Flaming Pestilence\",0x4\n".to_string(),
+            hit("15.000", 2000),
+            hit("20.000", 3000),
+            "4/6 14:01:20.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,1,20000\n".to_string(),
+        ].concat();
+
+        let rules = "[[phases]]\nencounter_id = 2820\nphase = 2\nname = \"Intermission\"\ncast = 421898\n".parse().unwrap();
+        let mut segmenter = CombatSegmenter::new(None).with_phases(Some(rules));
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(DamageTracker::new())];
+        EventParser::new(log.as_bytes()).for_each(|e| segmenter.dispatch(&mut handlers, &e));
+
+        let summary = handlers[0].summary().unwrap();
+        let rows = &summary.table("By phase").unwrap().rows;
+        assert_eq!(rows[0], ["Phase 1".into(), "Sønike-Ysondre".into(), Cell::Int(1000), Cell::Float(100., 0)]);
+        assert_eq!(rows[1], ["Intermission".into(), "Sønike-Ysondre".into(), Cell::Int(5000), Cell::Float(500., 0)]);
+    }
+}
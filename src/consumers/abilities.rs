@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+
+use crate::components::events::{Event, EventType};
+use crate::components::prefixes::Prefix;
+use crate::components::special::EncounterStart;
+use crate::components::suffixes::Suffix;
+use crate::consumers::{EventHandler, PeriodicMode};
+use crate::consumers::ownership::OwnershipResolver;
+use crate::consumers::pruning::LastSeen;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+#[derive(Debug, Default)]
+struct AbilityDamage {
+    spell_name: String,
+    amount: i64,
+    hits: u64,
+    crits: u64,
+}
+
+impl AbilityDamage {
+    fn crit_pct(&self) -> f64 {
+        if self.hits == 0 { 0. } else { 100. * self.crits as f64 / self.hits as f64 }
+    }
+
+    fn average_hit(&self) -> f64 {
+        if self.hits == 0 { 0. } else { self.amount as f64 / self.hits as f64 }
+    }
+}
+
+/// Player, spell ID (0 for melee) & whether it's the periodic part of the spell
+type AbilityKey = (String, u64, bool);
+
+/// Per (player, ability) damage breakdown, like the WarcraftLogs damage done table: hits, crit rate, average hit
+/// & each ability's share of the player's damage. Pet damage is attributed to the owner.
+#[derive(Debug, Default)]
+pub struct AbilityBreakdown {
+    abilities: HashMap<AbilityKey, AbilityDamage>,
+    owners: OwnershipResolver,
+    last_seen: LastSeen<String>,
+    periodic: PeriodicMode,
+}
+
+impl AbilityBreakdown {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn with_periodic(mut self, periodic: PeriodicMode) -> Self {
+        self.periodic = periodic;
+        self
+    }
+
+    fn player_total(&self, player: &str) -> i64 {
+        self.abilities.iter()
+            .filter(|((p, ..), _)| p == player)
+            .map(|(_, a)| a.amount)
+            .sum()
+    }
+}
+
+impl EventHandler for AbilityBreakdown {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        self.owners.update(event);
+
+        let EventType::Standard { source: Some(source), prefix, suffix: Suffix::Damage { amount, critical, .. }, .. } = &event.event_type
+            else { return; };
+        let Some((_, player)) = self.owners.resolve_player(source) else { return; };
+        self.last_seen.touch(&player, event.timestamp);
+
+        let (spell_id, spell_name) = prefix.spell_info().map_or((0, "Melee"), |s| (s.spell_id, &*s.spell_name));
+        let periodic = self.periodic == PeriodicMode::Separate && matches!(&**prefix, Prefix::SpellPeriodic(_));
+        let entry = self.abilities.entry((player, spell_id, periodic)).or_default();
+        if entry.spell_name.is_empty() {
+            entry.spell_name = if periodic { format!("{} (DoT)", spell_name) } else { spell_name.to_string() };
+        }
+        entry.amount += amount;
+        entry.hits += 1;
+        entry.crits += *critical as u64;
+    }
+
+    fn on_encounter_start(&mut self, _encounter: &EncounterStart) {
+        self.abilities.clear();
+        self.last_seen.clear();
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let totals = self.abilities.keys()
+            .map(|(player, ..)| player)
+            .unique()
+            .map(|player| (player, self.player_total(player)))
+            .collect::<HashMap<_, _>>();
+
+        let mut table = Table::new(&[("Player", 30), ("Ability", 25), ("Damage", 10), ("Share", 8), ("Hits", 6), ("Crit", 8), ("Avg Hit", 10)]);
+        self.abilities.iter()
+            .sorted_by_key(|((player, ..), a)| (-totals[player], player.clone(), -a.amount))
+            .for_each(|((player, ..), a)| {
+                let share = if totals[player] == 0 { 0. } else { 100. * a.amount as f64 / totals[player] as f64 };
+                table.push(vec![
+                    player.as_str().into(), a.spell_name.as_str().into(), a.amount.into(), Cell::Percent(share),
+                    a.hits.into(), Cell::Percent(a.crit_pct()), Cell::Float(a.average_hit(), 0),
+                ]);
+            });
+
+        Some(Summary::new("Abilities").with_table(table))
+    }
+
+    fn prune(&mut self, cutoff: NaiveDateTime) {
+        for player in self.last_seen.expire(cutoff) {
+            self.abilities.retain(|(p, ..), _| *p != player);
+        }
+        self.owners.prune(cutoff);
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.last_seen.len() + self.owners.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::abilities::AbilityBreakdown;
+    use crate::consumers::{EventHandler, PeriodicMode};
+    use crate::parser::EventParser;
+
+    #[test]
+    fn abilities() {
+        let log = "4/6 14:02:07.362  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,500,500,-1,1,0,0,0,nil,nil,nil\n\
+4/6 14:02:08.000  SPELL_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,8921,\"Moonfire\",0x40,Creature-0-1469-2549-12530-209333-000011428A,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,1000,1000,-1,64,0,0,0,1,nil,nil\n\
+4/6 14:02:09.000  SPELL_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,8921,\"Moonfire\",0x40,Creature-0-1469-2549-12530-209333-000011428A,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,500,500,-1,64,0,0,0,nil,nil,nil\n";
+
+        let mut tracker = AbilityBreakdown::new();
+        EventParser::new(log.as_bytes()).for_each(|e| tracker.handle(&e));
+
+        let moonfire = &tracker.abilities[&("Sønike-Ysondre".to_string(), 8921, false)];
+        assert_eq!((moonfire.amount, moonfire.hits), (1500, 2));
+        assert_eq!(moonfire.crit_pct(), 50.);
+        assert_eq!(moonfire.average_hit(), 750.);
+        assert_eq!(tracker.abilities[&("Sønike-Ysondre".to_string(), 0, false)].spell_name, "Melee");
+        assert_eq!(tracker.player_total("Sønike-Ysondre"), 2000);
+
+        let summary = tracker.summary().unwrap();
+        assert_eq!(summary.tables[0].rows[0][1], "Moonfire".into());
+    }
+
+    #[test]
+    fn periodic_mode() {
+        let log = "4/6 14:02:08.000  SPELL_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,8921,\"Moonfire\",0x40,Creature-0-1469-2549-12530-209333-000011428A,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,1000,1000,-1,64,0,0,0,nil,nil,nil\n\
+4/6 14:02:10.000  SPELL_PERIODIC_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,8921,\"Moonfire\",0x40,Creature-0-1469-2549-12530-209333-000011428A,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,200,200,-1,64,0,0,0,nil,nil,nil\n";
+
+        let mut merged = AbilityBreakdown::new();
+        EventParser::new(log.as_bytes()).for_each(|e| merged.handle(&e));
+        assert_eq!(merged.abilities.len(), 1);
+        assert_eq!(merged.abilities[&("Sønike-Ysondre".to_string(), 8921, false)].amount, 1200);
+
+        let mut separate = AbilityBreakdown::new().with_periodic(PeriodicMode::Separate);
+        EventParser::new(log.as_bytes()).for_each(|e| separate.handle(&e));
+        assert_eq!(separate.abilities[&("Sønike-Ysondre".to_string(), 8921, false)].amount, 1000);
+        assert_eq!(separate.abilities[&("Sønike-Ysondre".to_string(), 8921, true)].amount, 200);
+        assert!(separate.display().unwrap().contains("Moonfire (DoT)"));
+    }
+}
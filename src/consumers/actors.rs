@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::components::common::Actor;
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::error::ParseFailure;
+use crate::parser::ParseResult;
+
+/// An actor in an export: the raw GUID for joining against other data, and a short ID which can be used instead
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActorRef {
+    pub guid: String,
+    pub id: String,
+}
+
+/// An event or failure as exported, with the raw GUIDs & short IDs of the event's source & target next to it
+#[derive(Debug, Serialize)]
+pub struct IdentifiedResult<'a> {
+    #[serde(flatten)]
+    pub result: ParseResult<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_guid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_guid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<String>,
+}
+
+/// The source & target of events which have them
+pub fn event_actors(event: &Event) -> (Option<&Actor>, Option<&Actor>) {
+    match &event.event_type {
+        EventType::Standard { source, target, .. } | EventType::EnvironmentalDamage { source, target, .. } =>
+            (source.as_ref(), target.as_ref()),
+        EventType::Partial(partial) => (partial.source.as_ref(), partial.target.as_ref()),
+        EventType::Special { .. } | EventType::Unknown { .. } => (None, None),
+    }
+}
+
+/// Short IDs (A1, A2, ...) for actors, in the order they're first seen. They're stable for the whole export,
+/// so privacy conscious exports can drop the raw GUIDs without breaking references between rows.
+#[derive(Debug, Default)]
+pub struct ActorIds {
+    ids: HashMap<GUID, usize>,
+}
+
+impl ActorIds {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn id(&mut self, guid: &GUID) -> String {
+        let next = self.ids.len() + 1;
+        format!("A{}", self.ids.entry(guid.clone()).or_insert(next))
+    }
+
+    pub fn actor_ref(&mut self, guid: &GUID) -> ActorRef {
+        ActorRef { guid: guid.to_string(), id: self.id(guid) }
+    }
+
+    /// Short IDs of the event's source & target
+    pub fn event_ids(&mut self, event: &Event) -> (Option<String>, Option<String>) {
+        let (source, target) = event_actors(event);
+        (source.map(|a| self.id(&a.guid)), target.map(|a| self.id(&a.guid)))
+    }
+
+    pub fn identify<'a>(&mut self, result: &'a Result<Event, ParseFailure>) -> IdentifiedResult<'a> {
+        let (source, target) = result.as_ref().map_or((None, None), event_actors);
+        let mut actor_ref = |actor: Option<&Actor>| actor.map(|a| self.actor_ref(&a.guid)).map(|r| (r.guid, r.id)).unzip();
+        let (source_guid, source_id) = actor_ref(source);
+        let (target_guid, target_id) = actor_ref(target);
+        IdentifiedResult { result: ParseResult::from(result), source_guid, source_id, target_guid, target_id }
+    }
+
+    /// Carries on with an ID from an earlier export, eg. `("Player-1403-0A5506C6", "A1")`
+    pub fn restore(&mut self, guid: &str, id: &str) -> Result<()> {
+        let parsed = GUID::parse(guid)?.with_context(|| format!("Not an actor GUID: {:?}", guid))?;
+        let n = id.strip_prefix('A').and_then(|n| n.parse().ok())
+            .with_context(|| format!("Not a short actor ID: {:?}", id))?;
+        self.ids.insert(parsed, n);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize { self.ids.len() }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::components::guid::GUID;
+    use crate::consumers::actors::ActorIds;
+    use crate::parser::EventParser;
+
+    #[test]
+    fn stable_ids() {
+        let a = GUID::parse("Player-1403-0A5506C6").unwrap().unwrap();
+        let b = GUID::parse("Creature-0-1469-2549-12530-209333-000011428A").unwrap().unwrap();
+
+        let mut ids = ActorIds::new();
+        assert_eq!(ids.id(&a), "A1");
+        assert_eq!(ids.id(&b), "A2");
+        assert_eq!(ids.id(&a), "A1");
+        assert_eq!(ids.actor_ref(&b).guid, "Creature-0-1469-2549-12530-209333-000011428A");
+    }
+
+    #[test]
+    fn identified_json() {
+        let log = "4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:02:08.000  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,NOT_A_MISS,1\n";
+        let mut ids = ActorIds::new();
+        let json = EventParser::new(log.as_bytes())
+            .map(|e| serde_json::to_value(ids.identify(&e)).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(json[0]["event"]["event_type"]["Standard"]["name"], "SWING_MISSED");
+        assert_eq!(json[0]["source_guid"], "Player-1335-0A264B4C");
+        assert_eq!((&json[0]["source_id"], &json[0]["target_id"]), (&serde_json::json!("A1"), &serde_json::json!("A2")));
+        assert!(json[1]["error"].is_object());
+        assert!(json[1].get("source_id").is_none());
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+use std::thread;
+
+use anyhow::{bail, Context, Result};
+use chrono::{Duration, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::components::common::Actor;
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::Special;
+use crate::components::suffixes::Suffix;
+use crate::consumers::auras::ActiveAuras;
+use crate::consumers::hooks::{post, run};
+use crate::consumers::spikes::{DEFENSIVES, TANK_SPECS};
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+fn default_cooldown() -> f64 { 5. }
+
+#[derive(Debug, Clone, Deserialize)]
+struct AlertRule {
+    name: String,
+    /// Event types to match, eg. SPELL_DAMAGE. Any if empty
+    #[serde(default)]
+    events: Vec<String>,
+    spell: Option<u64>,
+    /// NPC IDs of the source or target
+    source_npc: Option<u64>,
+    target_npc: Option<u64>,
+    /// Only hits on or heals of tanks, going by their spec
+    #[serde(default)]
+    target_tank: bool,
+    /// Damage or healing done by the event
+    min_amount: Option<i64>,
+    /// The target has none of the major defensives up
+    #[serde(default)]
+    no_defensive: bool,
+    /// The target has none of these auras up
+    #[serde(default)]
+    missing_auras: Vec<u64>,
+    /// Shown when the alert fires. `{source}`, `{target}`, `{spell}` & `{amount}` are filled in from the event
+    message: String,
+    /// Ring the terminal bell
+    #[serde(default)]
+    sound: bool,
+    /// Shell command run, with the alert in WOW_* environment variables
+    command: Option<String>,
+    /// http:// or https:// URL sent a JSON POST
+    webhook: Option<String>,
+    /// Time before the alert can fire again for the same target
+    #[serde(default = "default_cooldown")]
+    cooldown_secs: f64,
+}
+
+/// Alerts to fire, loaded from TOML, eg.
+/// ```toml
+/// [[alerts]]
+/// name = "Unmitigated tank hit"
+/// events = ["SPELL_DAMAGE", "SWING_DAMAGE"]
+/// target_tank = true
+/// no_defensive = true
+/// min_amount = 1000000
+/// message = "{target} took {amount} from {spell} with no defensive up"
+/// sound = true
+/// webhook = "http://localhost:8123/alert"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AlertRules {
+    #[serde(default)]
+    alerts: Vec<AlertRule>,
+}
+
+impl AlertRules {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let s = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to open file: {:?}", path.as_ref()))?;
+        s.parse()
+    }
+}
+
+impl std::str::FromStr for AlertRules {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let rules: Self = toml::from_str(s).context("Bad alert rules")?;
+        if let Some(rule) = rules.alerts.iter().find(|r| r.webhook.as_ref().is_some_and(|w| !w.starts_with("http://") && !w.starts_with("https://"))) {
+            bail!("Alert {:?}: only http:// & https:// webhooks are supported", rule.name);
+        }
+        Ok(rules)
+    }
+}
+
+/// An alert which went off, sent as the webhook body
+#[derive(Debug, Clone, Serialize)]
+struct Fired {
+    alert: String,
+    message: String,
+    time: NaiveDateTime,
+    encounter: Option<String>,
+}
+
+/// Fires alerts on events matching the alert rules. Live, each one is printed as it happens & runs the rule's actions,
+/// otherwise they're only listed in the summary, eg. to try rules out on old logs.
+#[derive(Debug, Default)]
+pub struct AlertEngine {
+    rules: AlertRules,
+    live: bool,
+    tanks: HashSet<GUID>,
+    auras: ActiveAuras,
+    /// Last time each rule fired, per target
+    last_fired: HashMap<(usize, String), NaiveDateTime>,
+    fired: Vec<Fired>,
+}
+
+impl AlertEngine {
+    pub fn new(rules: AlertRules) -> Self {
+        Self { rules, ..Default::default() }
+    }
+
+    pub fn live(mut self, live: bool) -> Self {
+        self.live = live;
+        self
+    }
+
+    fn matches(&self, rule: &AlertRule, event: &Event) -> bool {
+        let EventType::Standard { name, source, target, prefix, suffix, .. } = &event.event_type else { return false; };
+        let npc_id = |actor: &Option<_>| match actor {
+            Some(Actor { guid: GUID::Creature { id, .. }, .. }) => Some(*id),
+            _ => None,
+        };
+        let amount = match suffix {
+            Suffix::Damage { amount, .. } => Some(*amount),
+            Suffix::Heal { amount, .. } => Some(*amount as i64),
+            _ => None,
+        };
+        let target_guid = target.as_ref().map(|t| &t.guid);
+
+        (rule.events.is_empty() || rule.events.iter().any(|e| **e == **name))
+            && rule.spell.is_none_or(|id| prefix.spell_info().is_some_and(|s| s.spell_id == id))
+            && rule.source_npc.is_none_or(|id| npc_id(source) == Some(id))
+            && rule.target_npc.is_none_or(|id| npc_id(target) == Some(id))
+            && (!rule.target_tank || target_guid.is_some_and(|g| self.tanks.contains(g)))
+            && rule.min_amount.is_none_or(|min| amount.is_some_and(|a| a >= min))
+            && (!rule.no_defensive && rule.missing_auras.is_empty() || target_guid.is_some_and(|g| {
+                !self.auras.on(g).any(|(id, _)| rule.no_defensive && DEFENSIVES.contains(&id) || rule.missing_auras.contains(&id))
+            }))
+    }
+
+    fn fire(&self, rule: &AlertRule, fired: &Fired) {
+        eprintln!("{}[{}] {}: {}", if rule.sound { "\x07" } else { "" }, fired.time.format("%H:%M:%S"), fired.alert, fired.message);
+        let _ = std::io::stderr().flush();
+
+        if let Some(command) = &rule.command {
+            let env = vec![("WOW_EVENT", "alert".to_string()), ("WOW_ALERT", fired.alert.clone()), ("WOW_MESSAGE", fired.message.clone())];
+            let command = command.clone();
+            thread::spawn(move || {
+                if let Err(e) = run(&command, env) { eprintln!("{e:#}"); }
+            });
+        }
+
+        if let Some(url) = &rule.webhook {
+            let url = url.clone();
+            let Ok(body) = serde_json::to_string(fired) else { return; };
+            thread::spawn(move || {
+                let headers = [("Content-Type", "application/json".to_string())];
+                if let Err(e) = post(&url, &headers, body.as_bytes()) { eprintln!("{e:#}"); }
+            });
+        }
+    }
+}
+
+/// Fills in a rule's message from the event
+fn render(message: &str, event: &Event) -> String {
+    let EventType::Standard { source, target, prefix, suffix, .. } = &event.event_type else { return message.to_string(); };
+    let name = |actor: &Option<Actor>| actor.as_ref().map_or("nobody".to_string(), |a| a.name.clone());
+    let amount = match suffix {
+        Suffix::Damage { amount, .. } => amount.to_string(),
+        Suffix::Heal { amount, .. } => amount.to_string(),
+        _ => String::new(),
+    };
+
+    message
+        .replace("{source}", &name(source))
+        .replace("{target}", &name(target))
+        .replace("{spell}", prefix.spell_info().map_or("Melee", |s| &s.spell_name))
+        .replace("{amount}", &amount)
+}
+
+impl EventHandler for AlertEngine {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        if let EventType::Special { details: Special::CombatantInfo(info), .. } = &event.event_type {
+            if info.spec_id.is_some_and(|s| TANK_SPECS.contains(&s)) {
+                self.tanks.insert(info.guid.clone());
+            } else {
+                self.tanks.remove(&info.guid);
+            }
+        }
+
+        for (i, rule) in self.rules.alerts.iter().enumerate() {
+            if !self.matches(rule, event) { continue; }
+
+            let EventType::Standard { target, .. } = &event.event_type else { continue; };
+            let key = (i, target.as_ref().map(|t| t.name.clone()).unwrap_or_default());
+            let cooldown = Duration::milliseconds((rule.cooldown_secs * 1000.) as i64);
+            if self.last_fired.get(&key).is_some_and(|t| event.timestamp - *t < cooldown) { continue; }
+            self.last_fired.insert(key, event.timestamp);
+
+            let fired = Fired {
+                alert: rule.name.clone(),
+                message: render(&rule.message, event),
+                time: event.timestamp,
+                encounter: event.context.encounter.as_ref().map(|e| e.name.clone()),
+            };
+            if self.live { self.fire(rule, &fired); }
+            self.fired.push(fired);
+        }
+
+        // Auras are updated after matching, so a hit removing a defensive still sees it up
+        self.auras.update(event);
+    }
+
+    fn display(&self) -> Option<String> {
+        let recent = self.fired.iter().rev().take(5).rev()
+            .map(|f| format!("[{}] {}: {}", f.time.format("%H:%M:%S"), f.alert, f.message))
+            .collect::<Vec<_>>();
+        Some(format!("Alerts: {} fired\n{}", self.fired.len(), recent.join("\n")))
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let mut table = Table::new(&[("Time", 8), ("Encounter", 25), ("Alert", 25), ("Message", 60)]);
+        for fired in &self.fired {
+            table.push(vec![
+                fired.time.format("%H:%M:%S").to_string().into(),
+                fired.encounter.as_deref().map_or(Cell::Empty, Cell::from),
+                fired.alert.as_str().into(),
+                fired.message.as_str().into(),
+            ]);
+        }
+        Some(Summary::new("Alerts").with_table(table))
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.auras.len() + self.last_fired.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::alerts::{AlertEngine, AlertRules};
+    use crate::consumers::EventHandler;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn unmitigated_tank_hit() {
+        let rules: AlertRules = r#"
+[[alerts]]
+name = "Unmitigated tank hit"
+events = ["SPELL_DAMAGE"]
+target_tank = true
+no_defensive = true
+min_amount = 400000
+message = "{target} took {amount} from {spell}"
+"#.parse().unwrap();
+        let hit = |time: &str, amount: u64| format!("4/6 14:02:{}  SPELL_DAMAGE,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,421971,\"Controlled Burn\",0x4,Player-1390-0C4E032E,0000000000000000,500000,1000000,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,483,{},{},-1,4,0,0,0,nil,nil,nil\n", time, amount, amount);
+        let log = [
+            "4/6 14:02:00.000  COMBATANT_INFO,Player-1390-0C4E032E,1,12648,1734,52761,1128,0,0,0,3511,3511,3511,900,0,4692,4692,4692,443,6741,533,533,533,11302,250,[(76034,96162,1)],(1,204080,199719,233396),[(207200,489,(),(),())],[Player-1098-0500B8C6,396092],145,0,0,0\n".to_string(),
+            hit("10.000", 100000),
+            hit("11.000", 450000),
+            // Within the cooldown
+            hit("12.000", 450000),
+            "4/6 14:02:20.000  SPELL_AURA_APPLIED,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,55233,\"Vampiric Blood\",0x20,BUFF\n".to_string(),
+            hit("21.000", 450000),
+            "4/6 14:02:25.000  SPELL_AURA_REMOVED,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,55233,\"Vampiric Blood\",0x20,BUFF\n".to_string(),
+            hit("30.000", 500000),
+        ].concat();
+
+        let mut engine = AlertEngine::new(rules);
+        EventParser::new(log.as_bytes()).for_each(|e| engine.handle(&e));
+
+        let summary = engine.summary().unwrap();
+        let rows = &summary.tables[0].rows;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0][2..], ["Unmitigated tank hit".into(), Cell::from("Stillnixx-Hyjal took 450000 from Controlled Burn")]);
+        assert_eq!(rows[1][0], "14:02:30".into());
+
+        assert!("[[alerts]]\nname = \"x\"\nmessage = \"x\"\nwebhook = \"https://example.com\"\n".parse::<AlertRules>().is_ok());
+        assert!("[[alerts]]\nname = \"x\"\nmessage = \"x\"\nwebhook = \"ftp://example.com\"\n".parse::<AlertRules>().is_err());
+    }
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::Special;
+use crate::components::suffixes::Suffix;
+
+/// Auras currently up on each unit, followed from the aura applied & removed events.
+/// Not a handler itself: it's kept up to date by the handlers which need to know what's up at a point in time.
+#[derive(Debug, Default)]
+pub struct ActiveAuras {
+    /// Spell names by spell ID, per unit
+    auras: HashMap<GUID, HashMap<u64, Arc<str>>>,
+}
+
+impl ActiveAuras {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn update(&mut self, event: &Event) {
+        match &event.event_type {
+            EventType::Standard { target: Some(target), prefix, suffix, .. } => {
+                let Some(spell) = prefix.spell_info() else { return; };
+                match suffix {
+                    Suffix::AuraApplied { .. } | Suffix::AuraAppliedDose { .. } | Suffix::AuraRefresh { .. } => {
+                        self.auras.entry(target.guid.clone()).or_default()
+                            .insert(spell.spell_id, spell.spell_name.clone());
+                    }
+                    Suffix::AuraRemoved { .. } => {
+                        if let Some(auras) = self.auras.get_mut(&target.guid) { auras.remove(&spell.spell_id); }
+                    }
+                    _ => {}
+                }
+            }
+            EventType::Special { details: Special::UnitDied { target: Some(target), .. }, .. } => {
+                self.auras.remove(&target.guid);
+            }
+            _ => {}
+        }
+    }
+
+    /// Spell IDs & names of the auras up on a unit
+    pub fn on(&self, guid: &GUID) -> impl Iterator<Item=(u64, &str)> {
+        self.auras.get(guid).into_iter().flatten().map(|(id, name)| (*id, &**name))
+    }
+
+    /// Number of units with auras tracked
+    pub fn len(&self) -> usize {
+        self.auras.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.auras.is_empty()
+    }
+}
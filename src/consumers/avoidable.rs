@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::components::common::Actor;
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::{EncounterEnd, EncounterStart};
+use crate::components::suffixes::Suffix;
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+use crate::summary::{Summary, Table};
+
+#[derive(Debug, Clone, Deserialize)]
+struct Tier {
+    spells: Vec<u64>,
+}
+
+/// Spells counted as avoidable, grouped by raid tier. Loaded from TOML, eg.
+/// ```toml
+/// friendly_fire = true
+///
+/// [tiers.amirdrassil]
+/// spells = [421971, 422026]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct AvoidableRules {
+    #[serde(default)]
+    tiers: HashMap<String, Tier>,
+    /// Also flag damage from other players & their pets
+    #[serde(default = "default_friendly_fire")]
+    friendly_fire: bool,
+}
+
+fn default_friendly_fire() -> bool { true }
+
+impl Default for AvoidableRules {
+    fn default() -> Self {
+        Self { tiers: HashMap::new(), friendly_fire: default_friendly_fire() }
+    }
+}
+
+impl AvoidableRules {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let s = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to open file: {:?}", path.as_ref()))?;
+        s.parse()
+    }
+
+    /// Raid tier the spell is avoidable in
+    pub fn tier_of(&self, spell_id: u64) -> Option<&str> {
+        self.tiers.iter()
+            .find(|(_, tier)| tier.spells.contains(&spell_id))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+impl std::str::FromStr for AvoidableRules {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        toml::from_str(s).context("Bad avoidable damage rules")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Kind {
+    Avoidable,
+    FriendlyFire,
+}
+
+#[derive(Debug, Default)]
+struct Hits {
+    count: usize,
+    amount: i64,
+}
+
+#[derive(Debug)]
+struct PullDamage {
+    encounter_name: String,
+    /// (player, ability, kind) -> hits taken
+    taken: HashMap<(String, String, Kind), Hits>,
+}
+
+/// Avoidable damage & friendly fire taken by each player in each pull.
+/// Absorbed damage is included, as the shield was spent on it.
+#[derive(Debug, Default)]
+pub struct AvoidableDamage {
+    rules: AvoidableRules,
+    pulls: Vec<PullDamage>,
+    in_encounter: bool,
+}
+
+impl AvoidableDamage {
+    pub fn new(rules: AvoidableRules) -> Self {
+        Self { rules, ..Self::default() }
+    }
+
+    fn is_friendly_fire(&self, source: Option<&Actor>, target: &Actor) -> bool {
+        let Some(source) = source else { return false; };
+        self.rules.friendly_fire
+            && source.guid != target.guid
+            && source.is_friendly()
+            && source.is_player_controlled()
+    }
+}
+
+impl EventHandler for AvoidableDamage {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        if !self.in_encounter { return; }
+
+        let EventType::Standard { source, target: Some(target), prefix, suffix: Suffix::Damage { amount, absorbed, .. }, .. } = &event.event_type
+            else { return; };
+        if !matches!(target.guid, GUID::Player { .. }) { return; }
+
+        let spell = prefix.spell_info();
+        let kind = if spell.is_some_and(|s| self.rules.tier_of(s.spell_id).is_some()) {
+            Kind::Avoidable
+        } else if self.is_friendly_fire(source.as_ref(), target) {
+            Kind::FriendlyFire
+        } else {
+            return;
+        };
+
+        let ability = spell.map_or("Melee".to_string(), |s| s.spell_name.to_string());
+        let Some(pull) = self.pulls.last_mut() else { return; };
+        let hits = pull.taken.entry((target.name.clone(), ability, kind)).or_default();
+        hits.count += 1;
+        hits.amount += amount + absorbed.max(&0);
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let tables = self.pulls.iter().enumerate()
+            .map(|(i, pull)| {
+                let mut table = Table::new(&[("Player", 30), ("Ability", 30), ("Kind", 14), ("Hits", 6), ("Damage", 10)])
+                    .with_title(format!("{} (pull {})", pull.encounter_name, i + 1));
+
+                pull.taken.iter()
+                    .sorted_by_key(|((player, ability, _), hits)| (player.clone(), std::cmp::Reverse(hits.amount), ability.clone()))
+                    .for_each(|((player, ability, kind), hits)| {
+                        let kind = match kind {
+                            Kind::Avoidable => "Avoidable",
+                            Kind::FriendlyFire => "Friendly fire",
+                        };
+                        table.push(vec![player.as_str().into(), ability.as_str().into(), kind.into(), hits.count.into(), hits.amount.into()]);
+                    });
+                table
+            });
+
+        Some(tables.fold(Summary::new("Avoidable damage taken"), Summary::with_table))
+    }
+
+    fn on_encounter_start(&mut self, encounter: &EncounterStart) {
+        self.pulls.push(PullDamage { encounter_name: encounter.encounter_name.clone(), taken: HashMap::new() });
+        self.in_encounter = true;
+    }
+
+    fn on_encounter_end(&mut self, _encounter: &EncounterEnd) {
+        self.in_encounter = false;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::{dispatch, EventHandler};
+    use crate::consumers::avoidable::{AvoidableDamage, AvoidableRules};
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    const RULES: &str = "[tiers.amirdrassil]\nspells = [421971, 422026]\n";
+
+    #[test]
+    fn rules() {
+        let rules: AvoidableRules = RULES.parse().unwrap();
+        assert_eq!(rules.tier_of(421971), Some("amirdrassil"));
+        assert_eq!(rules.tier_of(1), None);
+        assert!("[tiers.amirdrassil]\nspells = \"nope\"".parse::<AvoidableRules>().is_err());
+    }
+
+    #[test]
+    fn avoidable_per_pull() {
+        let log = "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 14:02:00.000  SPELL_DAMAGE,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,421971,\"Controlled Burn\",0x4,Player-1390-0C4E032E,0000000000000000,0,834740,2104,22733,3088,0,0,196960,250000,0,-2159.06,7174.82,2238,4.5667,481,10000,12000,-1,4,0,0,2000,nil,nil,nil\n\
+4/6 14:02:01.000  SPELL_DAMAGE,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,421971,\"Controlled Burn\",0x4,Player-1390-0C4E032E,0000000000000000,0,834740,2104,22733,3088,0,0,196960,250000,0,-2159.06,7174.82,2238,4.5667,481,5000,5000,-1,4,0,0,0,nil,nil,nil\n\
+4/6 14:02:02.000  SPELL_DAMAGE,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,1,\"Unavoidable Slam\",0x1,Player-1390-0C4E032E,0000000000000000,0,834740,2104,22733,3088,0,0,196960,250000,0,-2159.06,7174.82,2238,4.5667,481,5000,5000,-1,1,0,0,0,nil,nil,nil\n\
+4/6 14:02:03.000  SPELL_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,2,\"Oops\",0x4,Player-1390-0C4E032E,0000000000000000,0,834740,2104,22733,3088,0,0,196960,250000,0,-2159.06,7174.82,2238,4.5667,481,300,300,-1,4,0,0,0,nil,nil,nil\n\
+4/6 14:03:00.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,0,115000\n";
+
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(AvoidableDamage::new(RULES.parse().unwrap()))];
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        let summary = handlers[0].summary().unwrap();
+        let table = summary.table("Gnarlroot (pull 1)").unwrap();
+        assert_eq!(table.rows, vec![
+            vec!["Stillnixx-Hyjal".into(), "Controlled Burn".into(), "Avoidable".into(), Cell::Int(2), Cell::Int(17000)],
+            vec!["Stillnixx-Hyjal".into(), "Oops".into(), "Friendly fire".into(), Cell::Int(1), Cell::Int(300)],
+        ]);
+    }
+}
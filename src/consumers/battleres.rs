@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime};
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::Special;
+use crate::components::suffixes::Suffix;
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+/// Spells which use a battle res charge
+const BATTLE_RES_SPELLS: &[u64] = &[
+    20484,  // Rebirth
+    61999,  // Raise Ally
+    20707,  // Soulstone
+    95750,  // Soulstone Resurrection
+    391054, // Intercession
+    159931, // Gift of Chi-Ji (hunter pet)
+    345130, // Disposable Spectrophasic Reanimator
+    385403, // Tinker: Arclight Vital Correctors
+];
+
+/// A Mythic+ key gains a charge every 10 minutes
+const MYTHIC_PLUS_RECHARGE_MINS: i64 = 10;
+/// A raid gains a charge every 90 / group size minutes
+const RAID_RECHARGE_MINS: i64 = 90;
+/// A cast & the resurrect it causes are the same use of a charge if this close together
+const CAST_MATCH_SECS: i64 = 5;
+
+#[derive(Debug)]
+enum Entry {
+    Death { player: String },
+    BattleRes { player: String, caster: String, spell: String },
+}
+
+/// A key or a raid boss pull, sharing one pool of charges
+#[derive(Debug)]
+struct Segment {
+    name: String,
+    start: NaiveDateTime,
+    end: Option<NaiveDateTime>,
+    recharge: Duration,
+    timeline: Vec<(NaiveDateTime, Entry)>,
+}
+
+impl Segment {
+    fn used_by(&self, time: NaiveDateTime) -> i64 {
+        self.timeline.iter().filter(|(t, e)| *t <= time && matches!(e, Entry::BattleRes { .. })).count() as i64
+    }
+
+    /// Charges left at a time: one to start with, plus one per recharge
+    fn charges(&self, time: NaiveDateTime) -> i64 {
+        let gained = (time - self.start).num_milliseconds() / self.recharge.num_milliseconds().max(1);
+        1 + gained - self.used_by(time)
+    }
+
+    fn deaths(&self) -> usize {
+        self.timeline.iter().filter(|(_, e)| matches!(e, Entry::Death { .. })).count()
+    }
+
+    fn timeline_table(&self) -> Table {
+        let mut table = Table::new(&[("Time", 8), ("Event", 10), ("Player", 30), ("By", 30), ("Spell", 25), ("Charges", 8)])
+            .with_title(self.name.clone());
+
+        for (time, entry) in &self.timeline {
+            let since_start = Cell::Duration((*time - self.start).num_milliseconds() as f64 / 1000.);
+            let row = match entry {
+                Entry::Death { player } => vec![since_start, "Death".into(), player.as_str().into(), Cell::Empty, Cell::Empty],
+                Entry::BattleRes { player, caster, spell } =>
+                    vec![since_start, "Battle res".into(), player.as_str().into(), caster.as_str().into(), spell.as_str().into()],
+            };
+            table.push([row, vec![self.charges(*time).into()]].concat());
+        }
+
+        table
+    }
+}
+
+/// Player deaths & battle res use over each Mythic+ key & raid boss pull, with the charges left after each.
+/// Charges are worked out from the recharge rate of the content: a key starts with 1 charge & gains one every 10 minutes,
+/// a raid pull gains one every 90 / group size minutes.
+#[derive(Debug, Default)]
+pub struct BattleResTracker {
+    segments: Vec<Segment>,
+    in_segment: bool,
+    in_key: bool,
+    /// Battle res casts already counted, so the resurrect they cause isn't counted again
+    casts: HashMap<(GUID, u64), NaiveDateTime>,
+}
+
+impl BattleResTracker {
+    pub fn new() -> Self { Self::default() }
+
+    fn start(&mut self, name: String, start: NaiveDateTime, recharge: Duration) {
+        self.segments.push(Segment { name, start, end: None, recharge, timeline: vec![] });
+        self.in_segment = true;
+        self.casts.clear();
+    }
+
+    fn end(&mut self, time: NaiveDateTime) {
+        if let Some(segment) = self.segments.last_mut().filter(|_| self.in_segment) {
+            segment.end = Some(time);
+        }
+        self.in_segment = false;
+    }
+
+    fn push(&mut self, time: NaiveDateTime, entry: Entry) {
+        if let Some(segment) = self.segments.last_mut().filter(|_| self.in_segment) {
+            segment.timeline.push((time, entry));
+        }
+    }
+
+    /// Whether a player has died since they were last brought back, in the current segment
+    fn is_dead(&self, player: &str) -> bool {
+        let Some(segment) = self.segments.last().filter(|_| self.in_segment) else { return false; };
+        segment.timeline.iter().rev()
+            .find_map(|(_, e)| match e {
+                Entry::Death { player: p } if p == player => Some(true),
+                Entry::BattleRes { player: p, .. } if p == player => Some(false),
+                _ => None,
+            })
+            .unwrap_or(false)
+    }
+}
+
+impl EventHandler for BattleResTracker {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+
+        match &event.event_type {
+            EventType::Special { details, .. } => match details {
+                Special::ChallengeModeStart { zone_name, keystone_level, .. } => {
+                    self.start(format!("{} +{}", zone_name, keystone_level), event.timestamp, Duration::minutes(MYTHIC_PLUS_RECHARGE_MINS));
+                    self.in_key = true;
+                }
+                Special::ChallengeModeEnd { .. } => {
+                    self.end(event.timestamp);
+                    self.in_key = false;
+                }
+                // Bosses in a key share the key's charges
+                Special::EncounterStart(encounter) if !self.in_key => {
+                    let recharge = Duration::seconds(RAID_RECHARGE_MINS * 60 / encounter.group_size.max(1) as i64);
+                    self.start(encounter.encounter_name.clone(), event.timestamp, recharge);
+                }
+                Special::EncounterEnd(_) if !self.in_key => self.end(event.timestamp),
+                Special::UnitDied { target: Some(target), .. } if matches!(target.guid, GUID::Player { .. }) => {
+                    self.push(event.timestamp, Entry::Death { player: target.name.clone() });
+                }
+                _ => {}
+            },
+            EventType::Standard { source: Some(source), target, prefix, suffix, .. } => {
+                let Some(spell) = prefix.spell_info().filter(|s| BATTLE_RES_SPELLS.contains(&s.spell_id)) else { return; };
+                let key = (source.guid.clone(), spell.spell_id);
+                let Some(target) = target.as_ref().filter(|t| matches!(t.guid, GUID::Player { .. })) else { return; };
+
+                match suffix {
+                    // Casts at the living (eg. pre-placing a Soulstone) don't use a charge
+                    Suffix::CastSuccess if self.is_dead(&target.name) => { self.casts.insert(key, event.timestamp); }
+                    // Resurrects with a recent cast were counted when cast, eg. Rebirth, others weren't, eg. a Soulstone being used
+                    Suffix::Resurrect => {
+                        if let Some(cast) = self.casts.remove(&key) {
+                            if event.timestamp - cast <= Duration::seconds(CAST_MATCH_SECS) { return; }
+                        }
+                    }
+                    _ => return,
+                }
+
+                self.push(event.timestamp, Entry::BattleRes {
+                    player: target.name.clone(),
+                    caster: source.name.clone(),
+                    spell: spell.spell_name.to_string(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let mut table = Table::new(&[("Segment", 30), ("Deaths", 7), ("Battle res", 10), ("Charges left", 12)]);
+        for segment in &self.segments {
+            let end = segment.end.or(segment.timeline.last().map(|(t, _)| *t)).unwrap_or(segment.start);
+            table.push(vec![
+                segment.name.as_str().into(), segment.deaths().into(), segment.used_by(end).into(), segment.charges(end).into(),
+            ]);
+        }
+
+        let timelines = self.segments.iter().map(Segment::timeline_table);
+        Some(timelines.fold(Summary::new("Battle res").with_table(table), Summary::with_table))
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.casts.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::battleres::BattleResTracker;
+    use crate::consumers::EventHandler;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn charges() {
+        let log = "4/6 14:00:01.000  CHALLENGE_MODE_START,\"The Dawnbreaker\",2662,505,10,[10,109,148]\n\
+4/6 14:05:00.000  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,0\n\
+4/6 14:05:10.000  SPELL_CAST_SUCCESS,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,20484,\"Rebirth\",0x8,Player-1393-077C088C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70\n\
+4/6 14:05:10.000  SPELL_RESURRECT,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,20484,\"Rebirth\",0x8\n\
+4/6 14:06:00.000  SPELL_CAST_SUCCESS,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,20707,\"Soulstone\",0x20,Player-1390-0C4E032E,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70\n\
+4/6 14:12:00.000  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,0\n\
+4/6 14:12:05.000  SPELL_RESURRECT,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,95750,\"Soulstone Resurrection\",0x20\n\
+4/6 14:30:01.000  CHALLENGE_MODE_END,2662,1,10,1800000\n";
+
+        let mut tracker = BattleResTracker::new();
+        EventParser::new(log.as_bytes()).for_each(|e| tracker.handle(&e));
+
+        let summary = tracker.summary().unwrap();
+        assert_eq!(summary.tables[0].rows, vec![
+            vec!["The Dawnbreaker +10".into(), Cell::Int(2), Cell::Int(2), Cell::Int(2)],
+        ]);
+        let timeline = summary.table("The Dawnbreaker +10").unwrap();
+        assert_eq!(timeline.rows.iter().map(|r| r[5].clone()).collect::<Vec<_>>(), [
+            Cell::Int(1), Cell::Int(0), Cell::Int(1), Cell::Int(0),
+        ]);
+    }
+}
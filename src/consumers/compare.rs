@@ -0,0 +1,258 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::{EncounterEnd, EncounterStart, Special};
+use crate::components::suffixes::Suffix;
+use crate::consumers::ownership::OwnershipResolver;
+use crate::consumers::phases::PhaseStart;
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+/// The unit with the most health seen in the pull, taken to be the boss
+#[derive(Debug)]
+struct Boss {
+    guid: GUID,
+    current_hp: u64,
+    max_hp: u64,
+}
+
+#[derive(Debug)]
+struct Pull {
+    /// 1-based pull number, same as `--encounter`
+    id: usize,
+    encounter_name: String,
+    difficulty_id: u64,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    success: Option<bool>,
+    boss: Option<Boss>,
+    damage: HashMap<String, i64>,
+    /// Latest phase reached, from the phase rules
+    phase: Option<PhaseStart>,
+}
+
+impl Pull {
+    fn duration_secs(&self) -> f64 {
+        ((self.end - self.start).num_milliseconds() as f64 / 1000.).max(1.)
+    }
+
+    /// Boss health left at the end of the pull, none on kills
+    fn boss_percent(&self) -> Option<f64> {
+        if self.success == Some(true) { return Some(0.); }
+        self.boss.as_ref().map(|b| 100. * b.current_hp as f64 / b.max_hp.max(1) as f64)
+    }
+
+    fn dps(&self, player: &str) -> Option<f64> {
+        self.damage.get(player).map(|d| *d as f64 / self.duration_secs())
+    }
+}
+
+/// Progression over the pulls of each boss: how far each pull got, how long it lasted, the phase it ended in
+/// & how each player's DPS changed from pull to pull.
+/// Phases come from the phase rules, if there are any for the boss.
+#[derive(Debug, Default)]
+pub struct PullComparison {
+    owners: OwnershipResolver,
+    pulls: Vec<Pull>,
+    in_pull: bool,
+}
+
+impl PullComparison {
+    pub fn new() -> Self { Self::default() }
+
+    fn update_boss(pull: &mut Pull, event: &Event) {
+        let EventType::Standard { source, target, advanced_params: Some(params), .. } = &event.event_type else { return; };
+        let Some(guid) = &params.info_guid else { return; };
+        let Some(unit) = [source, target].into_iter().flatten().find(|a| &a.guid == guid) else { return; };
+        if !unit.is_hostile() || unit.is_player_controlled() { return; }
+
+        match &mut pull.boss {
+            Some(boss) if &boss.guid == guid => boss.current_hp = params.current_hp,
+            Some(boss) if boss.max_hp >= params.max_hp => {}
+            _ => pull.boss = Some(Boss { guid: guid.clone(), current_hp: params.current_hp, max_hp: params.max_hp }),
+        }
+    }
+
+    fn pulls_table(name: &str, pulls: &[&Pull]) -> Table {
+        let mut table = Table::new(&[("Pull", 4), ("Duration", 9), ("Result", 11), ("Boss HP", 8), ("Phase", 15), ("Raid DPS", 10)])
+            .with_title(name);
+        for pull in pulls {
+            let result = match pull.success {
+                Some(true) => "Kill",
+                Some(false) => "Wipe",
+                None => "In progress",
+            };
+            table.push(vec![
+                pull.id.into(),
+                Cell::Duration(pull.duration_secs()),
+                result.into(),
+                pull.boss_percent().map_or(Cell::Empty, Cell::Percent),
+                pull.phase.as_ref().map_or(Cell::Empty, |p| p.name.as_str().into()),
+                Cell::Float(pull.damage.values().sum::<i64>() as f64 / pull.duration_secs(), 0),
+            ]);
+        }
+        table
+    }
+
+    /// DPS per player & pull, with the change from their first pull to their last
+    fn dps_table(name: &str, pulls: &[&Pull]) -> Table {
+        let names = pulls.iter().map(|p| format!("Pull {}", p.id)).collect_vec();
+        let columns = [("Player", 25)].into_iter()
+            .chain(names.iter().map(|n| (n.as_str(), 9)))
+            .chain([("Trend", 7)])
+            .collect_vec();
+        let mut table = Table::new(&columns).with_title(format!("{} DPS", name));
+
+        let players = pulls.iter().flat_map(|p| p.damage.keys()).unique().sorted();
+        for player in players {
+            let dps = pulls.iter().map(|p| p.dps(player)).collect_vec();
+            let seen = dps.iter().flatten().collect_vec();
+            let trend = match (seen.first(), seen.last()) {
+                (Some(&&first), Some(&&last)) if seen.len() > 1 && first > 0. => Cell::Percent(100. * (last - first) / first),
+                _ => Cell::Empty,
+            };
+
+            table.push([player.as_str().into()].into_iter()
+                .chain(dps.iter().map(|d| d.map_or(Cell::Empty, |d| Cell::Float(d, 0))))
+                .chain([trend])
+                .collect());
+        }
+        table
+    }
+}
+
+impl EventHandler for PullComparison {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        self.owners.update(event);
+
+        if let EventType::Special { details: Special::EncounterStart(_), .. } = &event.event_type {
+            if let Some(pull) = self.pulls.last_mut() {
+                pull.start = event.timestamp;
+                pull.end = event.timestamp;
+            }
+            return;
+        }
+        if !self.in_pull { return; }
+        let Some(pull) = self.pulls.last_mut() else { return; };
+        Self::update_boss(pull, event);
+
+        let EventType::Standard { source: Some(source), suffix: Suffix::Damage { amount, .. }, .. } = &event.event_type else { return; };
+        let Some((_, player)) = self.owners.resolve_player(source) else { return; };
+        pull.end = event.timestamp;
+        *pull.damage.entry(player).or_default() += amount;
+    }
+
+    fn on_encounter_start(&mut self, encounter: &EncounterStart) {
+        // Timestamps are filled in when the ENCOUNTER_START event itself is handled
+        self.pulls.push(Pull {
+            id: self.pulls.len() + 1,
+            encounter_name: encounter.encounter_name.clone(),
+            difficulty_id: encounter.difficulty_id,
+            start: NaiveDateTime::default(),
+            end: NaiveDateTime::default(),
+            success: None,
+            boss: None,
+            damage: HashMap::new(),
+            phase: None,
+        });
+        self.in_pull = true;
+    }
+
+    fn on_encounter_end(&mut self, encounter: &EncounterEnd) {
+        self.in_pull = false;
+        if let Some(pull) = self.pulls.last_mut() {
+            pull.success = Some(encounter.success);
+            pull.end = pull.start + chrono::Duration::milliseconds(encounter.fight_time as i64);
+        }
+    }
+
+    fn on_phase_start(&mut self, phase: &PhaseStart) {
+        if let Some(pull) = self.pulls.last_mut().filter(|_| self.in_pull) {
+            pull.phase = Some(phase.clone());
+        }
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let bosses = self.pulls.iter()
+            .map(|p| ((p.encounter_name.as_str(), p.difficulty_id), p))
+            .into_group_map()
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+
+        let mut summary = Summary::new("Pull comparison");
+        for ((name, difficulty_id), pulls) in bosses {
+            let name = format!("{} (difficulty {})", name, difficulty_id);
+            let best = pulls.iter()
+                .filter_map(|p| Some((p, p.boss_percent()?)))
+                .min_by(|(_, a), (_, b)| a.total_cmp(b));
+            if let Some((pull, percent)) = best {
+                summary.notes.push(format!("{}: best pull {} at {:.1}%", name, pull.id, percent));
+            }
+
+            summary = summary
+                .with_table(Self::pulls_table(&name, &pulls))
+                .with_table(Self::dps_table(&name, &pulls));
+        }
+
+        Some(summary)
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.owners.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::EventHandler;
+    use crate::consumers::compare::PullComparison;
+    use crate::consumers::segments::CombatSegmenter;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    fn pull(start: &str, hits: &[(&str, &str, u64, u64)], phase_cast: Option<&str>, end: &str, success: u8, fight_ms: u64) -> String {
+        let hit = |(time, player, amount, hp): &(&str, &str, u64, u64)| format!("4/6 {time}  SPELL_DAMAGE,{player},0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,421971,\"Controlled Burn\",0x4,Creature-0-1469-2549-12530-209333-000011428A,0000000000000000,{hp},1000000,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,483,{amount},{amount},-1,4,0,0,0,nil,nil,nil\n");
+        let cast = |time: &str| format!("4/6 {time}  SPELL_CAST_START,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,0000000000000000,nil,0x80000000,0x80000000,421898,\"Flaming Pestilence\",0x4\n");
+
+        [format!("4/6 {start}  ENCOUNTER_START,2820,\"Gnarlroot\",16,20,2549\n")].into_iter()
+            .chain(hits.iter().map(hit))
+            .chain(phase_cast.map(cast))
+            .chain([format!("4/6 {end}  ENCOUNTER_END,2820,\"Gnarlroot\",16,20,{success},{fight_ms}\n")])
+            .collect()
+    }
+
+    #[test]
+    fn progression() {
+        let sonike = "Player-1335-0A264B4C,\"Sønike-Ysondre\"";
+        let stillnixx = "Player-1390-0C4E032E,\"Stillnixx-Hyjal\"";
+        let log = [
+            pull("14:00:00.000", &[("14:00:05.000", sonike, 100000, 600000), ("14:00:06.000", stillnixx, 50000, 550000)], None, "14:00:10.000", 0, 10000),
+            pull("14:10:00.000", &[("14:10:05.000", sonike, 300000, 200000)], Some("14:10:06.000"), "14:10:20.000", 0, 20000),
+            pull("14:20:00.000", &[("14:20:05.000", sonike, 600000, 0)], Some("14:20:06.000"), "14:20:30.000", 1, 30000),
+        ].concat();
+
+        let rules = "[[phases]]\nencounter_id = 2820\nphase = 2\nname = \"Intermission\"\ncast = 421898\n".parse().unwrap();
+        let mut segmenter = CombatSegmenter::new(None).with_phases(Some(rules));
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(PullComparison::new())];
+        EventParser::new(log.as_bytes()).for_each(|e| segmenter.dispatch(&mut handlers, &e));
+
+        let summary = handlers[0].summary().unwrap();
+        assert_eq!(summary.notes, ["Gnarlroot (difficulty 16): best pull 3 at 0.0%"]);
+        let pulls = &summary.tables[0];
+        assert_eq!(pulls.rows[0][1..], [Cell::Duration(10.), "Wipe".into(), Cell::Percent(55.), "Phase 1".into(), Cell::Float(15000., 0)]);
+        assert_eq!(pulls.rows[1][2..5], ["Wipe".into(), Cell::Percent(20.), "Intermission".into()]);
+        assert_eq!(pulls.rows[2][2..5], ["Kill".into(), Cell::Percent(0.), "Intermission".into()]);
+
+        let dps = summary.table("Gnarlroot (difficulty 16) DPS").unwrap();
+        assert_eq!(dps.find("Sønike-Ysondre").unwrap()[1..], [Cell::Float(10000., 0), Cell::Float(15000., 0), Cell::Float(20000., 0), Cell::Percent(100.)]);
+        assert_eq!(dps.find("Stillnixx-Hyjal").unwrap()[1..], [Cell::Float(5000., 0), Cell::Empty, Cell::Empty, Cell::Empty]);
+    }
+}
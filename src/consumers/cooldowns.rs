@@ -0,0 +1,170 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::Special;
+use crate::components::suffixes::Suffix;
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+use crate::summary::{Summary, Table};
+
+#[derive(Debug, Clone, Deserialize)]
+struct Cooldown {
+    spell_id: u64,
+    cooldown_secs: u64,
+}
+
+/// Major cooldowns to track, loaded from TOML, eg.
+/// ```toml
+/// [[cooldowns]]
+/// spell_id = 31884  # Avenging Wrath
+/// cooldown_secs = 120
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CooldownRules {
+    #[serde(default)]
+    cooldowns: Vec<Cooldown>,
+}
+
+impl CooldownRules {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let s = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to open file: {:?}", path.as_ref()))?;
+        s.parse()
+    }
+
+    fn cooldown_of(&self, spell_id: u64) -> Option<u64> {
+        self.cooldowns.iter().find(|c| c.spell_id == spell_id).map(|c| c.cooldown_secs)
+    }
+}
+
+impl std::str::FromStr for CooldownRules {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        toml::from_str(s).context("Bad cooldown rules")
+    }
+}
+
+#[derive(Debug)]
+struct Pull {
+    encounter: String,
+    start: NaiveDateTime,
+    end: Option<NaiveDateTime>,
+    /// Casts per player & spell
+    uses: HashMap<(String, u64), usize>,
+}
+
+/// Casts of tracked major cooldowns per pull, flagging players who never used one, or used it fewer times than
+/// the fight was long enough for. A player is taken to have a cooldown once they've cast it anywhere in the log.
+#[derive(Debug, Default)]
+pub struct CooldownUsage {
+    rules: CooldownRules,
+    /// Spell names of the cooldowns each player has, by spell ID
+    known: BTreeMap<String, BTreeMap<u64, String>>,
+    pulls: Vec<Pull>,
+}
+
+impl CooldownUsage {
+    pub fn new(rules: CooldownRules) -> Self {
+        Self { rules, ..Default::default() }
+    }
+}
+
+impl EventHandler for CooldownUsage {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+
+        match &event.event_type {
+            EventType::Special { details: Special::EncounterStart(encounter), .. } => self.pulls.push(Pull {
+                encounter: encounter.encounter_name.clone(),
+                start: event.timestamp,
+                end: None,
+                uses: HashMap::new(),
+            }),
+            EventType::Special { details: Special::EncounterEnd(_), .. } => {
+                if let Some(pull) = self.pulls.last_mut() { pull.end.get_or_insert(event.timestamp); }
+            }
+            EventType::Standard { source: Some(source), prefix, suffix: Suffix::CastSuccess, .. } => {
+                if !matches!(source.guid, GUID::Player { .. }) { return; }
+                let Some(spell) = prefix.spell_info() else { return; };
+                if self.rules.cooldown_of(spell.spell_id).is_none() { return; }
+
+                self.known.entry(source.name.clone()).or_default()
+                    .entry(spell.spell_id).or_insert_with(|| spell.spell_name.to_string());
+                if let Some(pull) = self.pulls.last_mut().filter(|p| p.end.is_none()) {
+                    *pull.uses.entry((source.name.clone(), spell.spell_id)).or_default() += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let tables = self.pulls.iter().enumerate().filter(|(_, p)| p.end.is_some()).map(|(i, pull)| {
+            let secs = (pull.end.unwrap() - pull.start).num_seconds().max(0) as u64;
+            let mut table = Table::new(&[("Player", 30), ("Spell", 25), ("Uses", 5), ("Possible", 8), ("Flag", 10)])
+                .with_title(format!("{} (pull {})", pull.encounter, i + 1));
+
+            for (player, spells) in &self.known {
+                for (spell_id, spell_name) in spells {
+                    let Some(cooldown) = self.rules.cooldown_of(*spell_id) else { continue; };
+                    let uses = pull.uses.get(&(player.clone(), *spell_id)).copied().unwrap_or(0);
+                    let possible = 1 + secs / cooldown.max(1);
+                    let flag = match uses {
+                        0 => "never used",
+                        u if (u as u64) < possible => "under used",
+                        _ => "",
+                    };
+                    table.push(vec![player.as_str().into(), spell_name.as_str().into(), uses.into(), possible.into(), flag.into()]);
+                }
+            }
+            table
+        });
+
+        Some(tables.fold(Summary::new("Cooldown usage"), Summary::with_table))
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.known.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::cooldowns::CooldownUsage;
+    use crate::consumers::EventHandler;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn usage() {
+        let rules = "[[cooldowns]]\nspell_id = 31884\ncooldown_secs = 120\n".parse().unwrap();
+        let cast = |time: &str, player: &str| format!("4/6 {}  SPELL_CAST_SUCCESS,{},0x514,0x0,0000000000000000,nil,0x80000000,0x80000000,31884,\"Avenging Wrath\",0x2,Player-1335-0A264B4C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70\n", time, player);
+        let sonike = "Player-1335-0A264B4C,\"Sønike-Ysondre\"";
+        let stillnixx = "Player-1390-0C4E032E,\"Stillnixx-Hyjal\"";
+
+        let log = [
+            cast("14:01:00.000", stillnixx),
+            "4/6 14:02:00.000  ENCOUNTER_START,2820,\"Gnarlroot\",16,20,2549\n".to_string(),
+            cast("14:02:05.000", sonike),
+            cast("14:04:10.000", sonike),
+            "4/6 14:07:00.000  ENCOUNTER_END,2820,\"Gnarlroot\",16,20,1,300000\n".to_string(),
+        ].concat();
+
+        let mut tracker = CooldownUsage::new(rules);
+        EventParser::new(log.as_bytes()).for_each(|e| tracker.handle(&e));
+
+        let summary = tracker.summary().unwrap();
+        let rows = &summary.table("Gnarlroot (pull 1)").unwrap().rows;
+        assert_eq!(rows[0][0], "Stillnixx-Hyjal".into());
+        assert_eq!(rows[0][2..], [Cell::from(0usize), 3u64.into(), "never used".into()]);
+        assert_eq!(rows[1][2..], [Cell::from(2usize), 3u64.into(), "under used".into()]);
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::prefixes::Prefix;
+use crate::components::special::{EncounterEnd, EncounterStart, Special};
+use crate::components::suffixes::Suffix;
+use crate::consumers::EventHandler;
+use crate::consumers::pruning::LastSeen;
+use crate::error::ParseFailure;
+use crate::summary::{Summary, Table};
+
+#[derive(Debug, Clone, Deserialize)]
+struct Mechanic {
+    spell_id: u64,
+    name: String,
+}
+
+/// Names of the mechanics deaths are put down to, loaded from TOML, eg.
+/// ```toml
+/// [[mechanics]]
+/// spell_id = 421971  # Controlled Burn
+/// name = "Fire puddle"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MechanicRules {
+    #[serde(default)]
+    mechanics: Vec<Mechanic>,
+}
+
+impl MechanicRules {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let s = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to open file: {:?}", path.as_ref()))?;
+        s.parse()
+    }
+
+    fn name_of(&self, spell_id: u64) -> Option<&str> {
+        self.mechanics.iter().find(|m| m.spell_id == spell_id).map(|m| m.name.as_str())
+    }
+}
+
+impl std::str::FromStr for MechanicRules {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        toml::from_str(s).context("Bad mechanic rules")
+    }
+}
+
+/// The last thing to hit a player
+#[derive(Debug, Clone)]
+struct KillingBlow {
+    spell_id: Option<u64>,
+    name: String,
+    /// Falling, drowning, lava etc.
+    environmental: bool,
+}
+
+/// Classifies each player death during an encounter by the mechanic that killed them.
+/// Deaths are attributed to the last damaging ability that hit the player, mapped through user defined rules.
+/// Abilities without a rule are reported by name. Counts accumulate per boss across all pulls.
+/// Deaths to the environment, eg. falling or lava, are also counted per player, in or out of encounters.
+#[derive(Debug, Default)]
+pub struct DeathRecap {
+    rules: MechanicRules,
+    last_hit: HashMap<GUID, KillingBlow>,
+    last_seen: LastSeen<GUID>,
+    boss: Option<String>,
+    histogram: HashMap<String, HashMap<String, usize>>,
+    /// Deaths per player & environment type
+    environmental: BTreeMap<(String, String), usize>,
+}
+
+impl DeathRecap {
+    pub fn new(rules: MechanicRules) -> Self {
+        Self { rules, ..Self::default() }
+    }
+
+    fn mechanic(&self, blow: &KillingBlow) -> String {
+        blow.spell_id
+            .and_then(|id| self.rules.name_of(id))
+            .unwrap_or(&blow.name)
+            .to_string()
+    }
+}
+
+impl EventHandler for DeathRecap {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+
+        match &event.event_type {
+            EventType::Standard { target: Some(target), prefix, suffix: Suffix::Damage { .. } | Suffix::Instakill { .. }, .. }
+            if matches!(target.guid, GUID::Player { .. }) => {
+                let blow = match &**prefix {
+                    Prefix::Swing => KillingBlow { spell_id: None, name: "Melee".to_string(), environmental: false },
+                    Prefix::Spell(Some(s)) | Prefix::SpellPeriodic(s) | Prefix::Range(s) | Prefix::SpellBuilding(s) =>
+                        KillingBlow { spell_id: Some(s.spell_id), name: s.spell_name.to_string(), environmental: false },
+                    Prefix::Spell(None) => return,
+                };
+                self.last_hit.insert(target.guid.clone(), blow);
+                self.last_seen.touch(&target.guid, event.timestamp);
+            }
+            EventType::EnvironmentalDamage { target: Some(target), env_type, .. } if matches!(target.guid, GUID::Player { .. }) => {
+                let blow = KillingBlow { spell_id: None, name: format!("{:?}", env_type), environmental: true };
+                self.last_hit.insert(target.guid.clone(), blow);
+                self.last_seen.touch(&target.guid, event.timestamp);
+            }
+
+            EventType::Special { details: Special::UnitDied { target: Some(target), .. }, .. } => {
+                let Some(blow) = self.last_hit.remove(&target.guid) else { return; };
+                if blow.environmental {
+                    *self.environmental.entry((target.name.clone(), blow.name.clone())).or_default() += 1;
+                }
+                let Some(boss) = &self.boss else { return; };
+
+                let mechanic = self.mechanic(&blow);
+                *self.histogram.entry(boss.clone()).or_default()
+                    .entry(mechanic).or_default() += 1;
+            }
+
+            _ => {}
+        }
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let tables = self.histogram.iter()
+            .sorted_by_key(|(boss, _)| *boss)
+            .map(|(boss, mechanics)| {
+                let mut table = Table::new(&[("Mechanic", 40), ("Deaths", 6)]).with_title(boss.clone());
+                mechanics.iter()
+                    .sorted_by_key(|(m, &n)| (std::cmp::Reverse(n), *m))
+                    .for_each(|(m, n)| table.push(vec![m.as_str().into(), (*n).into()]));
+                table
+            });
+
+        let mut summary = tables.fold(Summary::new("Deaths by mechanic"), Summary::with_table);
+        if !self.environmental.is_empty() {
+            let mut table = Table::new(&[("Player", 30), ("Cause", 10), ("Deaths", 6)]).with_title("Environment");
+            self.environmental.iter()
+                .for_each(|((player, cause), n)| table.push(vec![player.as_str().into(), cause.as_str().into(), (*n).into()]));
+            summary = summary.with_table(table);
+        }
+        Some(summary)
+    }
+
+    fn on_encounter_start(&mut self, encounter: &EncounterStart) {
+        self.boss = Some(encounter.encounter_name.clone());
+        self.last_hit.clear();
+        self.last_seen.clear();
+    }
+
+    fn on_encounter_end(&mut self, _encounter: &EncounterEnd) {
+        self.boss = None;
+    }
+
+    fn prune(&mut self, cutoff: NaiveDateTime) {
+        for guid in self.last_seen.expire(cutoff) {
+            self.last_hit.remove(&guid);
+        }
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.last_hit.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::{dispatch, EventHandler};
+    use crate::consumers::deaths::{DeathRecap, MechanicRules};
+    use crate::parser::EventParser;
+
+    #[test]
+    fn deaths_by_mechanic() {
+        let pull = "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 14:02:00.000  SPELL_DAMAGE,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,421971,\"Controlled Burn\",0x4,Player-1390-0C4E032E,0000000000000000,0,834740,2104,22733,3088,0,0,196960,250000,0,-2159.06,7174.82,2238,4.5667,481,144372,144372,1000,4,0,0,0,nil,nil,nil\n\
+4/6 14:02:00.100  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,0\n\
+4/6 14:02:01.000  SWING_DAMAGE,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Player-1335-0A264B4C,0000000000000000,0,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,500,500,100,1,0,0,0,nil,nil,nil\n\
+4/6 14:02:01.100  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,0\n\
+4/6 14:03:00.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,0,115000\n";
+        let log = [pull, pull].concat();
+
+        let rules = "[[mechanics]]\nspell_id = 421971\nname = \"Fire puddle\"\n".parse().unwrap();
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(DeathRecap::new(rules))];
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        let display = handlers[0].display().unwrap();
+        assert!(display.contains(&format!("{:>40}:{:>6}", "Fire puddle", 2)), "{}", display);
+        assert!(display.contains(&format!("{:>40}:{:>6}", "Melee", 2)), "{}", display);
+    }
+
+    #[test]
+    fn environmental() {
+        let fall = |time: &str| format!("4/6 {}  ENVIRONMENTAL_DAMAGE,0000000000000000,nil,0x80000000,0x80000000,Player-1329-070EBCFC,\"Naladrem-Ravencrest\",0x518,0x0,Player-1329-070EBCFC,0000000000000000,0,866544,14879,1421,5217,0,17,109,120,0,-931.46,2546.12,2133,4.8479,484,Falling,866544,866544,0,1,0,0,0,nil,nil,nil\n", time);
+        let died = |time: &str| format!("4/6 {}  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Player-1329-070EBCFC,\"Naladrem-Ravencrest\",0x518,0x0,0\n", time);
+        let log = [
+            fall("14:00:00.000"),
+            died("14:00:00.100"),
+            "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n".to_string(),
+            fall("14:02:00.000"),
+            died("14:02:00.100"),
+            "4/6 14:03:00.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,0,115000\n".to_string(),
+        ].concat();
+
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(DeathRecap::new(MechanicRules::default()))];
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        let summary = handlers[0].summary().unwrap();
+        assert_eq!(summary.table("Gnarlroot").unwrap().rows[0], ["Falling".into(), 1usize.into()]);
+        assert_eq!(summary.table("Environment").unwrap().rows[0], ["Naladrem-Ravencrest".into(), "Falling".into(), 2usize.into()]);
+    }
+
+    #[test]
+    fn rules_file() {
+        let path = std::env::temp_dir().join("wowlogs_mechanic_rules.toml");
+        std::fs::write(&path, "# Gnarlroot\n[[mechanics]]\nspell_id = 421971\nname = \"Fire puddle\"\n\n\
+[[mechanics]]\nspell_id = 422026\nname = \"Tortured Scream\"\n").unwrap();
+
+        let rules = MechanicRules::load(&path).unwrap();
+        assert_eq!(rules.mechanics.len(), 2);
+        assert_eq!(rules.name_of(421971), Some("Fire puddle"));
+        assert!("421971,Fire puddle".parse::<MechanicRules>().is_err());
+    }
+}
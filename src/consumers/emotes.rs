@@ -0,0 +1,108 @@
+use anyhow::Result;
+use chrono::NaiveDateTime;
+
+use crate::components::emote::EmoteText;
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::Special;
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+#[derive(Debug)]
+struct Warning {
+    /// Seconds into the pull
+    time: f64,
+    source: String,
+    text: EmoteText,
+}
+
+#[derive(Debug)]
+struct Pull {
+    encounter: String,
+    start: NaiveDateTime,
+    warnings: Vec<Warning>,
+}
+
+/// Raid warnings emoted by bosses during encounters, eg. "Fyrakk begins to cast [Wildfire]!", timed from the pull
+#[derive(Debug, Default)]
+pub struct BossEmoteTracker {
+    pulls: Vec<Pull>,
+}
+
+impl BossEmoteTracker {
+    pub fn new() -> Self { Self::default() }
+}
+
+impl EventHandler for BossEmoteTracker {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        let EventType::Special { details, .. } = &event.event_type else { return; };
+        let Some(encounter) = &event.context.encounter else { return; };
+
+        let (guid, source, text) = match details {
+            Special::EmoteStandard { actor: Some(actor), text } => (Some(&actor.guid), &actor.name, text),
+            Special::EmoteEnvironmental { source_guid, source_name, text, .. } => (source_guid.as_ref(), source_name, text),
+            _ => return,
+        };
+        if !matches!(guid, Some(GUID::Creature { .. })) { return; }
+
+        let text = EmoteText::parse(text);
+        if !text.is_warning() { return; }
+
+        if self.pulls.last().is_none_or(|p| p.start != encounter.start) {
+            self.pulls.push(Pull { encounter: encounter.name.clone(), start: encounter.start, warnings: vec![] });
+        }
+        let pull = self.pulls.last_mut().unwrap();
+        pull.warnings.push(Warning {
+            time: (event.timestamp - pull.start).num_milliseconds() as f64 / 1000.,
+            source: source.clone(),
+            text,
+        });
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let tables = self.pulls.iter().enumerate().map(|(i, pull)| {
+            let mut table = Table::new(&[("Time", 8), ("Source", 25), ("Spell", 8), ("Warning", 60)])
+                .with_title(format!("{} (pull {})", pull.encounter, i + 1));
+            for w in &pull.warnings {
+                table.push(vec![
+                    Cell::Duration(w.time),
+                    w.source.as_str().into(),
+                    w.text.spell_id.map_or(Cell::Empty, Cell::from),
+                    w.text.plain.as_str().into(),
+                ]);
+            }
+            table
+        });
+
+        Some(tables.fold(Summary::new("Boss emotes"), Summary::with_table))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::emotes::BossEmoteTracker;
+    use crate::consumers::EventHandler;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn warnings() {
+        let log = "4/6 14:02:00.000  EMOTE,Creature-0-1469-2549-12530-204931-000011428A,\"Fyrakk\",0000000000000000,nil,|TInterface\\Icons\\Spell_Fire_Fire.blp:20|t Fyrakk begins to cast |cFFFF0000|Hspell:423717|h[Wildfire]|h|r!\n\
+4/6 14:02:07.362  ENCOUNTER_START,2677,\"Fyrakk\",16,20,2549\n\
+4/6 14:02:10.000  EMOTE,Creature-0-1469-2549-12530-204931-000011428A,\"Fyrakk\",0000000000000000,nil,You will all burn!\n\
+4/6 14:02:19.862  EMOTE,Creature-0-1469-2549-12530-204931-000011428A,\"Fyrakk\",0000000000000000,nil,|TInterface\\Icons\\Spell_Fire_Fire.blp:20|t Fyrakk begins to cast |cFFFF0000|Hspell:423717|h[Wildfire]|h|r!\n\
+4/6 14:05:07.362  ENCOUNTER_END,2677,\"Fyrakk\",16,20,0,180000\n";
+
+        let mut tracker = BossEmoteTracker::new();
+        EventParser::new(log.as_bytes()).for_each(|e| tracker.handle(&e));
+
+        let summary = tracker.summary().unwrap();
+        let rows = &summary.table("Fyrakk (pull 1)").unwrap().rows;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0][..3], [Cell::Duration(12.5), "Fyrakk".into(), 423717u64.into()]);
+        assert_eq!(rows[0][3], "Fyrakk begins to cast [Wildfire]!".into());
+    }
+}
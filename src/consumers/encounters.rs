@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::{CreatureType, GUID};
+use crate::components::prefixes::Prefix;
+use crate::components::special::{EncounterEnd, EncounterStart, Special};
+use crate::components::suffixes::Suffix;
+use crate::consumers::EventHandler;
+use crate::consumers::ownership::OwnershipResolver;
+use crate::consumers::phases::PhaseStart;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+/// A single boss pull
+#[derive(Debug, Clone, Serialize)]
+pub struct Pull {
+    /// 1-based pull number, same as `--encounter`
+    pub id: usize,
+    pub encounter_id: u64,
+    pub encounter_name: String,
+    pub difficulty_id: u64,
+    pub start: NaiveDateTime,
+    /// Time of the latest event in the pull
+    pub end: NaiveDateTime,
+    /// None while the pull is in progress
+    pub success: Option<bool>,
+    /// Time of the killing blow on the last boss unit to die. ENCOUNTER_END lags this by a few seconds
+    pub kill_time: Option<NaiveDateTime>,
+    /// Phases reached, for encounters with phase rules
+    pub phases: Vec<PhaseStart>,
+    #[serde(skip)]
+    pub damage: HashMap<String, i64>,
+}
+
+impl Pull {
+    pub fn duration_secs(&self) -> f64 {
+        ((self.end - self.start).num_milliseconds() as f64 / 1000.).max(1.)
+    }
+
+    /// Time from the pull to the killing blow, for kills
+    pub fn kill_secs(&self) -> Option<f64> {
+        self.kill_time.map(|t| (t - self.start).num_milliseconds() as f64 / 1000.)
+    }
+}
+
+/// Times a player cast a spell
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpellCasts {
+    pub spell_id: u64,
+    pub spell_name: String,
+    pub count: usize,
+}
+
+/// Everything recorded so far
+#[derive(Debug, Default)]
+pub struct History {
+    pub pulls: Vec<Pull>,
+    /// Player -> spell ID -> casts, over the whole log
+    pub casts: HashMap<String, HashMap<u64, SpellCasts>>,
+    in_pull: bool,
+    /// Latest overkill hit on a hostile NPC in the current pull
+    last_killing_blow: Option<NaiveDateTime>,
+}
+
+impl History {
+    pub fn pull(&self, id: usize) -> Option<&Pull> {
+        self.pulls.get(id.checked_sub(1)?)
+    }
+
+    /// Casts by a player, most cast first. Player names are matched case insensitively.
+    pub fn player_casts(&self, name: &str) -> Option<Vec<SpellCasts>> {
+        let (_, casts) = self.casts.iter()
+            .find(|(player, _)| player.eq_ignore_ascii_case(name))?;
+
+        Some(casts.values()
+            .sorted_by_key(|c| (std::cmp::Reverse(c.count), c.spell_id))
+            .cloned()
+            .collect())
+    }
+
+    /// Fastest kill time of the encounter on a difficulty
+    pub fn best_kill_secs(&self, encounter_id: u64, difficulty_id: u64) -> Option<f64> {
+        self.pulls.iter()
+            .filter(|p| p.encounter_id == encounter_id && p.difficulty_id == difficulty_id)
+            .filter_map(|p| p.kill_secs())
+            .min_by(|a, b| a.total_cmp(b))
+    }
+}
+
+/// Records per pull damage & per player casts into a history that can be shared with other threads, eg. the HTTP API.
+/// Pet & guardian damage is attributed to the owning player.
+#[derive(Debug, Default)]
+pub struct EncounterHistory {
+    history: Arc<Mutex<History>>,
+    owners: OwnershipResolver,
+}
+
+impl EncounterHistory {
+    pub fn new() -> Self { Self::default() }
+
+    /// Handle to the recorded history
+    pub fn history(&self) -> Arc<Mutex<History>> {
+        self.history.clone()
+    }
+}
+
+impl EventHandler for EncounterHistory {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        self.owners.update(event);
+
+        if let EventType::Special { details: Special::EncounterStart(_), .. } = &event.event_type {
+            if let Some(pull) = self.history.lock().unwrap().pulls.last_mut() {
+                pull.start = event.timestamp;
+                pull.end = event.timestamp;
+            }
+            return;
+        }
+
+        let EventType::Standard { source, target, prefix, suffix, .. } = &event.event_type
+            else { return; };
+
+        let mut history = self.history.lock().unwrap();
+        if let (Suffix::Damage { overkill: Some(_), .. }, Some(target)) = (suffix, target) {
+            let npc = matches!(target.guid, GUID::Creature { unit_type: CreatureType::Creature | CreatureType::Vehicle, .. });
+            if history.in_pull && npc && target.is_hostile() {
+                history.last_killing_blow = Some(event.timestamp);
+            }
+        }
+
+        let Some(source) = source else { return; };
+        match suffix {
+            Suffix::Damage { amount, .. } if history.in_pull => {
+                let Some((_, name)) = self.owners.resolve_player(source) else { return; };
+                let Some(pull) = history.pulls.last_mut() else { return; };
+
+                pull.end = event.timestamp;
+                *pull.damage.entry(name).or_default() += amount;
+            }
+            Suffix::CastSuccess if matches!(source.guid, GUID::Player { .. }) => {
+                let Prefix::Spell(Some(spell)) = &**prefix else { return; };
+
+                history.casts.entry(source.name.clone()).or_default()
+                    .entry(spell.spell_id)
+                    .or_insert_with(|| SpellCasts { spell_id: spell.spell_id, spell_name: spell.spell_name.to_string(), count: 0 })
+                    .count += 1;
+            }
+            _ => {}
+        }
+    }
+
+    fn on_encounter_start(&mut self, encounter: &EncounterStart) {
+        let mut history = self.history.lock().unwrap();
+        let id = history.pulls.len() + 1;
+
+        // Timestamps are filled in when the ENCOUNTER_START event itself is handled
+        history.pulls.push(Pull {
+            id,
+            encounter_id: encounter.encounter_id,
+            encounter_name: encounter.encounter_name.clone(),
+            difficulty_id: encounter.difficulty_id,
+            start: NaiveDateTime::default(),
+            end: NaiveDateTime::default(),
+            success: None,
+            kill_time: None,
+            phases: vec![],
+            damage: HashMap::new(),
+        });
+        history.in_pull = true;
+        history.last_killing_blow = None;
+    }
+
+    fn on_encounter_end(&mut self, encounter: &EncounterEnd) {
+        let mut history = self.history.lock().unwrap();
+        history.in_pull = false;
+        let killing_blow = history.last_killing_blow.take();
+
+        if let Some(pull) = history.pulls.last_mut() {
+            pull.success = Some(encounter.success);
+            pull.kill_time = killing_blow.filter(|_| encounter.success);
+            pull.end = pull.kill_time
+                .unwrap_or(pull.start + chrono::Duration::milliseconds(encounter.fight_time as i64));
+        }
+    }
+
+    fn on_phase_start(&mut self, phase: &PhaseStart) {
+        let mut history = self.history.lock().unwrap();
+        if !history.in_pull { return; }
+        if let Some(pull) = history.pulls.last_mut() { pull.phases.push(phase.clone()); }
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let history = self.history.lock().unwrap();
+
+        let mut table = Table::new(&[("Pull", 4), ("Encounter", 30), ("Duration", 9), ("Result", 12), ("Kill", 9), ("Behind best", 12)]);
+        for p in &history.pulls {
+            let result = match p.success {
+                Some(true) => "Kill",
+                Some(false) => "Wipe",
+                None => "In progress",
+            };
+            // Kill time & how far off the best kill of the boss it was
+            let kill = p.kill_secs();
+            let behind = kill.zip(history.best_kill_secs(p.encounter_id, p.difficulty_id))
+                .map_or(Cell::Empty, |(secs, best)| Cell::Float(secs - best, 1));
+
+            table.push(vec![
+                p.id.into(), p.encounter_name.as_str().into(), Cell::Duration(p.duration_secs()), result.into(),
+                kill.map_or(Cell::Empty, |k| Cell::Float(k, 1)), behind,
+            ]);
+        }
+
+        Some(Summary::new("Pulls").with_table(table))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::{dispatch, EventHandler};
+    use crate::consumers::encounters::EncounterHistory;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    fn pull(start: &str, killing_blow: &str, end: &str) -> String {
+        format!("4/6 {start}  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 {killing_blow}  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,0,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,6000,6000,250,1,0,0,0,nil,nil,nil\n\
+4/6 {end}  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,1,300000\n")
+    }
+
+    #[test]
+    fn kill_time() {
+        let log = [
+            pull("14:00:00.000", "14:03:20.250", "14:03:24.000"),
+            pull("15:00:00.000", "15:03:10.000", "15:03:12.000"),
+        ].concat();
+
+        let tracker = EncounterHistory::new();
+        let history = tracker.history();
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(tracker)];
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        let history = history.lock().unwrap();
+        assert_eq!(history.pulls[0].kill_secs(), Some(200.25));
+        assert_eq!(history.pulls[0].duration_secs(), 200.25);
+        assert_eq!(history.best_kill_secs(2820, 14), Some(190.));
+        drop(history);
+
+        let summary = handlers[0].summary().unwrap();
+        let table = &summary.tables[0];
+        assert_eq!(table.get(0, "Kill"), Some(&Cell::Float(200.25, 1)));
+        assert_eq!(table.get(0, "Behind best"), Some(&Cell::Float(10.25, 1)));
+        assert_eq!(table.get(1, "Behind best"), Some(&Cell::Float(0., 1)));
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use itertools::Itertools;
+
+use crate::components::common::Actor;
+use crate::components::enums::PowerType;
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::EncounterStart;
+use crate::components::suffixes::Suffix;
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+#[derive(Debug, Default)]
+struct Generated {
+    amount: f64,
+    wasted: f64,
+    /// Paid for casts
+    spent: u64,
+    /// Taken by enemies' drains & leeches
+    drained: u64,
+    /// Taken from enemies by leeching
+    leeched: u64,
+}
+
+/// Resources generated by each player & how much of it was wasted by being at the cap (eg. rage or energy),
+/// along with what was spent on casts & lost to or gained by drains and leeches.
+/// `amount` in the log is what was gained, so the total generated is that plus the overcap.
+/// Spending comes from the power costs in the advanced params of the caster's SPELL_CAST_SUCCESS.
+#[derive(Debug, Default)]
+pub struct EnergizeWaste {
+    /// (player, power type) -> generated
+    generated: HashMap<(String, String), Generated>,
+}
+
+impl EnergizeWaste {
+    pub fn new() -> Self { Self::default() }
+}
+
+fn power_name(power_type: PowerType) -> String {
+    format!("{:?}", power_type)
+}
+
+impl EventHandler for EnergizeWaste {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(Event { event_type: EventType::Standard { source, target, advanced_params, suffix, .. }, .. }) = event
+            else { return; };
+        let player = |actor: &Option<Actor>| actor.as_ref()
+            .filter(|a| matches!(a.guid, GUID::Player { .. }))
+            .map(|a| a.name.clone());
+
+        match suffix {
+            Suffix::Energize { amount, over_energize, power_type, .. } => {
+                let Some(target) = player(target) else { return; };
+                let generated = self.generated.entry((target, power_name(*power_type))).or_default();
+                generated.amount += (amount + over_energize) as f64;
+                generated.wasted += *over_energize as f64;
+            }
+            Suffix::Drain { amount, power_type, .. } | Suffix::Leech { amount, power_type, .. } => {
+                if let Some(target) = player(target) {
+                    self.generated.entry((target, power_name(*power_type))).or_default().drained += amount;
+                }
+                if let (Some(source), Suffix::Leech { .. }) = (player(source), suffix) {
+                    self.generated.entry((source, power_name(*power_type))).or_default().leeched += amount;
+                }
+            }
+            Suffix::CastSuccess => {
+                let (Some(name), Some(params)) = (player(source), advanced_params) else { return; };
+                // The advanced params describe the caster for casts
+                if params.info_guid.as_ref() != source.as_ref().map(|s| &s.guid) { return; }
+
+                for power in params.power_info.iter().filter(|p| p.power_cost > 0) {
+                    let Some(power_type) = power.power_type else { continue; };
+                    self.generated.entry((name.clone(), power_name(power_type))).or_default().spent += power.power_cost;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let mut table = Table::new(&[
+            ("Player", 30), ("Power", 14), ("Generated", 10), ("Wasted", 10), ("Waste", 7), ("Spent", 10), ("Drained", 10), ("Leeched", 10),
+        ]);
+        self.generated.iter()
+            .sorted_by(|a, b| a.0.0.cmp(&b.0.0).then(b.1.wasted.total_cmp(&a.1.wasted)))
+            .for_each(|((player, power), g)| {
+                let share = if g.amount > 0. { 100. * g.wasted / g.amount } else { 0. };
+                table.push(vec![
+                    player.as_str().into(), power.as_str().into(), Cell::Float(g.amount, 0), Cell::Float(g.wasted, 0), Cell::Percent(share),
+                    g.spent.into(), g.drained.into(), g.leeched.into(),
+                ]);
+            });
+
+        Some(Summary::new("Resources").with_table(table))
+    }
+
+    fn on_encounter_start(&mut self, _encounter: &EncounterStart) {
+        self.generated.clear();
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.generated.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::energize::EnergizeWaste;
+    use crate::consumers::EventHandler;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn waste() {
+        let log = "4/6 14:00:01.000  SPELL_ENERGIZE,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,195707,\"Rage\",0x1,Player-1390-0C4E032E,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,20.0000,0.0000,1,1000\n\
+4/6 14:00:02.000  SPELL_ENERGIZE,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,195707,\"Rage\",0x1,Player-1390-0C4E032E,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,5.0000,15.0000,1,1000\n";
+
+        let mut waste = EnergizeWaste::new();
+        EventParser::new(log.as_bytes()).for_each(|e| waste.handle(&e));
+
+        let summary = waste.summary().unwrap();
+        assert_eq!(summary.tables[0].find("Stillnixx-Hyjal").unwrap(), [
+            "Stillnixx-Hyjal".into(), "Rage".into(), Cell::Float(40., 0), Cell::Float(15., 0), Cell::Percent(37.5),
+            0u64.into(), 0u64.into(), 0u64.into(),
+        ]);
+    }
+
+    #[test]
+    fn spent_and_drained() {
+        let log = "4/6 14:00:01.000  SPELL_CAST_SUCCESS,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,1464,\"Slam\",0x1,Player-1390-0C4E032E,0000000000000000,100,100,0,0,0,0,1,80,100,200,0.00,0.00,2232,0.0000,70\n\
+4/6 14:00:02.000  SPELL_DRAIN,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,12345,\"Mana Drain\",0x20,Player-1390-0C4E032E,0000000000000000,100,100,0,0,0,0,1,80,100,0,0.00,0.00,2232,0.0000,70,30,1,0,100\n";
+
+        let mut waste = EnergizeWaste::new();
+        EventParser::new(log.as_bytes()).for_each(|e| waste.handle(&e));
+
+        let rage = &waste.generated[&("Stillnixx-Hyjal".to_string(), "Rage".to_string())];
+        assert_eq!((rage.spent, rage.drained), (200, 30));
+    }
+}
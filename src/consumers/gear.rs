@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use itertools::Itertools;
+
+use crate::components::combatant::{COSMETIC_SLOTS, ENCHANTABLE_SLOTS, EquippedItem};
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::Special;
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+use crate::utils::parse_num;
+
+#[derive(Debug, Clone)]
+struct ItemInfo {
+    name: String,
+    /// Item set the item belongs to, eg. a raid tier set
+    set_id: Option<u64>,
+    sockets: usize,
+}
+
+/// Item names, sets & socket counts, from a file of `item_id,name[,set_id[,sockets]]` lines.
+/// Without one, items are described by slot & ID only, and tier sets can't be counted.
+#[derive(Debug, Clone, Default)]
+pub struct ItemDatabase {
+    items: HashMap<u64, ItemInfo>,
+}
+
+impl ItemDatabase {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let s = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to open file: {:?}", path.as_ref()))?;
+        s.parse()
+    }
+
+    /// eg. `Wrist (Bracers of the Sun)` or `Wrist (212345)` for unknown items
+    fn describe(&self, item: &EquippedItem) -> String {
+        match self.items.get(&item.item_id) {
+            Some(info) => format!("{} ({})", item.slot_name(), info.name),
+            None => format!("{} ({})", item.slot_name(), item.item_id),
+        }
+    }
+
+    /// Largest number of items from the same set
+    fn tier_pieces(&self, items: &[EquippedItem]) -> usize {
+        items.iter()
+            .filter_map(|i| self.items.get(&i.item_id)?.set_id)
+            .counts()
+            .into_values()
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Sockets without a gem. The log shows empty sockets on some items, the database fills in the rest.
+    fn empty_sockets(&self, item: &EquippedItem) -> usize {
+        let sockets = self.items.get(&item.item_id).map_or(0, |i| i.sockets).max(item.gem_ids.len());
+        sockets - item.gem_ids.iter().filter(|&&g| g != 0).count().min(sockets)
+    }
+}
+
+impl std::str::FromStr for ItemDatabase {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .comment(Some(b'#'))
+            .trim(csv::Trim::All)
+            .from_reader(s.as_bytes());
+
+        let items = reader.records()
+            .map(|r| {
+                let r = r?;
+                let field = |i| r.get(i).filter(|f| !f.is_empty());
+                let bad = || format!("Bad item: {:?}", r);
+
+                let item_id = parse_num(field(0).unwrap_or_default()).with_context(bad)?;
+                let name = field(1).with_context(bad)?.to_string();
+                let set_id = field(2).map(parse_num).transpose().with_context(bad)?.filter(|&id| id != 0);
+                let sockets = field(3).map(parse_num).transpose().with_context(bad)?.unwrap_or(0);
+                Ok((item_id, ItemInfo { name, set_id, sockets }))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { items })
+    }
+}
+
+/// Each player's gear as of their latest COMBATANT_INFO: average item level, tier set pieces,
+/// and the enchants & gems they're missing.
+#[derive(Debug, Default)]
+pub struct GearCheck {
+    items: ItemDatabase,
+    gear: HashMap<GUID, Vec<EquippedItem>>,
+    /// COMBATANT_INFO doesn't carry names, so they're picked up from other events
+    names: HashMap<GUID, String>,
+}
+
+impl GearCheck {
+    pub fn new(items: ItemDatabase) -> Self {
+        Self { items, ..Self::default() }
+    }
+}
+
+impl EventHandler for GearCheck {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+
+        match &event.event_type {
+            EventType::Special { details: Special::CombatantInfo(info), .. } => {
+                self.gear.insert(info.guid.clone(), info.equipped_items.clone());
+            }
+            EventType::Standard { source, target, .. } => {
+                for actor in [source, target].into_iter().flatten() {
+                    if matches!(actor.guid, GUID::Player { .. }) && !self.names.contains_key(&actor.guid) {
+                        self.names.insert(actor.guid.clone(), actor.name.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let mut table = Table::new(&[("Player", 30), ("Ilvl", 6), ("Tier", 5), ("Missing enchants", 40), ("Missing gems", 40)]);
+
+        self.gear.iter()
+            .map(|(guid, items)| (self.names.get(guid).cloned().unwrap_or_else(|| guid.to_string()), items))
+            .sorted_by(|a, b| a.0.cmp(&b.0))
+            .for_each(|(name, items)| {
+                let counted = items.iter().filter(|i| !COSMETIC_SLOTS.contains(&i.slot)).collect_vec();
+                let ilvl = counted.iter().map(|i| i.ilvl as f64).sum::<f64>() / counted.len().max(1) as f64;
+
+                let enchants = items.iter()
+                    .filter(|i| ENCHANTABLE_SLOTS.contains(&i.slot) && !i.is_enchanted())
+                    .map(|i| self.items.describe(i))
+                    .join(", ");
+                let gems = items.iter()
+                    .filter(|i| self.items.empty_sockets(i) > 0)
+                    .map(|i| match self.items.empty_sockets(i) {
+                        1 => self.items.describe(i),
+                        n => format!("{} x{}", self.items.describe(i), n),
+                    })
+                    .join(", ");
+
+                table.push(vec![name.into(), Cell::Float(ilvl, 1), self.items.tier_pieces(items).into(), enchants.into(), gems.into()]);
+            });
+
+        Some(Summary::new("Gear").with_table(table))
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.gear.len() + self.names.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::EventHandler;
+    use crate::consumers::gear::{GearCheck, ItemDatabase};
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn gear() {
+        // Head (tier, 1 empty socket), neck (1 of 3 sockets filled), chest (tier, enchanted), back (unenchanted)
+        let log = "4/6 14:01:05.100  COMBATANT_INFO,Player-1335-0A264B4C,1,12648,1734,52761,1128,0,0,0,3511,3511,3511,900,0,4692,4692,4692,443,6741,533,533,533,11302,251,[(76034,96162,1)],(1,204080,199719,233396),[(207200,489,(),(),(0)),(201759,480,(),(),(192985)),(0,0,(),(),()),(0,0,(),(),()),(207199,483,(7364,0,0),(),()),(0,0,(),(),()),(0,0,(),(),()),(0,0,(),(),()),(0,0,(),(),()),(0,0,(),(),()),(0,0,(),(),()),(0,0,(),(),()),(0,0,(),(),()),(0,0,(),(),()),(207101,476,(),(),())],[Player-1335-0A264B4C,396092],145,0,0,0\n\
+4/6 14:01:06.000  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,500,500,-1,1,0,0,0,nil,nil,nil\n";
+
+        let items: ItemDatabase = "207200,Skewers of Tormented Souls,1570\n207199,Hide of the Nightmare,1570\n201759,Ornately Engraved Amplifier,0,3\n".parse().unwrap();
+        let mut gear = GearCheck::new(items);
+        EventParser::new(log.as_bytes()).for_each(|e| gear.handle(&e));
+
+        let summary = gear.summary().unwrap();
+        assert_eq!(summary.tables[0].find("Sønike-Ysondre").unwrap(), [
+            "Sønike-Ysondre".into(), Cell::Float(482., 1), Cell::Int(2),
+            "Back (207101)".into(),
+            "Head (Skewers of Tormented Souls), Neck (Ornately Engraved Amplifier) x2".into(),
+        ]);
+    }
+}
@@ -0,0 +1,305 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::prefixes::Prefix;
+use crate::components::special::{EncounterStart, Special};
+use crate::components::suffixes::Suffix;
+use crate::consumers::{EventHandler, PeriodicMode};
+use crate::consumers::ownership::OwnershipResolver;
+use crate::consumers::percentiles::{format_percentile, Metric, Percentiles};
+use crate::consumers::pruning::LastSeen;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+/// Execute time assumed for instant casts (an un-hasted global cooldown)
+const GCD_SECONDS: f64 = 1.5;
+
+#[derive(Debug, Default)]
+struct SpellHealing {
+    spell_name: Arc<str>,
+    amount: u64,
+    overhealing: u64,
+    hits: u64,
+    crits: u64,
+    casts: u64,
+    execute_secs: f64,
+}
+
+impl SpellHealing {
+    fn effective(&self) -> u64 { self.amount.saturating_sub(self.overhealing) }
+
+    fn overheal_pct(&self) -> f64 {
+        if self.amount == 0 { 0. } else { 100. * self.overhealing as f64 / self.amount as f64 }
+    }
+
+    fn crit_pct(&self) -> f64 {
+        if self.hits == 0 { 0. } else { 100. * self.crits as f64 / self.hits as f64 }
+    }
+
+    /// Healing per execute time
+    fn hpet(&self) -> f64 {
+        if self.execute_secs == 0. { 0. } else { self.effective() as f64 / self.execute_secs }
+    }
+}
+
+/// Healer, spell ID & whether it's the periodic part of the spell
+type SpellKey = (String, u64, bool);
+
+/// Per (healer, spell) healing breakdown. Pet healing is attributed to the owner.
+/// Execute time comes from SPELL_CAST_START -> SPELL_CAST_SUCCESS pairs, instant casts count as a GCD,
+/// and is attributed to the direct part of a spell when HoTs are separated.
+/// HPS is ranked against reference percentiles when there's data for the boss & healer's spec.
+#[derive(Debug, Default)]
+pub struct HealingBreakdown {
+    spells: HashMap<SpellKey, SpellHealing>,
+    cast_starts: HashMap<(String, u64), NaiveDateTime>,
+    owners: OwnershipResolver,
+    last_seen: LastSeen<String>,
+    periodic: PeriodicMode,
+    percentiles: Percentiles,
+    encounter_id: Option<u64>,
+    specs: HashMap<GUID, u64>,
+    guids: HashMap<String, GUID>,
+    start_time: Option<NaiveDateTime>,
+    latest_time: Option<NaiveDateTime>,
+}
+
+impl HealingBreakdown {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn with_periodic(mut self, periodic: PeriodicMode) -> Self {
+        self.periodic = periodic;
+        self
+    }
+
+    pub(crate) fn with_percentiles(mut self, percentiles: Percentiles) -> Self {
+        self.percentiles = percentiles;
+        self
+    }
+
+    fn reset(&mut self) {
+        self.spells.clear();
+        self.cast_starts.clear();
+        self.last_seen.clear();
+        self.specs.clear();
+        self.guids.clear();
+        self.start_time = None;
+        self.latest_time = None;
+    }
+
+    fn percentile(&self, healer: &str, hps: f64) -> Option<f64> {
+        let spec = self.guids.get(healer).and_then(|g| self.specs.get(g))?;
+        self.percentiles.estimate(self.encounter_id?, *spec, Metric::Hps, hps)
+    }
+
+    /// Effective healing, HPS & its percentile, when there's reference data, for each healer
+    fn healer_table(&self, totals: &HashMap<&str, u64>) -> Table {
+        let duration = match (self.start_time, self.latest_time) {
+            (Some(start), Some(end)) => (end - start).num_seconds() + 1,
+            _ => 1,
+        };
+
+        let ranked = !self.percentiles.is_empty();
+        let columns = [("Healer", 30), ("Healing", 10), ("HPS", 10), ("Parse", 10)];
+        let mut table = Table::new(if ranked { &columns } else { &columns[..3] }).with_title("By healer");
+        totals.iter()
+            .sorted_by_key(|(healer, total)| (Reverse(**total), **healer))
+            .for_each(|(healer, total)| {
+                let hps = *total as f64 / duration as f64;
+                let mut row = vec![(*healer).into(), (*total).into(), Cell::Float(hps, 0)];
+                if ranked { row.push(format_percentile(self.percentile(healer, hps)).into()); }
+                table.push(row);
+            });
+        table
+    }
+}
+
+impl EventHandler for HealingBreakdown {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        self.owners.update(event);
+
+        if let EventType::Special { details: Special::CombatantInfo(info), .. } = &event.event_type {
+            if let Some(spec) = info.spec_id { self.specs.insert(info.guid.clone(), spec); }
+        }
+
+        let EventType::Standard { source: Some(source), prefix, suffix, .. } = &event.event_type
+            else { return; };
+
+        let spell_info = match &**prefix {
+            Prefix::Spell(Some(s)) | Prefix::SpellPeriodic(s) => s,
+            _ => return
+        };
+        let Some((guid, healer)) = self.owners.resolve_player(source) else { return; };
+        self.last_seen.touch(&healer, event.timestamp);
+        let key = (healer, spell_info.spell_id);
+        let periodic = self.periodic == PeriodicMode::Separate && matches!(&**prefix, Prefix::SpellPeriodic(_));
+
+        match suffix {
+            Suffix::Heal { amount, overhealing, critical, .. } => {
+                self.guids.entry(key.0.clone()).or_insert(guid);
+                self.start_time.get_or_insert(event.timestamp);
+                self.latest_time = Some(event.timestamp);
+
+                let entry = self.spells.entry((key.0, key.1, periodic)).or_default();
+                entry.spell_name = spell_info.spell_name.clone();
+                entry.amount += amount;
+                entry.overhealing += overhealing;
+                entry.hits += 1;
+                entry.crits += *critical as u64;
+            }
+            Suffix::CastStart => {
+                self.cast_starts.insert(key, event.timestamp);
+            }
+            Suffix::CastSuccess => {
+                let execute_secs = self.cast_starts.remove(&key)
+                    .map(|start| (event.timestamp - start).num_milliseconds() as f64 / 1000.)
+                    .map_or(GCD_SECONDS, |t| t.max(GCD_SECONDS));
+
+                let entry = self.spells.entry((key.0, key.1, false)).or_default();
+                entry.spell_name = spell_info.spell_name.clone();
+                entry.casts += 1;
+                entry.execute_secs += execute_secs;
+            }
+            _ => {}
+        }
+    }
+
+    fn on_encounter_start(&mut self, encounter: &EncounterStart) {
+        self.reset();
+        self.encounter_id = Some(encounter.encounter_id);
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let mut table = Table::new(&[("Healer", 30), ("Spell", 25), ("Healing", 10), ("Overheal", 10), ("Crit", 10), ("Casts", 6), ("HPET", 10)]);
+        let mut totals = HashMap::<&str, u64>::new();
+        for ((healer, _, _), v) in &self.spells {
+            *totals.entry(healer).or_default() += v.effective();
+        }
+
+        // Top healer first, each with their top spell first
+        self.spells.iter()
+            .filter(|(_, v)| v.hits > 0)
+            .sorted_by_key(|((healer, spell_id, periodic), v)| (Reverse(totals[healer.as_str()]), healer.clone(), Reverse(v.effective()), *spell_id, *periodic))
+            .for_each(|((healer, _, periodic), v)| {
+                let spell = if *periodic { format!("{} (HoT)", v.spell_name) } else { v.spell_name.to_string() };
+                table.push(vec![
+                    healer.as_str().into(), spell.into(), v.effective().into(),
+                    Cell::Percent(v.overheal_pct()), Cell::Percent(v.crit_pct()), v.casts.into(), Cell::Float(v.hpet(), 0),
+                ]);
+            });
+
+        let healers = self.healer_table(&totals);
+        Some(Summary::new("Healing").with_table(table).with_table(healers))
+    }
+
+    fn prune(&mut self, cutoff: NaiveDateTime) {
+        for healer in self.last_seen.expire(cutoff) {
+            self.spells.retain(|(h, _, _), _| *h != healer);
+            self.cast_starts.retain(|(h, _), _| *h != healer);
+            if let Some(guid) = self.guids.remove(&healer) { self.specs.remove(&guid); }
+        }
+        self.owners.prune(cutoff);
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.last_seen.len() + self.owners.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::{dispatch, EventHandler, PeriodicMode};
+    use crate::consumers::healing::HealingBreakdown;
+    use crate::consumers::percentiles::Percentiles;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn overheal_and_casts() {
+        let log = "4/6 14:09:44.000  SPELL_CAST_START,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,0000000000000000,nil,0x80000000,0x80000000,8936,\"Regrowth\",0x8\n\
+4/6 14:09:46.000  SPELL_CAST_SUCCESS,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,0000000000000000,nil,0x80000000,0x80000000,8936,\"Regrowth\",0x8,Player-1393-077C088C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72\n\
+4/6 14:09:46.100  SPELL_HEAL,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,8936,\"Regrowth\",0x8,Player-1393-077C088C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,1000,1000,250,0,1\n";
+
+        let mut tracker = HealingBreakdown::new();
+        EventParser::new(log.as_bytes()).for_each(|e| tracker.handle(&e));
+
+        let spell = &tracker.spells[&("Mubaku-BronzeDragonflight".to_string(), 8936, false)];
+        assert_eq!(spell.effective(), 750);
+        assert_eq!(spell.overheal_pct(), 25.);
+        assert_eq!(spell.crit_pct(), 100.);
+        assert_eq!(spell.casts, 1);
+        assert_eq!(spell.hpet(), 375.);
+        assert!(tracker.display().is_some());
+    }
+
+    #[test]
+    fn periodic_mode() {
+        let log = "4/6 14:09:46.100  SPELL_HEAL,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,8936,\"Regrowth\",0x8,Player-1393-077C088C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,1000,1000,0,0,nil\n\
+4/6 14:09:48.100  SPELL_PERIODIC_HEAL,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,8936,\"Regrowth\",0x8,Player-1393-077C088C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,200,200,0,0,nil\n";
+
+        let mut merged = HealingBreakdown::new();
+        EventParser::new(log.as_bytes()).for_each(|e| merged.handle(&e));
+        assert_eq!(merged.spells.len(), 1);
+        assert_eq!(merged.spells[&("Mubaku-BronzeDragonflight".to_string(), 8936, false)].effective(), 1200);
+
+        let mut separate = HealingBreakdown::new().with_periodic(PeriodicMode::Separate);
+        EventParser::new(log.as_bytes()).for_each(|e| separate.handle(&e));
+        assert_eq!(separate.spells[&("Mubaku-BronzeDragonflight".to_string(), 8936, false)].effective(), 1000);
+        assert_eq!(separate.spells[&("Mubaku-BronzeDragonflight".to_string(), 8936, true)].effective(), 200);
+        assert!(separate.display().unwrap().contains("Regrowth (HoT)"));
+    }
+
+    #[test]
+    fn healer_order() {
+        let heal = |healer: &str, spell: &str, amount: u64, overhealing: u64| format!("4/6 14:09:46.100  SPELL_HEAL,{healer},0x514,0x0,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,{spell},0x8,Player-1393-077C088C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,{amount},{amount},{overhealing},0,nil\n");
+        let anna = "Player-1393-0A000001,\"Anna-Ysondre\"";
+        let zed = "Player-1393-0A000002,\"Zed-Ysondre\"";
+        let log = [
+            heal(anna, "8936,\"Regrowth\"", 1000, 0),
+            heal(zed, "8936,\"Regrowth\"", 1500, 0),
+            heal(zed, "774,\"Rejuvenation\"", 2000, 0),
+            // More overhealing than healing shouldn't underflow
+            heal(anna, "774,\"Rejuvenation\"", 100, 300),
+        ].concat();
+
+        let mut tracker = HealingBreakdown::new();
+        EventParser::new(log.as_bytes()).for_each(|e| tracker.handle(&e));
+
+        let summary = tracker.summary().unwrap();
+        let order = summary.tables[0].rows.iter().map(|r| (r[0].clone(), r[1].clone(), r[2].clone())).collect::<Vec<_>>();
+        assert_eq!(order, [
+            ("Zed-Ysondre".into(), "Rejuvenation".into(), Cell::Int(2000)),
+            ("Zed-Ysondre".into(), "Regrowth".into(), Cell::Int(1500)),
+            ("Anna-Ysondre".into(), "Regrowth".into(), Cell::Int(1000)),
+            ("Anna-Ysondre".into(), "Rejuvenation".into(), Cell::Int(0)),
+        ]);
+    }
+
+    #[test]
+    fn hps_percentile() {
+        let log = "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 14:01:05.000  COMBATANT_INFO,Player-1393-077C088C,1,12648,1734,52761,1128,0,0,0,3511,3511,3511,900,0,4692,4692,4692,443,6741,533,533,533,11302,105,[(76034,96162,1)],(1,204080,199719,233396),[(207200,489,(),(),())],[Player-1393-077C088C,396092],145,0,0,0\n\
+4/6 14:01:06.000  SPELL_HEAL,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,8936,\"Regrowth\",0x8,Player-1393-077C088C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,1000,1000,0,0,nil\n\
+4/6 14:01:07.000  SPELL_HEAL,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,8936,\"Regrowth\",0x8,Player-1393-077C088C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,1000,1000,0,0,nil\n";
+
+        let mut percentiles = Percentiles::default();
+        percentiles.extend_from_str("2820,105,hps,50,250\n2820,105,hps,100,2000").unwrap();
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(HealingBreakdown::new().with_percentiles(percentiles))];
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        // 2000 healing over 2 seconds
+        let summary = handlers[0].summary().unwrap();
+        let healer = &summary.tables[1].rows[0];
+        assert_eq!(healer[2], Cell::Float(1000., 0));
+        assert_eq!(healer[3], "~71st".into());
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+
+use crate::components::events::{Event, EventType};
+use crate::components::prefixes::Prefix;
+use crate::components::special::EncounterStart;
+use crate::components::suffixes::Suffix;
+use crate::consumers::{EventHandler, PeriodicMode};
+use crate::consumers::ownership::OwnershipResolver;
+use crate::consumers::pruning::LastSeen;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+#[derive(Debug, Default)]
+struct HitStats {
+    spell_name: String,
+    hits: u64,
+    total: i64,
+    min: i64,
+    max: i64,
+    crits: u64,
+    glancing: u64,
+    crushing: u64,
+    resisted: u64,
+    blocked: u64,
+    absorbed: i64,
+}
+
+impl HitStats {
+    fn mean(&self) -> f64 {
+        if self.hits == 0 { 0. } else { self.total as f64 / self.hits as f64 }
+    }
+
+    /// Percentage of hits
+    fn pct(&self, count: u64) -> f64 {
+        if self.hits == 0 { 0. } else { 100. * count as f64 / self.hits as f64 }
+    }
+}
+
+/// Distribution of each ability's hits per player: smallest, largest & mean hit, how often it crit, glanced or crushed,
+/// and how much was resisted, blocked or absorbed. Pet damage is attributed to the owner.
+#[derive(Debug, Default)]
+pub struct HitDistribution {
+    /// By player, spell ID & whether it's the periodic part of the spell
+    abilities: HashMap<(String, u64, bool), HitStats>,
+    owners: OwnershipResolver,
+    last_seen: LastSeen<String>,
+    periodic: PeriodicMode,
+}
+
+impl HitDistribution {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn with_periodic(mut self, periodic: PeriodicMode) -> Self {
+        self.periodic = periodic;
+        self
+    }
+}
+
+impl EventHandler for HitDistribution {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        self.owners.update(event);
+
+        let EventType::Standard {
+            source: Some(source),
+            prefix,
+            suffix: Suffix::Damage { amount, resisted, blocked, absorbed, critical, glancing, crushing, .. },
+            ..
+        } = &event.event_type else { return; };
+        let Some((_, player)) = self.owners.resolve_player(source) else { return; };
+        self.last_seen.touch(&player, event.timestamp);
+
+        let (spell_id, spell_name) = prefix.spell_info().map_or((0, "Melee"), |s| (s.spell_id, &*s.spell_name));
+        let periodic = self.periodic == PeriodicMode::Separate && matches!(&**prefix, Prefix::SpellPeriodic(_));
+        let stats = self.abilities.entry((player, spell_id, periodic)).or_default();
+        if stats.hits == 0 {
+            stats.spell_name = if periodic { format!("{} (DoT)", spell_name) } else { spell_name.to_string() };
+            stats.min = *amount;
+            stats.max = *amount;
+        }
+        stats.hits += 1;
+        stats.total += amount;
+        stats.min = stats.min.min(*amount);
+        stats.max = stats.max.max(*amount);
+        stats.crits += *critical as u64;
+        stats.glancing += *glancing as u64;
+        stats.crushing += *crushing as u64;
+        stats.resisted += resisted;
+        stats.blocked += blocked;
+        stats.absorbed += absorbed;
+    }
+
+    fn on_encounter_start(&mut self, _encounter: &EncounterStart) {
+        self.abilities.clear();
+        self.last_seen.clear();
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let mut table = Table::new(&[
+            ("Player", 30), ("Ability", 25), ("Hits", 6), ("Min", 8), ("Max", 8), ("Mean", 8),
+            ("Crit", 7), ("Glance", 7), ("Crush", 7), ("Resisted", 9), ("Blocked", 9), ("Absorbed", 9),
+        ]);
+        self.abilities.iter()
+            .sorted_by_key(|((player, ..), s)| (player.clone(), -s.total))
+            .for_each(|((player, ..), s)| {
+                table.push(vec![
+                    player.as_str().into(), s.spell_name.as_str().into(), s.hits.into(),
+                    s.min.into(), s.max.into(), Cell::Float(s.mean(), 0),
+                    Cell::Percent(s.pct(s.crits)), Cell::Percent(s.pct(s.glancing)), Cell::Percent(s.pct(s.crushing)),
+                    s.resisted.into(), s.blocked.into(), s.absorbed.into(),
+                ]);
+            });
+
+        Some(Summary::new("Hit distribution").with_table(table))
+    }
+
+    fn prune(&mut self, cutoff: NaiveDateTime) {
+        for player in self.last_seen.expire(cutoff) {
+            self.abilities.retain(|(p, ..), _| *p != player);
+        }
+        self.owners.prune(cutoff);
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.last_seen.len() + self.owners.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::{EventHandler, PeriodicMode};
+    use crate::consumers::hits::HitDistribution;
+    use crate::parser::EventParser;
+
+    #[test]
+    fn distribution() {
+        let log = "4/6 14:02:07.362  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,500,600,-1,1,0,0,100,nil,1,nil\n\
+4/6 14:02:08.362  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,1500,1500,-1,1,0,50,0,1,nil,nil\n\
+4/6 14:02:09.362  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,1000,1000,-1,1,0,0,0,nil,nil,nil\n\
+4/6 14:02:10.362  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,1000,1000,-1,1,0,0,0,nil,nil,nil\n";
+
+        let mut tracker = HitDistribution::new();
+        EventParser::new(log.as_bytes()).for_each(|e| tracker.handle(&e));
+
+        let melee = &tracker.abilities[&("Sønike-Ysondre".to_string(), 0, false)];
+        assert_eq!((melee.hits, melee.min, melee.max, melee.mean()), (4, 500, 1500, 1000.));
+        assert_eq!((melee.pct(melee.crits), melee.pct(melee.glancing)), (25., 25.));
+        assert_eq!((melee.blocked, melee.absorbed), (50, 100));
+        assert!(tracker.display().unwrap().contains("Melee"));
+    }
+
+    #[test]
+    fn periodic_mode() {
+        let log = "4/6 14:02:08.000  SPELL_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,8921,\"Moonfire\",0x40,Creature-0-1469-2549-12530-209333-000011428A,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,1000,1000,-1,64,0,0,0,nil,nil,nil\n\
+4/6 14:02:10.000  SPELL_PERIODIC_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,8921,\"Moonfire\",0x40,Creature-0-1469-2549-12530-209333-000011428A,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,200,200,-1,64,0,0,0,nil,nil,nil\n";
+
+        let mut separate = HitDistribution::new().with_periodic(PeriodicMode::Separate);
+        EventParser::new(log.as_bytes()).for_each(|e| separate.handle(&e));
+        let ticks = &separate.abilities[&("Sønike-Ysondre".to_string(), 8921, true)];
+        assert_eq!((ticks.hits, ticks.max), (1, 200));
+        assert_eq!(ticks.spell_name, "Moonfire (DoT)");
+        assert_eq!(separate.abilities[&("Sønike-Ysondre".to_string(), 8921, false)].max, 1000);
+    }
+}
@@ -0,0 +1,162 @@
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+
+use crate::components::events::Event;
+use crate::components::special::{EncounterEnd, EncounterStart};
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+
+/// Sent as the webhook body, as JSON
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum PullEvent<'a> {
+    PullStart { encounter: &'a EncounterStart },
+    PullEnd { encounter: &'a EncounterEnd },
+}
+
+impl PullEvent<'_> {
+    /// Encounter details passed to commands, as `WOW_*` environment variables
+    fn env(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Self::PullStart { encounter: e } => vec![
+                ("WOW_EVENT", "pull_start".to_string()),
+                ("WOW_ENCOUNTER_ID", e.encounter_id.to_string()),
+                ("WOW_ENCOUNTER_NAME", e.encounter_name.clone()),
+                ("WOW_DIFFICULTY_ID", e.difficulty_id.to_string()),
+                ("WOW_GROUP_SIZE", e.group_size.to_string()),
+                ("WOW_INSTANCE_ID", e.instance_id.to_string()),
+            ],
+            Self::PullEnd { encounter: e } => vec![
+                ("WOW_EVENT", "pull_end".to_string()),
+                ("WOW_ENCOUNTER_ID", e.encounter_id.to_string()),
+                ("WOW_ENCOUNTER_NAME", e.encounter_name.clone()),
+                ("WOW_DIFFICULTY_ID", e.difficulty_id.to_string()),
+                ("WOW_GROUP_SIZE", e.group_size.to_string()),
+                ("WOW_SUCCESS", (e.success as u8).to_string()),
+                ("WOW_FIGHT_TIME_MS", e.fight_time.to_string()),
+            ],
+        }
+    }
+}
+
+/// Runs a shell command and / or calls a webhook when a pull starts or ends, eg. to start recording or dim the lights.
+/// Commands get the encounter details as `WOW_*` environment variables, webhooks get them as a JSON POST.
+/// Both run in the background so a slow hook never holds up parsing.
+#[derive(Debug, Default)]
+pub struct PullHooks {
+    on_start: Option<String>,
+    on_end: Option<String>,
+    webhook: Option<String>,
+}
+
+impl PullHooks {
+    pub fn new(on_start: Option<String>, on_end: Option<String>, webhook: Option<String>) -> Self {
+        Self { on_start, on_end, webhook }
+    }
+
+    fn fire(&self, command: Option<&String>, event: &PullEvent) {
+        if let Some(command) = command {
+            let env = event.env();
+            let command = command.clone();
+            thread::spawn(move || {
+                if let Err(e) = run(&command, env) { eprintln!("{e:#}"); }
+            });
+        }
+
+        if let Some(url) = &self.webhook {
+            let url = url.clone();
+            let Ok(body) = serde_json::to_string(event) else { return; };
+            thread::spawn(move || {
+                let headers = [("Content-Type", "application/json".to_string())];
+                if let Err(e) = post(&url, &headers, body.as_bytes()) { eprintln!("{e:#}"); }
+            });
+        }
+    }
+}
+
+/// Runs a command through the platform's shell & waits for it
+pub fn run(command: &str, env: Vec<(&str, String)>) -> Result<()> {
+    let mut shell = if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.args(["/C", command]);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.args(["-c", command]);
+        c
+    };
+
+    let status = shell.envs(env).status()
+        .with_context(|| format!("Failed to run hook: {:?}", command))?;
+    if !status.success() {
+        bail!("Hook {:?} failed: {}", command, status);
+    }
+    Ok(())
+}
+
+/// POSTs a body to an http:// or https:// URL, with any extra headers
+pub fn post(url: &str, headers: &[(&str, String)], body: &[u8]) -> Result<()> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(10)))
+        .build()
+        .into();
+
+    let mut request = agent.post(url);
+    for (name, value) in headers {
+        request = request.header(*name, value);
+    }
+    request.send(body).with_context(|| format!("POST to {:?} failed", url))?;
+    Ok(())
+}
+
+impl EventHandler for PullHooks {
+    fn handle(&mut self, _event: &Result<Event, ParseFailure>) {}
+
+    fn display(&self) -> Option<String> { None }
+
+    fn on_encounter_start(&mut self, encounter: &EncounterStart) {
+        self.fire(self.on_start.as_ref(), &PullEvent::PullStart { encounter });
+    }
+
+    fn on_encounter_end(&mut self, encounter: &EncounterEnd) {
+        self.fire(self.on_end.as_ref(), &PullEvent::PullEnd { encounter });
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    use crate::consumers::{dispatch, EventHandler};
+    use crate::consumers::hooks::PullHooks;
+    use crate::parser::EventParser;
+
+    #[test]
+    fn webhook() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/pull", listener.local_addr().unwrap());
+
+        let log = "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n";
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(PullHooks::new(None, None, Some(url)))];
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = String::new();
+        let mut buf = [0; 4096];
+        while !request.ends_with('}') {
+            let n = stream.read(&mut buf).unwrap();
+            if n == 0 { break; }
+            request.push_str(&String::from_utf8_lossy(&buf[..n]));
+        }
+        stream.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").unwrap();
+
+        assert!(request.starts_with("POST /pull HTTP/1.1"), "{}", request);
+        assert!(request.contains(r#""type":"pull_start""#) && request.contains(r#""encounter_name":"Gnarlroot""#), "{}", request);
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::Special;
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+/// Timer for each dungeon in seconds, by challenge mode ID (The War Within season 1)
+const PAR_TIMES: [(u64, u64); 8] = [
+    (503, 30 * 60), // Ara-Kara, City of Echoes
+    (502, 38 * 60), // City of Threads
+    (501, 33 * 60), // The Stonevault
+    (505, 35 * 60), // The Dawnbreaker
+    (375, 30 * 60), // Mists of Tirna Scithe
+    (376, 36 * 60), // The Necrotic Wake
+    (353, 33 * 60), // Siege of Boralus
+    (507, 34 * 60), // Grim Batol
+];
+
+/// Levels which add an affix, each worth extra score
+const AFFIX_LEVELS: [u64; 4] = [4, 7, 10, 12];
+
+/// A completed key, as stored in the key history file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyRecord {
+    pub start: NaiveDateTime,
+    pub dungeon: String,
+    pub challenge_mode_id: u64,
+    pub level: u64,
+    pub time_ms: u64,
+    /// Dungeon timer, if known
+    pub par_ms: Option<u64>,
+    pub timed: bool,
+    pub roster: Vec<String>,
+}
+
+impl KeyRecord {
+    /// Rough score for the key, following the shape of the in-game formula: a base for the level & affixes,
+    /// plus up to 15 for beating the timer by 40%, or minus up to 30 for going over by as much.
+    /// Keys more than 40% over time score nothing. Without a timer, timed keys score the base.
+    pub fn score(&self) -> f64 {
+        let base = 125. + 15. * self.level as f64 + 10. * AFFIX_LEVELS.iter().filter(|&&l| self.level >= l).count() as f64;
+
+        let Some(par) = self.par_ms else { return if self.timed { base } else { base - 15. }; };
+        let margin = (par as f64 - self.time_ms as f64) / (0.4 * par as f64);
+        match margin {
+            m if m >= 0. => base + 15. * m.min(1.),
+            m if m >= -1. => base - 15. + 15. * m,
+            _ => 0.,
+        }
+    }
+}
+
+/// Reads every key in a history file. A missing file has no keys.
+pub fn load_history<P: AsRef<Path>>(path: P) -> Result<Vec<KeyRecord>> {
+    let path = path.as_ref();
+    if !path.exists() { return Ok(vec![]); }
+
+    std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to open file: {:?}", path))?
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| serde_json::from_str(l).with_context(|| format!("Bad key record: {:?}", l)))
+        .collect()
+}
+
+/// Adds a key to the history file, unless it's already there from reading the same log before
+fn save(path: &Path, record: &KeyRecord) -> Result<bool> {
+    if load_history(path)?.iter().any(|r| r.start == record.start && r.dungeon == record.dungeon) {
+        return Ok(false);
+    }
+
+    let mut file = File::options().create(true).append(true).open(path)
+        .with_context(|| format!("Failed to open file: {:?}", path))?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(true)
+}
+
+#[derive(Debug)]
+struct Pending {
+    start: NaiveDateTime,
+    dungeon: String,
+    challenge_mode_id: u64,
+}
+
+/// Adds each completed Mythic+ key to a JSON lines history file, along with the group that ran it.
+#[derive(Debug)]
+pub struct KeyHistory {
+    path: PathBuf,
+    current: Option<Pending>,
+    roster: BTreeSet<String>,
+    saved: usize,
+}
+
+impl KeyHistory {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, current: None, roster: BTreeSet::new(), saved: 0 }
+    }
+}
+
+impl EventHandler for KeyHistory {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+
+        match &event.event_type {
+            EventType::Special { details: Special::ChallengeModeStart { zone_name, challenge_mode_id, .. }, .. } => {
+                self.current = Some(Pending { start: event.timestamp, dungeon: zone_name.clone(), challenge_mode_id: *challenge_mode_id });
+                self.roster.clear();
+            }
+            EventType::Special { details: Special::ChallengeModeEnd { success, keystone_level, total_time, .. }, .. } => {
+                let Some(pending) = self.current.take() else { return; };
+                let record = KeyRecord {
+                    start: pending.start,
+                    dungeon: pending.dungeon,
+                    challenge_mode_id: pending.challenge_mode_id,
+                    level: *keystone_level,
+                    time_ms: *total_time,
+                    par_ms: PAR_TIMES.iter().find(|(id, _)| *id == pending.challenge_mode_id).map(|(_, secs)| secs * 1000),
+                    timed: *success,
+                    roster: std::mem::take(&mut self.roster).into_iter().collect(),
+                };
+
+                match save(&self.path, &record) {
+                    Ok(true) => self.saved += 1,
+                    Ok(false) => {}
+                    Err(e) => eprintln!("{e:#}"),
+                }
+            }
+            EventType::Standard { source, target, .. } if self.current.is_some() => {
+                for actor in [source, target].into_iter().flatten() {
+                    if matches!(actor.guid, GUID::Player { .. }) && actor.in_group() && !self.roster.contains(&actor.name) {
+                        self.roster.insert(actor.name.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn display(&self) -> Option<String> {
+        Some(format!("Key history: {} keys added to {:?}", self.saved, self.path))
+    }
+}
+
+/// Each character's estimated season score after every key they ran, counting their best key in each dungeon
+pub fn history_report(records: &[KeyRecord]) -> Summary {
+    let mut best: HashMap<&str, HashMap<&str, f64>> = HashMap::new();
+    let mut tables: HashMap<&str, Table> = HashMap::new();
+
+    for record in records.iter().sorted_by_key(|r| r.start) {
+        let score = record.score();
+        for character in &record.roster {
+            let dungeons = best.entry(character).or_default();
+            let before = dungeons.values().sum::<f64>();
+            let dungeon = dungeons.entry(&record.dungeon).or_default();
+            *dungeon = dungeon.max(score);
+            let after = dungeons.values().sum::<f64>();
+
+            tables.entry(character)
+                .or_insert_with(|| Table::new(&[("Date", 16), ("Key", 30), ("Time", 8), ("Par", 8), ("Key score", 9), ("Score", 8), ("Change", 7)])
+                    .with_title(character.as_str()))
+                .push(vec![
+                    record.start.format("%Y-%m-%d %H:%M").to_string().into(),
+                    format!("{} +{}", record.dungeon, record.level).into(),
+                    Cell::Duration(record.time_ms as f64 / 1000.),
+                    record.par_ms.map(|p| Cell::Duration(p as f64 / 1000.)).into(),
+                    Cell::Float(score, 1),
+                    Cell::Float(after, 1),
+                    Cell::Float(after - before, 1),
+                ]);
+        }
+    }
+
+    tables.into_iter()
+        .sorted_by_key(|(character, _)| *character)
+        .fold(Summary::new("Mythic+ history"), |summary, (_, table)| summary.with_table(table))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::EventHandler;
+    use crate::consumers::keystones::{history_report, KeyHistory, load_history};
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    fn run(start: &str, level: u64, time_ms: u64, success: u64) -> String {
+        format!("4/6 {start}  CHALLENGE_MODE_START,\"The Dawnbreaker\",2662,505,{level},[10,109,148]\n\
+4/6 {start}  SPELL_CAST_SUCCESS,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,0000000000000000,nil,0x80000000,0x80000000,1680,\"Whirlwind\",0x1,Player-1390-0C4E032E,0000000000000000,0,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70\n\
+4/6 {start}  CHALLENGE_MODE_END,2662,{success},{level},{time_ms}\n")
+    }
+
+    #[test]
+    fn history() {
+        let path = std::env::temp_dir().join("wowlogs_key_history_test.jsonl");
+        let _ = std::fs::remove_file(&path);
+
+        // The par time for The Dawnbreaker is 35 minutes, 40% of which is 14 minutes
+        let log = run("14:00:00.000", 10, 28 * 60 * 1000, 1) + &run("16:00:00.000", 12, 42 * 60 * 1000, 0);
+        let mut history = KeyHistory::new(path.clone());
+        EventParser::new(log.as_bytes()).for_each(|e| history.handle(&e));
+        // Reading the same log again doesn't add the keys twice
+        EventParser::new(log.as_bytes()).for_each(|e| history.handle(&e));
+
+        let records = load_history(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].roster, vec!["Stillnixx-Hyjal"]);
+        assert_eq!(records[0].score(), 125. + 150. + 30. + 7.5);
+        assert_eq!(records[1].score(), 125. + 180. + 40. - 15. - 7.5);
+
+        let report = history_report(&records);
+        let table = report.table("Stillnixx-Hyjal").unwrap();
+        assert_eq!(table.get(0, "Change"), Some(&Cell::Float(312.5, 1)));
+        // Only the best key in each dungeon counts
+        assert_eq!(table.get(1, "Change"), Some(&Cell::Float(10., 1)));
+        assert_eq!(table.get(1, "Score"), Some(&Cell::Float(322.5, 1)));
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::Result;
+use chrono::{Datelike, Local, NaiveDateTime};
+
+use crate::components::events::Event;
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+
+/// The game flushes the log every few seconds, so some lag is normal
+const WARN_SECS: f64 = 10.;
+
+/// How far behind real time the parser is when watching a log: the wall clock vs. the latest event parsed.
+/// Warns once it falls far enough behind that live numbers can't be trusted.
+#[derive(Debug)]
+pub struct LagMonitor {
+    latest: Option<NaiveDateTime>,
+    /// Seconds behind, as of the latest event
+    lag: Option<f64>,
+    warned: bool,
+    now: fn() -> NaiveDateTime,
+}
+
+impl LagMonitor {
+    pub fn new() -> Self {
+        Self { latest: None, lag: None, warned: false, now: || Local::now().naive_local() }
+    }
+
+    pub fn lag_secs(&self) -> Option<f64> {
+        self.lag
+    }
+}
+
+impl EventHandler for LagMonitor {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        if self.latest.is_some_and(|t| t >= event.timestamp) { return; }
+        self.latest = Some(event.timestamp);
+
+        // Log lines don't have a year, so they're compared as if written this year
+        let now = (self.now)();
+        let Some(timestamp) = event.timestamp.with_year(now.year()) else { return; };
+        let lag = ((now - timestamp).num_milliseconds() as f64 / 1000.).max(0.);
+        self.lag = Some(lag);
+
+        if lag > WARN_SECS && !self.warned {
+            eprintln!("Parsing is {:.0}s behind the game, live numbers are stale", lag);
+            self.warned = true;
+        } else if lag <= WARN_SECS {
+            self.warned = false;
+        }
+    }
+
+    fn display(&self) -> Option<String> {
+        self.lag.map(|lag| match lag > WARN_SECS {
+            true => format!("Lag: {:.1}s (behind!)", lag),
+            false => format!("Lag: {:.1}s", lag),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use crate::consumers::EventHandler;
+    use crate::consumers::lag::LagMonitor;
+    use crate::parser::EventParser;
+
+    #[test]
+    fn lag() {
+        let log = "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 14:01:50.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,0,45000\n";
+
+        let mut monitor = LagMonitor::new();
+        monitor.now = || NaiveDateTime::parse_from_str("2031-04-06 14:02:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let mut events = EventParser::new(log.as_bytes());
+
+        monitor.handle(&events.next().unwrap());
+        assert_eq!(monitor.lag_secs(), Some(55.));
+        assert_eq!(monitor.display().unwrap(), "Lag: 55.0s (behind!)");
+
+        monitor.handle(&events.next().unwrap());
+        assert_eq!(monitor.lag_secs(), Some(10.));
+        assert_eq!(monitor.display().unwrap(), "Lag: 10.0s");
+    }
+}
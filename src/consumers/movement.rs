@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::{EncounterEnd, EncounterStart};
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+/// Moving this many yards between two positions counts as moving rather than standing still
+const MIN_MOVE_YARDS: f64 = 0.5;
+/// Gaps between positions longer than this aren't counted as moving or standing, eg. while dead
+const MAX_GAP_SECS: f64 = 5.;
+/// The boss's position is used for range for this long after it was seen
+const BOSS_POSITION_SECS: f64 = 3.;
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    time: NaiveDateTime,
+    x: f64,
+    y: f64,
+    map: u64,
+}
+
+impl Sample {
+    fn distance(&self, other: &Self) -> Option<f64> {
+        (self.map == other.map).then(|| (self.x - other.x).hypot(self.y - other.y))
+    }
+
+    fn secs_since(&self, other: &Self) -> f64 {
+        (self.time - other.time).num_milliseconds() as f64 / 1000.
+    }
+}
+
+#[derive(Debug, Default)]
+struct Movement {
+    name: String,
+    last: Option<Sample>,
+    distance: f64,
+    moving_secs: f64,
+    standing_secs: f64,
+    range_total: f64,
+    range_samples: u64,
+}
+
+impl Movement {
+    fn moving_pct(&self) -> f64 {
+        let total = self.moving_secs + self.standing_secs;
+        if total == 0. { 0. } else { 100. * self.moving_secs / total }
+    }
+
+    fn average_range(&self) -> Option<f64> {
+        (self.range_samples > 0).then(|| self.range_total / self.range_samples as f64)
+    }
+}
+
+/// How much each player moved over the current encounter: distance covered, time spent moving vs standing still,
+/// & their average range to the boss, taken to be the hostile unit with the most health.
+/// Positions come from the advanced params of the events the player is the subject of.
+#[derive(Debug, Default)]
+pub struct MovementTracker {
+    players: HashMap<GUID, Movement>,
+    /// GUID, max health & last position of the boss
+    boss: Option<(GUID, u64, Sample)>,
+    in_encounter: bool,
+}
+
+impl MovementTracker {
+    pub fn new() -> Self { Self::default() }
+
+    fn update_player(&mut self, guid: &GUID, name: &str, sample: Sample) {
+        let boss = self.boss.as_ref()
+            .map(|(_, _, b)| *b)
+            .filter(|b| sample.secs_since(b) <= BOSS_POSITION_SECS);
+
+        let player = self.players.entry(guid.clone()).or_default();
+        if player.name.is_empty() { player.name = name.to_string(); }
+
+        if let Some(last) = player.last {
+            let secs = sample.secs_since(&last);
+            match sample.distance(&last) {
+                Some(d) if secs <= MAX_GAP_SECS && d >= MIN_MOVE_YARDS => {
+                    player.distance += d;
+                    player.moving_secs += secs;
+                }
+                Some(_) if secs <= MAX_GAP_SECS => player.standing_secs += secs,
+                _ => {}
+            }
+        }
+        if let Some(range) = boss.and_then(|b| sample.distance(&b)) {
+            player.range_total += range;
+            player.range_samples += 1;
+        }
+        player.last = Some(sample);
+    }
+}
+
+impl EventHandler for MovementTracker {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        if !self.in_encounter { return; }
+        let EventType::Standard { source, target, advanced_params: Some(params), .. } = &event.event_type else { return; };
+        let Some(guid) = &params.info_guid else { return; };
+        let Some(unit) = [source, target].into_iter().flatten().find(|a| &a.guid == guid) else { return; };
+
+        let sample = Sample {
+            time: event.timestamp,
+            x: params.position.x as f64,
+            y: params.position.y as f64,
+            map: params.ui_map_id,
+        };
+
+        if matches!(guid, GUID::Player { .. }) {
+            self.update_player(guid, &unit.name, sample);
+        } else if unit.is_hostile() && !unit.is_player_controlled() {
+            match &mut self.boss {
+                Some((boss, _, position)) if boss == guid => *position = sample,
+                Some((_, max_hp, _)) if *max_hp >= params.max_hp => {}
+                _ => self.boss = Some((guid.clone(), params.max_hp, sample)),
+            }
+        }
+    }
+
+    fn on_encounter_start(&mut self, _encounter: &EncounterStart) {
+        self.players.clear();
+        self.boss = None;
+        self.in_encounter = true;
+    }
+
+    fn on_encounter_end(&mut self, _encounter: &EncounterEnd) {
+        self.in_encounter = false;
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let mut table = Table::new(&[("Player", 30), ("Distance", 9), ("Moving", 8), ("Standing", 8), ("Moving %", 8), ("Avg Range", 9)]);
+        self.players.values()
+            .sorted_by(|a, b| b.distance.total_cmp(&a.distance).then(a.name.cmp(&b.name)))
+            .for_each(|p| {
+                table.push(vec![
+                    p.name.as_str().into(), Cell::Float(p.distance, 0), Cell::Duration(p.moving_secs), Cell::Duration(p.standing_secs),
+                    Cell::Percent(p.moving_pct()), p.average_range().map_or(Cell::Empty, |r| Cell::Float(r, 1)),
+                ]);
+            });
+
+        Some(Summary::new("Movement").with_table(table))
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.players.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::{dispatch, EventHandler};
+    use crate::consumers::movement::MovementTracker;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn distance_and_range() {
+        let cast = |time: &str, x: f64, y: f64| format!("4/6 14:01:{}  SPELL_CAST_SUCCESS,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,0000000000000000,nil,0x80000000,0x80000000,1680,\"Whirlwind\",0x1,Player-1390-0C4E032E,0000000000000000,100,100,0,0,0,0,0,0,0,0,{:.2},{:.2},2232,0.0000,70\n", time, x, y);
+        let log = [
+            "4/6 14:01:00.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n".to_string(),
+            "4/6 14:01:01.000  SPELL_DAMAGE,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,1680,\"Whirlwind\",0x1,Creature-0-1469-2549-12530-209333-000011428A,0000000000000000,900,1000,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,100,100,-1,1,0,0,0,nil,nil,nil\n".to_string(),
+            cast("01.000", 0., 10.),
+            cast("02.000", 0., 10.),
+            cast("03.000", 0., 20.),
+            cast("05.000", 3., 24.),
+            "4/6 14:01:10.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,1,10000\n".to_string(),
+        ].concat();
+
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(MovementTracker::new())];
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        let summary = handlers[0].summary().unwrap();
+        let row = summary.tables[0].find("Stillnixx-Hyjal").unwrap();
+        assert_eq!(row[1], Cell::Float(15., 0));
+        assert_eq!(row[2], Cell::Duration(3.));
+        assert_eq!(row[3], Cell::Duration(1.));
+        // 10, 10 & 20 yards from the boss, then its position is too old
+        assert_eq!(row[5], Cell::Float(40. / 3., 1));
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime};
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::Special;
+use crate::components::suffixes::Suffix;
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+/// Timer penalty per death before The War Within
+const DEATH_PENALTY_SECS: i64 = 5;
+/// Timer penalty per death from The War Within (11.x) onwards
+const DEATH_PENALTY_SECS_TWW: i64 = 15;
+
+/// Death penalty for a game build, eg. "11.0.2"
+fn death_penalty_for(build_version: &str) -> i64 {
+    let major = build_version.split('.').next()
+        .and_then(|m| m.parse::<u64>().ok())
+        .unwrap_or_default();
+
+    if major >= 11 { DEATH_PENALTY_SECS_TWW } else { DEATH_PENALTY_SECS }
+}
+
+/// A boss pull within a run
+#[derive(Debug)]
+struct Split {
+    encounter_name: String,
+    pulled: NaiveDateTime,
+    /// When the pull ended & whether the boss died
+    ended: Option<(NaiveDateTime, bool)>,
+}
+
+#[derive(Debug)]
+struct Run {
+    zone_name: String,
+    keystone_level: u64,
+    start: NaiveDateTime,
+    /// Total time in ms & whether it was timed, once finished
+    result: Option<(u64, bool)>,
+    deaths: Vec<NaiveDateTime>,
+    penalty_per_death: i64,
+    /// Time between each death & the player's next cast
+    running_back: Duration,
+    bosses: Vec<Split>,
+}
+
+impl Run {
+    fn penalty(&self) -> Duration {
+        Duration::seconds(self.deaths.len() as i64 * self.penalty_per_death)
+    }
+
+    fn since_start(&self, time: NaiveDateTime) -> Cell {
+        Cell::Duration((time - self.start).num_milliseconds() as f64 / 1000.)
+    }
+
+    /// Per boss pull: when it was pulled & ended relative to the key starting, and the deaths since the previous pull ended
+    fn splits(&self) -> Table {
+        let mut table = Table::new(&[("Boss", 30), ("Pulled", 8), ("Split", 8), ("Fight", 8), ("Result", 8), ("Deaths", 7)])
+            .with_title(format!("{} +{}", self.zone_name, self.keystone_level));
+
+        let mut previous = self.start;
+        for boss in &self.bosses {
+            let (split, fight, result, deaths) = match boss.ended {
+                Some((ended, success)) => {
+                    let deaths = self.deaths.iter().filter(|&&t| t > previous && t <= ended).count();
+                    previous = ended;
+                    (
+                        self.since_start(ended),
+                        Cell::Duration((ended - boss.pulled).num_milliseconds() as f64 / 1000.),
+                        if success { "Kill" } else { "Wipe" },
+                        Cell::from(deaths),
+                    )
+                }
+                None => (Cell::Empty, Cell::Empty, "", Cell::Empty),
+            };
+            table.push(vec![boss.encounter_name.as_str().into(), self.since_start(boss.pulled), split, fight, result.into(), deaths]);
+        }
+
+        table
+    }
+}
+
+/// Mythic+ dungeon reports: per boss split times, whether the key was timed, and the time lost to deaths:
+/// the timer penalty for each death, plus the time each dead player took to get back into action
+/// (from their death to their next cast).
+#[derive(Debug, Default)]
+pub struct MythicPlusRuns {
+    runs: Vec<Run>,
+    in_run: bool,
+    /// Overrides the per death penalty picked from the game version
+    penalty_override: Option<i64>,
+    build_version: String,
+    /// Dead players & when they died
+    dead: HashMap<GUID, NaiveDateTime>,
+}
+
+impl MythicPlusRuns {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn with_death_penalty(mut self, secs: Option<i64>) -> Self {
+        self.penalty_override = secs;
+        self
+    }
+}
+
+impl EventHandler for MythicPlusRuns {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+
+        match &event.event_type {
+            EventType::Special { details, .. } => match details {
+                Special::CombatLogInfo { build_version, .. } => {
+                    self.build_version.clone_from(build_version);
+                }
+                Special::ChallengeModeStart { zone_name, keystone_level, .. } => {
+                    self.runs.push(Run {
+                        zone_name: zone_name.clone(),
+                        keystone_level: *keystone_level,
+                        start: event.timestamp,
+                        result: None,
+                        deaths: vec![],
+                        penalty_per_death: self.penalty_override.unwrap_or_else(|| death_penalty_for(&self.build_version)),
+                        running_back: Duration::zero(),
+                        bosses: vec![],
+                    });
+                    self.in_run = true;
+                    self.dead.clear();
+                }
+                Special::ChallengeModeEnd { success, total_time, .. } => {
+                    if let Some(run) = self.runs.last_mut().filter(|_| self.in_run) {
+                        run.result = Some((*total_time, *success));
+                    }
+                    self.in_run = false;
+                    self.dead.clear();
+                }
+                Special::EncounterStart(encounter) if self.in_run => {
+                    if let Some(run) = self.runs.last_mut() {
+                        run.bosses.push(Split { encounter_name: encounter.encounter_name.clone(), pulled: event.timestamp, ended: None });
+                    }
+                }
+                Special::EncounterEnd(encounter) if self.in_run => {
+                    if let Some(boss) = self.runs.last_mut().and_then(|r| r.bosses.last_mut()) {
+                        boss.ended = Some((event.timestamp, encounter.success));
+                    }
+                }
+                Special::UnitDied { target: Some(target), .. } if self.in_run => {
+                    if !matches!(target.guid, GUID::Player { .. }) { return; }
+                    if let Some(run) = self.runs.last_mut() { run.deaths.push(event.timestamp); }
+                    self.dead.insert(target.guid.clone(), event.timestamp);
+                }
+                _ => {}
+            },
+            EventType::Standard { source: Some(source), suffix: Suffix::CastSuccess, .. } if self.in_run => {
+                let Some(died) = self.dead.remove(&source.guid) else { return; };
+                if let Some(run) = self.runs.last_mut() { run.running_back += event.timestamp - died; }
+            }
+            _ => {}
+        }
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let mut table = Table::new(&[("Dungeon", 30), ("Level", 5), ("Time", 8), ("Result", 12), ("Deaths", 7),
+            ("Penalty", 8), ("Run back", 8), ("Lost", 8)]);
+
+        for run in &self.runs {
+            let (time, result) = match run.result {
+                Some((ms, timed)) => (Cell::Duration(ms as f64 / 1000.), if timed { "Timed" } else { "Depleted" }),
+                None => (Cell::Empty, "In progress"),
+            };
+            let lost = run.penalty() + run.running_back;
+            let secs = |d: Duration| Cell::Duration(d.num_milliseconds() as f64 / 1000.);
+
+            table.push(vec![
+                run.zone_name.as_str().into(), run.keystone_level.into(), time, result.into(), run.deaths.len().into(),
+                secs(run.penalty()), secs(run.running_back), secs(lost),
+            ]);
+        }
+
+        let splits = self.runs.iter().map(Run::splits);
+        Some(splits.fold(Summary::new("Mythic+ runs").with_table(table), Summary::with_table))
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.dead.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::EventHandler;
+    use crate::consumers::mythic::{death_penalty_for, MythicPlusRuns};
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    const LOG: &str = "4/6 14:00:00.000  COMBAT_LOG_VERSION,20,ADVANCED_LOG_ENABLED,1,BUILD_VERSION,11.0.2,PROJECT_ID,1\n\
+4/6 14:00:01.000  CHALLENGE_MODE_START,\"The Dawnbreaker\",2662,505,10,[10,109,148]\n\
+4/6 14:05:00.000  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,0\n\
+4/6 14:05:40.000  SPELL_CAST_SUCCESS,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,0000000000000000,nil,0x80000000,0x80000000,1680,\"Whirlwind\",0x1,Player-1390-0C4E032E,0000000000000000,0,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70\n\
+4/6 14:06:00.000  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,0\n\
+4/6 14:30:01.000  CHALLENGE_MODE_END,2662,1,10,1800000\n";
+
+    #[test]
+    fn time_lost() {
+        let mut runs = MythicPlusRuns::new();
+        EventParser::new(LOG.as_bytes()).for_each(|e| runs.handle(&e));
+
+        let summary = runs.summary().unwrap();
+        assert_eq!(summary.tables[0].find("The Dawnbreaker").unwrap(), [
+            "The Dawnbreaker".into(), Cell::Int(10), Cell::Duration(1800.), "Timed".into(), Cell::Int(1),
+            Cell::Duration(15.), Cell::Duration(40.), Cell::Duration(55.),
+        ]);
+
+        let display = runs.display().unwrap();
+        let row = format!("{:>30}:{:>5}|{:>8}|{:>12}|{:>7}|{:>8}|{:>8}|{:>8}",
+                          "The Dawnbreaker", 10, "30:00", "Timed", 1, "0:15", "0:40", "0:55");
+        assert!(display.contains(&row), "{}", display);
+    }
+
+    #[test]
+    fn boss_splits() {
+        let log = "4/6 14:00:00.000  COMBAT_LOG_VERSION,20,ADVANCED_LOG_ENABLED,1,BUILD_VERSION,11.0.2,PROJECT_ID,1\n\
+4/6 14:00:01.000  CHALLENGE_MODE_START,\"The Dawnbreaker\",2662,505,10,[10,109,148]\n\
+4/6 14:03:01.000  ENCOUNTER_START,2837,\"Speaker Shadowcrown\",8,5,2662\n\
+4/6 14:04:00.000  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,0\n\
+4/6 14:05:01.000  ENCOUNTER_END,2837,\"Speaker Shadowcrown\",8,5,1,120000\n\
+4/6 14:10:00.000  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,0\n\
+4/6 14:12:01.000  ENCOUNTER_START,2838,\"Anub'ikkaj\",8,5,2662\n\
+4/6 14:14:01.000  ENCOUNTER_END,2838,\"Anub'ikkaj\",8,5,0,120000\n\
+4/6 14:15:01.000  ENCOUNTER_START,2838,\"Anub'ikkaj\",8,5,2662\n\
+4/6 14:16:31.000  ENCOUNTER_END,2838,\"Anub'ikkaj\",8,5,1,90000\n";
+
+        let mut runs = MythicPlusRuns::new();
+        EventParser::new(log.as_bytes()).for_each(|e| runs.handle(&e));
+
+        let summary = runs.summary().unwrap();
+        let splits = summary.table("The Dawnbreaker +10").unwrap();
+        assert_eq!(splits.rows, vec![
+            vec!["Speaker Shadowcrown".into(), Cell::Duration(180.), Cell::Duration(300.), Cell::Duration(120.), "Kill".into(), Cell::Int(1)],
+            vec!["Anub'ikkaj".into(), Cell::Duration(720.), Cell::Duration(840.), Cell::Duration(120.), "Wipe".into(), Cell::Int(1)],
+            vec!["Anub'ikkaj".into(), Cell::Duration(900.), Cell::Duration(990.), Cell::Duration(90.), "Kill".into(), Cell::Int(0)],
+        ]);
+        assert_eq!(summary.tables[0].get(0, "Result"), Some(&"In progress".into()));
+    }
+
+    #[test]
+    fn penalty_override() {
+        let mut runs = MythicPlusRuns::new().with_death_penalty(Some(5));
+        EventParser::new(LOG.as_bytes()).for_each(|e| runs.handle(&e));
+        let table = &runs.summary().unwrap().tables[0];
+        assert_eq!(table.get(0, "Penalty"), Some(&Cell::Duration(5.)));
+        assert_eq!(table.get(0, "Lost"), Some(&Cell::Duration(45.)));
+    }
+
+    #[test]
+    fn penalty_by_build() {
+        assert_eq!(death_penalty_for("10.2.6"), 5);
+        assert_eq!(death_penalty_for("11.0.2"), 15);
+    }
+}
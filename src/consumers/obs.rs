@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use chrono::{Duration, NaiveDateTime};
+use itertools::Itertools;
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::{EncounterEnd, EncounterStart};
+use crate::components::suffixes::Suffix;
+use crate::consumers::EventHandler;
+use crate::consumers::ownership::OwnershipResolver;
+use crate::error::ParseFailure;
+
+/// Text of each file, with `{placeholders}` filled in
+#[derive(Debug, Clone)]
+pub struct ObsTemplates {
+    /// One line of the leaderboard: `{rank}`, `{name}`, `{dps}` & `{damage}`
+    pub row: String,
+    /// The followed player: `{name}`, `{dps}` & `{damage}`
+    pub personal: String,
+    /// `{boss}` & `{health}` as a percentage
+    pub boss: String,
+}
+
+impl Default for ObsTemplates {
+    fn default() -> Self {
+        Self {
+            row: "{rank}. {name} {dps}".to_string(),
+            personal: "{name}: {dps} DPS".to_string(),
+            boss: "{boss} {health}%".to_string(),
+        }
+    }
+}
+
+fn fill(template: &str, values: &[(&str, String)]) -> String {
+    values.iter().fold(template.to_string(), |text, (key, value)| text.replace(&format!("{{{}}}", key), value))
+}
+
+/// The unit with the most health seen in the pull, taken to be the boss
+#[derive(Debug)]
+struct Boss {
+    guid: GUID,
+    name: String,
+    current_hp: u64,
+    max_hp: u64,
+}
+
+/// Keeps small text files up to date for OBS text sources to point at: `leaderboard.txt` with the top damage
+/// dealers of the current pull, `personal.txt` with one player's DPS, & `boss.txt` with the boss's health.
+/// Files are rewritten at most once per second of log time, & replaced whole so OBS never reads half a file.
+#[derive(Debug)]
+pub struct ObsOverlay {
+    dir: PathBuf,
+    templates: ObsTemplates,
+    /// Player to write `personal.txt` for, eg. "Sønike" or "Sønike-Ysondre"
+    player: Option<String>,
+    top: usize,
+    owners: OwnershipResolver,
+    damage: HashMap<String, i64>,
+    start: Option<NaiveDateTime>,
+    latest: Option<NaiveDateTime>,
+    written: Option<NaiveDateTime>,
+    in_encounter: bool,
+    boss: Option<Boss>,
+}
+
+impl ObsOverlay {
+    pub fn new(dir: PathBuf, templates: ObsTemplates, player: Option<String>, top: usize) -> Self {
+        Self {
+            dir,
+            templates,
+            player,
+            top,
+            owners: OwnershipResolver::new(),
+            damage: HashMap::new(),
+            start: None,
+            latest: None,
+            written: None,
+            in_encounter: false,
+            boss: None,
+        }
+    }
+
+    fn dps(&self, damage: i64) -> f64 {
+        let secs = match (self.start, self.latest) {
+            (Some(start), Some(latest)) => (latest - start).num_milliseconds() as f64 / 1000.,
+            _ => 0.,
+        };
+        damage as f64 / secs.max(1.)
+    }
+
+    fn is_player(&self, name: &str) -> bool {
+        let Some(player) = &self.player else { return false; };
+        let player = player.to_lowercase();
+        let name = name.to_lowercase();
+        name == player || name.split_once('-').is_some_and(|(name, _)| name == player)
+    }
+
+    fn leaderboard(&self) -> String {
+        self.damage.iter()
+            .sorted_by_key(|(name, &damage)| (-damage, (*name).clone()))
+            .take(self.top)
+            .enumerate()
+            .map(|(i, (name, &damage))| fill(&self.templates.row, &[
+                ("rank", (i + 1).to_string()),
+                ("name", name.clone()),
+                ("dps", format!("{:.0}", self.dps(damage))),
+                ("damage", damage.to_string()),
+            ]))
+            .join("\n")
+    }
+
+    fn personal(&self) -> String {
+        let (name, damage) = self.damage.iter()
+            .find(|(name, _)| self.is_player(name))
+            .map_or((self.player.clone().unwrap_or_default(), 0), |(name, &damage)| (name.clone(), damage));
+
+        fill(&self.templates.personal, &[
+            ("name", name),
+            ("dps", format!("{:.0}", self.dps(damage))),
+            ("damage", damage.to_string()),
+        ])
+    }
+
+    fn boss_health(&self) -> String {
+        let Some(boss) = &self.boss else { return String::new(); };
+        let health = 100. * boss.current_hp as f64 / boss.max_hp.max(1) as f64;
+        fill(&self.templates.boss, &[("boss", boss.name.clone()), ("health", format!("{:.1}", health))])
+    }
+
+    fn write(&mut self) {
+        self.written = self.latest;
+
+        let mut files = vec![("leaderboard.txt", self.leaderboard()), ("boss.txt", self.boss_health())];
+        if self.player.is_some() {
+            files.push(("personal.txt", self.personal()));
+        }
+
+        for (name, text) in files {
+            if let Err(e) = self.replace(name, &text) { eprintln!("{e:#}"); }
+        }
+    }
+
+    /// Writes a file alongside & renames it over the old one
+    fn replace(&self, name: &str, text: &str) -> Result<()> {
+        let path = self.dir.join(name);
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, text)
+            .with_context(|| format!("Failed to write file: {:?}", tmp))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("Failed to replace file: {:?}", path))
+    }
+
+    fn update_boss(&mut self, event: &Event) {
+        let EventType::Standard { source, target, advanced_params: Some(params), .. } = &event.event_type else { return; };
+        let Some(guid) = &params.info_guid else { return; };
+        let Some(unit) = [source, target].into_iter().flatten().find(|a| &a.guid == guid) else { return; };
+        if !unit.is_hostile() || unit.is_player_controlled() { return; }
+
+        match &mut self.boss {
+            Some(boss) if &boss.guid == guid => boss.current_hp = params.current_hp,
+            Some(boss) if boss.max_hp >= params.max_hp => {}
+            _ => self.boss = Some(Boss { guid: guid.clone(), name: unit.name.clone(), current_hp: params.current_hp, max_hp: params.max_hp }),
+        }
+    }
+}
+
+impl EventHandler for ObsOverlay {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        self.latest = Some(event.timestamp);
+        self.start.get_or_insert(event.timestamp);
+        self.owners.update(event);
+
+        if let EventType::Standard { source: Some(source), suffix: Suffix::Damage { amount, .. }, .. } = &event.event_type {
+            if let Some((_, name)) = self.owners.resolve_player(source) {
+                *self.damage.entry(name).or_default() += amount;
+            }
+        }
+        if self.in_encounter {
+            self.update_boss(event);
+        }
+
+        if self.written.is_none_or(|w| event.timestamp - w >= Duration::seconds(1)) {
+            self.write();
+        }
+    }
+
+    fn display(&self) -> Option<String> {
+        None
+    }
+
+    fn on_start(&mut self) {
+        if let Err(e) = std::fs::create_dir_all(&self.dir) {
+            eprintln!("Failed to create directory {:?}: {}", self.dir, e);
+        }
+    }
+
+    fn on_encounter_start(&mut self, _encounter: &EncounterStart) {
+        self.in_encounter = true;
+        self.damage.clear();
+        self.start = None;
+        self.boss = None;
+    }
+
+    /// The final numbers stay up until the next pull
+    fn on_encounter_end(&mut self, _encounter: &EncounterEnd) {
+        self.in_encounter = false;
+        self.write();
+    }
+
+    fn on_finish(&mut self) {
+        self.write();
+    }
+
+    fn prune(&mut self, cutoff: NaiveDateTime) {
+        self.owners.prune(cutoff);
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.damage.len() + self.owners.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::{dispatch, EventHandler};
+    use crate::consumers::obs::{ObsOverlay, ObsTemplates};
+    use crate::parser::EventParser;
+
+    #[test]
+    fn overlay_files() {
+        let log = "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 14:01:15.000  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Creature-0-1469-2549-12530-209333-000011428A,0000000000000000,750,1000,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,1000,1000,-1,1,0,0,0,nil,nil,nil\n\
+4/6 14:01:15.000  SWING_DAMAGE,Player-1335-0A264B4D,\"Other-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Creature-0-1469-2549-12530-209333-000011428A,0000000000000000,500,1000,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,500,500,-1,1,0,0,0,nil,nil,nil\n\
+4/6 14:01:25.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,1,20000\n";
+
+        let dir = std::env::temp_dir().join("wowlogs_obs");
+        let _ = std::fs::remove_dir_all(&dir);
+        let templates = ObsTemplates { personal: "{dps}".to_string(), ..Default::default() };
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(ObsOverlay::new(dir.clone(), templates, Some("sønike".to_string()), 5))];
+        handlers.iter_mut().for_each(|h| h.on_start());
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("leaderboard.txt"), "1. Sønike-Ysondre 50\n2. Other-Ysondre 25");
+        assert_eq!(read("personal.txt"), "50");
+        assert_eq!(read("boss.txt"), "Gnarlroot 50.0%");
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::EncounterStart;
+use crate::components::suffixes::Suffix;
+use crate::consumers::EventHandler;
+use crate::consumers::ownership::OwnershipResolver;
+use crate::consumers::pruning::LastSeen;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+#[derive(Debug, Default)]
+struct HealerTotals {
+    healing: u64,
+    overhealing: u64,
+    /// Damage prevented by the healer's shields
+    absorbed: u64,
+    /// Size of the shields put up
+    shielded: u64,
+    /// Left on shields when they fell off
+    expired: u64,
+}
+
+impl HealerTotals {
+    fn effective(&self) -> u64 { self.healing - self.overhealing + self.absorbed }
+
+    fn overheal_pct(&self) -> f64 {
+        if self.healing == 0 { 0. } else { 100. * self.overhealing as f64 / self.healing as f64 }
+    }
+
+    fn shield_waste_pct(&self) -> f64 {
+        if self.shielded == 0 { 0. } else { 100. * self.expired as f64 / self.shielded as f64 }
+    }
+}
+
+/// Caster, target & spell ID of a shield
+type ShieldKey = (GUID, GUID, u64);
+
+/// Healing efficiency per healer: how much was overhealing, how much damage their shields absorbed,
+/// & how much of their shields expired unused. Shields are the auras applied with an amount, and whatever
+/// amount is left when they're removed was wasted. Ranked by effective healing, which includes absorbs.
+#[derive(Debug, Default)]
+pub struct OverhealAnalysis {
+    healers: HashMap<String, HealerTotals>,
+    /// Shields currently up, to tell shields apart from other auras with amounts
+    shields: HashMap<ShieldKey, String>,
+    owners: OwnershipResolver,
+    last_seen: LastSeen<String>,
+}
+
+impl OverhealAnalysis {
+    pub fn new() -> Self { Self::default() }
+}
+
+impl EventHandler for OverhealAnalysis {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        self.owners.update(event);
+
+        let EventType::Standard { source, target, prefix, suffix, .. } = &event.event_type else { return; };
+
+        // Absorbs credit the shield's caster, rather than the source of the event
+        let caster = match suffix {
+            Suffix::Absorbed { absorb_caster, .. } | Suffix::AbsorbedSupport { absorb_caster, .. } => Some(absorb_caster),
+            _ => source.as_ref(),
+        };
+        let Some((_, healer)) = caster.and_then(|c| self.owners.resolve_player(c)) else { return; };
+        let shield_key = |spell_id| Some((caster?.guid.clone(), target.as_ref()?.guid.clone(), spell_id));
+        let spell_id = prefix.spell_info().map(|s| s.spell_id);
+
+        match suffix {
+            Suffix::Heal { amount, overhealing, .. } => {
+                let totals = self.healers.entry(healer.clone()).or_default();
+                totals.healing += amount;
+                totals.overhealing += overhealing;
+            }
+            Suffix::Absorbed { absorbed_amount, .. } | Suffix::AbsorbedSupport { absorbed_amount, .. } => {
+                self.healers.entry(healer.clone()).or_default().absorbed += (*absorbed_amount).max(0) as u64;
+            }
+            Suffix::AuraApplied { amount: Some(amount), .. } if *amount > 0 => {
+                let Some(key) = spell_id.and_then(shield_key) else { return; };
+                self.healers.entry(healer.clone()).or_default().shielded += amount;
+                self.shields.insert(key, healer.clone());
+            }
+            Suffix::AuraRemoved { amount, .. } => {
+                let Some(key) = spell_id.and_then(shield_key) else { return; };
+                let Some(owner) = self.shields.remove(&key) else { return; };
+                self.healers.entry(owner).or_default().expired += amount.unwrap_or(0);
+            }
+            _ => return,
+        }
+        self.last_seen.touch(&healer, event.timestamp);
+    }
+
+    fn on_encounter_start(&mut self, _encounter: &EncounterStart) {
+        self.healers.clear();
+        self.shields.clear();
+        self.last_seen.clear();
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let mut table = Table::new(&[
+            ("Rank", 5), ("Healer", 30), ("Effective", 10), ("Healing", 10), ("Overheal", 9),
+            ("Absorbed", 10), ("Shields", 10), ("Expired", 10), ("Shield Waste", 13),
+        ]);
+        self.healers.iter()
+            .filter(|(_, t)| t.effective() > 0 || t.shielded > 0)
+            .sorted_by_key(|(healer, t)| (std::cmp::Reverse(t.effective()), (*healer).clone()))
+            .enumerate()
+            .for_each(|(i, (healer, t))| {
+                table.push(vec![
+                    (i + 1).into(), healer.as_str().into(), t.effective().into(), t.healing.into(), Cell::Percent(t.overheal_pct()),
+                    t.absorbed.into(), t.shielded.into(), t.expired.into(), Cell::Percent(t.shield_waste_pct()),
+                ]);
+            });
+
+        Some(Summary::new("Overhealing").with_table(table))
+    }
+
+    fn prune(&mut self, cutoff: NaiveDateTime) {
+        for healer in self.last_seen.expire(cutoff) {
+            self.healers.remove(&healer);
+            self.shields.retain(|_, h| *h != healer);
+        }
+        self.owners.prune(cutoff);
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.last_seen.len() + self.owners.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::EventHandler;
+    use crate::consumers::overheal::OverhealAnalysis;
+    use crate::parser::EventParser;
+
+    #[test]
+    fn shields_and_overheal() {
+        let log = "4/6 14:09:40.000  SPELL_AURA_APPLIED,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,17,\"Power Word: Shield\",0x2,BUFF,10000\n\
+4/6 14:09:41.000  SPELL_ABSORBED,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,17,\"Power Word: Shield\",0x2,4000,5000,nil\n\
+4/6 14:09:45.000  SPELL_AURA_REMOVED,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,17,\"Power Word: Shield\",0x2,BUFF,6000\n\
+4/6 14:09:46.100  SPELL_HEAL,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,8936,\"Regrowth\",0x8,Player-1393-077C088C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,1000,1000,250,0,nil\n\
+4/6 14:09:47.000  SPELL_AURA_REMOVED,Player-1393-077C088C,\"Mubaku-BronzeDragonflight\",0x514,0x0,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,1126,\"Mark of the Wild\",0x8,BUFF,5\n";
+
+        let mut tracker = OverhealAnalysis::new();
+        EventParser::new(log.as_bytes()).for_each(|e| tracker.handle(&e));
+
+        let totals = &tracker.healers["Mubaku-BronzeDragonflight"];
+        assert_eq!((totals.shielded, totals.absorbed, totals.expired), (10000, 4000, 6000));
+        assert_eq!(totals.shield_waste_pct(), 60.);
+        assert_eq!(totals.overheal_pct(), 25.);
+        assert_eq!(totals.effective(), 4750);
+        assert!(tracker.shields.is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+
+use crate::components::common::Actor;
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::suffixes::Suffix;
+use crate::consumers::pruning::LastSeen;
+
+/// Maps pet & guardian GUIDs to the player that owns them.
+/// Ownership is learned from SPELL_SUMMON events and from the owner GUID in advanced params.
+#[derive(Debug, Default)]
+pub struct OwnershipResolver {
+    owners: HashMap<GUID, GUID>,
+    player_names: HashMap<GUID, String>,
+    last_seen: LastSeen<GUID>,
+}
+
+impl OwnershipResolver {
+    pub fn new() -> Self { Self::default() }
+
+    /// Learns any ownership / naming info contained in the event
+    pub fn update(&mut self, event: &Event) {
+        let EventType::Standard { source, target, advanced_params, suffix, .. } = &event.event_type
+            else { return; };
+
+        for actor in [source, target].into_iter().flatten() {
+            if let GUID::Player { .. } = actor.guid {
+                if !self.player_names.contains_key(&actor.guid) {
+                    self.player_names.insert(actor.guid.clone(), actor.name.clone());
+                }
+                self.last_seen.touch(&actor.guid, event.timestamp);
+            } else if self.owners.contains_key(&actor.guid) {
+                self.last_seen.touch(&actor.guid, event.timestamp);
+            }
+        }
+
+        // Summoner -> summoned unit
+        if let (Suffix::Summon, Some(owner), Some(pet)) = (suffix, source, target) {
+            self.owners.insert(pet.guid.clone(), owner.guid.clone());
+            self.last_seen.touch(&pet.guid, event.timestamp);
+        }
+
+        // Advanced params carry the owner of the unit they describe
+        if let Some(params) = advanced_params {
+            if let (Some(unit), Some(owner)) = (&params.info_guid, &params.owner_guid) {
+                if unit != owner {
+                    self.owners.insert(unit.clone(), owner.clone());
+                    self.last_seen.touch(unit, event.timestamp);
+                }
+            }
+        }
+    }
+
+    /// Follows the ownership chain (eg. a totem summoned by a pet) up to the top-most owner
+    pub fn owner_of(&self, guid: &GUID) -> Option<&GUID> {
+        let mut current = self.owners.get(guid)?;
+
+        // Bounded to guard against cycles from bad data
+        for _ in 0..8 {
+            match self.owners.get(current) {
+                Some(next) if next != guid => current = next,
+                _ => break,
+            }
+        }
+
+        Some(current)
+    }
+
+    /// Forgets units not seen since the cutoff
+    pub fn prune(&mut self, cutoff: NaiveDateTime) {
+        for guid in self.last_seen.expire(cutoff) {
+            self.owners.remove(&guid);
+            self.player_names.remove(&guid);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.owners.len() + self.player_names.len()
+    }
+
+    /// Name of a player seen in any event
+    pub fn player_name(&self, guid: &GUID) -> Option<&str> {
+        self.player_names.get(guid).map(String::as_str)
+    }
+
+    /// Resolves an actor to the player responsible for it: itself if a player, otherwise its owner.
+    /// Owners not seen in any event yet are named by their GUID.
+    pub fn resolve_player(&self, actor: &Actor) -> Option<(GUID, String)> {
+        match &actor.guid {
+            GUID::Player { .. } => Some((actor.guid.clone(), actor.name.clone())),
+            guid => {
+                let owner = self.owner_of(guid).filter(|o| matches!(o, GUID::Player { .. }))?;
+                let name = self.player_names.get(owner).cloned().unwrap_or_else(|| owner.to_string());
+
+                Some((owner.clone(), name))
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::components::guid::GUID;
+    use crate::consumers::ownership::OwnershipResolver;
+    use crate::parser::EventParser;
+
+    #[test]
+    fn summon_and_advanced_owner() {
+        let log = "4/11 22:40:00.000  SPELL_SUMMON,Player-1329-09AF0ACF,\"Adamthebash-Ravencrest\",0x511,0x0,Creature-0-1465-2454-103-95072-000018584E,\"Greater Earth Elemental\",0xa28,0x0,198103,\"Earth Elemental\",0x8\n\
+4/11 22:40:01.000  SPELL_DAMAGE,Pet-0-1461-2548-10089-17252-01040EF8F7,\"Khil'arad\",0x1114,0x0,Creature-0-1469-2549-12091-204931-0000186743,\"Fyrakk\",0x10a48,0x0,54049,\"Shadow Bite\",0x20,Pet-0-1461-2548-10089-17252-01040EF8F7,Player-1329-0A0800FA,100,100,0,0,0,0,-1,0,0,0,0.00,0.00,2238,0.0000,70,500,500,-1,32,0,0,0,nil,nil,nil\n";
+
+        let mut resolver = OwnershipResolver::new();
+        EventParser::new(log.as_bytes())
+            .for_each(|e| resolver.update(&e.unwrap()));
+
+        let elemental = GUID::parse("Creature-0-1465-2454-103-95072-000018584E").unwrap().unwrap();
+        let shaman = GUID::parse("Player-1329-09AF0ACF").unwrap().unwrap();
+        assert_eq!(resolver.owner_of(&elemental), Some(&shaman));
+
+        let imp = GUID::parse("Pet-0-1461-2548-10089-17252-01040EF8F7").unwrap().unwrap();
+        let warlock = GUID::parse("Player-1329-0A0800FA").unwrap().unwrap();
+        assert_eq!(resolver.owner_of(&imp), Some(&warlock));
+    }
+}
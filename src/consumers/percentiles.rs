@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::utils::parse_num;
+
+/// Which kind of output a distribution describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Metric {
+    Dps,
+    Hps,
+}
+
+impl Metric {
+    fn parse(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "dps" => Ok(Self::Dps),
+            "hps" => Ok(Self::Hps),
+            _ => anyhow::bail!("Unknown metric: {:?}", s)
+        }
+    }
+}
+
+/// Per boss, per spec output distributions used to estimate roughly where a player's output ranks.
+/// Rows are `encounter_id,spec_id,metric,percentile,value`, eg. `2820,251,dps,50,412000`. None are bundled;
+/// they're loaded with `--percentile-reference`.
+#[derive(Debug, Default, Clone)]
+pub struct Percentiles {
+    /// (percentile, value) points, sorted by percentile
+    points: HashMap<(u64, u64, Metric), Vec<(f64, f64)>>,
+}
+
+impl Percentiles {
+    pub fn extend_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let s = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to open file: {:?}", path.as_ref()))?;
+        self.extend_from_str(&s)
+    }
+
+    /// Distributions in `s` replace any already loaded for the same boss, spec & metric
+    pub fn extend_from_str(&mut self, s: &str) -> Result<()> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .comment(Some(b'#'))
+            .trim(csv::Trim::All)
+            .from_reader(s.as_bytes());
+
+        let mut points: HashMap<_, Vec<(f64, f64)>> = HashMap::new();
+        for record in reader.records() {
+            let r = record?;
+            let field = |i| r.get(i).with_context(|| format!("Bad percentile row: {:?}", r));
+
+            let key = (parse_num(field(0)?)?, parse_num(field(1)?)?, Metric::parse(field(2)?)?);
+            let point = (parse_num(field(3)?)?, parse_num(field(4)?)?);
+            points.entry(key).or_default().push(point);
+        }
+
+        points.values_mut()
+            .for_each(|p| p.sort_by(|a, b| a.0.total_cmp(&b.0)));
+        self.points.extend(points);
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Linearly interpolated percentile of the value, None if there's no reference data
+    pub fn estimate(&self, encounter_id: u64, spec_id: u64, metric: Metric, value: f64) -> Option<f64> {
+        let points = self.points.get(&(encounter_id, spec_id, metric))?;
+
+        // Anchor the bottom of the distribution at zero output
+        let mut prev = (0., 0.);
+        for &(percentile, v) in points {
+            if value <= v {
+                let t = if v > prev.1 { (value - prev.1) / (v - prev.1) } else { 1. };
+                return Some(prev.0 + t * (percentile - prev.0));
+            }
+            prev = (percentile, v);
+        }
+
+        Some(prev.0)
+    }
+}
+
+/// eg. "~68th"
+pub fn format_percentile(p: Option<f64>) -> String {
+    let Some(p) = p else { return "-".to_string(); };
+    let n = p.round() as u64;
+
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+
+    format!("~{}{}", n, suffix)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::percentiles::{format_percentile, Metric, Percentiles};
+
+    #[test]
+    fn estimate() {
+        let mut p = Percentiles::default();
+        p.extend_from_str("# test data\n2820,251,dps,50,100\n2820,251,dps,90,200\n2820,251,dps,100,300").unwrap();
+
+        assert_eq!(p.estimate(2820, 251, Metric::Dps, 50.), Some(25.));
+        assert_eq!(p.estimate(2820, 251, Metric::Dps, 150.), Some(70.));
+        assert_eq!(p.estimate(2820, 251, Metric::Dps, 1000.), Some(100.));
+        assert_eq!(p.estimate(2820, 251, Metric::Hps, 150.), None);
+        assert_eq!(p.estimate(2820, 9999, Metric::Dps, 150.), None);
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(format_percentile(Some(68.2)), "~68th");
+        assert_eq!(format_percentile(Some(1.)), "~1st");
+        assert_eq!(format_percentile(Some(12.)), "~12th");
+        assert_eq!(format_percentile(Some(92.)), "~92nd");
+        assert_eq!(format_percentile(None), "-");
+    }
+}
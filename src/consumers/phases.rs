@@ -0,0 +1,161 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::Special;
+use crate::components::suffixes::Suffix;
+
+#[derive(Debug, Clone, Deserialize)]
+struct PhaseRule {
+    encounter_id: u64,
+    phase: usize,
+    name: Option<String>,
+    /// Spell ID the boss (or any hostile NPC) starts or finishes casting
+    cast: Option<u64>,
+    /// NPC ID of a unit dying
+    death: Option<u64>,
+}
+
+impl PhaseRule {
+    fn triggered_by(&self, event: &Event) -> bool {
+        match &event.event_type {
+            EventType::Standard { source: Some(source), prefix, suffix: Suffix::CastStart | Suffix::CastSuccess, .. } =>
+                self.cast.is_some() && source.is_hostile() && !source.is_player_controlled()
+                    && prefix.spell_info().map(|s| s.spell_id) == self.cast,
+            EventType::Special { details: Special::UnitDied { target: Some(target), .. }, .. } =>
+                matches!(target.guid, GUID::Creature { id, .. } if Some(id) == self.death),
+            _ => false,
+        }
+    }
+}
+
+/// What starts each phase of an encounter, loaded from TOML, eg.
+/// ```toml
+/// [[phases]]
+/// encounter_id = 2820
+/// phase = 2
+/// name = "Intermission"
+/// cast = 421898  # Flaming Pestilence
+///
+/// [[phases]]
+/// encounter_id = 2820
+/// phase = 3
+/// death = 210231  # Tortured Scream
+/// ```
+/// Phase 1 starts with the encounter. Phases only move forwards, so a rule for an earlier phase is ignored.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PhaseRules {
+    #[serde(default)]
+    phases: Vec<PhaseRule>,
+}
+
+impl PhaseRules {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let s = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to open file: {:?}", path.as_ref()))?;
+        s.parse()
+    }
+
+    fn name_of(&self, encounter_id: u64, phase: usize) -> String {
+        self.phases.iter()
+            .find(|r| r.encounter_id == encounter_id && r.phase == phase)
+            .and_then(|r| r.name.clone())
+            .unwrap_or_else(|| format!("Phase {}", phase))
+    }
+}
+
+impl std::str::FromStr for PhaseRules {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let rules: Self = toml::from_str(s).context("Bad phase rules")?;
+        if let Some(rule) = rules.phases.iter().find(|r| r.cast.is_none() && r.death.is_none()) {
+            bail!("Phase {} of encounter {} needs a cast or death to start it", rule.phase, rule.encounter_id);
+        }
+        Ok(rules)
+    }
+}
+
+/// The start of a phase of an encounter
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PhaseStart {
+    pub encounter_id: u64,
+    pub phase: usize,
+    pub name: String,
+    pub time: NaiveDateTime,
+}
+
+/// Follows the phases of encounters with rules, for the dispatcher to fire the phase hook with
+#[derive(Debug, Default)]
+pub struct PhaseDetector {
+    rules: PhaseRules,
+    /// Encounter in progress & its current phase
+    current: Option<(u64, usize)>,
+}
+
+impl PhaseDetector {
+    pub fn new(rules: PhaseRules) -> Self {
+        Self { rules, current: None }
+    }
+
+    /// The phase started by the event, if any
+    pub fn detect(&mut self, event: &Event) -> Option<PhaseStart> {
+        let (encounter_id, phase) = match &event.event_type {
+            EventType::Special { details: Special::EncounterStart(encounter), .. } => {
+                let encounter_id = encounter.encounter_id;
+                self.current = None;
+                if !self.rules.phases.iter().any(|r| r.encounter_id == encounter_id) { return None; }
+                (encounter_id, 1)
+            }
+            EventType::Special { details: Special::EncounterEnd(_), .. } => {
+                self.current = None;
+                return None;
+            }
+            _ => {
+                let (encounter_id, current) = self.current?;
+                let rule = self.rules.phases.iter()
+                    .filter(|r| r.encounter_id == encounter_id && r.phase > current)
+                    .find(|r| r.triggered_by(event))?;
+                (encounter_id, rule.phase)
+            }
+        };
+
+        self.current = Some((encounter_id, phase));
+        Some(PhaseStart { encounter_id, phase, name: self.rules.name_of(encounter_id, phase), time: event.timestamp })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::phases::{PhaseDetector, PhaseRules};
+    use crate::parser::EventParser;
+
+    #[test]
+    fn detect() {
+        let rules: PhaseRules = "[[phases]]\nencounter_id = 2820\nphase = 2\nname = \"Intermission\"\ncast = 421898\n\n\
+[[phases]]\nencounter_id = 2820\nphase = 3\ndeath = 210231\n".parse().unwrap();
+        let log = "4/6 14:00:00.000  ENCOUNTER_START,2820,\"Gnarlroot\",16,20,2549\n\
+4/6 14:00:30.000  SPELL_CAST_START,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,0000000000000000,nil,0x80000000,0x80000000,421898,\"Flaming Pestilence\",0x4\n\
+4/6 14:01:00.000  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Creature-0-1469-2549-12530-210231-000011428B,\"Tortured Scream\",0xa48,0x0,0\n\
+4/6 14:02:00.000  ENCOUNTER_END,2820,\"Gnarlroot\",16,20,1,120000\n\
+4/6 14:10:00.000  ENCOUNTER_START,2709,\"Igira the Cruel\",16,20,2549\n";
+
+        let mut detector = PhaseDetector::new(rules);
+        let phases = EventParser::new(log.as_bytes())
+            .filter_map(|e| detector.detect(&e.unwrap()))
+            .map(|p| (p.phase, p.name, p.time.format("%M:%S").to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(phases, [
+            (1, "Phase 1".to_string(), "00:00".to_string()),
+            (2, "Intermission".to_string(), "00:30".to_string()),
+            (3, "Phase 3".to_string(), "01:00".to_string()),
+        ]);
+
+        assert!("[[phases]]\nencounter_id = 2820\nphase = 2\n".parse::<PhaseRules>().is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime};
+
+use crate::components::events::Event;
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+
+/// Last time each key was seen, so idle entries can be expired
+#[derive(Debug)]
+pub struct LastSeen<K> {
+    times: HashMap<K, NaiveDateTime>,
+}
+
+impl<K> Default for LastSeen<K> {
+    fn default() -> Self { Self { times: HashMap::new() } }
+}
+
+impl<K: Hash + Eq + Clone> LastSeen<K> {
+    pub fn touch(&mut self, key: &K, time: NaiveDateTime) {
+        match self.times.get_mut(key) {
+            Some(t) => *t = time,
+            None => { self.times.insert(key.clone(), time); }
+        }
+    }
+
+    /// Removes & returns the keys not seen since the cutoff
+    pub fn expire(&mut self, cutoff: NaiveDateTime) -> Vec<K> {
+        let expired = self.times.iter()
+            .filter(|(_, &t)| t < cutoff)
+            .map(|(k, _)| k.clone())
+            .collect::<Vec<_>>();
+
+        expired.iter().for_each(|k| { self.times.remove(k); });
+
+        expired
+    }
+
+    pub fn len(&self) -> usize { self.times.len() }
+
+    pub fn clear(&mut self) { self.times.clear() }
+}
+
+/// Drops actors from handlers once they've been idle for a while, and caps the total number of tracked actors.
+/// Pruning only happens outside of encounters so encounter stats are never cut short.
+#[derive(Debug)]
+pub struct Pruner {
+    idle: Option<Duration>,
+    max_actors: Option<usize>,
+    latest: Option<NaiveDateTime>,
+    in_encounter: bool,
+}
+
+impl Pruner {
+    pub fn new(idle_minutes: Option<i64>, max_actors: Option<usize>) -> Self {
+        Self { idle: idle_minutes.map(Duration::minutes), max_actors, latest: None, in_encounter: false }
+    }
+
+    pub fn observe(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        self.latest = Some(event.timestamp);
+        self.in_encounter = event.context.encounter.is_some();
+    }
+
+    pub fn prune(&self, handlers: &mut [Box<dyn EventHandler>]) {
+        if self.in_encounter { return; }
+        let Some(latest) = self.latest else { return; };
+
+        if let Some(idle) = self.idle {
+            handlers.iter_mut().for_each(|h| h.prune(latest - idle));
+        }
+
+        // Least recently seen actors go first: keep shrinking the window until we're under the cap
+        if let Some(max_actors) = self.max_actors {
+            let mut window = self.idle.unwrap_or(Duration::hours(1));
+            while handlers.iter().map(|h| h.tracked_actors()).sum::<usize>() > max_actors
+                && window > Duration::zero() {
+                window = window / 2;
+                handlers.iter_mut().for_each(|h| h.prune(latest - window));
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::{DamageTracker, EventHandler};
+    use crate::consumers::pruning::Pruner;
+    use crate::parser::EventParser;
+
+    #[test]
+    fn prune_idle_actors() {
+        let log = "4/6 14:00:00.000  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,500,500,-1,1,0,0,0,nil,nil,nil\n\
+4/6 14:30:00.000  SWING_DAMAGE,Player-604-0A77B54A,\"Sangrenar-Thrall\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-604-0A77B54A,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,500,500,-1,1,0,0,0,nil,nil,nil\n";
+
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(DamageTracker::new())];
+        let mut pruner = Pruner::new(Some(10), None);
+        EventParser::new(log.as_bytes()).for_each(|e| {
+            pruner.observe(&e);
+            handlers.iter_mut().for_each(|h| h.handle(&e));
+        });
+
+        let before = handlers[0].tracked_actors();
+        pruner.prune(&mut handlers);
+        assert!(handlers[0].tracked_actors() < before);
+        assert!(handlers[0].display().unwrap().contains("Sangrenar"));
+        assert!(!handlers[0].display().unwrap().contains("Sønike"));
+    }
+
+    #[test]
+    fn actor_cap() {
+        let log = "4/6 14:00:00.000  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,500,500,-1,1,0,0,0,nil,nil,nil\n\
+4/6 14:00:05.000  SWING_DAMAGE,Player-604-0A77B54A,\"Sangrenar-Thrall\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-604-0A77B54A,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,500,500,-1,1,0,0,0,nil,nil,nil\n";
+
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(DamageTracker::new())];
+        let mut pruner = Pruner::new(None, Some(2));
+        EventParser::new(log.as_bytes()).for_each(|e| {
+            pruner.observe(&e);
+            handlers.iter_mut().for_each(|h| h.handle(&e));
+        });
+
+        pruner.prune(&mut handlers);
+        assert!(handlers[0].tracked_actors() <= 2);
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+
+use crate::components::events::{Event, EventType};
+use crate::components::special::Special;
+use crate::components::suffixes::Suffix;
+use crate::consumers::EventHandler;
+use crate::consumers::ownership::OwnershipResolver;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+#[derive(Debug)]
+struct Match {
+    match_type: String,
+    start: NaiveDateTime,
+    team_id: u64,
+    /// Seconds, winning team & team ratings, once finished
+    result: Option<(u64, u64, [u64; 2])>,
+    /// Player -> damage done. Pet damage goes to the owner.
+    damage: HashMap<String, i64>,
+}
+
+/// Arena matches: how long each lasted, who won, the teams' ratings afterwards & each player's damage.
+/// Results are from the point of view of the player writing the log.
+#[derive(Debug, Default)]
+pub struct PvpMatchTracker {
+    matches: Vec<Match>,
+    in_match: bool,
+    owners: OwnershipResolver,
+}
+
+impl PvpMatchTracker {
+    pub fn new() -> Self { Self::default() }
+}
+
+impl EventHandler for PvpMatchTracker {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        self.owners.update(event);
+
+        match &event.event_type {
+            EventType::Special { details: Special::ArenaMatchStart { match_type, team_id, .. }, .. } => {
+                self.matches.push(Match {
+                    match_type: match_type.clone(),
+                    start: event.timestamp,
+                    team_id: *team_id,
+                    result: None,
+                    damage: HashMap::new(),
+                });
+                self.in_match = true;
+            }
+            EventType::Special { details: Special::ArenaMatchEnd { winning_team, match_duration, team_ratings }, .. } => {
+                if let Some(m) = self.matches.last_mut().filter(|_| self.in_match) {
+                    m.result = Some((*match_duration, *winning_team, *team_ratings));
+                }
+                self.in_match = false;
+            }
+            EventType::Standard { source: Some(source), suffix: Suffix::Damage { amount, .. }, .. } if self.in_match => {
+                let Some((_, name)) = self.owners.resolve_player(source) else { return; };
+                if let Some(m) = self.matches.last_mut() { *m.damage.entry(name).or_default() += amount; }
+            }
+            _ => {}
+        }
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let mut matches = Table::new(&[("Match", 6), ("Type", 20), ("Started", 9), ("Duration", 9), ("Result", 8), ("Rating", 7), ("Enemy", 7)]);
+        let mut damage_tables = vec![];
+
+        for (i, m) in self.matches.iter().enumerate() {
+            let (duration, result, rating, enemy) = match m.result {
+                Some((secs, winner, ratings)) => (
+                    Cell::Duration(secs as f64),
+                    if winner == m.team_id { "Win" } else { "Loss" },
+                    ratings.get(m.team_id as usize).copied().into(),
+                    ratings.get(1 - m.team_id.min(1) as usize).copied().into(),
+                ),
+                None => (Cell::Empty, "Unknown", Cell::Empty, Cell::Empty),
+            };
+            matches.push(vec![(i + 1).into(), m.match_type.as_str().into(), m.start.format("%H:%M:%S").to_string().into(),
+                duration, result.into(), rating, enemy]);
+
+            let secs = m.result.map_or(0, |(secs, _, _)| secs).max(1) as f64;
+            let mut table = Table::new(&[("Player", 30), ("Damage", 10), ("DPS", 10)])
+                .with_title(format!("Match {} ({})", i + 1, m.match_type));
+            m.damage.iter()
+                .sorted_by_key(|(_, &v)| std::cmp::Reverse(v))
+                .for_each(|(name, v)| table.push(vec![name.as_str().into(), Cell::Int(*v), Cell::Float(*v as f64 / secs, 0)]));
+            damage_tables.push(table);
+        }
+
+        let summary = Summary::new("Arena matches").with_table(matches);
+        Some(damage_tables.into_iter().fold(summary, Summary::with_table))
+    }
+
+    fn prune(&mut self, cutoff: NaiveDateTime) {
+        self.owners.prune(cutoff);
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.owners.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::EventHandler;
+    use crate::consumers::pvp::PvpMatchTracker;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn arena_match() {
+        let log = "4/6 20:00:00.000  ARENA_MATCH_START,1672,33,3v3,1\n\
+4/6 20:00:30.000  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x548,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,Player-1335-0A264B4C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,10000,10000,-1,1,0,0,0,nil,nil,nil\n\
+4/6 20:02:00.000  ARENA_MATCH_END,1,120,1587,1579\n\
+4/6 20:02:01.000  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x548,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,Player-1335-0A264B4C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,10000,10000,-1,1,0,0,0,nil,nil,nil\n";
+
+        let mut tracker = PvpMatchTracker::new();
+        EventParser::new(log.as_bytes()).for_each(|e| tracker.handle(&e));
+
+        let summary = tracker.summary().unwrap();
+        assert_eq!(summary.tables[0].rows[0], [
+            Cell::Int(1), "3v3".into(), "20:00:00".into(), Cell::Duration(120.), "Win".into(), Cell::Int(1579), Cell::Int(1587),
+        ]);
+        let damage = summary.table("Match 1 (3v3)").unwrap();
+        assert_eq!(damage.rows, vec![vec!["Sønike-Ysondre".into(), Cell::Int(10000), Cell::Float(83.33333333333333, 0)]]);
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::{bail, Result};
+use itertools::Itertools;
+
+use crate::consumers::EventHandler;
+
+struct Registration<'a> {
+    name: &'static str,
+    /// Handlers which have to run earlier in the pipeline
+    requires: &'static [&'static str],
+    build: Box<dyn Fn() -> Box<dyn EventHandler> + 'a>,
+}
+
+/// Handlers available to a pipeline by name, with the other handlers each one relies on.
+/// Pipelines are checked as they're built, so a misconfigured one fails with a clear message
+/// instead of quietly producing a wrong or doubled up report.
+#[derive(Default)]
+pub struct HandlerRegistry<'a> {
+    registrations: Vec<Registration<'a>>,
+}
+
+impl<'a> HandlerRegistry<'a> {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn register<F>(&mut self, name: &'static str, requires: &'static [&'static str], build: F) -> Result<()>
+    where
+        F: Fn() -> Box<dyn EventHandler> + 'a,
+    {
+        if self.registrations.iter().any(|r| r.name == name) {
+            bail!("Handler {:?} is registered twice", name);
+        }
+        self.registrations.push(Registration { name, requires, build: Box::new(build) });
+        Ok(())
+    }
+
+    /// Builds the named handlers in order. Fails on unknown or repeated names, or when a handler's
+    /// requirements aren't earlier in the list.
+    pub fn build<'n, I: IntoIterator<Item = &'n str>>(&self, names: I) -> Result<Vec<Box<dyn EventHandler>>> {
+        let names = names.into_iter().collect_vec();
+
+        let mut handlers = vec![];
+        for (i, name) in names.iter().enumerate() {
+            let Some(registration) = self.registrations.iter().find(|r| r.name == *name) else {
+                bail!("Unknown handler {:?}, expected one of: {}", name, self.registrations.iter().map(|r| r.name).join(", "));
+            };
+            if names[..i].contains(name) {
+                bail!("Handler {:?} is in the pipeline more than once", name);
+            }
+            if let Some(missing) = registration.requires.iter().find(|r| !names[..i].contains(r)) {
+                bail!("Handler {:?} requires {:?} to run before it", name, missing);
+            }
+
+            handlers.push((registration.build)());
+        }
+
+        Ok(handlers)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::{DamageTracker, NulLogger};
+    use crate::consumers::registry::HandlerRegistry;
+
+    fn registry() -> HandlerRegistry<'static> {
+        let mut registry = HandlerRegistry::new();
+        registry.register("damage", &[], || Box::new(DamageTracker::new())).unwrap();
+        registry.register("directory", &[], || Box::new(NulLogger)).unwrap();
+        registry.register("recap", &["directory"], || Box::new(NulLogger)).unwrap();
+        registry
+    }
+
+    #[test]
+    fn pipelines() {
+        let registry = registry();
+        assert_eq!(registry.build(["damage", "directory", "recap"]).unwrap().len(), 3);
+
+        let err = registry.build(["damage", "damage"]).err().unwrap();
+        assert_eq!(err.to_string(), "Handler \"damage\" is in the pipeline more than once");
+
+        let err = registry.build(["recap", "directory"]).err().unwrap();
+        assert_eq!(err.to_string(), "Handler \"recap\" requires \"directory\" to run before it");
+
+        let err = registry.build(["dps"]).err().unwrap();
+        assert_eq!(err.to_string(), "Unknown handler \"dps\", expected one of: damage, directory, recap");
+    }
+
+    #[test]
+    fn duplicate_registration() {
+        let mut registry = registry();
+        assert!(registry.register("damage", &[], || Box::new(NulLogger)).is_err());
+    }
+}
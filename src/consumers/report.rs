@@ -0,0 +1,187 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::prefixes::Prefix;
+use crate::components::special::{EncounterEnd, EncounterStart, Special};
+use crate::components::suffixes::Suffix;
+use crate::consumers::actors::ActorRef;
+use crate::consumers::EventHandler;
+use crate::consumers::timeseries::{Bins, SeriesRecorder};
+use crate::error::ParseFailure;
+
+/// Page template, the data is substituted in for the placeholder
+const TEMPLATE: &str = include_str!("../../resources/report.html");
+const PLACEHOLDER: &str = "/*DATA*/";
+
+/// Width of each point on the charts
+const BUCKET_SECS: i64 = 5;
+
+#[derive(Debug, Serialize)]
+struct Death {
+    time_secs: f64,
+    player: String,
+    killing_blow: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct EncounterData<'a> {
+    encounter_name: &'a str,
+    duration_secs: f64,
+    success: Option<bool>,
+    /// Player -> per second damage in each bucket
+    damage: Bins,
+    healing: Bins,
+    deaths: &'a [Death],
+    /// Player -> GUID & short ID
+    actors: &'a BTreeMap<String, ActorRef>,
+}
+
+#[derive(Debug, Serialize)]
+struct ReportData<'a> {
+    bucket_secs: i64,
+    encounters: Vec<EncounterData<'a>>,
+}
+
+/// Writes a standalone HTML page with damage & healing over time, and a death timeline, for each encounter.
+/// The page is rewritten after each encounter so it stays up to date in watch mode.
+#[derive(Debug)]
+pub struct HtmlReport {
+    path: PathBuf,
+    recorder: SeriesRecorder,
+    /// Deaths in each encounter
+    deaths: Vec<Vec<Death>>,
+    last_hit: HashMap<GUID, String>,
+}
+
+impl HtmlReport {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, recorder: SeriesRecorder::new(BUCKET_SECS), deaths: vec![], last_hit: HashMap::new() }
+    }
+
+    pub fn render(&self) -> Result<String> {
+        let encounters = self.recorder.encounters.iter()
+            .zip(&self.deaths)
+            .map(|(series, deaths)| EncounterData {
+                encounter_name: &series.encounter_name,
+                duration_secs: series.duration_secs,
+                success: series.success,
+                damage: series.damage.rates(BUCKET_SECS),
+                healing: series.healing.rates(BUCKET_SECS),
+                deaths,
+                actors: &series.actors,
+            })
+            .collect();
+
+        let data = serde_json::to_string(&ReportData { bucket_secs: BUCKET_SECS, encounters })?
+            // Names can't be allowed to close the script tag
+            .replace("</", "<\\/");
+
+        Ok(TEMPLATE.replace(PLACEHOLDER, &data))
+    }
+
+    fn write(&self) -> Result<()> {
+        std::fs::write(&self.path, self.render()?)
+            .with_context(|| format!("Failed to write file: {:?}", self.path))
+    }
+}
+
+impl EventHandler for HtmlReport {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        let Some(elapsed) = self.recorder.update(event) else { return; };
+        let Some(deaths) = self.deaths.last_mut() else { return; };
+
+        match &event.event_type {
+            EventType::Special { details: Special::UnitDied { target: Some(target), .. }, .. } => {
+                if !matches!(target.guid, GUID::Player { .. }) { return; }
+                deaths.push(Death {
+                    time_secs: elapsed,
+                    player: target.name.clone(),
+                    killing_blow: self.last_hit.remove(&target.guid),
+                });
+            }
+            EventType::Standard { target: Some(target), prefix, suffix: Suffix::Damage { .. }, .. } => {
+                if !matches!(target.guid, GUID::Player { .. }) { return; }
+                let ability = match &**prefix {
+                    Prefix::Swing => "Melee".to_string(),
+                    Prefix::Spell(Some(s)) | Prefix::SpellPeriodic(s) | Prefix::Range(s) | Prefix::SpellBuilding(s) => s.spell_name.to_string(),
+                    Prefix::Spell(None) => return,
+                };
+                self.last_hit.insert(target.guid.clone(), ability);
+            }
+            EventType::EnvironmentalDamage { target: Some(target), env_type, .. } => {
+                if !matches!(target.guid, GUID::Player { .. }) { return; }
+                self.last_hit.insert(target.guid.clone(), format!("{:?}", env_type));
+            }
+            _ => {}
+        }
+    }
+
+    fn display(&self) -> Option<String> {
+        Some(format!("HTML report: {} encounters written to {:?}", self.recorder.encounters.len(), self.path))
+    }
+
+    fn on_encounter_start(&mut self, encounter: &EncounterStart) {
+        self.recorder.start_encounter(encounter);
+        self.deaths.push(vec![]);
+        self.last_hit.clear();
+    }
+
+    fn on_encounter_end(&mut self, encounter: &EncounterEnd) {
+        self.recorder.end_encounter(encounter);
+        if let Err(e) = self.write() { eprintln!("{e:#}"); }
+    }
+
+    fn on_finish(&mut self) {
+        self.recorder.pad();
+        if let Err(e) = self.write() { eprintln!("{e:#}"); }
+    }
+
+    fn prune(&mut self, cutoff: NaiveDateTime) {
+        self.recorder.prune(cutoff);
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.recorder.tracked_actors() + self.last_hit.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::{dispatch, EventHandler};
+    use crate::consumers::report::HtmlReport;
+    use crate::parser::EventParser;
+
+    #[test]
+    fn report() {
+        let log = "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 14:01:06.000  SPELL_DAMAGE,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,421971,\"Controlled Burn\",0x4,Player-1390-0C4E032E,0000000000000000,0,834740,2104,22733,3088,0,0,196960,250000,0,-2159.06,7174.82,2238,4.5667,481,144372,144372,1000,4,0,0,0,nil,nil,nil\n\
+4/6 14:01:12.000  SWING_DAMAGE,Player-1335-0A264B4C,\"</script>\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,0,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,6000,6000,-1,1,0,0,0,nil,nil,nil\n\
+4/6 14:01:13.000  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,0\n\
+4/6 14:01:20.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,0,15000\n";
+
+        let path = std::env::temp_dir().join("wowlogs_report_test.html");
+        let _ = std::fs::remove_file(&path);
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(HtmlReport::new(path.clone()))];
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        let html = std::fs::read_to_string(&path).unwrap();
+        let data = html.split("const DATA = ").nth(1).unwrap().split(";\n").next().unwrap();
+        let data: serde_json::Value = serde_json::from_str(data).unwrap();
+
+        let encounter = &data["encounters"][0];
+        assert_eq!(encounter["encounter_name"], "Gnarlroot");
+        assert_eq!(encounter["damage"]["</script>"], serde_json::json!([0., 1200., 0.]));
+        assert_eq!(encounter["deaths"][0]["player"], "Stillnixx-Hyjal");
+        assert_eq!(encounter["deaths"][0]["killing_blow"], "Controlled Burn");
+        assert_eq!(encounter["deaths"][0]["time_secs"], 8.);
+        assert_eq!(html.matches("</script>").count(), 1);
+    }
+}
@@ -0,0 +1,68 @@
+use anyhow::Result;
+
+use crate::components::common::Actor;
+use crate::components::events::{Event, EventType};
+use crate::consumers::ownership::OwnershipResolver;
+use crate::error::ParseFailure;
+
+/// Whether a player's name matches, eg. "Sønike" or "Sønike-Ysondre" for "Sønike-Ysondre".
+/// Names include the realm, but it's optional when matching.
+pub fn is_named(name: &str, player: &str) -> bool {
+    name.eq_ignore_ascii_case(player)
+        || name.split('-').next().is_some_and(|name| name.eq_ignore_ascii_case(player))
+}
+
+/// Restricts events to those the named players, or their pets, are the source or target of.
+/// Special events are always kept, so encounters still start & end. Failed lines are dropped.
+#[derive(Debug, Default)]
+pub struct PlayerScope {
+    players: Vec<String>,
+    owners: OwnershipResolver,
+}
+
+impl PlayerScope {
+    pub fn new(players: Vec<String>) -> Self {
+        Self { players, owners: OwnershipResolver::new() }
+    }
+
+    fn in_scope(&self, actor: &Option<Actor>) -> bool {
+        let Some((_, name)) = actor.as_ref().and_then(|a| self.owners.resolve_player(a)) else { return false; };
+        self.players.iter().any(|p| is_named(&name, p))
+    }
+
+    /// Whether the event should be passed on. Every event should go through this, so pets are still learnt
+    /// from events outside the scope.
+    pub fn keep(&mut self, event: &Result<Event, ParseFailure>) -> bool {
+        let Ok(event) = event else { return false; };
+        self.owners.update(event);
+
+        match &event.event_type {
+            EventType::Standard { source, target, .. } | EventType::EnvironmentalDamage { source, target, .. } =>
+                self.in_scope(source) || self.in_scope(target),
+            EventType::Partial(partial) => self.in_scope(&partial.source) || self.in_scope(&partial.target),
+            EventType::Special { .. } | EventType::Unknown { .. } => true,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::scope::{is_named, PlayerScope};
+    use crate::parser::EventParser;
+
+    #[test]
+    fn scope() {
+        assert!(is_named("Sønike-Ysondre", "sønike") && is_named("Sønike-Ysondre", "Sønike-Ysondre"));
+        assert!(!is_named("Sønike-Ysondre", "Sønik"));
+
+        let log = "4/6 14:02:07.362  SPELL_SUMMON,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-98035-000011428B,\"Dreadstalker\",0xa28,0x0,104316,\"Call Dreadstalkers\",0x20\n\
+4/6 14:02:08.362  SWING_MISSED,Creature-0-1469-2549-12530-98035-000011428B,\"Dreadstalker\",0xa28,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:02:09.362  SWING_MISSED,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:02:10.362  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,0\n";
+
+        let mut scope = PlayerScope::new(vec!["Sønike".to_string()]);
+        let kept = EventParser::new(log.as_bytes()).map(|e| scope.keep(&e)).collect::<Vec<_>>();
+        assert_eq!(kept, [true, true, false, true]);
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime};
+
+use crate::components::events::{Event, EventType};
+use crate::components::special::{EncounterEnd, EncounterStart, Special};
+use crate::components::suffixes::Suffix;
+use crate::consumers::{dispatch, EventHandler};
+use crate::consumers::phases::{PhaseDetector, PhaseRules};
+use crate::consumers::scope::PlayerScope;
+use crate::error::ParseFailure;
+
+#[derive(Debug)]
+struct Segment {
+    encounter_name: String,
+    start: NaiveDateTime,
+    last_damage: NaiveDateTime,
+}
+
+/// Splits combat outside of encounters (dungeon trash, open world) into pulls, so trackers can report them like
+/// encounters. A pull starts on damage between a hostile & a non-hostile actor, and ends once there's been none for
+/// the gap. Handlers see these through the usual encounter hooks, with an encounter ID of 0.
+/// Pulls are always reported as successful, as there's no way to tell a wipe from a reset.
+#[derive(Debug)]
+pub struct CombatSegmenter {
+    gap: Option<Duration>,
+    in_encounter: bool,
+    instance_id: u64,
+    current: Option<Segment>,
+    pulls: usize,
+    /// Only events of these players reach the handlers
+    scope: Option<PlayerScope>,
+    phases: Option<PhaseDetector>,
+}
+
+impl CombatSegmenter {
+    pub fn new(gap_secs: Option<i64>) -> Self {
+        Self { gap: gap_secs.map(Duration::seconds), in_encounter: false, instance_id: 0, current: None, pulls: 0, scope: None, phases: None }
+    }
+
+    pub fn with_scope(mut self, scope: Option<PlayerScope>) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Fires the phase hook for the phases of encounters with rules
+    pub fn with_phases(mut self, rules: Option<PhaseRules>) -> Self {
+        self.phases = rules.map(PhaseDetector::new);
+        self
+    }
+
+    /// Passes an event to every handler, firing the encounter hooks for any pull it starts or ends, & the phase hook
+    /// after any event which starts a phase
+    pub fn dispatch(&mut self, handlers: &mut [Box<dyn EventHandler>], event: &Result<Event, ParseFailure>) {
+        // Phases are followed whatever the scope, as the events starting them rarely involve players
+        let phase = self.phases.as_mut().zip(event.as_ref().ok()).and_then(|(phases, e)| phases.detect(e));
+        self.dispatch_pulls(handlers, event);
+        if let Some(phase) = phase {
+            handlers.iter_mut().for_each(|h| h.on_phase_start(&phase));
+        }
+    }
+
+    fn dispatch_pulls(&mut self, handlers: &mut [Box<dyn EventHandler>], event: &Result<Event, ParseFailure>) {
+        if self.scope.as_mut().is_some_and(|s| !s.keep(event)) { return; }
+        let Some(gap) = self.gap else { return dispatch(handlers, event); };
+        let Ok(e) = event else { return dispatch(handlers, event); };
+
+        match &e.event_type {
+            EventType::Special { details: Special::EncounterStart(_), .. } => {
+                self.end(handlers);
+                self.in_encounter = true;
+            }
+            EventType::Special { details: Special::EncounterEnd(_), .. } => self.in_encounter = false,
+            EventType::Special { details: Special::ZoneChange { instance_id, .. }, .. } => self.instance_id = *instance_id,
+            _ => {}
+        }
+
+        if !self.in_encounter {
+            if self.current.as_ref().is_some_and(|s| e.timestamp - s.last_damage > gap) {
+                self.end(handlers);
+            }
+
+            if is_combat(e) {
+                match &mut self.current {
+                    Some(segment) => segment.last_damage = e.timestamp,
+                    None => {
+                        self.pulls += 1;
+                        let segment = Segment { encounter_name: format!("Combat {}", self.pulls), start: e.timestamp, last_damage: e.timestamp };
+                        let start = EncounterStart {
+                            encounter_id: 0,
+                            encounter_name: segment.encounter_name.clone(),
+                            difficulty_id: 0,
+                            group_size: 0,
+                            instance_id: self.instance_id,
+                        };
+                        handlers.iter_mut().for_each(|h| h.on_encounter_start(&start));
+                        self.current = Some(segment);
+                    }
+                }
+            }
+        }
+
+        dispatch(handlers, event);
+    }
+
+    /// Ends the pull in progress, eg. once the log has been read
+    pub fn finish(&mut self, handlers: &mut [Box<dyn EventHandler>]) {
+        self.end(handlers);
+    }
+
+    fn end(&mut self, handlers: &mut [Box<dyn EventHandler>]) {
+        let Some(segment) = self.current.take() else { return; };
+        let end = EncounterEnd {
+            encounter_id: 0,
+            encounter_name: segment.encounter_name,
+            difficulty_id: 0,
+            group_size: 0,
+            success: true,
+            fight_time: (segment.last_damage - segment.start).num_milliseconds() as u64,
+        };
+        handlers.iter_mut().for_each(|h| h.on_encounter_end(&end));
+    }
+}
+
+/// Damage between a hostile & a non-hostile actor
+fn is_combat(event: &Event) -> bool {
+    match &event.event_type {
+        EventType::Standard { source: Some(source), target: Some(target), suffix: Suffix::Damage { .. }, .. } =>
+            source.is_hostile() != target.is_hostile(),
+        _ => false,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use anyhow::Result;
+
+    use crate::components::events::Event;
+    use crate::components::special::{EncounterEnd, EncounterStart};
+    use crate::consumers::EventHandler;
+    use crate::consumers::segments::CombatSegmenter;
+    use crate::error::ParseFailure;
+    use crate::parser::EventParser;
+
+    struct PullRecorder {
+        pulls: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl EventHandler for PullRecorder {
+        fn handle(&mut self, _event: &Result<Event, ParseFailure>) {}
+
+        fn on_encounter_start(&mut self, encounter: &EncounterStart) {
+            self.pulls.borrow_mut().push(format!("start {}", encounter.encounter_name));
+        }
+
+        fn on_encounter_end(&mut self, encounter: &EncounterEnd) {
+            self.pulls.borrow_mut().push(format!("end {} {}", encounter.encounter_name, encounter.fight_time));
+        }
+    }
+
+    const HIT: &str = "SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Trash\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,500,500,-1,1,0,0,0,nil,nil,nil";
+
+    #[test]
+    fn segments() {
+        let log = format!("4/6 14:00:00.000  {HIT}\n\
+4/6 14:00:05.000  {HIT}\n\
+4/6 14:00:30.000  {HIT}\n\
+4/6 14:01:00.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 14:01:05.000  {HIT}\n\
+4/6 14:02:00.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,1,60000\n\
+4/6 14:02:01.000  {HIT}\n");
+
+        let pulls = Rc::new(RefCell::new(vec![]));
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(PullRecorder { pulls: pulls.clone() })];
+        let mut segmenter = CombatSegmenter::new(Some(10));
+        EventParser::new(log.as_bytes()).for_each(|e| segmenter.dispatch(&mut handlers, &e));
+        segmenter.finish(&mut handlers);
+
+        assert_eq!(*pulls.borrow(), vec![
+            "start Combat 1", "end Combat 1 5000",
+            "start Combat 2", "end Combat 2 0",
+            "start Gnarlroot", "end Gnarlroot 60000",
+            "start Combat 3", "end Combat 3 0",
+        ]);
+    }
+
+    #[test]
+    fn disabled() {
+        let log = format!("4/6 14:00:00.000  {HIT}\n4/6 14:01:00.000  {HIT}\n");
+
+        let pulls = Rc::new(RefCell::new(vec![]));
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(PullRecorder { pulls: pulls.clone() })];
+        let mut segmenter = CombatSegmenter::new(None);
+        EventParser::new(log.as_bytes()).for_each(|e| segmenter.dispatch(&mut handlers, &e));
+        segmenter.finish(&mut handlers);
+
+        assert!(pulls.borrow().is_empty());
+    }
+}
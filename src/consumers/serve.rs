@@ -0,0 +1,131 @@
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tungstenite::WebSocket;
+
+use crate::components::events::Event;
+use crate::consumers::actors::ActorIds;
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+
+/// Messages sent to clients, as JSON
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message<'a> {
+    Event {
+        event: &'a Event,
+        /// Short IDs of the source & target, stable for the session
+        source_id: Option<String>,
+        target_id: Option<String>,
+    },
+    Error { location: String, error: String, raw: &'a str },
+    Snapshot { trackers: &'a [String] },
+}
+
+/// WebSocket clients connected to the server. Clones share the same clients.
+#[derive(Clone)]
+pub struct Broadcaster {
+    clients: Arc<Mutex<Vec<WebSocket<TcpStream>>>>,
+    port: u16,
+}
+
+impl Broadcaster {
+    /// Accepts WebSocket connections on localhost in the background. Port 0 picks a free port.
+    pub fn listen(port: u16) -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .with_context(|| format!("Failed to listen on port {}", port))?;
+        let port = listener.local_addr()?.port();
+
+        let clients = Arc::new(Mutex::new(vec![]));
+        let accepted = clients.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                match tungstenite::accept(stream) {
+                    Ok(ws) => accepted.lock().unwrap().push(ws),
+                    Err(e) => eprintln!("WebSocket handshake failed: {}", e),
+                }
+            }
+        });
+
+        Ok(Self { clients, port })
+    }
+
+    pub fn port(&self) -> u16 { self.port }
+
+    pub fn clients(&self) -> usize { self.clients.lock().unwrap().len() }
+
+    /// Sends to every client, dropping any that have gone away
+    fn send(&self, message: &Message) {
+        let Ok(json) = serde_json::to_string(message) else { return; };
+
+        self.clients.lock().unwrap()
+            .retain_mut(|ws| ws.send(tungstenite::Message::text(json.as_str())).is_ok());
+    }
+
+    /// Sends the current output of the trackers
+    pub fn snapshot(&self, trackers: &[String]) {
+        self.send(&Message::Snapshot { trackers });
+    }
+}
+
+/// Broadcasts every parsed event to WebSocket clients
+pub struct EventBroadcast {
+    broadcaster: Broadcaster,
+    ids: ActorIds,
+}
+
+impl EventBroadcast {
+    pub fn new(broadcaster: Broadcaster) -> Self { Self { broadcaster, ids: ActorIds::new() } }
+}
+
+impl EventHandler for EventBroadcast {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let message = match event {
+            Ok(event) => {
+                let (source_id, target_id) = self.ids.event_ids(event);
+                Message::Event { event, source_id, target_id }
+            }
+            Err(e) => Message::Error { location: e.location(), error: e.source.to_string(), raw: &e.raw },
+        };
+        self.broadcaster.send(&message);
+    }
+
+    fn display(&self) -> Option<String> { None }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::consumers::EventHandler;
+    use crate::consumers::serve::{Broadcaster, EventBroadcast};
+    use crate::parser::EventParser;
+
+    #[test]
+    fn broadcast() {
+        let broadcaster = Broadcaster::listen(0).unwrap();
+        let (mut client, _) = tungstenite::connect(format!("ws://127.0.0.1:{}", broadcaster.port())).unwrap();
+        for _ in 0..100 {
+            if broadcaster.clients() == 1 { break; }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let log = "4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n";
+        let mut handler = EventBroadcast::new(broadcaster.clone());
+        EventParser::new(log.as_bytes()).for_each(|e| handler.handle(&e));
+        broadcaster.snapshot(&["Damage".to_string()]);
+
+        let event: serde_json::Value = serde_json::from_str(client.read().unwrap().to_text().unwrap()).unwrap();
+        assert_eq!(event["type"], "event");
+        assert_eq!(event["event"]["event_type"]["Standard"]["name"], "SWING_MISSED");
+        assert_eq!((&event["source_id"], &event["target_id"]), (&serde_json::json!("A1"), &serde_json::json!("A2")));
+
+        let snapshot: serde_json::Value = serde_json::from_str(client.read().unwrap().to_text().unwrap()).unwrap();
+        assert_eq!(snapshot["type"], "snapshot");
+        assert_eq!(snapshot["trackers"][0], "Damage");
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime};
+use itertools::Itertools;
+
+use crate::components::common::Actor;
+use crate::components::events::{Event, EventType};
+use crate::components::guid::{CreatureType, GUID};
+use crate::components::special::{EncounterEnd, EncounterStart, Special};
+use crate::components::suffixes::Suffix;
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+/// Spawns of the same creature within this long of the first one are counted as one wave
+const WAVE_GAP_SECS: i64 = 3;
+
+#[derive(Debug)]
+struct PullSpawns {
+    encounter_name: String,
+    start: Option<NaiveDateTime>,
+    seen: HashSet<GUID>,
+    /// Creature ID -> name & the time each spawn first showed up
+    spawns: HashMap<u64, (String, Vec<NaiveDateTime>)>,
+}
+
+impl PullSpawns {
+    /// (time since pull, count) for each wave of the creature
+    fn waves(&self, times: &[NaiveDateTime]) -> Vec<(Duration, usize)> {
+        let start = self.start.unwrap_or_default();
+        let mut waves: Vec<(NaiveDateTime, usize)> = vec![];
+
+        for &t in times.iter().sorted() {
+            match waves.last_mut() {
+                Some((wave_start, n)) if t - *wave_start <= Duration::seconds(WAVE_GAP_SECS) => *n += 1,
+                _ => waves.push((t, 1)),
+            }
+        }
+
+        waves.into_iter()
+            .map(|(t, n)| (t - start, n))
+            .collect()
+    }
+}
+
+/// Hostile creatures appearing during each pull, grouped into spawn waves by creature ID.
+/// A spawn is timed from the first damage, miss or aura event involving it.
+#[derive(Debug, Default)]
+pub struct AddSpawns {
+    pulls: Vec<PullSpawns>,
+    in_encounter: bool,
+}
+
+impl AddSpawns {
+    pub fn new() -> Self { Self::default() }
+
+    fn observe(&mut self, actor: &Actor, time: NaiveDateTime) {
+        let GUID::Creature { unit_type: CreatureType::Creature | CreatureType::Vehicle, id, .. } = &actor.guid
+            else { return; };
+        if !actor.is_hostile() { return; }
+        let Some(pull) = self.pulls.last_mut() else { return; };
+
+        if pull.seen.insert(actor.guid.clone()) {
+            pull.spawns.entry(*id)
+                .or_insert_with(|| (actor.name.clone(), vec![]))
+                .1.push(time);
+        }
+    }
+}
+
+impl EventHandler for AddSpawns {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        if !self.in_encounter { return; }
+
+        match &event.event_type {
+            EventType::Special { details: Special::EncounterStart(_), .. } => {
+                if let Some(pull) = self.pulls.last_mut() { pull.start = Some(event.timestamp); }
+            }
+            EventType::Standard {
+                source,
+                target,
+                suffix: Suffix::Damage { .. }
+                | Suffix::DamageLanded { .. }
+                | Suffix::Missed { .. }
+                | Suffix::AuraApplied { .. }
+                | Suffix::AuraAppliedDose { .. }
+                | Suffix::AuraRefresh { .. },
+                ..
+            } => {
+                [source, target].into_iter()
+                    .flatten()
+                    .for_each(|actor| self.observe(actor, event.timestamp));
+            }
+            _ => {}
+        }
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let tables = self.pulls.iter().enumerate()
+            .map(|(i, pull)| {
+                let mut table = Table::new(&[("Creature", 30), ("ID", 8), ("Wave", 8), ("Count", 6)])
+                    .with_title(format!("{} (pull {})", pull.encounter_name, i + 1));
+
+                pull.spawns.iter()
+                    .sorted_by_key(|(id, (_, times))| (times.iter().min().copied(), **id))
+                    .for_each(|(id, (name, times))| {
+                        for (t, n) in pull.waves(times) {
+                            table.push(vec![name.as_str().into(), (*id).into(), Cell::Duration(t.num_seconds() as f64), n.into()]);
+                        }
+                    });
+                table
+            });
+
+        Some(tables.fold(Summary::new("Add spawns"), Summary::with_table))
+    }
+
+    fn on_encounter_start(&mut self, encounter: &EncounterStart) {
+        self.pulls.push(PullSpawns {
+            encounter_name: encounter.encounter_name.clone(),
+            start: None,
+            seen: HashSet::new(),
+            spawns: HashMap::new(),
+        });
+        self.in_encounter = true;
+    }
+
+    fn on_encounter_end(&mut self, _encounter: &EncounterEnd) {
+        self.in_encounter = false;
+        // Individual GUIDs are only needed while the pull is running
+        if let Some(pull) = self.pulls.last_mut() { pull.seen.clear(); }
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.pulls.last().map_or(0, |p| p.seen.len())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::{dispatch, EventHandler};
+    use crate::consumers::spawns::AddSpawns;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn spawn_waves() {
+        let hit = |time: &str, spawn: &str| format!("4/6 {}  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-210231-{},\"Tainted Lasher\",0xa48,0x0,MISS,1\n", time, spawn);
+        let log = [
+            "4/6 14:00:00.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n".to_string(),
+            hit("14:00:12.000", "000011428A"),
+            hit("14:00:13.000", "000011428B"),
+            hit("14:00:14.000", "000011428A"),
+            hit("14:00:45.500", "000011428C"),
+            "4/6 14:01:00.000  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-26125-000011428D,\"Risen Ghoul\",0xa28,0x0,MISS,1\n".to_string(),
+            "4/6 14:02:00.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,0,120000\n".to_string(),
+        ].concat();
+
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(AddSpawns::new())];
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        let summary = handlers[0].summary().unwrap();
+        let table = summary.table("Gnarlroot (pull 1)").unwrap();
+        assert_eq!(table.rows, vec![
+            vec!["Tainted Lasher".into(), Cell::Int(210231), Cell::Duration(12.), Cell::Int(2)],
+            vec!["Tainted Lasher".into(), Cell::Int(210231), Cell::Duration(45.), Cell::Int(1)],
+        ]);
+
+        let display = handlers[0].display().unwrap();
+        assert!(display.contains(&format!("{:>30}:{:>8}|{:>8}|{:>6}", "Tainted Lasher", 210231, "0:12", 2)), "{}", display);
+        assert!(!display.contains("Risen Ghoul"), "{}", display);
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime};
+use itertools::Itertools;
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::{EncounterStart, Special};
+use crate::components::suffixes::Suffix;
+use crate::consumers::auras::ActiveAuras;
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+/// Protection Paladin & Warrior, Guardian, Blood, Brewmaster & Vengeance
+pub(crate) const TANK_SPECS: &[u64] = &[66, 73, 104, 250, 268, 581];
+
+/// Major defensives, both the tank's own & externals cast on them
+pub(crate) const DEFENSIVES: &[u64] = &[
+    31850,  // Ardent Defender
+    86659,  // Guardian of Ancient Kings
+    871,    // Shield Wall
+    12975,  // Last Stand
+    61336,  // Survival Instincts
+    22812,  // Barkskin
+    48792,  // Icebound Fortitude
+    55233,  // Vampiric Blood
+    48707,  // Anti-Magic Shell
+    120954, // Fortifying Brew
+    122278, // Dampen Harm
+    187827, // Metamorphosis (Vengeance)
+    33206,  // Pain Suppression
+    102342, // Ironbark
+    6940,   // Blessing of Sacrifice
+    116849, // Life Cocoon
+    1022,   // Blessing of Protection
+];
+
+/// Damage taken within this long is counted as one spike
+const SPIKE_WINDOW_MS: i64 = 2000;
+/// Share of the tank's max health a spike has to take
+const SPIKE_HEALTH: f64 = 0.4;
+
+#[derive(Debug)]
+struct Hit {
+    time: NaiveDateTime,
+    amount: i64,
+    spell: Arc<str>,
+}
+
+#[derive(Debug)]
+struct Spike {
+    encounter: String,
+    /// Seconds into the pull
+    time: f64,
+    tank: String,
+    damage: i64,
+    max_hp: u64,
+    /// Spell of the biggest hit in the spike
+    biggest: Arc<str>,
+    defensives: Vec<String>,
+}
+
+/// Big bursts of damage taken by tanks during encounters, & the defensives they had up at the time.
+/// Spikes without any are the tank busters which went unmitigated.
+#[derive(Debug, Default)]
+pub struct TankSpikes {
+    tanks: HashSet<GUID>,
+    auras: ActiveAuras,
+    /// Recent hits on each tank, along with their max health
+    windows: HashMap<GUID, (u64, VecDeque<Hit>)>,
+    spikes: Vec<Spike>,
+}
+
+impl TankSpikes {
+    pub fn new() -> Self { Self::default() }
+}
+
+impl EventHandler for TankSpikes {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        self.auras.update(event);
+
+        let (target, prefix, amount, params) = match &event.event_type {
+            EventType::Special { details: Special::CombatantInfo(info), .. } => {
+                if info.spec_id.is_some_and(|s| TANK_SPECS.contains(&s)) {
+                    self.tanks.insert(info.guid.clone());
+                } else {
+                    self.tanks.remove(&info.guid);
+                }
+                return;
+            }
+            EventType::Standard { target: Some(target), prefix, advanced_params, suffix: Suffix::Damage { amount, .. }, .. } =>
+                (target, prefix, *amount, advanced_params),
+            _ => return,
+        };
+        let Some(encounter) = &event.context.encounter else { return; };
+        if !self.tanks.contains(&target.guid) { return; }
+
+        let (max_hp, hits) = self.windows.entry(target.guid.clone()).or_default();
+        if let Some(params) = params.as_ref().filter(|p| p.info_guid.as_ref() == Some(&target.guid)) {
+            *max_hp = params.max_hp;
+        }
+        let spell = prefix.spell_info().map_or_else(|| Arc::from("Melee"), |s| s.spell_name.clone());
+        hits.push_back(Hit { time: event.timestamp, amount, spell });
+        while hits.front().is_some_and(|h| event.timestamp - h.time > Duration::milliseconds(SPIKE_WINDOW_MS)) {
+            hits.pop_front();
+        }
+
+        let damage = hits.iter().map(|h| h.amount).sum::<i64>();
+        if *max_hp == 0 || (damage as f64) < *max_hp as f64 * SPIKE_HEALTH { return; }
+
+        let first = hits.front().unwrap().time;
+        self.spikes.push(Spike {
+            encounter: encounter.name.clone(),
+            time: (first - encounter.start).num_milliseconds() as f64 / 1000.,
+            tank: target.name.clone(),
+            damage,
+            max_hp: *max_hp,
+            biggest: hits.iter().max_by_key(|h| h.amount).unwrap().spell.clone(),
+            defensives: self.auras.on(&target.guid)
+                .filter(|(id, _)| DEFENSIVES.contains(id))
+                .map(|(_, name)| name.to_string())
+                .sorted()
+                .collect(),
+        });
+        // Each hit only counts towards one spike
+        hits.clear();
+    }
+
+    fn on_encounter_start(&mut self, _encounter: &EncounterStart) {
+        self.windows.clear();
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let mut spikes = Table::new(&[("Encounter", 25), ("Time", 8), ("Tank", 25), ("Damage", 10), ("% HP", 6), ("Biggest hit", 25), ("Defensives", 30)])
+            .with_title("Spikes");
+        let mut by_tank = BTreeMap::<&str, (usize, usize)>::new();
+
+        for spike in &self.spikes {
+            spikes.push(vec![
+                spike.encounter.as_str().into(),
+                Cell::Duration(spike.time),
+                spike.tank.as_str().into(),
+                spike.damage.into(),
+                Cell::Percent(spike.damage as f64 / spike.max_hp as f64 * 100.),
+                (*spike.biggest).into(),
+                match spike.defensives.is_empty() {
+                    true => "none".into(),
+                    false => spike.defensives.join(", ").into(),
+                },
+            ]);
+
+            let (count, unmitigated) = by_tank.entry(&spike.tank).or_default();
+            *count += 1;
+            if spike.defensives.is_empty() { *unmitigated += 1; }
+        }
+
+        let mut tanks = Table::new(&[("Tank", 25), ("Spikes", 7), ("Unmitigated", 11)]).with_title("By tank");
+        by_tank.iter().for_each(|(tank, (count, unmitigated))| tanks.push(vec![(*tank).into(), (*count).into(), (*unmitigated).into()]));
+
+        Some(Summary::new("Tank spikes").with_table(tanks).with_table(spikes))
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.auras.len() + self.windows.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::EventHandler;
+    use crate::consumers::spikes::TankSpikes;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn spikes() {
+        let hit = |time: &str, amount: u64| format!("4/6 14:02:{}  SPELL_DAMAGE,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,421971,\"Controlled Burn\",0x4,Player-1390-0C4E032E,0000000000000000,500000,1000000,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,483,{},{},-1,4,0,0,0,nil,nil,nil\n", time, amount, amount);
+        let log = [
+            "4/6 14:02:00.000  COMBATANT_INFO,Player-1390-0C4E032E,1,12648,1734,52761,1128,0,0,0,3511,3511,3511,900,0,4692,4692,4692,443,6741,533,533,533,11302,250,[(76034,96162,1)],(1,204080,199719,233396),[(207200,489,(),(),())],[Player-1098-0500B8C6,396092],145,0,0,0\n".to_string(),
+            "4/6 14:02:07.000  ENCOUNTER_START,2820,\"Gnarlroot\",16,20,2549\n".to_string(),
+            hit("10.000", 250000),
+            hit("11.000", 200000),
+            "4/6 14:02:20.000  SPELL_AURA_APPLIED,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,55233,\"Vampiric Blood\",0x20,BUFF\n".to_string(),
+            hit("21.000", 450000),
+            hit("30.000", 100000),
+            "4/6 14:05:07.000  ENCOUNTER_END,2820,\"Gnarlroot\",16,20,1,180000\n".to_string(),
+        ].concat();
+
+        let mut tracker = TankSpikes::new();
+        EventParser::new(log.as_bytes()).for_each(|e| tracker.handle(&e));
+
+        let summary = tracker.summary().unwrap();
+        let spikes = &summary.table("Spikes").unwrap().rows;
+        assert_eq!(spikes.len(), 2);
+        assert_eq!(spikes[0][1..5], [Cell::Duration(3.), "Stillnixx-Hyjal".into(), Cell::Int(450000), Cell::Percent(45.)]);
+        assert_eq!(spikes[0][6], "none".into());
+        assert_eq!(spikes[1][6], "Vampiric Blood".into());
+        assert_eq!(summary.table("By tank").unwrap().rows[0][1..], [Cell::Int(2), Cell::Int(1)]);
+    }
+}
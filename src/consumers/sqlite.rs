@@ -0,0 +1,187 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, params};
+
+use crate::components::events::Event;
+use crate::components::special::EncounterEnd;
+use crate::consumers::actors::{ActorIds, event_actors};
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+
+/// Rows written between commits
+const BATCH: usize = 10_000;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS events (
+    timestamp TEXT NOT NULL,
+    event TEXT NOT NULL,
+    source_id TEXT,
+    source_guid TEXT,
+    source_name TEXT,
+    target_id TEXT,
+    target_guid TEXT,
+    target_name TEXT,
+    -- The whole event, as written by the json sink
+    json TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS failures (
+    location TEXT NOT NULL,
+    byte_offset INTEGER NOT NULL,
+    raw TEXT NOT NULL,
+    reason TEXT NOT NULL
+);
+";
+
+/// Writes each event, or why its line failed, to an SQLite database. The events & failures tables are replaced,
+/// as the short actor IDs are only stable within one export, unless it's resumed with [SqliteLogger::append].
+/// Rows are committed in batches & at the end of each encounter.
+pub struct SqliteLogger {
+    conn: Connection,
+    ids: ActorIds,
+    pending: usize,
+    /// The first write which failed, reported when finished rather than on every row
+    error: Option<anyhow::Error>,
+}
+
+impl SqliteLogger {
+    pub(crate) fn new(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open database: {:?}", path))?;
+        conn.execute_batch("DROP TABLE IF EXISTS events; DROP TABLE IF EXISTS failures;")
+            .with_context(|| format!("Failed to drop tables in {:?}", path))?;
+        Self::start(conn, path)
+    }
+
+    /// Adds to the tables of an earlier export rather than replacing them, carrying on with its short IDs
+    pub(crate) fn append(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open database: {:?}", path))?;
+        Self::start(conn, path)
+    }
+
+    fn start(conn: Connection, path: &Path) -> Result<Self> {
+        conn.execute_batch(SCHEMA)
+            .with_context(|| format!("Failed to create tables in {:?}", path))?;
+
+        let mut ids = ActorIds::new();
+        let mut actors = conn.prepare("SELECT source_guid, source_id FROM events WHERE source_id IS NOT NULL \
+            UNION SELECT target_guid, target_id FROM events WHERE target_id IS NOT NULL")?;
+        for row in actors.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))? {
+            let (guid, id) = row?;
+            ids.restore(&guid, &id)?;
+        }
+        drop(actors);
+
+        conn.execute_batch("BEGIN")?;
+        Ok(Self { conn, ids, pending: 0, error: None })
+    }
+
+    fn insert(&mut self, event: &Result<Event, ParseFailure>) -> Result<()> {
+        match event {
+            Ok(e) => {
+                let (source, target) = event_actors(e);
+                let identified = self.ids.identify(event);
+                let json = serde_json::to_string(&identified)?;
+                self.conn.prepare_cached("INSERT INTO events VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)")?
+                    .execute(params![
+                        e.timestamp.to_string(),
+                        e.event_type.name(),
+                        identified.source_id,
+                        identified.source_guid,
+                        source.map(|a| &a.name),
+                        identified.target_id,
+                        identified.target_guid,
+                        target.map(|a| &a.name),
+                        json,
+                    ])?;
+            }
+            Err(f) => {
+                self.conn.prepare_cached("INSERT INTO failures VALUES (?1, ?2, ?3, ?4)")?
+                    .execute(params![f.location(), f.byte_offset as i64, f.raw, f.source.to_string()])?;
+            }
+        }
+
+        self.pending += 1;
+        if self.pending >= BATCH { self.commit()?; }
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<()> {
+        self.conn.execute_batch("COMMIT; BEGIN")?;
+        self.pending = 0;
+        Ok(())
+    }
+
+    fn record(&mut self, result: Result<()>) {
+        if let (Err(e), None) = (result, &self.error) {
+            self.error = Some(e);
+        }
+    }
+}
+
+impl EventHandler for SqliteLogger {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let result = self.insert(event);
+        self.record(result);
+    }
+
+    fn on_encounter_end(&mut self, _encounter: &EncounterEnd) {
+        let result = self.commit();
+        self.record(result);
+    }
+
+    fn on_finish(&mut self) {
+        let result = self.conn.execute_batch("COMMIT").map_err(Into::into);
+        self.record(result);
+        if let Some(e) = self.error.take() {
+            eprintln!("Failed to write to the database: {e:#}");
+        }
+    }
+
+    fn display(&self) -> Option<String> {
+        None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::consumers::EventHandler;
+    use crate::consumers::sqlite::SqliteLogger;
+    use crate::parser::EventParser;
+
+    #[test]
+    fn write() {
+        let log = "4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:02:08.000  SWING_MISSED,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,MISS,1\n\
+4/6 14:02:09.000  NOT_AN_EVENT\n";
+
+        let path = std::env::temp_dir().join("wowlogs_sqlite_test.db");
+        // Written twice, as a second export replaces the first
+        for _ in 0..2 {
+            let mut logger = SqliteLogger::new(&path).unwrap();
+            EventParser::new(log.as_bytes()).for_each(|e| logger.handle(&e));
+            logger.on_finish();
+            assert!(logger.error.is_none());
+        }
+
+        let conn = Connection::open(&path).unwrap();
+        let rows = conn.prepare("SELECT event, source_id, source_guid, target_id, target_name FROM events").unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)))
+            .unwrap()
+            .collect::<Result<Vec<(String, String, String, String, String)>, _>>()
+            .unwrap();
+        assert_eq!(rows, [
+            ("SWING_MISSED".into(), "A1".into(), "Player-1335-0A264B4C".into(), "A2".into(), "Gnarlroot".into()),
+            ("SWING_MISSED".into(), "A2".into(), "Creature-0-1469-2549-12530-209333-000011428A".into(), "A1".into(), "Sønike-Ysondre".into()),
+        ]);
+
+        let json: String = conn.query_row("SELECT json FROM events LIMIT 1", [], |r| r.get(0)).unwrap();
+        assert!(json.contains("\"source_id\":\"A1\""), "{}", json);
+        let failed: String = conn.query_row("SELECT raw FROM failures", [], |r| r.get(0)).unwrap();
+        assert!(failed.contains("NOT_AN_EVENT"));
+    }
+}
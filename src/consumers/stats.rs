@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+
+use crate::components::events::{Event, EventType};
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+/// Actors listed in the summary, by number of events
+const TOP_ACTORS: usize = 20;
+
+#[derive(Debug, Default)]
+struct ActorEvents {
+    as_source: u64,
+    as_target: u64,
+}
+
+/// A first look at a log: events per type & per actor, parse failures & the time span it covers
+#[derive(Debug, Default)]
+pub struct StatsCollector {
+    events: HashMap<String, u64>,
+    actors: HashMap<String, ActorEvents>,
+    unknown: u64,
+    malformed: u64,
+    first: Option<NaiveDateTime>,
+    last: Option<NaiveDateTime>,
+}
+
+impl StatsCollector {
+    pub fn new() -> Self { Self::default() }
+
+    fn total(&self) -> u64 {
+        self.events.values().sum()
+    }
+}
+
+impl EventHandler for StatsCollector {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let event = match event {
+            Ok(event) => event,
+            Err(failure) => {
+                if failure.source.is_malformed() { self.malformed += 1; } else { self.unknown += 1; }
+                return;
+            }
+        };
+
+        *self.events.entry(event.event_type.name().to_string()).or_default() += 1;
+        self.first.get_or_insert(event.timestamp);
+        self.last = Some(event.timestamp);
+
+        if let EventType::Standard { source, target, .. } = &event.event_type {
+            if let Some(source) = source {
+                self.actors.entry(source.name.clone()).or_default().as_source += 1;
+            }
+            if let Some(target) = target {
+                self.actors.entry(target.name.clone()).or_default().as_target += 1;
+            }
+        }
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let time = |t: Option<NaiveDateTime>| t.map_or(Cell::Empty, |t| t.format("%m/%d %H:%M:%S").to_string().into());
+        let span = self.first.zip(self.last).map_or(0., |(first, last)| (last - first).num_milliseconds() as f64 / 1000.);
+
+        let mut overview = Table::new(&[("Events", 10), ("Unknown", 8), ("Malformed", 10), ("First", 15), ("Last", 15), ("Span", 10)])
+            .with_title("Overview");
+        overview.push(vec![
+            self.total().into(), self.unknown.into(), self.malformed.into(), time(self.first), time(self.last), Cell::Duration(span),
+        ]);
+
+        let mut events = Table::new(&[("Event", 30), ("Count", 10)]).with_title("Events");
+        self.events.iter()
+            .sorted_by_key(|(name, count)| (std::cmp::Reverse(**count), name.as_str()))
+            .for_each(|(name, count)| events.push(vec![name.as_str().into(), (*count).into()]));
+
+        let mut actors = Table::new(&[("Actor", 30), ("As source", 10), ("As target", 10)]).with_title("Actors");
+        self.actors.iter()
+            .sorted_by_key(|(name, a)| (std::cmp::Reverse(a.as_source + a.as_target), name.as_str()))
+            .take(TOP_ACTORS)
+            .for_each(|(name, a)| actors.push(vec![name.as_str().into(), a.as_source.into(), a.as_target.into()]));
+
+        Some(Summary::new("Log stats").with_table(overview).with_table(events).with_table(actors))
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.actors.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::EventHandler;
+    use crate::consumers::stats::StatsCollector;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn stats() {
+        let log = "4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:02:09.362  SWING_MISSED,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,MISS,1\n\
+4/6 14:02:10.000  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0xZZZ,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:02:10.000  SPELL_SOMETHING_NEW,Player-1335-0A264B4C\n\
+4/6 14:02:12.362  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,0\n";
+
+        let mut stats = StatsCollector::new();
+        EventParser::new(log.as_bytes()).for_each(|e| stats.handle(&e));
+
+        let summary = stats.summary().unwrap();
+        let overview = &summary.table("Overview").unwrap().rows[0];
+        assert_eq!(overview[..3], [Cell::from(3u64), 1u64.into(), 1u64.into()]);
+        assert_eq!(overview[5], Cell::Duration(5.));
+
+        let events = &summary.table("Events").unwrap().rows;
+        assert_eq!(events[0][..2], [Cell::from("SWING_MISSED"), 2u64.into()]);
+
+        let actors = &summary.table("Actors").unwrap().rows;
+        assert_eq!(actors[0][1..], [Cell::from(1u64), 1u64.into()]);
+        assert!(stats.display().unwrap().contains("UNIT_DIED"));
+    }
+}
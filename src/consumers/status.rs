@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+
+use crate::components::events::{Event, EventType};
+use crate::components::special::{EncounterEnd, EncounterStart};
+use crate::components::suffixes::Suffix;
+use crate::consumers::EventHandler;
+use crate::consumers::ownership::OwnershipResolver;
+use crate::error::ParseFailure;
+
+/// A compact, single line summary: current encounter, elapsed time, parse rate & top damage dealer.
+#[derive(Debug)]
+pub struct StatusLine {
+    encounter: Option<String>,
+    encounter_start: Option<NaiveDateTime>,
+    latest_time: Option<NaiveDateTime>,
+    damage: HashMap<String, i64>,
+    owners: OwnershipResolver,
+    events: u64,
+    started: Instant,
+}
+
+impl StatusLine {
+    pub fn new() -> Self {
+        Self {
+            encounter: None,
+            encounter_start: None,
+            latest_time: None,
+            damage: HashMap::new(),
+            owners: OwnershipResolver::new(),
+            events: 0,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl EventHandler for StatusLine {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        self.events += 1;
+        let Ok(event) = event else { return; };
+        self.latest_time = Some(event.timestamp);
+        if self.encounter.is_some() && self.encounter_start.is_none() {
+            self.encounter_start = Some(event.timestamp);
+        }
+        self.owners.update(event);
+
+        if let EventType::Standard { source: Some(source), suffix: Suffix::Damage { amount, .. }, .. } = &event.event_type {
+            if let Some((_, name)) = self.owners.resolve_player(source) {
+                *self.damage.entry(name).or_default() += amount;
+            }
+        }
+    }
+
+    fn display(&self) -> Option<String> {
+        let encounter = match (&self.encounter, self.encounter_start, self.latest_time) {
+            (Some(name), Some(start), Some(latest)) => {
+                let elapsed = (latest - start).num_seconds();
+                format!("{} {}:{:02}", name, elapsed / 60, elapsed % 60)
+            }
+            _ => "No encounter".to_string()
+        };
+
+        let rate = self.events as f64 / self.started.elapsed().as_secs_f64().max(1.);
+
+        let top = self.damage.iter()
+            .max_by_key(|(_, &v)| v)
+            .map_or("-".to_string(), |(name, v)| format!("{} {}", name, v));
+
+        Some(format!("{} | {:.0} lines/s | Top: {}", encounter, rate, top))
+    }
+
+    fn on_encounter_start(&mut self, encounter: &EncounterStart) {
+        self.encounter = Some(encounter.encounter_name.clone());
+        self.encounter_start = None;
+        self.damage.clear();
+    }
+
+    fn on_encounter_end(&mut self, _encounter: &EncounterEnd) {
+        self.encounter = None;
+        self.encounter_start = None;
+    }
+
+    fn prune(&mut self, cutoff: NaiveDateTime) {
+        self.owners.prune(cutoff);
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.damage.len() + self.owners.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::{dispatch, EventHandler};
+    use crate::consumers::status::StatusLine;
+    use crate::parser::EventParser;
+
+    #[test]
+    fn status_line() {
+        let log = "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 14:02:10.000  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,500,500,-1,1,0,0,0,nil,nil,nil\n";
+
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(StatusLine::new())];
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        let line = handlers[0].display().unwrap();
+        assert!(line.starts_with("Gnarlroot 1:05 |"), "{}", line);
+        assert!(line.ends_with("Top: Sønike-Ysondre 500"), "{}", line);
+    }
+}
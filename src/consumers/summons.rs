@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime};
+use itertools::Itertools;
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::{EncounterStart, Special};
+use crate::components::suffixes::Suffix;
+use crate::consumers::EventHandler;
+use crate::consumers::ownership::OwnershipResolver;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+#[derive(Debug)]
+struct Life {
+    owner: String,
+    summon: String,
+    start: NaiveDateTime,
+    last_seen: NaiveDateTime,
+    /// When the unit died, was destroyed or dissipated
+    end: Option<NaiveDateTime>,
+}
+
+/// How long each player kept their totems, treants & other summons alive, from SPELL_SUMMON to the unit dying,
+/// being destroyed or dissipating. Uptime is the share of the encounter (or the log, outside of encounters)
+/// with at least one of that summon alive. Summons by pets count towards the owning player.
+#[derive(Debug, Default)]
+pub struct SummonUptime {
+    lives: Vec<Life>,
+    /// Summons still alive -> index into lives
+    alive: HashMap<GUID, usize>,
+    start: Option<NaiveDateTime>,
+    latest: Option<NaiveDateTime>,
+    owners: OwnershipResolver,
+}
+
+impl SummonUptime {
+    pub fn new() -> Self { Self::default() }
+
+    fn end(&mut self, guid: &GUID, time: NaiveDateTime) {
+        if let Some(i) = self.alive.remove(guid) { self.lives[i].end = Some(time); }
+    }
+}
+
+impl EventHandler for SummonUptime {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        self.owners.update(event);
+
+        let time = event.timestamp;
+        if self.start.is_none() { self.start = Some(time); }
+        self.latest = Some(time);
+
+        match &event.event_type {
+            EventType::Standard { source, target, suffix, .. } => {
+                for actor in [source, target].into_iter().flatten() {
+                    if let Some(&i) = self.alive.get(&actor.guid) { self.lives[i].last_seen = time; }
+                }
+
+                let (Suffix::Summon, Some(source), Some(target)) = (suffix, source, target) else { return; };
+                let Some((_, owner)) = self.owners.resolve_player(source) else { return; };
+
+                // Re-summoning the same unit replaces it
+                self.end(&target.guid, time);
+                self.alive.insert(target.guid.clone(), self.lives.len());
+                self.lives.push(Life { owner, summon: target.name.clone(), start: time, last_seen: time, end: None });
+            }
+            EventType::Special {
+                details: Special::UnitDied { target: Some(target), .. }
+                | Special::UnitDestroyed { target: Some(target), .. }
+                | Special::UnitDissipates { target: Some(target), .. },
+                ..
+            } => self.end(&target.guid, time),
+            _ => {}
+        }
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let (Some(start), Some(latest)) = (self.start, self.latest) else {
+            return Some(Summary::new("Summon uptime"));
+        };
+        let window = (latest - start).num_milliseconds().max(1) as f64;
+
+        let mut table = Table::new(&[("Owner", 30), ("Summon", 30), ("Count", 6), ("Avg life", 9), ("Uptime", 7)]);
+        self.lives.iter()
+            .into_group_map_by(|l| (l.owner.clone(), l.summon.clone()))
+            .into_iter()
+            .sorted_by(|a, b| a.0.cmp(&b.0))
+            .for_each(|((owner, summon), lives)| {
+                let spans = lives.iter()
+                    .map(|l| (l.start, l.end.unwrap_or(latest)))
+                    .sorted()
+                    .collect_vec();
+                let total: Duration = spans.iter().map(|(s, e)| *e - *s).sum();
+
+                // Overlapping summons only count once towards uptime
+                let mut up = Duration::zero();
+                let mut covered = start;
+                for (s, e) in &spans {
+                    let s = (*s).max(covered);
+                    if *e > s { up += *e - s; }
+                    covered = covered.max(*e);
+                }
+
+                table.push(vec![
+                    owner.as_str().into(),
+                    summon.as_str().into(),
+                    lives.len().into(),
+                    Cell::Duration(total.num_milliseconds() as f64 / 1000. / lives.len() as f64),
+                    Cell::Percent(100. * up.num_milliseconds() as f64 / window),
+                ]);
+            });
+
+        Some(Summary::new("Summon uptime").with_table(table))
+    }
+
+    fn on_encounter_start(&mut self, _encounter: &EncounterStart) {
+        self.lives.clear();
+        self.alive.clear();
+        self.start = None;
+        self.latest = None;
+    }
+
+    /// Summons not seen since the cutoff are assumed to have expired when they were last seen
+    fn prune(&mut self, cutoff: NaiveDateTime) {
+        let expired = self.alive.iter()
+            .filter(|(_, &i)| self.lives[i].last_seen < cutoff)
+            .map(|(guid, &i)| (guid.clone(), self.lives[i].last_seen))
+            .collect_vec();
+        expired.into_iter().for_each(|(guid, time)| self.end(&guid, time));
+        self.owners.prune(cutoff);
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.alive.len() + self.owners.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::EventHandler;
+    use crate::consumers::summons::SummonUptime;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn uptime() {
+        let log = "4/6 14:00:00.000  SPELL_CAST_SUCCESS,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,0000000000000000,nil,0x80000000,0x80000000,1,\"Start\",0x1,Player-1390-0C4E032E,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70\n\
+4/6 14:00:10.000  SPELL_SUMMON,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,Creature-0-1469-2549-12530-102392-000011428A,\"Treant\",0xa28,0x0,102693,\"Grove Guardians\",0x8\n\
+4/6 14:00:15.000  SPELL_SUMMON,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,Creature-0-1469-2549-12530-102392-000011428B,\"Treant\",0xa28,0x0,102693,\"Grove Guardians\",0x8\n\
+4/6 14:00:25.000  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Creature-0-1469-2549-12530-102392-000011428A,\"Treant\",0xa28,0x0,0\n\
+4/6 14:00:30.000  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Creature-0-1469-2549-12530-102392-000011428B,\"Treant\",0xa28,0x0,0\n\
+4/6 14:00:40.000  SPELL_SUMMON,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,Creature-0-1469-2549-12530-5925-000011428C,\"Grounding Totem\",0xa28,0x0,8177,\"Grounding Totem\",0x8\n\
+4/6 14:00:50.000  SPELL_CAST_SUCCESS,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,0000000000000000,nil,0x80000000,0x80000000,1,\"End\",0x1,Player-1390-0C4E032E,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70\n";
+
+        let mut summons = SummonUptime::new();
+        EventParser::new(log.as_bytes()).for_each(|e| summons.handle(&e));
+
+        let summary = summons.summary().unwrap();
+        let table = &summary.tables[0];
+        assert_eq!(table.find("Stillnixx-Hyjal").unwrap(), [
+            "Stillnixx-Hyjal".into(), "Grounding Totem".into(), Cell::Int(1), Cell::Duration(10.), Cell::Percent(20.),
+        ]);
+        assert_eq!(table.rows[1], [
+            "Stillnixx-Hyjal".into(), "Treant".into(), Cell::Int(2), Cell::Duration(15.), Cell::Percent(40.),
+        ]);
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::EncounterStart;
+use crate::components::suffixes::Suffix;
+use crate::consumers::ownership::OwnershipResolver;
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+/// Damage added to other players by the Augmentation Evoker's buffs, eg. Ebon Might & Prescience.
+/// The game logs it as the buffed player's own damage, with a _SUPPORT event naming the Evoker it came from.
+/// Moving it from the buffed players to the Evoker gives each player's true damage, as attributed by WCL.
+/// Like the damage tracker, this covers the latest encounter, or the whole log if there's none.
+#[derive(Debug, Default)]
+pub struct SupportDamageTracker {
+    owners: OwnershipResolver,
+    /// Each player's damage as logged, pets included
+    damage: HashMap<String, i64>,
+    start_time: Option<NaiveDateTime>,
+    latest_time: Option<NaiveDateTime>,
+    /// Damage per Evoker & buff, along with the buff's name
+    by_spell: HashMap<(GUID, u64), (String, i64)>,
+    /// Damage per buffed player & Evoker
+    by_player: HashMap<(String, GUID), i64>,
+}
+
+impl SupportDamageTracker {
+    pub fn new() -> Self { Self::default() }
+
+    fn evoker_name(&self, guid: &GUID) -> String {
+        self.owners.player_name(guid).map_or_else(|| guid.to_string(), str::to_string)
+    }
+
+    /// Each player's damage with what they lent to Evokers taken off, & what they received as an Evoker added on
+    fn true_damage(&self) -> Table {
+        // Damage, lent & received
+        let mut players = self.damage.iter()
+            .map(|(player, damage)| (player.clone(), [*damage, 0, 0]))
+            .collect::<HashMap<_, _>>();
+        for ((player, evoker), amount) in &self.by_player {
+            players.entry(player.clone()).or_default()[1] += amount;
+            players.entry(self.evoker_name(evoker)).or_default()[2] += amount;
+        }
+
+        let duration = match (self.start_time, self.latest_time) {
+            (Some(start), Some(end)) => (end - start).num_seconds() + 1,
+            _ => 1,
+        } as f64;
+        let mut table = Table::new(&[("Player", 30), ("Damage", 10), ("Lent", 10), ("Received", 10), ("True damage", 12), ("DPS", 10), ("True DPS", 10)])
+            .with_title("True damage");
+        players.into_iter()
+            .map(|(player, [damage, lent, received])| (player, damage, lent, received, damage - lent + received))
+            .sorted_by_key(|(player, .., true_damage)| (-true_damage, player.clone()))
+            .for_each(|(player, damage, lent, received, true_damage)| table.push(vec![
+                player.as_str().into(),
+                damage.into(),
+                lent.into(),
+                received.into(),
+                true_damage.into(),
+                Cell::Float(damage as f64 / duration, 0),
+                Cell::Float(true_damage as f64 / duration, 0),
+            ]));
+        table
+    }
+}
+
+impl EventHandler for SupportDamageTracker {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        self.owners.update(event);
+
+        let EventType::Standard { source: Some(source), prefix, suffix, supporter, .. } = &event.event_type else { return; };
+        let Some((_, player)) = self.owners.resolve_player(source) else { return; };
+
+        match (suffix, supporter) {
+            (Suffix::Damage { amount, .. }, _) => {
+                self.start_time.get_or_insert(event.timestamp);
+                self.latest_time = Some(event.timestamp);
+                *self.damage.entry(player).or_default() += amount;
+            }
+            (Suffix::DamageSupport { amount, .. }, Some(evoker)) => {
+                let Some(spell) = prefix.spell_info() else { return; };
+                let (_, damage) = self.by_spell.entry(((**evoker).clone(), spell.spell_id))
+                    .or_insert_with(|| (spell.spell_name.to_string(), 0));
+                *damage += amount;
+                *self.by_player.entry((player, (**evoker).clone())).or_default() += amount;
+            }
+            _ => {}
+        }
+    }
+
+    fn on_encounter_start(&mut self, _encounter: &EncounterStart) {
+        self.damage.clear();
+        self.by_spell.clear();
+        self.by_player.clear();
+        self.start_time = None;
+        self.latest_time = None;
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let by_spell = self.by_spell.iter()
+            .map(|((evoker, _), (spell, damage))| ((self.evoker_name(evoker), spell.as_str()), *damage))
+            .collect::<BTreeMap<_, _>>();
+        let mut evokers = Table::new(&[("Evoker", 30), ("Buff", 25), ("Damage", 12)]).with_title("By evoker");
+        by_spell.into_iter()
+            .for_each(|((evoker, spell), damage)| evokers.push(vec![evoker.into(), spell.into(), damage.into()]));
+
+        let by_player = self.by_player.iter()
+            .map(|((player, evoker), damage)| ((player.as_str(), self.evoker_name(evoker)), *damage))
+            .collect::<BTreeMap<_, _>>();
+        let mut players = Table::new(&[("Player", 30), ("Evoker", 30), ("Damage lent", 12)]).with_title("By player");
+        by_player.into_iter()
+            .for_each(|((player, evoker), damage)| players.push(vec![player.into(), evoker.into(), damage.into()]));
+
+        Some(Summary::new("Support damage").with_table(self.true_damage()).with_table(evokers).with_table(players))
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.damage.len() + self.owners.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::support::SupportDamageTracker;
+    use crate::consumers::EventHandler;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn reattributes() {
+        let support = |spell: &str, amount: u64| format!("2/15 20:32:16.706  SPELL_DAMAGE_SUPPORT,Player-1329-0A00AB32,\"Twigsneak-Ravencrest\",0x514,0x0,Creature-0-4233-2549-14868-200927-00004E626C,\"Smolderon\",0x10a48,0x0,{},0x40,Creature-0-4233-2549-14868-200927-00004E626C,0000000000000000,1439613911,1442829510,0,0,5043,0,3,3,100,0,4043.26,13109.35,2233,2.9862,73,{},73,-1,8,0,0,0,1,nil,nil,Player-1329-09E79FE9\n", spell, amount);
+        let log = [
+            "2/15 20:32:15.000  SPELL_CAST_SUCCESS,Player-1329-09E79FE9,\"Adamthebash-Ravencrest\",0x511,0x0,0000000000000000,nil,0x80000000,0x80000000,395152,\"Ebon Might\",0x50,Player-1329-09E79FE9,0000000000000000,846460,846460,16429,15797,5313,94077,3,100,100,0,3110.69,13146.01,2232,0.7478,486\n".to_string(),
+            "2/15 20:32:15.500  SPELL_DAMAGE,Player-1329-0A00AB32,\"Twigsneak-Ravencrest\",0x514,0x0,Creature-0-4233-2549-14868-200927-00004E626C,\"Smolderon\",0x10a48,0x0,185763,\"Pistol Shot\",0x1,Creature-0-4233-2549-14868-200927-00004E626C,0000000000000000,1439613911,1442829510,0,0,5043,0,3,3,100,0,4043.26,13109.35,2233,2.9862,73,5000,5000,-1,1,0,0,0,nil,nil,nil\n".to_string(),
+            support("410089,\"Prescience\"", 163),
+            support("395152,\"Ebon Might\"", 200),
+            support("395152,\"Ebon Might\"", 300),
+        ].concat();
+
+        let mut tracker = SupportDamageTracker::new();
+        EventParser::new(log.as_bytes()).for_each(|e| tracker.handle(&e));
+
+        let summary = tracker.summary().unwrap();
+        let evokers = &summary.table("By evoker").unwrap().rows;
+        assert_eq!(evokers[0], ["Adamthebash-Ravencrest".into(), "Ebon Might".into(), Cell::Int(500)]);
+        assert_eq!(evokers[1], ["Adamthebash-Ravencrest".into(), "Prescience".into(), Cell::Int(163)]);
+        assert_eq!(summary.table("By player").unwrap().rows[0], ["Twigsneak-Ravencrest".into(), "Adamthebash-Ravencrest".into(), Cell::Int(663)]);
+
+        // A single hit, so over 1s
+        let true_damage = &summary.table("True damage").unwrap().rows;
+        assert_eq!(true_damage[0], ["Twigsneak-Ravencrest".into(), Cell::Int(5000), Cell::Int(663), Cell::Int(0), Cell::Int(4337), Cell::Float(5000., 0), Cell::Float(4337., 0)]);
+        assert_eq!(true_damage[1], ["Adamthebash-Ravencrest".into(), Cell::Int(0), Cell::Int(0), Cell::Int(663), Cell::Int(663), Cell::Float(0., 0), Cell::Float(663., 0)]);
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+
+use crate::components::common::Actor;
+use crate::components::enums::{SpellSchool, SpellSchoolSet};
+use crate::components::events::{Event, EventType};
+use crate::components::flags::Reaction;
+use crate::components::guid::{CreatureType, GUID};
+use crate::components::special::{EncounterStart, Special};
+use crate::components::suffixes::Suffix;
+use crate::consumers::EventHandler;
+use crate::consumers::pruning::LastSeen;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+/// Share of effective healing that generates threat, split between all enemies in combat
+const HEALING_THREAT: f64 = 0.5;
+
+/// Entries shown per target
+const TOP_N: usize = 5;
+
+/// Class stances, forms & buffs that scale threat: (aura spell ID, multiplier, only for this school)
+const THREAT_AURAS: [(u64, f64, Option<SpellSchool>); 10] = [
+    // Warrior
+    (71, 1.3, None),       // Defensive Stance
+    (2457, 0.8, None),     // Battle Stance
+    (2458, 0.8, None),     // Berserker Stance
+    // Druid
+    (5487, 1.3, None),     // Bear Form
+    (9634, 1.3, None),     // Dire Bear Form
+    (768, 0.71, None),     // Cat Form
+    // Paladin
+    (25780, 1.6, Some(SpellSchool::Holy)), // Righteous Fury
+    (1038, 0.7, None),     // Blessing of Salvation
+    (25895, 0.7, None),    // Greater Blessing of Salvation
+    // Shaman
+    (25909, 0.8, None),    // Tranquil Air Totem
+];
+
+#[derive(Debug, Default)]
+struct TargetThreat {
+    name: String,
+    alive: bool,
+    /// Player or pet -> threat
+    threat: HashMap<String, f64>,
+}
+
+/// Estimated threat tables per enemy, for Classic tanks. Damage generates threat 1:1 & effective healing at 0.5
+/// split across every enemy in combat, scaled by the class stances, forms & buffs active on the source.
+/// Ability specific threat (eg. Sunder Armor) & passive class modifiers (eg. rogues) aren't modelled, as class isn't logged.
+#[derive(Debug, Default)]
+pub struct ThreatTracker {
+    targets: HashMap<GUID, TargetThreat>,
+    /// Threat aura spell IDs active on each actor
+    auras: HashMap<GUID, HashSet<u64>>,
+    last_seen: LastSeen<GUID>,
+}
+
+impl ThreatTracker {
+    pub fn new() -> Self { Self::default() }
+
+    /// Threat multiplier for an actor's damage or healing of the given school
+    fn multiplier(&self, actor: &GUID, school: Option<SpellSchoolSet>) -> f64 {
+        let Some(auras) = self.auras.get(actor) else { return 1.; };
+
+        THREAT_AURAS.iter()
+            .filter(|(id, _, _)| auras.contains(id))
+            .filter(|(_, _, only)| only.is_none_or(|s| school.is_some_and(|school| school.contains(s.into()))))
+            .map(|(_, mult, _)| mult)
+            .product()
+    }
+
+    fn add(&mut self, target: &Actor, source: &Actor, threat: f64, time: NaiveDateTime) {
+        let entry = self.targets.entry(target.guid.clone())
+            .or_insert_with(|| TargetThreat { name: target.name.clone(), alive: true, ..Default::default() });
+        *entry.threat.entry(source.name.clone()).or_default() += threat;
+        self.last_seen.touch(&target.guid, time);
+    }
+}
+
+/// Players & their pets generate threat
+fn is_friendly(actor: &Actor) -> bool {
+    !actor.is_hostile()
+        && matches!(actor.guid, GUID::Player { .. } | GUID::Creature { unit_type: CreatureType::Pet, .. })
+}
+
+fn is_enemy(actor: &Actor) -> bool {
+    actor.is_hostile() && matches!(actor.guid, GUID::Creature { .. })
+}
+
+impl EventHandler for ThreatTracker {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+
+        match &event.event_type {
+            EventType::Special { details: Special::UnitDied { target: Some(target), .. }, .. } => {
+                if let Some(t) = self.targets.get_mut(&target.guid) { t.alive = false; }
+                self.auras.remove(&target.guid);
+            }
+            EventType::Standard { source: Some(source), target: Some(target), prefix, suffix, .. } => {
+                let multiplier = |s: &Self| s.multiplier(&source.guid, event.event_type.schools().effective());
+
+                match suffix {
+                    Suffix::AuraApplied { .. } | Suffix::AuraRemoved { .. } => {
+                        let Some(spell) = prefix.spell_info() else { return; };
+                        if !THREAT_AURAS.iter().any(|(id, _, _)| *id == spell.spell_id) { return; }
+
+                        let auras = self.auras.entry(target.guid.clone()).or_default();
+                        if matches!(suffix, Suffix::AuraApplied { .. }) {
+                            auras.insert(spell.spell_id);
+                        } else {
+                            auras.remove(&spell.spell_id);
+                        }
+                    }
+                    Suffix::Damage { amount, .. } if is_friendly(source) && is_enemy(target) => {
+                        let threat = *amount as f64 * multiplier(self);
+                        self.add(target, source, threat, event.timestamp);
+                    }
+                    Suffix::Heal { amount, overhealing, .. } if is_friendly(source) => {
+                        let enemies = self.targets.iter()
+                            .filter(|(_, t)| t.alive)
+                            .map(|(guid, t)| Actor { guid: guid.clone(), name: t.name.clone(), flags: Reaction::Hostile as u64, raid_flags: None })
+                            .collect_vec();
+                        if enemies.is_empty() { return; }
+
+                        let threat = amount.saturating_sub(*overhealing) as f64 * HEALING_THREAT * multiplier(self)
+                            / enemies.len() as f64;
+                        enemies.iter().for_each(|enemy| self.add(enemy, source, threat, event.timestamp));
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let tables = self.targets.values()
+            .sorted_by(|a, b| b.threat.values().sum::<f64>().total_cmp(&a.threat.values().sum::<f64>()))
+            .map(|target| {
+                let title = if target.alive { target.name.clone() } else { format!("{} (dead)", target.name) };
+                let mut table = Table::new(&[("Source", 30), ("Threat", 12), ("Of top", 7)]).with_title(title);
+
+                let top = target.threat.values().copied().fold(0., f64::max);
+                target.threat.iter()
+                    .sorted_by(|a, b| b.1.total_cmp(a.1))
+                    .take(TOP_N)
+                    .for_each(|(name, threat)| table.push(vec![name.as_str().into(), Cell::Float(*threat, 0), Cell::Percent(100. * threat / top)]));
+                table
+            });
+
+        Some(tables.fold(Summary::new("Threat"), Summary::with_table))
+    }
+
+    fn on_encounter_start(&mut self, _encounter: &EncounterStart) {
+        self.targets.clear();
+        self.last_seen.clear();
+    }
+
+    fn prune(&mut self, cutoff: NaiveDateTime) {
+        self.last_seen.expire(cutoff).iter()
+            .for_each(|guid| { self.targets.remove(guid); });
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.targets.len() + self.auras.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::EventHandler;
+    use crate::consumers::threat::ThreatTracker;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn threat_table() {
+        let log = "1/12 20:00:00.000  SPELL_AURA_APPLIED,Player-4395-01C5EEA8,\"Tank-Whitemane\",0x514,0x0,Player-4395-01C5EEA8,\"Tank-Whitemane\",0x514,0x0,71,\"Defensive Stance\",0x1,BUFF\n\
+1/12 20:00:01.000  SWING_DAMAGE,Player-4395-01C5EEA8,\"Tank-Whitemane\",0x514,0x0,Creature-0-5208-409-12345-11502-000011428A,\"Ragnaros\",0x10a48,0x0,Player-4395-01C5EEA8,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,0,0.0000,60,1000,1000,-1,1,0,0,0,nil,nil,nil\n\
+1/12 20:00:02.000  SWING_DAMAGE,Player-4395-01C5EEA9,\"Rogue-Whitemane\",0x514,0x0,Creature-0-5208-409-12345-11502-000011428A,\"Ragnaros\",0x10a48,0x0,Player-4395-01C5EEA9,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,0,0.0000,60,1200,1200,-1,1,0,0,0,nil,nil,nil\n\
+1/12 20:00:03.000  SPELL_HEAL,Player-4395-01C5EEAA,\"Priest-Whitemane\",0x514,0x0,Player-4395-01C5EEA8,\"Tank-Whitemane\",0x514,0x0,2060,\"Greater Heal\",0x2,Player-4395-01C5EEA8,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,0,0.0000,60,2000,1800,200,0,nil\n";
+
+        let mut tracker = ThreatTracker::new();
+        EventParser::new(log.as_bytes()).for_each(|e| tracker.handle(&e));
+
+        let summary = tracker.summary().unwrap();
+        let table = summary.table("Ragnaros").unwrap();
+        assert_eq!(table.find("Tank-Whitemane").unwrap()[1], Cell::Float(1300., 0));
+        assert_eq!(table.find("Rogue-Whitemane").unwrap()[1], Cell::Float(1200., 0));
+        assert_eq!(table.find("Priest-Whitemane").unwrap()[1], Cell::Float(900., 0));
+
+        let display = tracker.display().unwrap();
+        assert!(display.contains(&format!("{:>30}:{:>12.0}|{:>6.1}%", "Tank-Whitemane", 1300., 100.)), "{}", display);
+    }
+}
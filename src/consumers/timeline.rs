@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime};
+use itertools::Itertools;
+
+use crate::components::common::Actor;
+use crate::components::events::{Event, EventType};
+use crate::components::guid::GUID;
+use crate::components::special::{EncounterStart, Special};
+use crate::components::suffixes::Suffix;
+use crate::consumers::EventHandler;
+use crate::error::ParseFailure;
+use crate::summary::{Cell, Summary, Table};
+
+/// Gaps in activity longer than this end a stretch of combat
+const COMBAT_GAP_SECS: i64 = 5;
+
+#[derive(Debug, Clone)]
+struct Appearance {
+    name: String,
+    first_seen: NaiveDateTime,
+    last_seen: NaiveDateTime,
+    /// Time spent in stretches of activity
+    in_combat: Duration,
+    /// Summoner's name & when
+    summoned: Option<(String, NaiveDateTime)>,
+    /// When the unit died, was destroyed or dissipated
+    gone: Option<NaiveDateTime>,
+}
+
+/// When each actor first & last appeared, how long it was active, and when pets were summoned & dismissed.
+/// Times are relative to the first event seen, or to the start of the current encounter.
+#[derive(Debug, Default)]
+pub struct ActorTimeline {
+    actors: HashMap<GUID, Appearance>,
+    start: Option<NaiveDateTime>,
+}
+
+impl ActorTimeline {
+    pub fn new() -> Self { Self::default() }
+
+    fn touch(&mut self, actor: &Actor, time: NaiveDateTime) -> &mut Appearance {
+        let appearance = self.actors.entry(actor.guid.clone())
+            .or_insert_with(|| Appearance {
+                name: actor.name.clone(),
+                first_seen: time,
+                last_seen: time,
+                in_combat: Duration::zero(),
+                summoned: None,
+                gone: None,
+            });
+
+        let gap = time - appearance.last_seen;
+        if gap <= Duration::seconds(COMBAT_GAP_SECS) {
+            appearance.in_combat += gap;
+        }
+        appearance.last_seen = time;
+
+        appearance
+    }
+
+    /// Seconds since the start
+    fn offset(&self, time: NaiveDateTime) -> Cell {
+        Cell::Float((time - self.start.unwrap_or(time)).num_milliseconds() as f64 / 1000., 1)
+    }
+}
+
+impl EventHandler for ActorTimeline {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        let Ok(event) = event else { return; };
+        let time = event.timestamp;
+        if self.start.is_none() { self.start = Some(time); }
+
+        match &event.event_type {
+            EventType::Standard { source, target, suffix, .. } => {
+                if let Some(source) = source { self.touch(source, time); }
+                if let Some(target) = target {
+                    let summoner = match (suffix, source) {
+                        (Suffix::Summon, Some(source)) => Some(source.name.clone()),
+                        _ => None,
+                    };
+
+                    let appearance = self.touch(target, time);
+                    if let Some(summoner) = summoner {
+                        appearance.summoned = Some((summoner, time));
+                        appearance.gone = None;
+                    }
+                }
+            }
+            EventType::Special {
+                details: Special::UnitDied { target: Some(target), .. }
+                | Special::UnitDestroyed { target: Some(target), .. }
+                | Special::UnitDissipates { target: Some(target), .. },
+                ..
+            } => {
+                self.touch(target, time).gone = Some(time);
+            }
+            _ => {}
+        }
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let mut table = Table::new(&[("Actor", 30), ("First", 9), ("Last", 9), ("Active", 9), ("Summoned by", 20),
+            ("Summoned", 9), ("Gone", 9)]);
+
+        self.actors.values()
+            .sorted_by_key(|a| (a.first_seen, a.name.clone()))
+            .for_each(|a| {
+                let (summoner, summoned) = match &a.summoned {
+                    Some((summoner, t)) => (summoner.as_str().into(), self.offset(*t)),
+                    None => (Cell::Empty, Cell::Empty),
+                };
+                table.push(vec![
+                    a.name.as_str().into(),
+                    self.offset(a.first_seen),
+                    self.offset(a.last_seen),
+                    Cell::Float(a.in_combat.num_milliseconds() as f64 / 1000., 1),
+                    summoner,
+                    summoned,
+                    a.gone.map_or(Cell::Empty, |t| self.offset(t)),
+                ]);
+            });
+
+        Some(Summary::new("Actor timeline").with_table(table))
+    }
+
+    fn on_encounter_start(&mut self, _encounter: &EncounterStart) {
+        self.actors.clear();
+        self.start = None;
+    }
+
+    fn prune(&mut self, cutoff: NaiveDateTime) {
+        self.actors.retain(|_, a| a.last_seen >= cutoff);
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.actors.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::EventHandler;
+    use crate::consumers::timeline::ActorTimeline;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn timeline() {
+        let log = "4/6 14:00:00.000  SPELL_SUMMON,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x514,0x0,Creature-0-1469-2549-12530-26125-000011428A,\"Risen Ghoul\",0xa28,0x0,46585,\"Raise Dead\",0x20\n\
+4/6 14:00:02.000  SWING_MISSED,Creature-0-1469-2549-12530-26125-000011428A,\"Risen Ghoul\",0xa28,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:00:30.000  SWING_MISSED,Creature-0-1469-2549-12530-26125-000011428A,\"Risen Ghoul\",0xa28,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:00:31.500  UNIT_DIED,0000000000000000,nil,0x80000000,0x80000000,Creature-0-1469-2549-12530-26125-000011428A,\"Risen Ghoul\",0xa28,0x0,0\n";
+
+        let mut timeline = ActorTimeline::new();
+        EventParser::new(log.as_bytes()).for_each(|e| timeline.handle(&e));
+
+        let summary = timeline.summary().unwrap();
+        assert_eq!(summary.tables[0].find("Risen Ghoul").unwrap(), [
+            "Risen Ghoul".into(), Cell::Float(0., 1), Cell::Float(31.5, 1), Cell::Float(3.5, 1),
+            "Stillnixx-Hyjal".into(), Cell::Float(0., 1), Cell::Float(31.5, 1),
+        ]);
+        assert_eq!(timeline.tracked_actors(), 3);
+    }
+}
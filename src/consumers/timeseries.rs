@@ -0,0 +1,266 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::components::events::{Event, EventType};
+use crate::components::special::{EncounterEnd, EncounterStart, Special};
+use crate::components::suffixes::Suffix;
+use crate::consumers::actors::{ActorIds, ActorRef};
+use crate::consumers::EventHandler;
+use crate::consumers::ownership::OwnershipResolver;
+use crate::error::ParseFailure;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum SeriesFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+/// Per player totals in fixed width time bins
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(transparent)]
+pub struct Bins(BTreeMap<String, Vec<f64>>);
+
+impl Bins {
+    fn add(&mut self, bin: usize, name: String, amount: f64) {
+        let values = self.0.entry(name).or_default();
+        if values.len() <= bin { values.resize(bin + 1, 0.); }
+        values[bin] += amount;
+    }
+
+    fn pad(&mut self, bins: usize) {
+        self.0.values_mut()
+            .for_each(|v| v.resize(bins.max(v.len()), 0.));
+    }
+
+    /// Per second amounts in each bin
+    pub fn rates(&self, bin_secs: i64) -> Self {
+        Self(self.0.iter()
+            .map(|(name, v)| (name.clone(), v.iter().map(|x| x / bin_secs as f64).collect()))
+            .collect())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Vec<f64>)> {
+        self.0.iter()
+    }
+}
+
+/// Damage & healing over time for one encounter
+#[derive(Debug, Clone, Serialize)]
+pub struct EncounterSeries {
+    pub encounter_name: String,
+    pub start: NaiveDateTime,
+    pub duration_secs: f64,
+    pub success: Option<bool>,
+    pub damage: Bins,
+    pub healing: Bins,
+    /// Player -> GUID & short ID
+    pub actors: BTreeMap<String, ActorRef>,
+}
+
+/// Bins player damage & effective healing over the course of each encounter. Pet output goes to the owner.
+#[derive(Debug)]
+pub struct SeriesRecorder {
+    pub bin_secs: i64,
+    pub encounters: Vec<EncounterSeries>,
+    in_encounter: bool,
+    owners: OwnershipResolver,
+    ids: ActorIds,
+}
+
+impl SeriesRecorder {
+    pub fn new(bin_secs: i64) -> Self {
+        Self { bin_secs, encounters: vec![], in_encounter: false, owners: OwnershipResolver::new(), ids: ActorIds::new() }
+    }
+
+    /// Records the event, returning the seconds since the encounter started if one is running
+    pub fn update(&mut self, event: &Event) -> Option<f64> {
+        self.owners.update(event);
+        if !self.in_encounter { return None; }
+        let encounter = self.encounters.last_mut()?;
+
+        if let EventType::Special { details: Special::EncounterStart(_), .. } = &event.event_type {
+            encounter.start = event.timestamp;
+        }
+
+        let elapsed = (event.timestamp - encounter.start).num_milliseconds().max(0) as f64 / 1000.;
+        let bin = (elapsed / self.bin_secs as f64) as usize;
+        encounter.duration_secs = encounter.duration_secs.max(elapsed);
+
+        if let EventType::Standard { source: Some(source), suffix, .. } = &event.event_type {
+            let amount = match suffix {
+                Suffix::Damage { amount, .. } => Some((&mut encounter.damage, *amount as f64)),
+                Suffix::Heal { amount, overhealing, .. } => Some((&mut encounter.healing, amount.saturating_sub(*overhealing) as f64)),
+                _ => None,
+            };
+
+            if let (Some((bins, amount)), Some((guid, name))) = (amount, self.owners.resolve_player(source)) {
+                if !encounter.actors.contains_key(&name) {
+                    encounter.actors.insert(name.clone(), self.ids.actor_ref(&guid));
+                }
+                bins.add(bin, name, amount);
+            }
+        }
+
+        Some(elapsed)
+    }
+
+    pub fn start_encounter(&mut self, encounter: &EncounterStart) {
+        self.encounters.push(EncounterSeries {
+            encounter_name: encounter.encounter_name.clone(),
+            start: NaiveDateTime::default(),
+            duration_secs: 0.,
+            success: None,
+            damage: Bins::default(),
+            healing: Bins::default(),
+            actors: BTreeMap::new(),
+        });
+        self.in_encounter = true;
+    }
+
+    pub fn end_encounter(&mut self, encounter: &EncounterEnd) {
+        self.in_encounter = false;
+        if let Some(series) = self.encounters.last_mut() { series.success = Some(encounter.success); }
+        self.pad();
+    }
+
+    /// Pads every series out to the length of its encounter
+    pub fn pad(&mut self) {
+        let bin_secs = self.bin_secs as f64;
+        for series in &mut self.encounters {
+            let bins = (series.duration_secs / bin_secs).ceil() as usize;
+            series.damage.pad(bins);
+            series.healing.pad(bins);
+        }
+    }
+
+    pub fn prune(&mut self, cutoff: NaiveDateTime) {
+        self.owners.prune(cutoff);
+    }
+
+    pub fn tracked_actors(&self) -> usize {
+        self.owners.len() + self.ids.len()
+    }
+
+    /// One row per pull, second & player. Players are given by name, GUID & short ID.
+    pub fn to_csv(&self) -> String {
+        let rows = self.encounters.iter().enumerate()
+            .flat_map(|(i, series)| {
+                let players = series.damage.0.keys().chain(series.healing.0.keys()).unique().sorted();
+                let bins = (series.duration_secs / self.bin_secs as f64).ceil() as usize;
+
+                players
+                    .flat_map(move |player| (0..bins).map(move |bin| {
+                        let value = |b: &Bins| b.0.get(player).and_then(|v| v.get(bin)).copied().unwrap_or_default();
+                        (player, bin, value(&series.damage), value(&series.healing))
+                    }))
+                    .map(move |(player, bin, damage, healing)| {
+                        let (guid, id) = series.actors.get(player).map_or(("", ""), |a| (a.guid.as_str(), a.id.as_str()));
+                        format!("{},{:?},{},{:?},{},{},{},{}", i + 1, series.encounter_name, bin as i64 * self.bin_secs, player, guid, id, damage, healing)
+                    })
+            })
+            .join("\n");
+
+        format!("pull,encounter_name,second,player,player_guid,actor_id,damage,healing\n{}\n", rows)
+    }
+}
+
+/// Exports per player damage & healing in 1 second bins for each encounter, for plotting burst windows & cooldown usage.
+/// The file is rewritten after each encounter.
+#[derive(Debug)]
+pub struct TimeSeriesExport {
+    path: PathBuf,
+    format: SeriesFormat,
+    recorder: SeriesRecorder,
+}
+
+impl TimeSeriesExport {
+    pub fn new(path: PathBuf, format: SeriesFormat) -> Self {
+        Self { path, format, recorder: SeriesRecorder::new(1) }
+    }
+
+    fn write(&self) -> Result<()> {
+        let s = match self.format {
+            SeriesFormat::Csv => self.recorder.to_csv(),
+            SeriesFormat::Json => serde_json::to_string(&self.recorder.encounters)?,
+        };
+
+        std::fs::write(&self.path, s)
+            .with_context(|| format!("Failed to write file: {:?}", self.path))
+    }
+}
+
+impl EventHandler for TimeSeriesExport {
+    fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+        if let Ok(event) = event { self.recorder.update(event); }
+    }
+
+    fn display(&self) -> Option<String> {
+        Some(format!("Time series: {} encounters written to {:?}", self.recorder.encounters.len(), self.path))
+    }
+
+    fn on_encounter_start(&mut self, encounter: &EncounterStart) {
+        self.recorder.start_encounter(encounter);
+    }
+
+    fn on_encounter_end(&mut self, encounter: &EncounterEnd) {
+        self.recorder.end_encounter(encounter);
+        if let Err(e) = self.write() { eprintln!("{e:#}"); }
+    }
+
+    fn on_finish(&mut self) {
+        self.recorder.pad();
+        if let Err(e) = self.write() { eprintln!("{e:#}"); }
+    }
+
+    fn prune(&mut self, cutoff: NaiveDateTime) {
+        self.recorder.prune(cutoff);
+    }
+
+    fn tracked_actors(&self) -> usize {
+        self.recorder.tracked_actors()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::{dispatch, EventHandler};
+    use crate::consumers::timeseries::{SeriesFormat, TimeSeriesExport};
+    use crate::parser::EventParser;
+
+    const LOG: &str = "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 14:01:06.500  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,0,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,6000,6000,-1,1,0,0,0,nil,nil,nil\n\
+4/6 14:01:06.900  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,0,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,1000,1000,-1,1,0,0,0,nil,nil,nil\n\
+4/6 14:01:08.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,0,3000\n";
+
+    fn export(format: SeriesFormat) -> String {
+        let path = std::env::temp_dir().join(format!("wowlogs_timeseries_test.{:?}", format));
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(TimeSeriesExport::new(path.clone(), format))];
+        EventParser::new(LOG.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn csv() {
+        let csv = export(SeriesFormat::Csv);
+        assert_eq!(csv, "pull,encounter_name,second,player,player_guid,actor_id,damage,healing\n\
+1,\"Gnarlroot\",0,\"Sønike-Ysondre\",Player-1335-0A264B4C,A1,0,0\n\
+1,\"Gnarlroot\",1,\"Sønike-Ysondre\",Player-1335-0A264B4C,A1,7000,0\n\
+1,\"Gnarlroot\",2,\"Sønike-Ysondre\",Player-1335-0A264B4C,A1,0,0\n");
+    }
+
+    #[test]
+    fn json() {
+        let json: serde_json::Value = serde_json::from_str(&export(SeriesFormat::Json)).unwrap();
+        assert_eq!(json[0]["damage"]["Sønike-Ysondre"], serde_json::json!([0., 7000., 0.]));
+        assert_eq!(json[0]["success"], false);
+        assert_eq!(json[0]["actors"]["Sønike-Ysondre"], serde_json::json!({ "guid": "Player-1335-0A264B4C", "id": "A1" }));
+    }
+}
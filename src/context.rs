@@ -0,0 +1,183 @@
+//! What's known about the log at each event, from the special events before it.
+//! Tracked once by the parsers so consumers don't each have to follow zones & encounters themselves.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+
+use chrono::NaiveDateTime;
+
+use crate::components::common::Actor;
+use crate::components::events::{Event, EventType};
+use crate::components::format::LogFormat;
+use crate::components::guid::GUID;
+use crate::components::special::Special;
+use crate::error::ParseFailure;
+
+/// From ZONE_CHANGE
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    pub instance_id: u64,
+    pub name: String,
+}
+
+/// From MAP_CHANGE
+#[derive(Debug, Clone, PartialEq)]
+pub struct Map {
+    pub ui_map_id: u64,
+    pub name: String,
+}
+
+/// From ENCOUNTER_START
+#[derive(Debug, Clone, PartialEq)]
+pub struct Encounter {
+    pub encounter_id: u64,
+    pub name: String,
+    pub difficulty_id: u64,
+    pub start: NaiveDateTime,
+}
+
+/// State of the log as of an event, including the event itself.
+/// Shared between events until something changes, so attaching it to every event is cheap.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionContext {
+    /// Layout of the log, incl. its version & whether advanced logging is on
+    pub format: LogFormat,
+    pub zone: Option<Zone>,
+    pub map: Option<Map>,
+    /// Cleared by ENCOUNTER_END, or a zone change in case the end never made it into the log
+    pub encounter: Option<Encounter>,
+}
+
+static EMPTY: LazyLock<Arc<SessionContext>> = LazyLock::new(Arc::default);
+
+impl SessionContext {
+    /// Context of events which didn't come through a parser
+    pub fn empty() -> Arc<Self> {
+        EMPTY.clone()
+    }
+
+    /// Moves the context on past an event & attaches it to the event
+    fn follow(session: &mut Arc<Self>, event: &mut Event) {
+        if let EventType::Special { details, .. } = &event.event_type {
+            match details {
+                Special::ZoneChange { instance_id, zone_name, .. } => {
+                    let session = Arc::make_mut(session);
+                    session.zone = Some(Zone { instance_id: *instance_id, name: zone_name.clone() });
+                    session.encounter = None;
+                }
+                Special::MapChange { ui_map_id, ui_map_name, .. } =>
+                    Arc::make_mut(session).map = Some(Map { ui_map_id: *ui_map_id, name: ui_map_name.clone() }),
+                Special::EncounterStart(start) => Arc::make_mut(session).encounter = Some(Encounter {
+                    encounter_id: start.encounter_id,
+                    name: start.encounter_name.clone(),
+                    difficulty_id: start.difficulty_id,
+                    start: event.timestamp,
+                }),
+                Special::EncounterEnd(_) => Arc::make_mut(session).encounter = None,
+                _ => {}
+            }
+        }
+
+        event.context = session.clone();
+    }
+}
+
+/// Names which stand in for an actor's real one, eg. corpses targeted by a battle res
+fn is_unnamed(name: &str) -> bool {
+    matches!(name, "" | "nil" | "Unknown")
+}
+
+/// Everything a parser carries from one event to the next
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub context: Arc<SessionContext>,
+    /// Last known name of each actor
+    names: HashMap<GUID, String>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self { context: SessionContext::empty(), names: HashMap::new() }
+    }
+}
+
+impl Session {
+    /// Moves the session on past an event: attaches the context & fills in unnamed actors
+    pub(crate) fn follow(&mut self, format: &LogFormat, event: &mut Result<Event, ParseFailure>) {
+        if self.context.format != *format {
+            Arc::make_mut(&mut self.context).format = *format;
+        }
+        let Ok(event) = event else { return; };
+        SessionContext::follow(&mut self.context, event);
+
+        if let EventType::Standard { source, target, .. } = &mut event.event_type {
+            self.backfill(source);
+            self.backfill(target);
+        }
+    }
+
+    fn backfill(&mut self, actor: &mut Option<Actor>) {
+        let Some(actor) = actor else { return; };
+
+        if is_unnamed(&actor.name) {
+            if let Some(name) = self.names.get(&actor.guid) {
+                actor.name.clone_from(name);
+            }
+        } else if self.names.get(&actor.guid) != Some(&actor.name) {
+            self.names.insert(actor.guid.clone(), actor.name.clone());
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::components::events::EventType;
+    use crate::parser::{ChunkParser, EventParser};
+
+    #[test]
+    fn follows_session() {
+        let log = "4/6 14:00:00.000  COMBAT_LOG_VERSION,20,ADVANCED_LOG_ENABLED,0,BUILD_VERSION,10.2.6,PROJECT_ID,1\n\
+4/6 14:00:01.000  ZONE_CHANGE,2549,\"Amirdrassil, the Dream's Hope\",16\n\
+4/6 14:00:01.000  MAP_CHANGE,2232,\"Wellspring Atrium\",3000.000000,2000.000000,1000.000000,-1000.000000\n\
+4/6 14:02:07.362  ENCOUNTER_START,2820,\"Gnarlroot\",16,20,2549\n\
+4/6 14:02:08.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:02:09.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:05:07.362  ENCOUNTER_END,2820,\"Gnarlroot\",16,20,1,180000\n";
+
+        let events = EventParser::new(log.as_bytes()).collect::<Result<Vec<_>, _>>().unwrap();
+        assert!(!events[0].context.format.advanced_log_enabled);
+        assert_eq!(events[1].context.zone.as_ref().unwrap().name, "Amirdrassil, the Dream's Hope");
+        assert_eq!(events[2].context.map.as_ref().unwrap().ui_map_id, 2232);
+
+        let encounter = events[4].context.encounter.as_ref().unwrap();
+        assert_eq!((encounter.encounter_id, encounter.start), (2820, events[3].timestamp));
+        // Unchanged context is shared
+        assert!(Arc::ptr_eq(&events[4].context, &events[5].context));
+        assert!(events[6].context.encounter.is_none());
+
+        // Carried over between chunks
+        let mut parser = ChunkParser::new();
+        let (first, rest) = log.split_at(log.find("4/6 14:02:08").unwrap());
+        parser.push(first);
+        let events = parser.push(rest);
+        assert_eq!(events[0].as_ref().unwrap().context.encounter.as_ref().unwrap().name, "Gnarlroot");
+    }
+
+    #[test]
+    fn backfills_names() {
+        let log = "4/11 22:38:50.000  SWING_MISSED,Player-1329-09AF0ACF,\"Adamthebash-Ravencrest\",0x511,0x0,Corpse-0-1465-2454-103-0-000018584E,\"Stillnixx-Hyjal\",0x4228,0x0,MISS,1\n\
+4/11 22:38:54.708  SPELL_CAST_SUCCESS,Player-1329-09AF0ACF,\"Adamthebash-Ravencrest\",0x511,0x0,Corpse-0-1465-2454-103-0-000018584E,\"Unknown\",0x4228,0x0,20484,\"Rebirth\",0x8,Player-1329-09AF0ACF,0000000000000000,732698,846460,16347,15718,5632,0,0,250000,250000,5000,66.53,3330.43,2133,4.7368,486\n\
+4/11 22:38:55.000  SPELL_CAST_SUCCESS,Player-1329-09AF0ACF,\"Adamthebash-Ravencrest\",0x511,0x0,Corpse-0-1465-2454-103-0-000018584F,\"Unknown\",0x4228,0x0,20484,\"Rebirth\",0x8,Player-1329-09AF0ACF,0000000000000000,732698,846460,16347,15718,5632,0,0,250000,250000,5000,66.53,3330.43,2133,4.7368,486\n";
+
+        let names = EventParser::new(log.as_bytes())
+            .map(|e| match e.unwrap().event_type {
+                EventType::Standard { target, .. } => target.unwrap().name,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Stillnixx-Hyjal", "Stillnixx-Hyjal", "Unknown"]);
+    }
+}
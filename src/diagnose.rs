@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use itertools::Itertools;
+
+use crate::error::ParseFailure;
+use crate::parser::ParseOptions;
+use crate::summary::{Summary, Table};
+use wowlogs_parser::mmap::MmapParser;
+
+/// Failures of one kind for one event type
+#[derive(Debug)]
+struct FailureGroup {
+    count: usize,
+    /// Where the first one was, to go & look at it
+    first: String,
+}
+
+/// Parse failures of a log grouped by event type & what went wrong, to spot events changed by a new build
+#[derive(Debug, Default)]
+pub struct Diagnosis {
+    lines: usize,
+    groups: HashMap<(String, String), FailureGroup>,
+}
+
+/// Event name from a raw line, eg. `SPELL_ABSORBED`
+fn event_name(raw: &str) -> &str {
+    let first = raw.split(',').next().unwrap_or_default();
+    first.split_once("  ").map_or(first, |(_, name)| name)
+}
+
+impl Diagnosis {
+    pub fn add(&mut self, result: &Result<impl Sized, ParseFailure>) {
+        self.lines += 1;
+        let Err(failure) = result else { return; };
+
+        let key = (event_name(&failure.raw).to_string(), failure.source.problem());
+        self.groups.entry(key)
+            .or_insert_with(|| FailureGroup { count: 0, first: failure.location() })
+            .count += 1;
+    }
+
+    pub fn failures(&self) -> usize {
+        self.groups.values().map(|g| g.count).sum()
+    }
+
+    pub fn summary(&self) -> Summary {
+        let mut table = Table::new(&[("Event", 30), ("Failures", 9), ("Problem", 40), ("First", 30)]);
+        self.groups.iter()
+            .sorted_by_key(|((event, problem), g)| (std::cmp::Reverse(g.count), event.clone(), problem.clone()))
+            .for_each(|((event, problem), g)| table.push(vec![
+                event.as_str().into(), g.count.into(), problem.as_str().into(), g.first.as_str().into(),
+            ]));
+
+        let mut summary = Summary::new("Parse failures").with_table(table);
+        summary.notes.push(format!("{} of {} lines failed to parse", self.failures(), self.lines));
+        summary
+    }
+}
+
+/// Parses the whole log, grouping the failures
+pub fn diagnose(path: &Path, options: ParseOptions) -> Result<Diagnosis> {
+    let mut diagnosis = Diagnosis::default();
+    MmapParser::open(path)?
+        .with_options(options)
+        .for_each(|e| diagnosis.add(&e));
+
+    Ok(diagnosis)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnose::Diagnosis;
+    use crate::parser::EventParser;
+    use crate::summary::Cell;
+
+    #[test]
+    fn groups() {
+        let log = "4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:02:08.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0xZZZ,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:02:09.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0xYYY,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:02:10.362  SPELL_SOMETHING_NEW,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0\n";
+
+        let mut diagnosis = Diagnosis::default();
+        EventParser::new(log.as_bytes()).for_each(|e| diagnosis.add(&e));
+        assert_eq!(diagnosis.failures(), 3);
+
+        let summary = diagnosis.summary();
+        let rows = &summary.tables[0].rows;
+        assert_eq!(rows[0][..3], [Cell::from("SWING_MISSED"), 2usize.into(), "field 3 not hex u64".into()]);
+        assert_eq!(rows[0][3], "line 2 (byte 156)".into());
+        assert_eq!(rows[1][0], "SPELL_SOMETHING_NEW".into());
+        assert_eq!(summary.notes, ["3 of 4 lines failed to parse"]);
+    }
+}
@@ -0,0 +1,230 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+/// Why a log line failed to parse. Carries the raw line so it can be inspected or re-parsed later.
+#[derive(Debug, Error)]
+pub enum ParseError {
+    /// The event type isn't one we know about. Usually safe to skip.
+    #[error("Unknown event type {event_type:?}: {raw}")]
+    UnknownEvent { event_type: String, raw: String },
+
+    /// A field couldn't be parsed into the expected type
+    #[error("Bad field {index}: expected {expected}, got {got:?}: {raw}")]
+    BadField { index: usize, expected: String, got: String, raw: String },
+
+    /// A known event with more or fewer fields than expected, eg. after a patch changed its layout
+    #[error("{event_type} has {got} fields, expected {expected}: {raw}")]
+    FieldCount { event_type: String, expected: usize, got: usize, raw: String },
+
+    #[error("Bad timestamp {got:?}: {raw}")]
+    BadTimestamp { got: String, raw: String },
+
+    /// Anything else wrong with the structure of the line
+    #[error("Malformed line ({reason}): {raw}")]
+    Malformed { reason: String, raw: String },
+
+    /// The line couldn't be read as text, eg. garbled bytes at the end of a log still being written
+    #[error("Unreadable line ({reason}): {raw}")]
+    Unreadable { reason: String, raw: String },
+}
+
+impl ParseError {
+    /// Known events with bad contents, as opposed to events we don't understand yet
+    pub fn is_malformed(&self) -> bool {
+        !matches!(self, Self::UnknownEvent { .. })
+    }
+
+    pub fn raw(&self) -> &str {
+        match self {
+            Self::UnknownEvent { raw, .. }
+            | Self::BadField { raw, .. }
+            | Self::FieldCount { raw, .. }
+            | Self::BadTimestamp { raw, .. }
+            | Self::Malformed { raw, .. }
+            | Self::Unreadable { raw, .. } => raw
+        }
+    }
+
+    /// What went wrong, without the line, so failures of the same kind can be grouped. eg. `field 8 not u64`
+    pub fn problem(&self) -> String {
+        match self {
+            Self::UnknownEvent { .. } => "unknown event type".to_string(),
+            Self::BadField { index, expected, .. } => format!("field {} not {}", index, expected),
+            Self::FieldCount { expected, got, .. } => format!("{} fields, expected {}", got, expected),
+            Self::BadTimestamp { .. } => "bad timestamp".to_string(),
+            Self::Malformed { reason, .. } => reason.clone(),
+            Self::Unreadable { .. } => "unreadable".to_string(),
+        }
+    }
+
+    /// Converts a component's error into a structured one, with the line the component was parsed from
+    pub(crate) fn from_component(err: ComponentError, line: &[&str]) -> Self {
+        let raw = line.join(",");
+        match err {
+            ComponentError::UnknownEventType(event_type) => Self::UnknownEvent { event_type, raw },
+            ComponentError::TooFewFields { event_type, expected, got } => Self::FieldCount { event_type, expected, got, raw },
+            ComponentError::BadField { index: Some(index), expected, got } => Self::BadField { index, expected, got, raw },
+            err => Self::Malformed { reason: err.to_string(), raw },
+        }
+    }
+}
+
+/// A line which failed to parse, along with where it came from so it can be found & re-parsed later
+#[derive(Debug, Error)]
+#[error("{}: {source}", self.location())]
+pub struct ParseFailure {
+    pub file: Option<PathBuf>,
+    /// Unknown when parsing started part way through a file
+    pub line_no: Option<u64>,
+    pub byte_offset: u64,
+    /// The line exactly as it appears in the log
+    pub raw: String,
+    pub source: ParseError,
+}
+
+impl ParseFailure {
+    /// eg. `WoWCombatLog.txt:12 (byte 3456)`
+    pub fn location(&self) -> String {
+        let line = match (&self.file, self.line_no) {
+            (Some(file), Some(line)) => format!("{}:{}", file.display(), line),
+            (Some(file), None) => file.display().to_string(),
+            (None, Some(line)) => format!("line {}", line),
+            (None, None) => "unknown line".to_string(),
+        };
+
+        format!("{} (byte {})", line, self.byte_offset)
+    }
+}
+
+/// Why part of a line (an actor, spell, suffix, ...) failed to parse. Turned into a `ParseError` once the whole line is known.
+#[derive(Debug, Error)]
+pub enum ComponentError {
+    /// An event type with no matching special event, or prefix & suffix
+    #[error("Unknown event type: {0}")]
+    UnknownEventType(String),
+
+    /// A known event's line ends before all of its fields, eg. when the game crashed mid write.
+    /// Both counts include the event type, as `ParseError::FieldCount` does
+    #[error("{event_type} has {got} fields, expected at least {expected}")]
+    TooFewFields { event_type: String, expected: usize, got: usize },
+
+    /// A single field failed to parse. The index counts from the first field the failing parser was given,
+    /// & is None until the parser which knows it says which field the value came from.
+    #[error("Failed to parse {expected}: {got:?}")]
+    BadField { index: Option<usize>, expected: String, got: String },
+
+    #[error("{0}")]
+    Malformed(String),
+}
+
+impl ComponentError {
+    pub(crate) fn bad_field(got: &str, expected: &str) -> Self {
+        Self::BadField { index: None, expected: expected.to_string(), got: got.to_string() }
+    }
+
+    /// For errors from a parser given the fields from `start` on, counts the bad field's index from the field before
+    pub(crate) fn offset(self, start: usize) -> Self {
+        match self {
+            Self::BadField { index: Some(index), expected, got } => Self::BadField { index: Some(start + index), expected, got },
+            err => err,
+        }
+    }
+
+    /// For errors from fields which had the one at `removed` taken out, counts the bad field's index as if it was still there
+    pub(crate) fn skipping(self, removed: Option<usize>) -> Self {
+        match (self, removed) {
+            (Self::BadField { index: Some(index), expected, got }, Some(removed)) if index >= removed =>
+                Self::BadField { index: Some(index + 1), expected, got },
+            (err, _) => err,
+        }
+    }
+
+    /// For errors from parsing a value taken out of the field at `index`
+    pub(crate) fn in_field(self, index: usize) -> Self {
+        match self {
+            Self::BadField { index: None, expected, got } => Self::BadField { index: Some(index), expected, got },
+            err => err,
+        }
+    }
+}
+
+pub type Result<T, E = ComponentError> = std::result::Result<T, E>;
+
+/// Returns early with a `ComponentError::Malformed`, formatted like `format!`
+macro_rules! malformed {
+    ($($arg:tt)*) => { return Err($crate::error::ComponentError::Malformed(format!($($arg)*))) };
+}
+pub(crate) use malformed;
+
+/// Adds what was being parsed to a component's error, like anyhow's `Context`.
+/// Bad fields are kept as they are, so they can still be located in the line.
+pub(crate) trait Context<T> {
+    fn context<C: Display>(self, context: C) -> Result<T>;
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, f: F) -> Result<T>;
+}
+
+impl<T> Context<T> for Result<T> {
+    fn context<C: Display>(self, context: C) -> Result<T> {
+        self.with_context(|| context)
+    }
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.map_err(|err| match err {
+            err @ (ComponentError::BadField { .. } | ComponentError::UnknownEventType(_) | ComponentError::TooFewFields { .. }) => err,
+            err => ComponentError::Malformed(format!("{}: {}", f(), err)),
+        })
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context<C: Display>(self, context: C) -> Result<T> {
+        self.with_context(|| context)
+    }
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.ok_or_else(|| ComponentError::Malformed(f().to_string()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::components::events::Event;
+    use crate::error::ParseError;
+
+    #[test]
+    fn unknown_event() {
+        let line = vec!["4/6 14:02:07.362  SOMETHING_NEW", "Player-1335-0A264B4C", "Sønike-Ysondre", "0x514", "0x0", "Creature-0-1469-2549-12530-209333-000011428A", "Gnarlroot", "0x10a48", "0x0", "1"];
+        let err = Event::parse(&line).unwrap_err();
+        assert!(matches!(&err, ParseError::UnknownEvent { event_type, .. } if event_type == "SOMETHING_NEW"));
+        assert!(!err.is_malformed());
+    }
+
+    #[test]
+    fn bad_field() {
+        let line = vec!["4/6 14:02:07.362  SWING_MISSED", "Player-1335-0A264B4C", "Sønike-Ysondre", "0xZZZ", "0x0", "Creature-0-1469-2549-12530-209333-000011428A", "Gnarlroot", "0x10a48", "0x0", "MISS", "1"];
+        let err = Event::parse(&line).unwrap_err();
+        assert!(matches!(&err, ParseError::BadField { index: 3, got, .. } if got == "0xZZZ"), "{:?}", err);
+        assert_eq!(err.raw(), line.join(","));
+        assert_eq!(err.problem(), "field 3 not hex u64");
+    }
+
+    #[test]
+    fn bad_suffix_field() {
+        // The same text earlier in the line shouldn't be mistaken for the bad field
+        let line = vec!["4/6 14:02:07.362  SWING_MISSED", "Player-1335-0A264B4C", "X", "0x514", "0x0", "Creature-0-1469-2549-12530-209333-000011428A", "Gnarlroot", "0x10a48", "0x0", "MISS", "X"];
+        let err = Event::parse(&line).unwrap_err();
+        assert!(matches!(&err, ParseError::BadField { index: 10, got, .. } if got == "X"), "{:?}", err);
+        assert_eq!(err.problem(), "field 10 not bool");
+    }
+
+    #[test]
+    fn bad_timestamp() {
+        let line = vec!["13/45 14:02:07.362  SWING_MISSED", "Player-1335-0A264B4C"];
+        let err = Event::parse(&line).unwrap_err();
+        assert!(matches!(err, ParseError::BadTimestamp { .. }), "{:?}", err);
+    }
+}
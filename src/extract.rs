@@ -0,0 +1,183 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDateTime;
+
+use crate::components::common::Actor;
+use crate::components::events::{Event, EventType};
+use crate::components::format::LogFormat;
+use crate::components::special::Special;
+use crate::consumers::scope::is_named;
+use crate::index::LogIndex;
+use crate::parser::EventParser;
+use crate::split::copy_range;
+
+/// Parses a timestamp as written in the log, eg. `4/6 14:01:05` or `4/6 14:01:05.250`
+pub fn parse_log_time(s: &str) -> Result<NaiveDateTime> {
+    // Log lines don't have a year, the parser assumes the same one
+    NaiveDateTime::parse_from_str(&["2024/", s.trim()].join(""), "%Y/%_m/%d %H:%M:%S%.f")
+        .with_context(|| format!("Expected a timestamp like \"4/6 14:01:05\", got {:?}", s))
+}
+
+/// Which lines to keep. Every filter given must match.
+#[derive(Debug, Default)]
+pub struct Filter {
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+    /// Only pulls of this boss, or the Nth pull
+    pub boss: Option<String>,
+    /// Only events this player is the source or target of. Special events are always kept.
+    pub player: Option<String>,
+}
+
+impl Filter {
+    fn in_time(&self, timestamp: NaiveDateTime) -> bool {
+        self.from.is_none_or(|from| timestamp >= from)
+            && self.to.is_none_or(|to| timestamp <= to)
+    }
+
+    fn is_player(&self, actor: &Option<Actor>) -> bool {
+        let (Some(player), Some(actor)) = (&self.player, actor) else { return false; };
+        is_named(&actor.name, player)
+    }
+
+    fn keep(&self, event: &Event) -> bool {
+        if !self.in_time(event.timestamp) { return false; }
+        match &event.event_type {
+            EventType::Standard { source, target, .. } if self.player.is_some() =>
+                self.is_player(source) || self.is_player(target),
+            _ => true,
+        }
+    }
+}
+
+/// Copies the lines matching the filter to a new combat log, returning the number of lines written.
+/// COMBAT_LOG_VERSION headers are always kept so the output can be parsed on its own.
+pub fn extract(log: &Path, out_path: &Path, filter: &Filter) -> Result<usize> {
+    let mut file = File::open(log)
+        .with_context(|| format!("Failed to open file: {:?}", log))?;
+    let mut out = BufWriter::new(File::create(out_path)
+        .with_context(|| format!("Failed to create file: {:?}", out_path))?);
+
+    // Only the selected pulls are scanned, each with the header in effect before it
+    let regions = match &filter.boss {
+        Some(boss) => {
+            let index = LogIndex::load_or_build(log)?;
+            let regions = index.select(boss);
+            if regions.is_empty() {
+                bail!("No pulls of {:?} found", boss);
+            }
+            regions.into_iter()
+                .map(|r| (index.header_before(r.range.start).map(|h| h.start..h.end), r.range, r.format))
+                .collect()
+        }
+        None => vec![(None, 0..file.metadata()?.len(), LogFormat::default())],
+    };
+
+    let mut written = 0;
+    let mut last_header = None;
+    for (header, range, format) in regions {
+        if let Some(header) = header.filter(|h| last_header.as_ref() != Some(h)) {
+            copy_range(&mut file, header.clone(), &mut out)?;
+            last_header = Some(header);
+            written += 1;
+        }
+        written += copy_lines(log, range, format, filter, &mut out)?;
+    }
+    out.flush()?;
+
+    Ok(written)
+}
+
+/// Copies the raw bytes of each kept line in the range, so they're written exactly as the game wrote them
+fn copy_lines(log: &Path, range: Range<u64>, format: LogFormat, filter: &Filter, out: &mut impl Write) -> Result<usize> {
+    let mut file = File::open(log)?;
+    file.seek(SeekFrom::Start(range.start))?;
+    let mut parser = EventParser::with_format(BufReader::new(file.take(range.end - range.start)), format)
+        .starting_at(range.start);
+
+    let mut raw = BufReader::new(File::open(log)?);
+    raw.seek(SeekFrom::Start(range.start))?;
+    let mut pos = range.start;
+
+    let mut written = 0;
+    // Lines that fail to parse have no timestamp, they go with the line before
+    let mut keep_failed = filter.player.is_none() && filter.from.is_none();
+    while let Some(event) = parser.next() {
+        let keep = match &event {
+            Ok(event) => {
+                keep_failed = filter.player.is_none() && filter.in_time(event.timestamp);
+                let header = matches!(event.event_type, EventType::Special { details: Special::CombatLogInfo { .. }, .. });
+                header || filter.keep(event)
+            }
+            Err(_) => keep_failed,
+        };
+        if !keep { continue; }
+
+        let (start, end) = parser.last_span();
+        raw.seek_relative((start - pos) as i64)?;
+        std::io::copy(&mut Read::by_ref(&mut raw).take(end - start), out)?;
+        pos = end;
+        written += 1;
+    }
+
+    Ok(written)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::extract::{extract, parse_log_time, Filter};
+    use crate::parser::EventParser;
+
+    const LOG: &str = "4/6 14:00:00.000  COMBAT_LOG_VERSION,20,ADVANCED_LOG_ENABLED,1,BUILD_VERSION,10.2.6,PROJECT_ID,1\n\
+4/6 14:00:01.000  ZONE_CHANGE,2549,\"Amirdrassil, the Dream's Hope\",16\n\
+4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:02:08.000  SWING_MISSED,Player-1335-0A264B4D,\"Other-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:12:00.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,1,162742\n\
+4/6 14:20:00.000  ENCOUNTER_START,2709,\"Igira the Cruel\",14,19,2549\n\
+4/6 14:25:00.000  ENCOUNTER_END,2709,\"Igira the Cruel\",14,19,0,300000\n";
+
+    fn run(name: &str, filter: &Filter) -> String {
+        let dir = std::env::temp_dir().join(format!("wowlogs_extract_test_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("WoWCombatLog.txt");
+        std::fs::write(&path, LOG).unwrap();
+
+        let out = dir.join("out.txt");
+        let written = extract(&path, &out, filter).unwrap();
+        let extracted = std::fs::read_to_string(out).unwrap();
+        assert_eq!(extracted.lines().count(), written);
+        assert!(EventParser::new(extracted.as_bytes()).all(|e| e.is_ok()));
+        extracted
+    }
+
+    fn lines(indices: &[usize]) -> String {
+        let lines = LOG.lines().collect::<Vec<_>>();
+        indices.iter().map(|&i| format!("{}\n", lines[i])).collect()
+    }
+
+    #[test]
+    fn by_time() {
+        let filter = Filter { from: Some(parse_log_time("4/6 14:02:00").unwrap()), to: Some(parse_log_time("4/6 14:12:00.000").unwrap()), ..Default::default() };
+        assert_eq!(run("time", &filter), lines(&[0, 3, 4, 5]));
+        assert!(parse_log_time("yesterday").is_err());
+    }
+
+    #[test]
+    fn by_boss() {
+        let filter = Filter { boss: Some("igira the cruel".into()), ..Default::default() };
+        assert_eq!(run("boss", &filter), lines(&[0, 6, 7]));
+    }
+
+    #[test]
+    fn by_player() {
+        let filter = Filter { boss: Some("Gnarlroot".into()), player: Some("Sønike".into()), ..Default::default() };
+        assert_eq!(run("player", &filter), lines(&[0, 2, 3, 5]));
+    }
+}
@@ -0,0 +1,151 @@
+//! C API for embedding the parser, eg. in an overlay. See `include/wowlogs_parser.h`.
+//! Events are handed over one at a time as JSON strings, which the caller frees with `wowlogs_string_free`.
+//! Only built with the `ffi` feature, eg. `cargo build --release --features ffi`.
+
+use std::collections::VecDeque;
+use std::ffi::{c_char, CStr, CString};
+use std::fs::File;
+use std::io::BufReader;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::components::events::Event;
+use crate::error::ParseFailure;
+use crate::parser::{ChunkParser, EventParser, ParseResult};
+
+enum Source {
+    File(Box<EventParser<BufReader<File>>>),
+    /// Text pushed in by the caller, eg. as it tails a live log
+    Chunks { parser: Box<ChunkParser>, pending: VecDeque<Result<Event, ParseFailure>> },
+}
+
+/// Opaque parser handle
+pub struct WowlogsParser {
+    source: Source,
+}
+
+impl WowlogsParser {
+    fn next(&mut self) -> Option<Result<Event, ParseFailure>> {
+        match &mut self.source {
+            Source::File(parser) => parser.next(),
+            Source::Chunks { pending, .. } => pending.pop_front(),
+        }
+    }
+}
+
+/// Opens a combat log file. Returns null if the file can't be opened.
+///
+/// # Safety
+/// `path` must be a valid, nul terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn wowlogs_parser_open(path: *const c_char) -> *mut WowlogsParser {
+    if path.is_null() { return ptr::null_mut(); }
+    let Ok(path) = CStr::from_ptr(path).to_str() else { return ptr::null_mut(); };
+    let Ok(file) = File::open(path) else { return ptr::null_mut(); };
+
+    let parser = EventParser::new(BufReader::new(file)).with_file(path);
+    Box::into_raw(Box::new(WowlogsParser { source: Source::File(Box::new(parser)) }))
+}
+
+/// Creates a parser fed with `wowlogs_parser_push`
+#[no_mangle]
+pub extern "C" fn wowlogs_parser_new() -> *mut WowlogsParser {
+    let source = Source::Chunks { parser: Box::default(), pending: VecDeque::new() };
+    Box::into_raw(Box::new(WowlogsParser { source }))
+}
+
+/// Adds text to a parser made with `wowlogs_parser_new`. Complete lines become available from `wowlogs_parser_next_event_json`.
+/// Returns false if the parser reads from a file, or the text isn't valid UTF-8.
+///
+/// # Safety
+/// `parser` must come from `wowlogs_parser_new` and not have been freed. `chunk` must be a valid, nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn wowlogs_parser_push(parser: *mut WowlogsParser, chunk: *const c_char) -> bool {
+    let (Some(parser), false) = (parser.as_mut(), chunk.is_null()) else { return false; };
+    let Source::Chunks { parser, pending } = &mut parser.source else { return false; };
+    let Ok(chunk) = CStr::from_ptr(chunk).to_str() else { return false; };
+
+    pending.extend(parser.push(chunk));
+    true
+}
+
+/// The next event as JSON, `{"event": ...}` or `{"error": ...}` for lines which failed to parse.
+/// Returns null once there are no more lines, for now. Free the string with `wowlogs_string_free`.
+///
+/// # Safety
+/// `parser` must come from `wowlogs_parser_open` or `wowlogs_parser_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn wowlogs_parser_next_event_json(parser: *mut WowlogsParser) -> *mut c_char {
+    let Some(parser) = parser.as_mut() else { return ptr::null_mut(); };
+    // A panic can't unwind into the caller, so it's treated as the end of the log
+    let Ok(Some(result)) = panic::catch_unwind(AssertUnwindSafe(|| parser.next())) else { return ptr::null_mut(); };
+
+    serde_json::to_string(&ParseResult::from(&result)).ok()
+        .and_then(|json| CString::new(json).ok())
+        .map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// # Safety
+/// `parser` must come from `wowlogs_parser_open` or `wowlogs_parser_new`, and not already have been freed. Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn wowlogs_parser_free(parser: *mut WowlogsParser) {
+    if !parser.is_null() { drop(Box::from_raw(parser)); }
+}
+
+/// # Safety
+/// `s` must be a string returned by this library, and not already have been freed. Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn wowlogs_string_free(s: *mut c_char) {
+    if !s.is_null() { drop(CString::from_raw(s)); }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{CStr, CString};
+
+    use crate::ffi::{wowlogs_parser_free, wowlogs_parser_new, wowlogs_parser_next_event_json, wowlogs_parser_open, wowlogs_parser_push, wowlogs_string_free};
+
+    unsafe fn next(parser: *mut super::WowlogsParser) -> Option<serde_json::Value> {
+        let s = wowlogs_parser_next_event_json(parser);
+        if s.is_null() { return None; }
+        let value = serde_json::from_str(CStr::from_ptr(s).to_str().unwrap()).unwrap();
+        wowlogs_string_free(s);
+        Some(value)
+    }
+
+    #[test]
+    fn chunks() {
+        unsafe {
+            let parser = wowlogs_parser_new();
+            let chunk = CString::new("4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n4/6 14:01:06.000  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0xZZZ,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n4/6 14:01").unwrap();
+            assert!(wowlogs_parser_push(parser, chunk.as_ptr()));
+
+            assert_eq!(next(parser).unwrap()["event"]["event_type"]["Special"]["name"], "ENCOUNTER_START");
+            assert!(next(parser).unwrap()["error"]["raw"].as_str().unwrap().contains("0xZZZ"));
+            // The cut off line waits for the rest
+            assert!(next(parser).is_none());
+
+            wowlogs_parser_free(parser);
+        }
+    }
+
+    #[test]
+    fn open() {
+        let path = std::env::temp_dir().join("wowlogs_ffi_test.txt");
+        std::fs::write(&path, "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n").unwrap();
+
+        unsafe {
+            let missing = CString::new("/no/such/WoWCombatLog.txt").unwrap();
+            assert!(wowlogs_parser_open(missing.as_ptr()).is_null());
+
+            let path = CString::new(path.to_str().unwrap()).unwrap();
+            let parser = wowlogs_parser_open(path.as_ptr());
+            assert!(next(parser).unwrap()["event"].is_object());
+            assert!(next(parser).is_none());
+            // Only chunk parsers can be pushed to
+            assert!(!wowlogs_parser_push(parser, path.as_ptr()));
+            wowlogs_parser_free(parser);
+        }
+    }
+}
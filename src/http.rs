@@ -0,0 +1,178 @@
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::consumers::encounters::History;
+
+/// Decodes %XX escapes, eg. non-ASCII player names
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn not_found(what: &str) -> (u16, Value) {
+    (404, json!({ "error": format!("{} not found", what) }))
+}
+
+/// Answers a GET request for the path
+///   /encounters                   every pull so far
+///   /encounters/{id}              a single pull, by 1-based pull number
+///   /encounters/{id}/damage       damage done per player in a pull
+///   /players/{name}/casts         casts per spell by a player
+pub fn route(history: &History, path: &str) -> (u16, Value) {
+    let path = path.split('?').next().unwrap_or_default();
+    let segments = path.split('/').filter(|s| !s.is_empty()).map(percent_decode).collect_vec();
+    let segments = segments.iter().map(String::as_str).collect_vec();
+
+    match segments.as_slice() {
+        ["encounters"] => (200, json!(history.pulls)),
+        ["encounters", id] => match id.parse().ok().and_then(|id| history.pull(id)) {
+            Some(pull) => (200, json!(pull)),
+            None => not_found("Encounter"),
+        },
+        ["encounters", id, "damage"] => match id.parse().ok().and_then(|id| history.pull(id)) {
+            Some(pull) => {
+                let rows = pull.damage.iter()
+                    .sorted_by_key(|(name, &v)| (std::cmp::Reverse(v), *name))
+                    .map(|(name, &v)| json!({ "name": name, "damage": v, "dps": v as f64 / pull.duration_secs() }))
+                    .collect_vec();
+                (200, json!(rows))
+            }
+            None => not_found("Encounter"),
+        },
+        ["players", name, "casts"] => match history.player_casts(name) {
+            Some(casts) => (200, json!(casts)),
+            None => not_found("Player"),
+        },
+        _ => not_found("Endpoint"),
+    }
+}
+
+/// A JSON API serving the history from a background thread
+pub struct ApiServer {
+    port: u16,
+    handle: JoinHandle<()>,
+}
+
+impl ApiServer {
+    /// Listens on localhost. Port 0 picks a free port.
+    pub fn start(history: Arc<Mutex<History>>, port: u16) -> Result<Self> {
+        let server = Server::http(("127.0.0.1", port))
+            .map_err(|e| anyhow!("Failed to listen on port {}: {}", port, e))?;
+        let port = server.server_addr().to_ip().map_or(port, |a| a.port());
+
+        let handle = thread::spawn(move || {
+            let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+
+            for request in server.incoming_requests() {
+                let (status, body) = match request.method() {
+                    Method::Get => route(&history.lock().unwrap(), request.url()),
+                    _ => (405, json!({ "error": "Only GET is supported" })),
+                };
+
+                let response = Response::from_string(body.to_string())
+                    .with_status_code(status)
+                    .with_header(content_type.clone());
+                let _ = request.respond(response);
+            }
+        });
+
+        Ok(Self { port, handle })
+    }
+
+    pub fn port(&self) -> u16 { self.port }
+
+    /// Keeps serving until the process is stopped
+    pub fn join(self) {
+        let _ = self.handle.join();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    use crate::consumers::{dispatch, EventHandler};
+    use crate::consumers::encounters::EncounterHistory;
+    use crate::http::{ApiServer, percent_decode, route};
+    use crate::parser::EventParser;
+
+    const LOG: &str = "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 14:01:06.000  SPELL_CAST_SUCCESS,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,0000000000000000,nil,0x80000000,0x80000000,1680,\"Whirlwind\",0x1,Player-1335-0A264B4C,0000000000000000,0,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70\n\
+4/6 14:02:01.000  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,0,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,6000,6000,-1,1,0,0,0,nil,nil,nil\n\
+4/6 14:03:05.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,0,120000\n";
+
+    #[test]
+    fn routes() {
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![];
+        let tracker = EncounterHistory::new();
+        let history = tracker.history();
+        handlers.push(Box::new(tracker));
+        EventParser::new(LOG.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        let history = history.lock().unwrap();
+        let (status, encounters) = route(&history, "/encounters");
+        assert_eq!(status, 200);
+        assert_eq!(encounters[0]["encounter_name"], "Gnarlroot");
+        assert_eq!(encounters[0]["success"], false);
+
+        let (_, damage) = route(&history, "/encounters/1/damage");
+        assert_eq!(damage[0]["name"], "Sønike-Ysondre");
+        assert_eq!(damage[0]["damage"], 6000);
+        assert_eq!(damage[0]["dps"], 50.);
+
+        let (_, casts) = route(&history, "/players/s%C3%B8nike-ysondre/casts");
+        assert_eq!(casts[0]["spell_name"], "Whirlwind");
+        assert_eq!(casts[0]["count"], 1);
+
+        assert_eq!(route(&history, "/encounters/2").0, 404);
+        assert_eq!(route(&history, "/players/Nobody/casts").0, 404);
+        assert_eq!(route(&history, "/nothing").0, 404);
+    }
+
+    #[test]
+    fn serves_json() {
+        let server = ApiServer::start(Default::default(), 0).unwrap();
+
+        let mut stream = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+        stream.write_all(b"GET /encounters HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.contains("application/json"), "{}", response);
+        assert!(response.ends_with("[]"), "{}", response);
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(percent_decode("S%C3%B8nike-Ysondre"), "Sønike-Ysondre");
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+}
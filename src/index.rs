@@ -0,0 +1,302 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Take};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::components::events::{Event, EventType};
+use crate::components::format::LogFormat;
+use crate::components::special::Special;
+use crate::parser::EventParser;
+use crate::summary::{Summary, Table};
+
+/// Events worth seeking to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Marker {
+    LogHeader(LogFormat),
+    ZoneChange { zone_name: String },
+    EncounterStart { encounter_id: u64, encounter_name: String },
+    EncounterEnd { encounter_id: u64, success: bool },
+}
+
+impl Marker {
+    fn from_event(event: &Event) -> Option<Self> {
+        let EventType::Special { details, .. } = &event.event_type else { return None; };
+
+        let marker = match details {
+            Special::CombatLogInfo { log_version, project_id, advanced_log_enabled, .. } =>
+                Self::LogHeader(LogFormat::new(*log_version, *project_id, *advanced_log_enabled)),
+            Special::ZoneChange { zone_name, .. } => Self::ZoneChange { zone_name: zone_name.clone() },
+            Special::EncounterStart(e) => Self::EncounterStart { encounter_id: e.encounter_id, encounter_name: e.encounter_name.clone() },
+            Special::EncounterEnd(e) => Self::EncounterEnd { encounter_id: e.encounter_id, success: e.success },
+            _ => return None
+        };
+
+        Some(marker)
+    }
+}
+
+/// A marker & the byte range of its line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub start: u64,
+    pub end: u64,
+    pub marker: Marker,
+}
+
+/// A single pull, and where to find it
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncounterRegion {
+    pub encounter_name: String,
+    pub range: Range<u64>,
+    /// Whether the pull was a kill, if it got an ENCOUNTER_END
+    pub success: Option<bool>,
+    pub format: LogFormat,
+}
+
+/// Byte offsets of encounter & zone boundaries in a log, persisted next to it so later runs can seek straight to them.
+/// Logs only ever get appended to, so an existing index is extended rather than rebuilt.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LogIndex {
+    /// Used to spot when the log has been replaced by a different one
+    first_line: String,
+    indexed_bytes: u64,
+    entries: Vec<IndexEntry>,
+}
+
+/// First line of the log, used to spot when a log has been replaced by a different one
+pub fn first_line(log: &Path) -> Result<String> {
+    let mut line = String::new();
+    BufReader::new(File::open(log)?).read_line(&mut line)?;
+    Ok(line.trim_end().to_string())
+}
+
+impl LogIndex {
+    /// Where the index for a log lives
+    pub fn path_for(log: &Path) -> PathBuf {
+        let mut path = OsString::from(log.as_os_str());
+        path.push(".idx");
+        PathBuf::from(path)
+    }
+
+    /// Loads the index for a log, indexing anything new & saving it back if needed
+    pub fn load_or_build(log: &Path) -> Result<Self> {
+        let first = first_line(log)
+            .with_context(|| format!("Failed to open file: {:?}", log))?;
+
+        let mut index = std::fs::read_to_string(Self::path_for(log)).ok()
+            .and_then(|s| serde_json::from_str::<Self>(&s).ok())
+            .filter(|i| i.first_line == first)
+            .unwrap_or_else(|| Self { first_line: first, ..Self::default() });
+
+        let indexed_bytes = index.indexed_bytes;
+        index.update(log)?;
+        if index.indexed_bytes != indexed_bytes {
+            index.save(log)?;
+        }
+
+        Ok(index)
+    }
+
+    pub fn save(&self, log: &Path) -> Result<()> {
+        let path = Self::path_for(log);
+        std::fs::write(&path, serde_json::to_string(self)?)
+            .with_context(|| format!("Failed to write file: {:?}", path))
+    }
+
+    /// Indexes the part of the log not yet covered
+    pub fn update(&mut self, log: &Path) -> Result<()> {
+        let mut file = File::open(log)
+            .with_context(|| format!("Failed to open file: {:?}", log))?;
+        let len = file.metadata()?.len();
+        if len < self.indexed_bytes {
+            *self = Self { first_line: first_line(log)?, ..Self::default() };
+        }
+
+        // A partially written last line will be picked up next time
+        let mut last_byte = [0u8];
+        let complete = len == 0 || {
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last_byte)?;
+            last_byte[0] == b'\n'
+        };
+
+        let base = self.indexed_bytes;
+        file.seek(SeekFrom::Start(base))?;
+        let mut parser = EventParser::with_format(BufReader::new(file), self.format_at(base))
+            .starting_at(base);
+
+        let mut last_start = base;
+        while let Some(event) = parser.next() {
+            let (start, end) = parser.last_span();
+            last_start = start;
+
+            if let Some(marker) = event.ok().as_ref().and_then(Marker::from_event) {
+                self.entries.push(IndexEntry { start, end, marker });
+            }
+        }
+
+        self.indexed_bytes = if complete { len } else { last_start };
+        self.entries.retain(|e| e.end <= self.indexed_bytes);
+
+        Ok(())
+    }
+
+    /// Layout of the log at the given offset
+    pub fn format_at(&self, byte: u64) -> LogFormat {
+        self.entries.iter()
+            .take_while(|e| e.start < byte)
+            .filter_map(|e| match e.marker {
+                Marker::LogHeader(format) => Some(format),
+                _ => None
+            })
+            .last()
+            .unwrap_or_default()
+    }
+
+    /// The last COMBAT_LOG_VERSION header before the given offset
+    pub fn header_before(&self, byte: u64) -> Option<&IndexEntry> {
+        self.entries.iter()
+            .take_while(|e| e.start < byte)
+            .filter(|e| matches!(e.marker, Marker::LogHeader(_)))
+            .last()
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// Every pull in the log. Pulls without an ENCOUNTER_END run up to whatever comes next.
+    pub fn encounters(&self) -> Vec<EncounterRegion> {
+        let mut regions = vec![];
+        let mut open: Option<(String, u64)> = None;
+
+        for entry in &self.entries {
+            match &entry.marker {
+                Marker::EncounterStart { encounter_name, .. } => {
+                    if let Some((name, start)) = open.take() {
+                        regions.push((name, start..entry.start, None));
+                    }
+                    open = Some((encounter_name.clone(), entry.start));
+                }
+                Marker::EncounterEnd { success, .. } => {
+                    if let Some((name, start)) = open.take() {
+                        regions.push((name, start..entry.end, Some(*success)));
+                    }
+                }
+                Marker::LogHeader(_) => {
+                    if let Some((name, start)) = open.take() {
+                        regions.push((name, start..entry.start, None));
+                    }
+                }
+                Marker::ZoneChange { .. } => {}
+            }
+        }
+        if let Some((name, start)) = open {
+            regions.push((name, start..self.indexed_bytes, None));
+        }
+
+        regions.into_iter()
+            .map(|(encounter_name, range, success)| EncounterRegion { format: self.format_at(range.start), encounter_name, range, success })
+            .collect()
+    }
+
+    /// The pulls & zone changes found, with their byte offsets
+    pub fn summary(&self) -> Summary {
+        let mut pulls = Table::new(&[("Pull", 5), ("Encounter", 30), ("Result", 7), ("Start", 12), ("End", 12)]).with_title("Pulls");
+        for (i, pull) in self.encounters().iter().enumerate() {
+            let result = match pull.success {
+                Some(true) => "Kill",
+                Some(false) => "Wipe",
+                None => "",
+            };
+            pulls.push(vec![(i + 1).into(), pull.encounter_name.as_str().into(), result.into(), pull.range.start.into(), pull.range.end.into()]);
+        }
+
+        let mut zones = Table::new(&[("Zone", 40), ("Start", 12)]).with_title("Zones");
+        for entry in &self.entries {
+            if let Marker::ZoneChange { zone_name } = &entry.marker {
+                zones.push(vec![zone_name.as_str().into(), entry.start.into()]);
+            }
+        }
+
+        let mut summary = Summary::new("Log index").with_table(pulls).with_table(zones);
+        summary.notes.push(format!("{} bytes indexed", self.indexed_bytes));
+        summary
+    }
+
+    /// Pulls matching the selector: either a 1-based pull number, or a boss name (case insensitive)
+    pub fn select(&self, selector: &str) -> Vec<EncounterRegion> {
+        let encounters = self.encounters();
+
+        match selector.parse::<usize>() {
+            Ok(n) => encounters.into_iter().skip(n.saturating_sub(1)).take((n > 0) as usize).collect(),
+            Err(_) => encounters.into_iter()
+                .filter(|e| e.encounter_name.eq_ignore_ascii_case(selector))
+                .collect(),
+        }
+    }
+}
+
+/// Parses just the given region of a log
+pub fn read_region(log: &Path, region: &EncounterRegion) -> Result<EventParser<Take<BufReader<File>>>> {
+    let mut file = File::open(log)
+        .with_context(|| format!("Failed to open file: {:?}", log))?;
+    file.seek(SeekFrom::Start(region.range.start))?;
+
+    Ok(EventParser::with_format(BufReader::new(file).take(region.range.end - region.range.start), region.format)
+        .with_file(log)
+        .starting_at(region.range.start))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    use crate::components::events::EventType;
+    use crate::index::{LogIndex, read_region};
+
+    const LOG: &str = "4/6 14:00:00.000  COMBAT_LOG_VERSION,20,ADVANCED_LOG_ENABLED,1,BUILD_VERSION,10.2.6,PROJECT_ID,1\n\
+4/6 14:00:01.000  ZONE_CHANGE,2549,\"Amirdrassil, the Dream's Hope\",16\n\
+4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:12:00.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,1,162742\n\
+4/6 14:20:00.000  ENCOUNTER_START,2709,\"Igira the Cruel\",14,19,2549\n";
+
+    #[test]
+    fn index_and_seek() {
+        let log = std::env::temp_dir().join("wowlogs_index_test.txt");
+        std::fs::write(&log, LOG).unwrap();
+        let _ = std::fs::remove_file(LogIndex::path_for(&log));
+
+        let index = LogIndex::load_or_build(&log).unwrap();
+        assert_eq!(index.entries().len(), 5);
+        assert!(LogIndex::path_for(&log).exists());
+
+        let pulls = index.select("gnarlroot");
+        assert_eq!(pulls.len(), 1);
+        let events = read_region(&log, &pulls[0]).unwrap().collect::<Vec<_>>();
+        assert_eq!(events.len(), 3);
+        assert!(events.iter().all(|e| e.is_ok()));
+        assert!(matches!(&events[1].as_ref().unwrap().event_type, EventType::Standard { name, .. } if &**name == "SWING_MISSED"));
+
+        // Appended lines extend the existing index
+        OpenOptions::new().append(true).open(&log).unwrap()
+            .write_all(b"4/6 14:25:00.000  ENCOUNTER_END,2709,\"Igira the Cruel\",14,19,0,300000\n").unwrap();
+        let index = LogIndex::load_or_build(&log).unwrap();
+        assert_eq!(index.entries().len(), 6);
+        assert_eq!(index.select("2")[0].encounter_name, "Igira the Cruel");
+        assert_eq!(read_region(&log, &index.select("2")[0]).unwrap().count(), 2);
+
+        let summary = index.summary();
+        let pulls = &summary.table("Pulls").unwrap().rows;
+        assert_eq!(pulls[0][1..3], ["Gnarlroot".into(), "Kill".into()]);
+        assert_eq!(pulls[1][1..3], ["Igira the Cruel".into(), "Wipe".into()]);
+        assert_eq!(summary.table("Zones").unwrap().rows[0][0], "Amirdrassil, the Dream's Hope".into());
+    }
+}
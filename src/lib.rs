@@ -0,0 +1,15 @@
+//! The parsing core: log lines in, typed events out.
+//! Kept free of file watching, servers & the CLI so it builds for wasm32-unknown-unknown.
+
+pub mod components;
+pub mod context;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod parser;
+pub mod traits;
+pub mod utils;
+#[cfg(feature = "wasm")]
+pub mod wasm;
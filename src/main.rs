@@ -1,22 +1,87 @@
 use std::fmt::Debug;
 use std::fs::File;
-use std::io::{BufReader, Read, Seek, SeekFrom};
-use std::path::Path;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
-use clap::Parser;
+use anyhow::{bail, Context, Result};
+use clap::{CommandFactory, Parser};
+use clap::error::ErrorKind;
 use itertools::Itertools;
 use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
+// The parsing core lives in the library so it can also be built for the browser
+use wowlogs_parser::{components, context, error, parser, utils};
+use wowlogs_parser::mmap::MmapParser;
 
-use crate::cli::{Cli, OutputMode, ReadMode};
-use crate::consumers::{DamageTracker, EventHandler, FileLogger, NulLogger, StdLogger};
-use crate::parser::EventParser;
+use crate::checkpoint::Checkpoints;
+use crate::cli::{Cli, OutputMode, ReadMode, Sink, Tracker};
+use crate::components::events::{Event, EventType};
+use crate::components::format::LogFormat;
+use crate::components::special::Special;
+use crate::context::Session;
+use crate::consumers::{DamageTracker, dispatch, EventHandler, FileLogger, JsonLogger, NulLogger, StdLogger};
+use crate::consumers::abilities::AbilityBreakdown;
+use crate::consumers::alerts::{AlertEngine, AlertRules};
+use crate::consumers::avoidable::{AvoidableDamage, AvoidableRules};
+use crate::consumers::battleres::BattleResTracker;
+use crate::consumers::compare::PullComparison;
+use crate::consumers::cooldowns::{CooldownRules, CooldownUsage};
+use crate::consumers::deaths::{DeathRecap, MechanicRules};
+use crate::consumers::emotes::BossEmoteTracker;
+use crate::consumers::encounters::EncounterHistory;
+use crate::consumers::energize::EnergizeWaste;
+use crate::consumers::gear::{GearCheck, ItemDatabase};
+use crate::consumers::healing::HealingBreakdown;
+use crate::consumers::hits::HitDistribution;
+use crate::consumers::hooks::PullHooks;
+use crate::consumers::keystones::{self, KeyHistory};
+use crate::consumers::lag::LagMonitor;
+use crate::consumers::movement::MovementTracker;
+use crate::consumers::mythic::MythicPlusRuns;
+use crate::consumers::obs::{ObsOverlay, ObsTemplates};
+use crate::consumers::overheal::OverhealAnalysis;
+use crate::consumers::percentiles::Percentiles;
+use crate::consumers::phases::PhaseRules;
+use crate::consumers::pruning::Pruner;
+use crate::consumers::registry::HandlerRegistry;
+use crate::consumers::pvp::PvpMatchTracker;
+use crate::consumers::report::HtmlReport;
+use crate::consumers::scope::PlayerScope;
+use crate::consumers::segments::CombatSegmenter;
+use crate::consumers::serve::{Broadcaster, EventBroadcast};
+use crate::consumers::spawns::AddSpawns;
+use crate::consumers::spikes::TankSpikes;
+use crate::consumers::sqlite::SqliteLogger;
+use crate::consumers::stats::StatsCollector;
+use crate::consumers::status::StatusLine;
+use crate::consumers::summons::SummonUptime;
+use crate::consumers::support::SupportDamageTracker;
+use crate::consumers::threat::ThreatTracker;
+use crate::consumers::timeline::ActorTimeline;
+use crate::consumers::timeseries::TimeSeriesExport;
+use crate::http::ApiServer;
+use crate::index::LogIndex;
+use crate::parser::{EventParser, ParseOptions};
+use crate::summary::{render_batch, render_handlers};
+use crate::utils::wildcard_match;
 
-mod traits;
-mod utils;
-mod parser;
+mod anonymize;
+mod bench;
+mod checkpoint;
+mod diagnose;
+mod extract;
+mod http;
+mod index;
+mod query;
+mod wcl;
+mod retry;
+mod schema;
+mod split;
+mod summary;
+mod upload;
 mod consumers;
-mod components;
 mod cli;
 
 
@@ -25,86 +90,809 @@ fn parse_file<R: Read>(buf_reader: R, handlers: &mut [Box<dyn EventHandler>]) {
     let reader = EventParser::new(buf_reader);
 
     reader
-        .for_each(|e| {
-            handlers.iter_mut()
-                .for_each(|h| {
-                    h.handle(&e);
-                });
-        });
+        .for_each(|e| dispatch(handlers, &e));
 }
 
-/// Processes an entire file
-fn process<P: AsRef<Path> + Debug>(path: P, handlers: &mut [Box<dyn EventHandler>]) -> Result<()> {
-    let file = File::open(&path)
+/// Lines read from the start of a file before deciding it isn't a combat log
+const SMOKE_TEST_LINES: usize = 10;
+
+/// Fails fast on files which aren't combat logs, rather than streaming out a parse failure for every line.
+/// A log is accepted if any of its first few lines parse, so fragments without a COMBAT_LOG_VERSION header still work.
+fn check_combat_log(path: &Path, options: ParseOptions) -> Result<()> {
+    let file = File::open(path)
         .with_context(|| format!("Failed to open file: {:?}", path))?;
 
-    let reader = EventParser::new(file);
+    let events = EventParser::new(file).with_file(path).with_options(options).take(SMOKE_TEST_LINES).collect_vec();
+    if events.is_empty() || events.iter().any(Result::is_ok) { return Ok(()); }
 
-    reader
-        .for_each(|e| {
-            handlers.iter_mut()
-                .for_each(|h| {
-                    h.handle(&e);
-                });
-        });
+    let first = events.iter().find_map(|e| e.as_ref().err()).map(|e| e.to_string()).unwrap_or_default();
+    bail!("{:?} doesn't look like a combat log: it has no COMBAT_LOG_VERSION header and none of its first {} lines parse.\n\
+        First failure: {}\n\
+        Combat logs are written to World of Warcraft/_retail_/Logs/WoWCombatLog-*.txt while combat logging is enabled (/combatlog).",
+        path, events.len(), first)
+}
+
+/// Processes an entire file, or the rest of it after the checkpoint
+fn process<P: AsRef<Path> + Debug>(path: P, options: ParseOptions, handlers: &mut [Box<dyn EventHandler>], segmenter: &mut CombatSegmenter, checkpoints: Option<&mut Checkpoints>) -> Result<()> {
+    let path = path.as_ref();
+    check_combat_log(path, options)?;
+
+    let mut parser = MmapParser::open(path)?.with_options(options);
+    if checkpoints.is_some() {
+        // A line still being written is picked up complete by the next run
+        parser = parser.complete_lines_only();
+    }
+    if let Some(position) = checkpoints.as_deref().and_then(|c| c.resume(path)) {
+        parser = parser.starting_at(position.byte_offset, position.format);
+    }
+
+    let mut checkpoints = checkpoints;
+    let mut last_timestamp = None;
+    while let Some(e) = parser.next() {
+        if let Ok(event) = &e { last_timestamp = Some(event.timestamp); }
+        segmenter.dispatch(handlers, &e);
+
+        // Saved after each encounter too, so an interrupted run doesn't start the whole log over
+        let encounter_end = matches!(&e, Ok(Event { event_type: EventType::Special { details: Special::EncounterEnd(_), .. }, .. }));
+        if let Some(checkpoints) = checkpoints.as_deref_mut().filter(|_| encounter_end) {
+            checkpoints.record(path, parser.position(), last_timestamp, *parser.log_format())?;
+            checkpoints.save()?;
+        }
+    }
+
+    if let Some(checkpoints) = checkpoints {
+        checkpoints.record(path, parser.position(), last_timestamp, *parser.log_format())?;
+        checkpoints.save()?;
+    }
+
+    Ok(())
+}
+
+/// Processes only the selected encounters, seeking to them using the log's index
+fn process_encounters(path: &Path, selector: &str, options: ParseOptions, handlers: &mut [Box<dyn EventHandler>], segmenter: &mut CombatSegmenter) -> Result<()> {
+    check_combat_log(path, options)?;
+    let index = LogIndex::load_or_build(path)?;
+    let regions = index.select(selector);
+    if regions.is_empty() {
+        bail!("No encounters matching {:?}", selector);
+    }
+
+    for region in &regions {
+        index::read_region(path, region)?
+            .with_options(options)
+            .for_each(|e| segmenter.dispatch(handlers, &e));
+    }
 
     Ok(())
 }
 
 
-/// Watches a logile and parses them as they stream in
-fn watch<P: AsRef<Path>>(path: P, handlers: &mut [Box<dyn EventHandler>]) -> Result<()> {
-    let (tx, rx) = std::sync::mpsc::channel();
+fn is_combat_log(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with("WoWCombatLog") && n.ends_with(".txt"))
+}
+
+/// Expands globs in the file names of the given paths, and Logs directories to every combat log in them if `dirs` is set.
+/// Matches are sorted by name, which is the order the game wrote them in within a year.
+fn expand_logs(paths: &[PathBuf], dirs: bool) -> Result<Vec<PathBuf>> {
+    let mut logs = vec![];
+    for path in paths {
+        let name = path.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+        let pattern = if dirs && path.is_dir() {
+            "WoWCombatLog*.txt".to_string()
+        } else if name.contains(['*', '?']) {
+            name
+        } else {
+            logs.push(path.clone());
+            continue;
+        };
 
+        let dir = if path.is_dir() { path.as_path() } else { path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new(".")) };
+        let matches = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read directory: {:?}", dir))?
+            .filter_map(Result::ok)
+            .map(|e| e.path())
+            .filter(|p| p.is_file() && p.file_name().is_some_and(|n| wildcard_match(&pattern, &n.to_string_lossy(), '*', '?')))
+            .sorted()
+            .collect_vec();
+        if matches.is_empty() {
+            bail!("No logs matching {:?}", path);
+        }
+        logs.extend(matches);
+    }
+
+    Ok(logs)
+}
+
+/// Most recently modified combat log in a Logs directory
+fn newest_log(dir: &Path) -> Result<Option<PathBuf>> {
+    let newest = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory: {:?}", dir))?
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| is_combat_log(p))
+        .filter_map(|p| Some((p.metadata().ok()?.modified().ok()?, p)))
+        .max()
+        .map(|(_, p)| p);
+
+    Ok(newest)
+}
+
+/// Shows the trackers in watch mode, at most once per interval.
+/// Updates skipped during a burst of writes to the log are shown once the interval is up.
+struct Refresh<F> {
+    render: F,
+    interval: Duration,
+    last: Option<Instant>,
+    stale: bool,
+}
+
+impl<F> Refresh<F>
+where
+    F: FnMut(&[Box<dyn EventHandler>]) -> Result<()>,
+{
+    fn new(render: F, interval: Option<Duration>) -> Self {
+        Self { render, interval: interval.unwrap_or_default(), last: None, stale: false }
+    }
+
+    /// New events have been handled
+    fn updated(&mut self, handlers: &[Box<dyn EventHandler>], now: Instant) -> Result<()> {
+        self.stale = true;
+        self.flush(handlers, now)
+    }
+
+    /// Shows the trackers if they've changed & the interval is up
+    fn flush(&mut self, handlers: &[Box<dyn EventHandler>], now: Instant) -> Result<()> {
+        if !self.stale || self.last.is_some_and(|t| now - t < self.interval) { return Ok(()); }
+        self.last = Some(now);
+        self.stale = false;
+        (self.render)(handlers)
+    }
+
+    /// How long to wait for the log to change before showing a skipped update
+    fn timeout(&self, now: Instant) -> Duration {
+        match (self.stale, self.last) {
+            (true, Some(t)) => self.interval.saturating_sub(now - t),
+            (true, None) => Duration::ZERO,
+            (false, _) => Duration::MAX,
+        }
+    }
+}
+
+enum WatchMessage {
+    Changed(Box<notify::Result<notify::Event>>),
+    Interrupt,
+}
+
+/// What wakes the watcher up: the log changing or an interrupt arriving on `messages`, or a skipped update being due
+struct Wakeups<F> {
+    messages: Receiver<WatchMessage>,
+    refresh: Refresh<F>,
+}
+
+/// Sends changes to the log, or the files in a Logs directory, until the returned watcher is dropped
+fn watch_changes(path: &Path, changes: Sender<WatchMessage>) -> Result<RecommendedWatcher> {
     // Automatically select the best implementation for your platform.
     // You can also access each implementation directly e.g. INotifyWatcher.
-    let mut watcher = RecommendedWatcher::new(tx, Config::default())?;
+    let mut watcher = RecommendedWatcher::new(move |e| { let _ = changes.send(WatchMessage::Changed(Box::new(e))); }, Config::default())?;
 
     // Add a path to be watched. All files and directories at that path and
     // below will be monitored for changes.
-    watcher.watch(path.as_ref(), RecursiveMode::NonRecursive)?;
+    watcher.watch(path, RecursiveMode::NonRecursive)?;
+    Ok(watcher)
+}
+
+/// Stop watching between batches of lines on Ctrl-C, so the trackers are finished & reported as usual.
+/// A second Ctrl-C, or one after watching has stopped, quits straight away
+fn stop_on_ctrl_c(interrupt: Sender<WatchMessage>) {
+    let interrupted = AtomicBool::new(false);
+    if let Err(e) = ctrlc::set_handler(move || {
+        if interrupted.swap(true, Ordering::SeqCst) || interrupt.send(WatchMessage::Interrupt).is_err() {
+            std::process::exit(130);
+        }
+    }) {
+        eprintln!("Ctrl-C won't stop cleanly: {e}");
+    }
+}
+
+/// Watches a logfile and parses them as they stream in.
+/// If given a Logs directory, follows the newest combat log & switches over when the game starts a new one.
+/// The changes come from [watch_changes], & the handlers are shown after each new batch of lines.
+/// With checkpoints, picks up where the last run stopped, so lines written while it wasn't running are read too.
+/// Tails the log until an interrupt arrives, or every sender of the messages goes away
+fn watch<P, F>(path: P, options: ParseOptions, handlers: &mut [Box<dyn EventHandler>], pruner: &mut Pruner, segmenter: &mut CombatSegmenter, mut checkpoints: Option<&mut Checkpoints>, wakeups: Wakeups<F>) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnMut(&[Box<dyn EventHandler>]) -> Result<()>,
+{
+    let path = path.as_ref();
+    let Wakeups { messages, mut refresh } = wakeups;
+
+    let watching_dir = path.is_dir();
+    let mut current = if watching_dir { newest_log(path)? } else { Some(path.to_path_buf()) };
 
     // Get the number of bytes currently in the file - we only want to tail it
-    let mut prev_size = File::open(path)?.metadata()?.len();
+    let mut prev_size = match &current {
+        Some(p) => File::open(p)?.metadata()?.len(),
+        None => 0
+    };
+    let mut format = LogFormat::default();
+    let mut session = Session::default();
+    if let Some(position) = current.as_deref().zip(checkpoints.as_deref()).and_then(|(log, c)| c.resume(log)) {
+        prev_size = position.byte_offset;
+        format = position.format;
+    }
+
+    loop {
+        let event = match messages.recv_timeout(refresh.timeout(Instant::now())) {
+            Ok(WatchMessage::Changed(changed)) => match *changed {
+                Ok(event) => event,
+                Err(_) => continue,
+            },
+            Ok(WatchMessage::Interrupt) => {
+                eprintln!("Stopping");
+                break;
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                refresh.flush(handlers, Instant::now())?;
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        // A new log has been started
+        if watching_dir {
+            if let Some(new) = event.paths.iter().find(|p| is_combat_log(p) && Some(*p) != current.as_ref()) {
+                if newest_log(path)?.as_ref() == Some(new) {
+                    eprintln!("Switching to {:?}", new);
+                    current = Some(new.clone());
+                    prev_size = 0;
+                    format = LogFormat::default();
+                    session = Session::default();
+                }
+            }
+        }
+
+        let Some(current) = &current else { continue; };
+        if !event.paths.contains(current) { continue; }
+
+        let lines = complete_lines(current, prev_size)?;
+        if lines.is_empty() { continue; }
+
+        let mut parser = EventParser::with_format(&lines[..], format)
+            .with_file(current)
+            .starting_at(prev_size)
+            .with_options(options)
+            .with_session(session);
+        let mut last_timestamp = None;
+        parser.by_ref()
+            .for_each(|e| {
+                if let Ok(event) = &e { last_timestamp = Some(event.timestamp); }
+                pruner.observe(&e);
+                segmenter.dispatch(handlers, &e);
+            });
+        format = *parser.log_format();
+        session = parser.into_session();
+        pruner.prune(handlers);
+        refresh.updated(handlers, Instant::now())?;
+
+        prev_size += lines.len() as u64;
+        if let Some(checkpoints) = checkpoints.as_deref_mut() {
+            // Losing a checkpoint shouldn't stop the watcher
+            if let Err(e) = checkpoints.record(current, prev_size, last_timestamp, format).and_then(|_| checkpoints.save()) {
+                eprintln!("{e:#}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Lines written after the offset. A partially written last line is left until the rest of it arrives.
+fn complete_lines(path: &Path, from: u64) -> Result<Vec<u8>> {
+    let mut file = File::open(path)
+        .with_context(|| format!("Failed to open file: {:?}", path))?;
+    file.seek(SeekFrom::Start(from))?;
+
+    let mut lines = vec![];
+    file.read_to_end(&mut lines)?;
+    let complete = lines.iter().rposition(|&b| b == b'\n').map_or(0, |end| end + 1);
+    lines.truncate(complete);
+
+    Ok(lines)
+}
 
+/// Converts a Warcraft Logs export into a combat log file
+fn import_wcl(input: &Path, output: &Path) -> Result<()> {
+    let json = std::fs::read_to_string(input)
+        .with_context(|| format!("Failed to open file: {:?}", input))?;
+    let conversion = wcl::convert(&json)?;
 
-    for event in rx.iter().filter_map(Result::ok) {
-        let mut file = File::open(&event.paths[0])?;
-        let new_size = file.metadata()?.len();
+    std::fs::write(output, conversion.lines.join("\n") + "\n")
+        .with_context(|| format!("Failed to write file: {:?}", output))?;
 
-        file.seek(SeekFrom::Current(prev_size as i64))?;
+    println!("Wrote {} lines to {:?}", conversion.lines.len(), output);
+    conversion.skipped.iter()
+        .sorted()
+        .for_each(|(event_type, n)| println!("Skipped {} {:?} events", n, event_type));
+
+    Ok(())
+}
 
-        parse_file(BufReader::new(file), handlers);
-        println!("{}", handlers.iter().filter_map(|h| h.display()).join("\n---\n"));
+/// Re-parses a failed lines file & reports what's been fixed
+fn retry_failed(failed_path: &Path, remaining: Option<&Path>) -> Result<()> {
+    let report = retry::retry(failed_path)?;
+    println!("{}", report.display());
 
-        prev_size = new_size;
+    if let Some(path) = remaining {
+        report.write_still_failing(path)?;
+        println!("Wrote {} still failing lines to {:?}", report.still_failing.len(), path);
     }
 
     Ok(())
 }
 
+/// `append` picks up an earlier export where the sink supports it, rather than replacing it
+fn sink_handler(sink: &Sink, append: bool) -> Box<dyn EventHandler> {
+    let handler: Result<Box<dyn EventHandler>> = match sink {
+        Sink::Std => Ok(Box::new(StdLogger::new())),
+        Sink::None => Ok(Box::new(NulLogger)),
+        Sink::Json(path) if append => JsonLogger::append(path).map(|l| Box::new(l) as _),
+        Sink::Json(path) => JsonLogger::new(path).map(|l| Box::new(l) as _),
+        Sink::Sqlite(path) if append => SqliteLogger::append(path).map(|l| Box::new(l) as _),
+        Sink::Sqlite(path) => SqliteLogger::new(path).map(|l| Box::new(l) as _),
+        Sink::Html(path) => Ok(Box::new(HtmlReport::new(path.clone()))),
+        Sink::File { good_path, failed_path } => FileLogger::new(good_path, failed_path).map(|l| Box::new(l) as _),
+    };
+    handler.unwrap_or_else(|e| {
+        eprintln!("{e:#}");
+        std::process::exit(1);
+    })
+}
+
+fn tracker_name(tracker: Tracker) -> &'static str {
+    match tracker {
+        Tracker::Damage => "damage",
+        Tracker::Healing => "healing",
+        Tracker::Abilities => "abilities",
+        Tracker::Hits => "hits",
+        Tracker::Overheal => "overheal",
+        Tracker::Deaths => "deaths",
+        Tracker::Timeline => "timeline",
+        Tracker::Adds => "adds",
+        Tracker::MythicPlus => "mythic-plus",
+        Tracker::BattleRes => "battle-res",
+        Tracker::Movement => "movement",
+        Tracker::Threat => "threat",
+        Tracker::Avoidable => "avoidable",
+        Tracker::Summons => "summons",
+        Tracker::Energize => "energize",
+        Tracker::Gear => "gear",
+        Tracker::Pvp => "pvp",
+        Tracker::Stats => "stats",
+        Tracker::Emotes => "emotes",
+        Tracker::Cooldowns => "cooldowns",
+        Tracker::TankSpikes => "tank-spikes",
+        Tracker::Support => "support",
+    }
+}
+
+/// Every tracker which can be selected on the command line, configured from the arguments
+fn tracker_registry<'a>(
+    args: &'a Cli,
+    percentiles: &'a Percentiles,
+    mechanic_rules: &'a MechanicRules,
+    avoidable_rules: &'a AvoidableRules,
+    cooldown_rules: &'a CooldownRules,
+    item_db: &'a ItemDatabase,
+) -> Result<HandlerRegistry<'a>> {
+    let mut registry = HandlerRegistry::new();
+    registry.register("damage", &[], || Box::new(DamageTracker::new().with_percentiles(percentiles.clone()).with_priority_targets(&args.priority_targets)))?;
+    registry.register("healing", &[], || Box::new(HealingBreakdown::new().with_periodic(args.periodic).with_percentiles(percentiles.clone())))?;
+    registry.register("abilities", &[], || Box::new(AbilityBreakdown::new().with_periodic(args.periodic)))?;
+    registry.register("hits", &[], || Box::new(HitDistribution::new().with_periodic(args.periodic)))?;
+    registry.register("overheal", &[], || Box::new(OverhealAnalysis::new()))?;
+    registry.register("deaths", &[], || Box::new(DeathRecap::new(mechanic_rules.clone())))?;
+    registry.register("timeline", &[], || Box::new(ActorTimeline::new()))?;
+    registry.register("adds", &[], || Box::new(AddSpawns::new()))?;
+    registry.register("mythic-plus", &[], || Box::new(MythicPlusRuns::new().with_death_penalty(args.death_penalty_secs)))?;
+    registry.register("battle-res", &[], || Box::new(BattleResTracker::new()))?;
+    registry.register("movement", &[], || Box::new(MovementTracker::new()))?;
+    registry.register("threat", &[], || Box::new(ThreatTracker::new()))?;
+    registry.register("avoidable", &[], || Box::new(AvoidableDamage::new(avoidable_rules.clone())))?;
+    registry.register("summons", &[], || Box::new(SummonUptime::new()))?;
+    registry.register("energize", &[], || Box::new(EnergizeWaste::new()))?;
+    registry.register("gear", &[], || Box::new(GearCheck::new(item_db.clone())))?;
+    registry.register("pvp", &[], || Box::new(PvpMatchTracker::new()))?;
+    registry.register("stats", &[], || Box::new(StatsCollector::new()))?;
+    registry.register("emotes", &[], || Box::new(BossEmoteTracker::new()))?;
+    registry.register("cooldowns", &[], || Box::new(CooldownUsage::new(cooldown_rules.clone())))?;
+    registry.register("tank-spikes", &[], || Box::new(TankSpikes::new()))?;
+    registry.register("support", &[], || Box::new(SupportDamageTracker::new()))?;
+    Ok(registry)
+}
+
 fn execute(args: Cli) {
-    // Handlers
-    let mut handlers: Vec<Box<dyn EventHandler>> = vec![
-        Box::new(DamageTracker::new()),
-    ];
-
-    // Output mode
-    handlers.push(match args.output_mode {
-        OutputMode::Std => Box::new(StdLogger::new()),
-        OutputMode::File { good_path, failed_path } =>
-            Box::new(FileLogger::new(&good_path, &failed_path).unwrap()),
-        OutputMode::None => Box::new(NulLogger)
+    // Tools which don't stream a log file
+    if let Some(OutputMode::Bench { repeats, assert_min_throughput }) = args.output_mode {
+        if let Err(e) = bench::assert_throughput(repeats, assert_min_throughput) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(OutputMode::WclImport { input, output }) = &args.output_mode {
+        if let Err(e) = import_wcl(input, output) {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(OutputMode::Schema { format }) = args.output_mode {
+        match schema::schema().and_then(|s| s.render(format)) {
+            Ok(s) => println!("{}", s),
+            Err(e) => {
+                eprintln!("{e:#}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(OutputMode::MplusHistory { path }) = &args.output_mode {
+        match keystones::load_history(path) {
+            Ok(records) => println!("{}", keystones::history_report(&records).render(args.summary_format)),
+            Err(e) => {
+                eprintln!("{e:#}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(OutputMode::Retry { failed_path, remaining }) = &args.output_mode {
+        if let Err(e) = retry_failed(failed_path, remaining.as_deref()) {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let (Some(wowlog_path), Some(read_mode)) = (args.wowlog_paths.first().cloned(), args.read_mode.clone()) else {
+        Cli::command()
+            .error(ErrorKind::MissingRequiredArgument, "<WOWLOG_PATHS> and <READ_MODE> are required for this output mode")
+            .exit()
+    };
+    if args.output_mode.is_none() && args.outs.is_empty() {
+        Cli::command()
+            .error(ErrorKind::MissingSubcommand, "An <OUTPUT_MODE> or at least one --out is required")
+            .exit()
+    }
+    let options = ParseOptions { game_version: args.game_version, lenient: args.lenient, strict: args.strict, recover: args.recover };
+    let logs = expand_logs(&args.wowlog_paths, matches!(read_mode, ReadMode::Process)).unwrap_or_else(|e| {
+        eprintln!("{e:#}");
+        std::process::exit(1);
+    });
+    let tool = matches!(args.output_mode, Some(OutputMode::Anonymize { .. } | OutputMode::Extract { .. } | OutputMode::Upload { .. } | OutputMode::SplitFiles { .. } | OutputMode::Query { .. } | OutputMode::Index | OutputMode::Diagnose | OutputMode::Compare));
+    if logs.len() > 1 && (tool || matches!(read_mode, ReadMode::Watch)) {
+        Cli::command()
+            .error(ErrorKind::ArgumentConflict, "Several logs can only be read by process mode trackers")
+            .exit()
+    }
+    if let Some(OutputMode::Anonymize { output }) = &args.output_mode {
+        match anonymize::anonymize(&wowlog_path, output) {
+            Ok(players) => eprintln!("Anonymized {} players to {:?}", players, output),
+            Err(e) => {
+                eprintln!("{e:#}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(OutputMode::Extract { output, from, to, boss, player }) = &args.output_mode {
+        let filter = extract::Filter { from: *from, to: *to, boss: boss.clone(), player: player.clone() };
+        match extract::extract(&wowlog_path, output, &filter) {
+            Ok(written) => eprintln!("Wrote {} lines to {:?}", written, output),
+            Err(e) => {
+                eprintln!("{e:#}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(OutputMode::Upload { url, token, chunk_mb }) = &args.output_mode {
+        match upload::upload(&wowlog_path, url, token.as_deref(), chunk_mb * 1024 * 1024) {
+            Ok(chunks) => eprintln!("Uploaded {} chunks to {}", chunks, url),
+            Err(e) => {
+                eprintln!("{e:#}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(OutputMode::Query { sql }) = &args.output_mode {
+        match query::query(&wowlog_path, sql, args.encounter.as_deref()) {
+            Ok(summary) => println!("{}", summary.render(args.summary_format)),
+            Err(e) => {
+                eprintln!("{e:#}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if matches!(args.output_mode, Some(OutputMode::Index)) {
+        match LogIndex::load_or_build(&wowlog_path) {
+            Ok(index) => {
+                eprintln!("Indexed to {:?}", LogIndex::path_for(&wowlog_path));
+                println!("{}", index.summary().render(args.summary_format));
+            }
+            Err(e) => {
+                eprintln!("{e:#}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if matches!(args.output_mode, Some(OutputMode::Diagnose)) {
+        match diagnose::diagnose(&wowlog_path, options) {
+            Ok(diagnosis) => println!("{}", diagnosis.summary().render(args.summary_format)),
+            Err(e) => {
+                eprintln!("{e:#}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    let phase_rules = args.phase_rules.as_deref().map(PhaseRules::load).transpose().unwrap_or_else(|e| {
+        eprintln!("{e:#}");
+        std::process::exit(1);
     });
+    if let Some(OutputMode::Compare) = &args.output_mode {
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(PullComparison::new())];
+        let mut segmenter = CombatSegmenter::new(None).with_phases(phase_rules);
+        if let Err(e) = process(&wowlog_path, options, &mut handlers, &mut segmenter, None) {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }
+        handlers.iter_mut().for_each(|h| h.on_finish());
+        println!("{}", render_handlers(&handlers, args.summary_format));
+        return;
+    }
+    if let Some(OutputMode::SplitFiles { dir, json }) = &args.output_mode {
+        match split::split_encounters(&wowlog_path, dir, *json) {
+            Ok(written) => eprintln!("Wrote {} pulls to {:?}", written.len(), dir),
+            Err(e) => {
+                eprintln!("{e:#}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let mechanic_rules = match &args.mechanic_rules {
+        Some(path) => MechanicRules::load(path).unwrap_or_else(|e| {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }),
+        None => Default::default(),
+    };
+
+    let avoidable_rules = match &args.avoidable_rules {
+        Some(path) => AvoidableRules::load(path).unwrap_or_else(|e| {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }),
+        None => Default::default(),
+    };
+
+    let cooldown_rules = match &args.cooldown_rules {
+        Some(path) => CooldownRules::load(path).unwrap_or_else(|e| {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }),
+        None => Default::default(),
+    };
+
+    let item_db = match &args.item_db {
+        Some(path) => ItemDatabase::load(path).unwrap_or_else(|e| {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }),
+        None => Default::default(),
+    };
+
+    let mut percentiles = Percentiles::default();
+    if let Some(path) = &args.percentile_reference {
+        percentiles.extend_from_file(path).unwrap_or_else(|e| {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        });
+    }
+
+    // Handlers
+    let trackers = || -> Vec<Box<dyn EventHandler>> {
+        if args.status_line {
+            return vec![Box::new(StatusLine::new())];
+        }
+        let registry = tracker_registry(&args, &percentiles, &mechanic_rules, &avoidable_rules, &cooldown_rules, &item_db).unwrap();
+        let names = args.trackers.iter().map(|t| tracker_name(*t)).collect_vec();
+        registry.build(names).unwrap_or_else(|e| {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        })
+    };
+    let mut handlers = trackers();
+
+    // Pull hooks are for live use, not for pulls from long ago
+    let hooked = args.on_pull_start.is_some() || args.on_pull_end.is_some() || args.pull_webhook.is_some();
+    if hooked && matches!(read_mode, ReadMode::Watch) {
+        handlers.push(Box::new(PullHooks::new(args.on_pull_start.clone(), args.on_pull_end.clone(), args.pull_webhook.clone())));
+    }
+
+    if let Some(path) = &args.key_history {
+        handlers.push(Box::new(KeyHistory::new(path.clone())));
+    }
+
+    if matches!(read_mode, ReadMode::Watch) {
+        handlers.push(Box::new(LagMonitor::new()));
+    }
+
+    if let Some(path) = &args.alert_rules {
+        let rules = AlertRules::load(path).unwrap_or_else(|e| {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        });
+        handlers.push(Box::new(AlertEngine::new(rules).live(matches!(read_mode, ReadMode::Watch))));
+    }
+
+    let broadcaster = match args.output_mode {
+        Some(OutputMode::Serve { port }) => {
+            let broadcaster = Broadcaster::listen(port).unwrap_or_else(|e| {
+                eprintln!("{e:#}");
+                std::process::exit(1);
+            });
+            eprintln!("Serving on ws://127.0.0.1:{}", broadcaster.port());
+            Some(broadcaster)
+        }
+        _ => None,
+    };
+
+    let encounter_history = EncounterHistory::new();
+    let api_server = match args.output_mode {
+        Some(OutputMode::ServeHttp { port }) => {
+            let server = ApiServer::start(encounter_history.history(), port).unwrap_or_else(|e| {
+                eprintln!("{e:#}");
+                std::process::exit(1);
+            });
+            eprintln!("Serving on http://127.0.0.1:{}", server.port());
+            Some(server)
+        }
+        _ => None,
+    };
+
+    // Output mode & sinks
+    if let Some(mode) = &args.output_mode {
+        handlers.push(match mode {
+            OutputMode::Std => sink_handler(&Sink::Std, false),
+            OutputMode::File { good_path, failed_path } =>
+                sink_handler(&Sink::File { good_path: good_path.clone(), failed_path: failed_path.clone() }, false),
+            OutputMode::None => sink_handler(&Sink::None, false),
+            OutputMode::Html { path } => sink_handler(&Sink::Html(path.clone()), false),
+            OutputMode::Timeseries { path, format } => Box::new(TimeSeriesExport::new(path.clone(), *format)),
+            OutputMode::Serve { .. } => Box::new(EventBroadcast::new(broadcaster.clone().unwrap())),
+            OutputMode::ServeHttp { .. } => Box::new(encounter_history),
+            OutputMode::Obs { dir, player, top, row_template, personal_template, boss_template } => {
+                let templates = ObsTemplates { row: row_template.clone(), personal: personal_template.clone(), boss: boss_template.clone() };
+                Box::new(ObsOverlay::new(dir.clone(), templates, player.clone(), *top))
+            }
+            OutputMode::Bench { .. } | OutputMode::WclImport { .. } | OutputMode::Retry { .. } | OutputMode::Schema { .. }
+            | OutputMode::MplusHistory { .. } | OutputMode::SplitFiles { .. }
+            | OutputMode::Extract { .. } | OutputMode::Anonymize { .. } | OutputMode::Upload { .. } | OutputMode::Query { .. } | OutputMode::Index | OutputMode::Diagnose
+            | OutputMode::Compare => unreachable!(),
+        });
+    }
+    // A resumed run only reads what's new, so adds to the exports of the runs before it
+    let resume = args.checkpoint.as_deref().is_some_and(Path::exists);
+    handlers.extend(args.outs.iter().map(|sink| sink_handler(sink, resume)));
+
+    handlers.iter_mut().for_each(|h| h.on_start());
+    let scope = (!args.players.is_empty()).then(|| PlayerScope::new(args.players.clone()));
+    let mut segmenter = CombatSegmenter::new(args.combat_gap_secs).with_scope(scope).with_phases(phase_rules);
 
     // Inputs
-    match args.read_mode {
-        ReadMode::Watch => watch(args.wowlog_path, &mut handlers).unwrap(),
-        ReadMode::Process => process(args.wowlog_path, &mut handlers).unwrap(),
+    let mut checkpoints = args.checkpoint.as_deref().map(Checkpoints::load).transpose().unwrap_or_else(|e| {
+        eprintln!("{e:#}");
+        std::process::exit(1);
+    });
+    let mut reports = vec![];
+    match read_mode {
+        ReadMode::Watch => {
+            let mut pruner = Pruner::new(args.prune_idle_minutes, args.max_tracked_actors);
+            let render = |handlers: &[Box<dyn EventHandler>]| -> Result<()> {
+                let displays = handlers.iter().filter_map(|h| h.display()).collect_vec();
+                if let Some(broadcaster) = &broadcaster {
+                    broadcaster.snapshot(&displays);
+                }
+
+                if args.status_line {
+                    // Redraw the same terminal line rather than scrolling
+                    print!("\r\x1b[2K{}", displays.join(" | "));
+                    std::io::stdout().flush()?;
+                } else {
+                    if args.redraw {
+                        // Clear the terminal & draw from the top rather than scrolling
+                        print!("\x1b[2J\x1b[H");
+                    }
+                    println!("{}", render_handlers(handlers, args.summary_format));
+                }
+                Ok(())
+            };
+            let (tx, rx) = std::sync::mpsc::channel();
+            stop_on_ctrl_c(tx.clone());
+            let _watcher = watch_changes(&wowlog_path, tx).unwrap_or_else(|e| {
+                eprintln!("{e:#}");
+                std::process::exit(1);
+            });
+            let wakeups = Wakeups { messages: rx, refresh: Refresh::new(render, args.refresh_interval) };
+            watch(&wowlog_path, options, &mut handlers, &mut pruner, &mut segmenter, checkpoints.as_mut(), wakeups).unwrap()
+        }
+        ReadMode::Process => {
+            // With several logs, each also gets its own set of trackers alongside the ones for the whole batch
+            let batch = logs.len() > 1;
+            for log in &logs {
+                let split = handlers.len();
+                if batch {
+                    let mut own = trackers();
+                    own.iter_mut().for_each(|h| h.on_start());
+                    handlers.append(&mut own);
+                }
+
+                let result = match &args.encounter {
+                    Some(selector) => process_encounters(log, selector, options, &mut handlers, &mut segmenter),
+                    None => process(log, options, &mut handlers, &mut segmenter, checkpoints.as_mut()),
+                };
+                if let Err(e) = result {
+                    // One bad log shouldn't lose the rest of the batch
+                    if batch {
+                        eprintln!("{:?}: {e:#}", log);
+                    } else {
+                        eprintln!("{e:#}");
+                        std::process::exit(1);
+                    }
+                }
+
+                if batch {
+                    segmenter.finish(&mut handlers);
+                    let mut own = handlers.split_off(split);
+                    own.iter_mut().for_each(|h| h.on_finish());
+                    let name = log.file_name().map_or(log.display().to_string(), |n| n.to_string_lossy().into_owned());
+                    reports.push((name, own));
+                }
+            }
+        }
+    }
+
+    finish(&mut handlers, &mut segmenter);
+    if reports.is_empty() {
+        println!("{}", render_handlers(&handlers, args.summary_format));
+    } else {
+        println!("{}", render_batch(&reports, &handlers, args.summary_format));
+    }
+
+    // Keep answering requests for the finished results
+    if let Some(server) = api_server {
+        eprintln!("Finished processing, still serving. Press Ctrl-C to stop.");
+        server.join();
     }
 }
 
 
+/// Closes any open segments, then lets the handlers finish up
+fn finish(handlers: &mut [Box<dyn EventHandler>], segmenter: &mut CombatSegmenter) {
+    segmenter.finish(handlers);
+    handlers.iter_mut().for_each(|h| h.on_finish());
+}
+
+
 fn main() {
     let args = Cli::parse();
     execute(args);
@@ -113,16 +901,25 @@ fn main() {
 
 #[cfg(test)]
 mod tests {
-    use std::fs::File;
-    use std::path::PathBuf;
+    use std::cell::{Cell, RefCell};
+    use std::fs::{File, OpenOptions};
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::rc::Rc;
     use std::str::FromStr;
+    use std::time::{Duration, Instant};
 
-    use clap::Parser;
+    use clap::{Parser, ValueEnum};
 
-    use crate::{execute, parse_file};
-    use crate::cli::Cli;
+    use crate::{check_combat_log, complete_lines, execute, expand_logs, finish, newest_log, parse_file, process, tracker_name, tracker_registry, watch, watch_changes, Refresh, Wakeups, WatchMessage};
+    use crate::checkpoint::Checkpoints;
+    use crate::cli::{Cli, Tracker};
+    use crate::components::events::Event;
     use crate::consumers::{EventHandler, StdLogger};
-    use crate::parser::EventParser;
+    use crate::consumers::pruning::Pruner;
+    use crate::consumers::segments::CombatSegmenter;
+    use crate::error::ParseFailure;
+    use crate::parser::{EventParser, ParseOptions};
 
     #[test]
     fn test1() {
@@ -176,6 +973,285 @@ mod tests {
     }
 
 
+    #[test]
+    fn test_newest_log() {
+        let dir = std::env::temp_dir().join("wowlogs_newest_log");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(newest_log(&dir).unwrap(), None);
+
+        std::fs::write(dir.join("WoWCombatLog-041124_213746.txt"), "").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(dir.join("WoWCombatLog-041224_180000.txt"), "").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(dir.join("Other.txt"), "").unwrap();
+
+        assert_eq!(newest_log(&dir).unwrap(), Some(dir.join("WoWCombatLog-041224_180000.txt")));
+    }
+
+    #[test]
+    fn test_complete_lines() {
+        let log = std::env::temp_dir().join("wowlogs_complete_lines.txt");
+        std::fs::write(&log, "first\nsecond\nthi").unwrap();
+
+        assert_eq!(complete_lines(&log, 0).unwrap(), b"first\nsecond\n");
+        assert_eq!(complete_lines(&log, 6).unwrap(), b"second\n");
+        assert!(complete_lines(&log, 13).unwrap().is_empty());
+
+        std::fs::write(&log, "first\nsecond\nthird\n").unwrap();
+        assert_eq!(complete_lines(&log, 13).unwrap(), b"third\n");
+    }
+
+    #[test]
+    fn test_tracker_registry() {
+        let args = Cli::parse_from(["wow.exe", "logs.txt", "process", "none"]);
+        let (percentiles, mechanic_rules, avoidable_rules, cooldown_rules, item_db) = Default::default();
+        let registry = tracker_registry(&args, &percentiles, &mechanic_rules, &avoidable_rules, &cooldown_rules, &item_db).unwrap();
+
+        let all = Tracker::value_variants().iter().map(|t| tracker_name(*t));
+        assert_eq!(registry.build(all).unwrap().len(), Tracker::value_variants().len());
+        assert!(registry.build(["damage", "damage"]).is_err());
+    }
+
+    #[test]
+    fn test_expand_logs() {
+        let dir = std::env::temp_dir().join("wowlogs_expand_logs");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["WoWCombatLog-041224_180000.txt", "WoWCombatLog-041124_213746.txt", "notes.txt"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let both = vec![dir.join("WoWCombatLog-041124_213746.txt"), dir.join("WoWCombatLog-041224_180000.txt")];
+
+        assert_eq!(expand_logs(&[dir.join("WoWCombatLog-*.txt")], false).unwrap(), both);
+        assert_eq!(expand_logs(&[dir.join("WoWCombatLog-0412??_*.txt")], false).unwrap(), both[1..]);
+        assert_eq!(expand_logs(std::slice::from_ref(&dir), true).unwrap(), both);
+        // Watch mode follows the directory itself
+        assert_eq!(expand_logs(std::slice::from_ref(&dir), false).unwrap(), [dir.as_path()]);
+        assert!(expand_logs(&[dir.join("*.csv")], false).is_err());
+    }
+
+    #[test]
+    fn test_watch_interrupt() {
+        /// Counts the events which parsed, & whether the handler was finished
+        struct Finished { events: Rc<Cell<usize>>, finished: Rc<Cell<bool>> }
+        impl EventHandler for Finished {
+            fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+                if event.is_ok() { self.events.set(self.events.get() + 1); }
+            }
+
+            fn on_finish(&mut self) { self.finished.set(true); }
+        }
+
+        let dir = std::env::temp_dir().join("wowlogs_watch_interrupt");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("WoWCombatLog-041124_213746.txt");
+        let checkpoint_path = dir.join("checkpoints.json");
+        std::fs::write(&log, "4/6 14:00:00.000  COMBAT_LOG_VERSION,20,ADVANCED_LOG_ENABLED,1,BUILD_VERSION,10.2.6,PROJECT_ID,1\n").unwrap();
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let writer = {
+            let (tx, log, checkpoint_path) = (tx.clone(), log.clone(), checkpoint_path.clone());
+            std::thread::spawn(move || {
+                let line = "4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n";
+                // Appends until the watcher has checkpointed everything written, as it may not be watching yet
+                for _ in 0..100 {
+                    OpenOptions::new().append(true).open(&log).unwrap().write_all(line.as_bytes()).unwrap();
+                    std::thread::sleep(Duration::from_millis(100));
+                    let len = std::fs::metadata(&log).unwrap().len();
+                    let checkpointed = Checkpoints::load(&checkpoint_path).ok()
+                        .and_then(|c| c.resume(&log).map(|p| p.byte_offset));
+                    if checkpointed == Some(len) { break; }
+                }
+                tx.send(WatchMessage::Interrupt).unwrap();
+            })
+        };
+
+        let (events, finished) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(false)));
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(Finished { events: events.clone(), finished: finished.clone() })];
+        let mut segmenter = CombatSegmenter::new(None);
+        let mut checkpoints = Checkpoints::load(&checkpoint_path).unwrap();
+        let _watcher = watch_changes(&log, tx).unwrap();
+        let wakeups = Wakeups { messages: rx, refresh: Refresh::new(|_: &[Box<dyn EventHandler>]| Ok(()), None) };
+        watch(&log, ParseOptions::default(), &mut handlers, &mut Pruner::new(None, None), &mut segmenter, Some(&mut checkpoints), wakeups).unwrap();
+        finish(&mut handlers, &mut segmenter);
+        writer.join().unwrap();
+
+        assert!(events.get() > 0);
+        assert!(finished.get());
+        let len = std::fs::metadata(&log).unwrap().len();
+        assert_eq!(Checkpoints::load(&checkpoint_path).unwrap().resume(&log).unwrap().byte_offset, len);
+    }
+
+    #[test]
+    fn test_checkpoint_resume() {
+        /// Counts the events which parsed
+        struct Count(Rc<Cell<usize>>);
+        impl EventHandler for Count {
+            fn handle(&mut self, event: &Result<Event, ParseFailure>) {
+                if event.is_ok() { self.0.set(self.0.get() + 1); }
+            }
+        }
+        let run = |log: &Path, checkpoints: &mut Checkpoints| {
+            let count = Rc::new(Cell::new(0));
+            let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(Count(count.clone()))];
+            process(log, ParseOptions::default(), &mut handlers, &mut CombatSegmenter::new(None), Some(checkpoints)).unwrap();
+            count.get()
+        };
+
+        let dir = std::env::temp_dir().join("wowlogs_checkpoint_resume");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("WoWCombatLog-041124_213746.txt");
+        let header = "9/28 20:15:41.000  COMBAT_LOG_VERSION,9,ADVANCED_LOG_ENABLED,1,BUILD_VERSION,3.4.3,PROJECT_ID,11\n";
+        let line = "9/28 20:15:41.735  SPELL_DAMAGE,Player-4395-01C5EEA6,\"Xyz-Whitemane\",0x512,0x0,Creature-0-4395-615-22-30452-00001A2B3C,\"Tenebron\",0x10a48,0x0,57591,\"Vengeance\",0x2,Creature-0-4395-615-22-30452-00001A2B3C,0000000000000000,3487744,3500000,0,0,0,-1,0,0,0,3262.87,532.15,0,1.6829,83,12256,12255,-1,2,0,0,0,nil,nil,nil\n";
+        std::fs::write(&log, format!("{header}{line}")).unwrap();
+
+        let mut checkpoints = Checkpoints::load(&dir.join("checkpoints.json")).unwrap();
+        assert_eq!(run(&log, &mut checkpoints), 2);
+        assert_eq!(run(&log, &mut checkpoints), 0);
+
+        // Only the new line is read, with the classic layout from the header before the checkpoint
+        std::fs::write(&log, format!("{header}{line}{line}")).unwrap();
+        let mut checkpoints = Checkpoints::load(&dir.join("checkpoints.json")).unwrap();
+        assert_eq!(run(&log, &mut checkpoints), 1);
+        assert!(checkpoints.resume(&log).unwrap().last_timestamp.is_some());
+
+        // A partly written line is left for the next run
+        let (start, rest) = line.split_at(100);
+        std::fs::write(&log, format!("{header}{line}{line}{start}")).unwrap();
+        assert_eq!(run(&log, &mut checkpoints), 0);
+        std::fs::write(&log, format!("{header}{line}{line}{start}{rest}")).unwrap();
+        assert_eq!(run(&log, &mut checkpoints), 1);
+    }
+
+    #[test]
+    fn test_checkpoint_encounter_end() {
+        /// The saved checkpoint as each event is handled
+        struct Saved(PathBuf, Rc<RefCell<Vec<Option<u64>>>>);
+        impl EventHandler for Saved {
+            fn handle(&mut self, _event: &Result<Event, ParseFailure>) {
+                let offset = Checkpoints::load(&self.0.join("checkpoints.json")).unwrap()
+                    .resume(&self.0.join("WoWCombatLog-041124_213746.txt")).map(|p| p.byte_offset);
+                self.1.borrow_mut().push(offset);
+            }
+        }
+
+        let dir = std::env::temp_dir().join("wowlogs_checkpoint_encounter_end");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("WoWCombatLog-041124_213746.txt");
+        let lines = [
+            "4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n",
+            "4/6 14:12:00.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,1,162742\n",
+            "4/6 14:13:00.000  ENCOUNTER_START,2709,\"Igira the Cruel\",14,19,2549\n",
+        ];
+        std::fs::write(&log, lines.concat()).unwrap();
+
+        let saved = Rc::new(RefCell::new(vec![]));
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(Saved(dir.clone(), saved.clone()))];
+        let mut checkpoints = Checkpoints::load(&dir.join("checkpoints.json")).unwrap();
+        process(&log, ParseOptions::default(), &mut handlers, &mut CombatSegmenter::new(None), Some(&mut checkpoints)).unwrap();
+
+        // Saved once the encounter ended, before the end of the log
+        let encounter_end = (lines[0].len() + lines[1].len()) as u64;
+        assert_eq!(*saved.borrow(), [None, None, Some(encounter_end)]);
+        assert_eq!(checkpoints.resume(&log).unwrap().byte_offset, encounter_end + lines[2].len() as u64);
+    }
+
+    #[test]
+    fn test_check_combat_log() {
+        let dir = std::env::temp_dir().join("wowlogs_check_combat_log");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let log = dir.join("WoWCombatLog-041124_213746.txt");
+        std::fs::write(&log, "2/15 20:14:12.865  COMBAT_LOG_VERSION,20,ADVANCED_LOG_ENABLED,1,BUILD_VERSION,10.2.5,PROJECT_ID,1\n").unwrap();
+        assert!(check_combat_log(&log, ParseOptions::default()).is_ok());
+
+        let notes = dir.join("notes.txt");
+        std::fs::write(&notes, "Raid night\nBring flasks, food & runes\n").unwrap();
+        let err = check_combat_log(&notes, ParseOptions::default()).unwrap_err().to_string();
+        assert!(err.contains("doesn't look like a combat log") && err.contains("WoWCombatLog-*.txt"), "{}", err);
+    }
+
+    #[test]
+    fn test_refresh() {
+        let shown = Cell::new(0);
+        let mut refresh = Refresh::new(|_: &[Box<dyn EventHandler>]| {
+            shown.set(shown.get() + 1);
+            Ok(())
+        }, Some(Duration::from_secs(1)));
+        let start = Instant::now();
+        assert_eq!(refresh.timeout(start), Duration::MAX);
+
+        // A burst of writes is shown once, then the rest when the interval is up
+        refresh.updated(&[], start).unwrap();
+        refresh.updated(&[], start + Duration::from_millis(300)).unwrap();
+        assert_eq!(shown.get(), 1);
+        assert_eq!(refresh.timeout(start + Duration::from_millis(400)), Duration::from_millis(600));
+        refresh.flush(&[], start + Duration::from_secs(1)).unwrap();
+        assert_eq!(shown.get(), 2);
+        refresh.flush(&[], start + Duration::from_secs(3)).unwrap();
+        assert_eq!(shown.get(), 2);
+
+        // Without an interval every write is shown
+        let mut refresh = Refresh::new(|_: &[Box<dyn EventHandler>]| Ok(()), None);
+        refresh.updated(&[], start).unwrap();
+        assert_eq!(refresh.timeout(start), Duration::MAX);
+    }
+
+    #[test]
+    fn test_sinks() {
+        let dir = std::env::temp_dir().join("wowlogs_sinks");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("WoWCombatLog-041124_213746.txt");
+        std::fs::write(&log, "2/15 20:14:12.865  COMBAT_LOG_VERSION,20,ADVANCED_LOG_ENABLED,1,BUILD_VERSION,10.2.5,PROJECT_ID,1\n2/15 20:14:13.000  NOT_AN_EVENT\n").unwrap();
+
+        let [json, db, good, bad] = ["events.jsonl", "raid.db", "good.txt", "bad.txt"].map(|f| dir.join(f).to_str().unwrap().to_string());
+        let args = Cli::parse_from(["wow.exe", log.to_str().unwrap(), "process",
+            "--out", &format!("json:{json}"), "--out", &format!("sqlite:{db}"), "--out", &format!("file:{good},{bad}")]);
+        execute(args);
+
+        let json = std::fs::read_to_string(json).unwrap();
+        assert_eq!(json.lines().count(), 2);
+        assert!(json.lines().next().unwrap().starts_with("{\"event\":") && json.lines().nth(1).unwrap().starts_with("{\"error\":"), "{}", json);
+        assert_eq!(std::fs::read_to_string(good).unwrap().lines().count(), 1);
+        assert!(std::fs::read_to_string(bad).unwrap().contains("NOT_AN_EVENT"));
+        let db = rusqlite::Connection::open(db).unwrap();
+        let count = |table: &str| db.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |r| r.get::<_, i64>(0)).unwrap();
+        assert_eq!((count("events"), count("failures")), (1, 1));
+    }
+
+    #[test]
+    fn test_sinks_resume() {
+        let dir = std::env::temp_dir().join("wowlogs_sinks_resume");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("WoWCombatLog-041124_213746.txt");
+        let swing = |source: &str, target: &str| format!("2/15 20:14:13.000  SWING_MISSED,{source},\"A\",0x514,0x0,{target},\"B\",0x10a48,0x0,MISS,1\n");
+        let (player, boss, add) = ("Player-1335-0A264B4C", "Creature-0-1469-2549-12530-209333-000011428A", "Creature-0-1469-2549-12530-210231-000011428B");
+        std::fs::write(&log, "2/15 20:14:12.865  COMBAT_LOG_VERSION,20,ADVANCED_LOG_ENABLED,1,BUILD_VERSION,10.2.5,PROJECT_ID,1\n".to_string() + &swing(player, boss)).unwrap();
+
+        let [json, db, checkpoint] = ["events.jsonl", "raid.db", "checkpoint.json"].map(|f| dir.join(f).to_str().unwrap().to_string());
+        let run = || execute(Cli::parse_from(["wow.exe", log.to_str().unwrap(), "process", "--checkpoint", &checkpoint,
+            "--out", &format!("json:{json}"), "--out", &format!("sqlite:{db}")]));
+        run();
+        std::fs::OpenOptions::new().append(true).open(&log).unwrap().write_all(swing(add, player).as_bytes()).unwrap();
+        run();
+
+        // The second run only adds the new line, & carries on with the first run's IDs
+        let json = std::fs::read_to_string(json).unwrap();
+        assert_eq!(json.lines().count(), 3, "{}", json);
+        assert!(json.lines().nth(2).unwrap().contains("\"source_id\":\"A3\",\"target_guid\":\"Player-1335-0A264B4C\",\"target_id\":\"A1\""), "{}", json);
+        let db = rusqlite::Connection::open(db).unwrap();
+        let ids = db.prepare("SELECT source_id, target_id FROM events WHERE source_id IS NOT NULL").unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?))).unwrap()
+            .collect::<Result<Vec<(String, String)>, _>>().unwrap();
+        assert_eq!(ids, [("A1".to_string(), "A2".to_string()), ("A3".to_string(), "A1".to_string())]);
+    }
+
     #[test]
     fn test_real() {
         let args = Cli::parse_from(["wow.exe", r"E:\Games\Blizzard\World of Warcraft\_retail_\Logs\WoWCombatLog-041124_213746.txt", "process", "file", "good2.txt", "bad2.txt"]);
@@ -0,0 +1,174 @@
+//! Reads a whole log through a memory map, rather than copying it through a buffered reader.
+//! Faster on large logs, but the file mustn't be truncated while it's mapped, so it's only
+//! meant for finished logs. Logs being written to are read with `EventParser`.
+
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use memmap2::Mmap;
+
+use crate::components::events::Event;
+use crate::components::format::{GameVersion, LogFormat};
+use crate::context::{Session, SessionContext};
+use crate::error::ParseFailure;
+use crate::parser::{follow_header, parse_line, ParseOptions, trim_line};
+
+pub struct MmapParser {
+    map: Mmap,
+    /// Byte offset of the next line
+    pos: usize,
+    /// Byte offset parsing stops at
+    end: usize,
+    line_no: u64,
+    /// Line numbers can only be counted when reading from the start of the file
+    count_lines: bool,
+    format: LogFormat,
+    /// The layout was chosen by the user, so headers in the log don't change it
+    pinned: bool,
+    options: ParseOptions,
+    session: Session,
+    file: PathBuf,
+}
+
+impl MmapParser {
+    /// Maps the log, failing with an error that names it
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let with_path = |action: &'static str| move |e: io::Error| io::Error::new(e.kind(), format!("Failed to {} file: {:?}: {}", action, path, e));
+        let file = File::open(path).map_err(with_path("open"))?;
+        // Safety: combat logs are only ever appended to, & this is only used on logs which are no longer being written
+        let map = unsafe { Mmap::map(&file) }.map_err(with_path("map"))?;
+
+        Ok(Self { end: map.len(), map, pos: 0, line_no: 0, count_lines: true, format: LogFormat::default(), pinned: false, options: ParseOptions::default(), session: Session::default(), file: path.to_path_buf() })
+    }
+
+    /// Starts part way through the file, at the start of a line, with the layout the log has there
+    pub fn starting_at(mut self, offset: u64, format: LogFormat) -> Self {
+        self.pos = offset as usize;
+        self.count_lines = offset == 0;
+        if !self.pinned {
+            self.format = format;
+        }
+        self
+    }
+
+    /// Stops after the last complete line, leaving a line which is still being written for later
+    pub fn complete_lines_only(mut self) -> Self {
+        self.end = self.map.iter().rposition(|&b| b == b'\n').map_or(0, |end| end + 1);
+        self
+    }
+
+    /// Forces the layout of a game version, rather than detecting it from the log's headers
+    pub fn with_game_version(mut self, version: Option<GameVersion>) -> Self {
+        if let Some(version) = version {
+            self.format = version.format();
+            self.pinned = true;
+        }
+        self
+    }
+
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self.with_game_version(options.game_version)
+    }
+
+    /// Layout of the log, as detected from the latest COMBAT_LOG_VERSION header
+    pub fn log_format(&self) -> &LogFormat {
+        &self.format
+    }
+
+    /// Context of the last event returned
+    pub fn context(&self) -> &Arc<SessionContext> {
+        &self.session.context
+    }
+
+    /// Everything needed to carry on parsing the log from here in another parser
+    pub fn into_session(self) -> Session {
+        self.session
+    }
+
+    /// Byte offset of the next line
+    pub fn position(&self) -> u64 {
+        self.pos.min(self.end) as u64
+    }
+}
+
+impl Iterator for MmapParser {
+    type Item = Result<Event, ParseFailure>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.pos >= self.end { return None; }
+
+            let start = self.pos;
+            let end = self.map[start..self.end].iter().position(|&b| b == b'\n').map_or(self.end, |n| start + n);
+            self.pos = end + 1;
+            self.line_no += 1;
+
+            let Some(line) = trim_line(&self.map[start..end]) else { continue; };
+
+            let mut val = parse_line(line, &self.format, &self.options)
+                .map_err(|(raw, source)| ParseFailure {
+                    file: Some(self.file.clone()),
+                    line_no: self.count_lines.then_some(self.line_no),
+                    byte_offset: start as u64,
+                    raw,
+                    source,
+                });
+            follow_header(&mut self.format, self.pinned, &val);
+            self.session.follow(&self.format, &mut val);
+
+            return Some(val);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::mmap::MmapParser;
+    use crate::parser::EventParser;
+
+    #[test]
+    fn same_as_buffered() {
+        let log = "9/28 20:15:41.000  COMBAT_LOG_VERSION,9,ADVANCED_LOG_ENABLED,1,BUILD_VERSION,3.4.3,PROJECT_ID,11\r\n\
+9/28 20:15:41.735  SPELL_DAMAGE,Player-4395-01C5EEA6,\"Xyz-Whitemane\",0x512,0x0,Creature-0-4395-615-22-30452-00001A2B3C,\"Tenebron\",0x10a48,0x0,57591,\"Vengeance\",0x2,Creature-0-4395-615-22-30452-00001A2B3C,0000000000000000,3487744,3500000,0,0,0,-1,0,0,0,3262.87,532.15,0,1.6829,83,12256,12255,-1,2,0,0,0,nil,nil,nil\r\n\
+# Comments are skipped\r\n\
+\r\n\
+4/11 22:19:57.499  EMOTE,Creature-0-1465-2444-137-194909-00009853CD,\"Feather-Ruffling Duck\",0000000000000000,nil,\"Take control, of the Duck!\"\r\n\
+4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0xZZZ,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\r\n\
+4/6 14:02:08.000  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1";
+
+        let dir = std::env::temp_dir().join("wowlogs_mmap_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("WoWCombatLog.txt");
+        std::fs::write(&path, log).unwrap();
+
+        let expected = EventParser::new(log.as_bytes()).with_file(&path).map(|e| format!("{:?}", e)).collect::<Vec<_>>();
+        let mut parser = MmapParser::open(&path).unwrap();
+        let got = parser.by_ref().map(|e| format!("{:?}", e)).collect::<Vec<_>>();
+        assert_eq!(got, expected);
+        assert_eq!(got.len(), 5);
+        assert!(parser.log_format().is_classic());
+        assert_eq!(parser.position(), log.len() as u64);
+
+        // Picking up after the header
+        let offset = log.find("9/28 20:15:41.735").unwrap() as u64;
+        let resumed = MmapParser::open(&path).unwrap().starting_at(offset, *parser.log_format()).collect::<Vec<_>>();
+        assert!(resumed[0].is_ok());
+        assert_eq!(resumed.len(), 4);
+        assert!(resumed[2].as_ref().is_err_and(|f| f.line_no.is_none() && f.byte_offset == log.find("4/6 14:02:07").unwrap() as u64));
+
+        // The last line has no newline, so it could still be being written
+        let mut parser = MmapParser::open(&path).unwrap().complete_lines_only();
+        assert_eq!(parser.by_ref().count(), 4);
+        assert_eq!(parser.position(), log.rfind('\n').unwrap() as u64 + 1);
+
+        let missing = dir.join("missing.txt");
+        let err = MmapParser::open(&missing).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert!(err.to_string().starts_with(&format!("Failed to open file: {:?}: ", missing)), "{}", err);
+    }
+}
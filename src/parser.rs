@@ -1,37 +1,477 @@
-use std::io::Read;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::sync::Arc;
 
-use anyhow::Result;
 use itertools::Itertools;
+use serde::Serialize;
 
-use crate::components::events::Event;
+use crate::components::events::{Event, EventType};
+use crate::components::format::{GameVersion, LogFormat};
+use crate::components::special::Special;
+use crate::context::{Session, SessionContext};
+use crate::error::{ParseError, ParseFailure};
+
+/// Splits a line into fields, dropping the quotes around quoted ones.
+/// The game only quotes strings, & never escapes quotes inside them, so a quoted field runs to the next quote.
+pub(crate) fn split_fields(line: &str) -> Vec<&str> {
+    let mut fields = Vec::with_capacity(48);
+    let mut i = 0;
+    while i <= line.len() {
+        if line[i..].starts_with('"') {
+            let close = line[i + 1..].find('"').map_or(line.len(), |e| i + 1 + e);
+            fields.push(&line[i + 1..close]);
+            i = line[close..].find(',').map_or(line.len(), |c| close + c) + 1;
+        } else {
+            let end = line[i..].find(',').map_or(line.len(), |c| i + c);
+            fields.push(&line[i..end]);
+            i = end + 1;
+        }
+    }
+    fields
+}
+
+/// A field of `line` as it was written, keeping its quotes. The fields from [split_fields] point into the line,
+/// so a quoted one starts just after its opening quote.
+pub(crate) fn raw_field<'a>(line: &'a str, field: &str) -> &'a str {
+    let start = field.as_ptr() as usize - line.as_ptr() as usize;
+    let end = start + field.len();
+    if start == 0 || line.as_bytes()[start - 1] != b'"' {
+        return &line[start..end];
+    }
+    // A cut off line can end inside the quotes
+    let closed = line.as_bytes().get(end) == Some(&b'"');
+    &line[start - 1..end + closed as usize]
+}
+
+/// Strips the line ending, or None for lines which should be skipped: blank lines & comments
+pub(crate) fn trim_line(line: &[u8]) -> Option<&[u8]> {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    (!line.is_empty() && line[0] != b'#').then_some(line)
+}
+
+/// How forgiving the parsers are of lines they don't fully understand
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions {
+    /// Forces the layout of a game version, rather than detecting it from the log's headers
+    pub game_version: Option<GameVersion>,
+    /// Keep events with unknown types as `EventType::Unknown`, rather than failing them
+    pub lenient: bool,
+    /// Fail events which didn't use exactly all of their line's fields, eg. after a patch added a field
+    pub strict: bool,
+    /// Keep known events whose lines end early as `EventType::Partial`, rather than failing them
+    pub recover: bool,
+}
+
+/// Parses a line, returning its raw text alongside any failure
+pub(crate) fn parse_line(line: &[u8], format: &LogFormat, options: &ParseOptions) -> Result<Event, (String, ParseError)> {
+    let line = std::str::from_utf8(line).map_err(|e| {
+        let raw = String::from_utf8_lossy(line).into_owned();
+        (raw.clone(), ParseError::Unreadable { reason: e.to_string(), raw })
+    })?;
+
+    let fields = split_fields(line);
+    match Event::parse_with_format(&fields, format) {
+        Err(ParseError::UnknownEvent { .. }) if options.lenient =>
+            Event::parse_unknown(&fields.iter().map(|f| raw_field(line, f)).collect_vec()),
+        Err(ParseError::FieldCount { expected, got, .. }) if options.recover && got < expected =>
+            Event::parse_partial(&fields, &fields.iter().map(|f| raw_field(line, f)).collect_vec()),
+        Ok(event) if options.strict => event.check_field_count(&fields, format).map(|_| event),
+        result => result,
+    }.map_err(|e| (line.to_string(), e))
+}
 
 pub struct EventParser<R> {
-    reader: csv::Reader<R>,
+    reader: BufReader<R>,
+    /// The line being parsed, reused between lines
+    line: Vec<u8>,
+    /// Bytes read so far
+    pos: u64,
+    line_no: u64,
+    format: LogFormat,
+    /// Byte range of the last line read
+    span: (u64, u64),
+    /// A read failed, so there's nothing more to parse
+    done: bool,
+    file: Option<PathBuf>,
+    /// Where the reader starts in the file
+    offset: u64,
+    /// Line numbers can only be counted when reading from the start of the file
+    count_lines: bool,
+    /// The layout was chosen by the user, so headers in the log don't change it
+    pinned: bool,
+    options: ParseOptions,
+    session: Session,
 }
 
 impl<R: Read> EventParser<R> {
     pub fn new(reader: R) -> Self {
-        let mut binding = csv::ReaderBuilder::new();
-        let reader = binding
-            .has_headers(false)
-            .flexible(true)
-            .from_reader(reader);
+        Self::with_format(reader, LogFormat::default())
+    }
+
+    /// Starts with a known layout, eg. when reading from the middle of a log
+    pub fn with_format(reader: R, format: LogFormat) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            line: vec![],
+            pos: 0,
+            line_no: 0,
+            format,
+            span: (0, 0),
+            done: false,
+            file: None,
+            offset: 0,
+            count_lines: true,
+            pinned: false,
+            options: ParseOptions::default(),
+            session: Session::default(),
+        }
+    }
+
+    /// Name of the file being read, attached to any failures
+    pub fn with_file<P: Into<PathBuf>>(mut self, file: P) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// The reader starts part way through the file, at this byte offset
+    pub fn starting_at(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self.count_lines = offset == 0;
+        self
+    }
+
+    /// Forces the layout of a game version, rather than detecting it from the log's headers
+    pub fn with_game_version(mut self, version: Option<GameVersion>) -> Self {
+        if let Some(version) = version {
+            self.format = version.format();
+            self.pinned = true;
+        }
+        self
+    }
 
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self.with_game_version(options.game_version)
+    }
+
+    /// Carries on a session from an earlier parser, eg. when reading the rest of a log as it's written
+    pub fn with_session(mut self, session: Session) -> Self {
+        self.session = session;
+        self
+    }
 
-        Self { reader }
+    /// Layout of the log, as detected from the latest COMBAT_LOG_VERSION header
+    pub fn log_format(&self) -> &LogFormat {
+        &self.format
+    }
+
+    /// Context of the last event returned
+    pub fn context(&self) -> &Arc<SessionContext> {
+        &self.session.context
+    }
+
+    /// Everything needed to carry on parsing the log from here in another parser
+    pub fn into_session(self) -> Session {
+        self.session
+    }
+
+    /// Start & end byte offsets in the file of the last line returned
+    pub fn last_span(&self) -> (u64, u64) {
+        (self.offset + self.span.0, self.offset + self.span.1)
     }
 }
 
 impl<R: Read> Iterator for EventParser<R> {
-    type Item = Result<Event>;
+    type Item = Result<Event, ParseFailure>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let val = self.reader
-            .records()
-            .filter_map(Result::ok)
-            .map(|line| Event::parse(&line.iter().collect_vec()))
-            .next();
+        loop {
+            if self.done { return None; }
+
+            self.line.clear();
+            let start = self.pos;
+            let read = self.reader.read_until(b'\n', &mut self.line);
+            self.pos += self.line.len() as u64;
+            self.line_no += 1;
+            self.span = (start, self.pos);
+
+            let val = match read {
+                Ok(0) => return None,
+                Ok(_) => {
+                    let Some(line) = trim_line(&self.line) else { continue; };
+                    parse_line(line, &self.format, &self.options)
+                }
+                // Fail whatever was read before the error, then stop
+                Err(e) => {
+                    self.done = true;
+                    let raw = String::from_utf8_lossy(&self.line).trim_end().to_string();
+                    Err((raw.clone(), ParseError::Unreadable { reason: e.to_string(), raw }))
+                }
+            };
+
+            let mut val = val.map_err(|(raw, source)| ParseFailure {
+                file: self.file.clone(),
+                line_no: self.count_lines.then_some(self.line_no),
+                byte_offset: self.offset + start,
+                raw,
+                source,
+            });
+            follow_header(&mut self.format, self.pinned, &val);
+            self.session.follow(&self.format, &mut val);
+
+            return Some(val);
+        }
+    }
+}
 
-        val
+/// Switches layouts when a new log header is seen, unless the layout was pinned by the user
+pub(crate) fn follow_header(format: &mut LogFormat, pinned: bool, event: &Result<Event, ParseFailure>) {
+    if let (false, Ok(Event {
+                  event_type: EventType::Special {
+                      details: Special::CombatLogInfo { log_version, project_id, advanced_log_enabled, .. }, ..
+                  }, ..
+              })) = (pinned, event) {
+        *format = LogFormat::new(*log_version, *project_id, *advanced_log_enabled);
     }
-}
\ No newline at end of file
+}
+
+
+/// An event, or why its line failed to parse. The shape events are handed to other languages in, as JSON.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseResult<'a> {
+    Event(&'a Event),
+    Error { byte_offset: u64, raw: &'a str, reason: String },
+}
+
+impl<'a> From<&'a Result<Event, ParseFailure>> for ParseResult<'a> {
+    fn from(result: &'a Result<Event, ParseFailure>) -> Self {
+        match result {
+            Ok(event) => Self::Event(event),
+            Err(f) => Self::Error { byte_offset: f.byte_offset, raw: &f.raw, reason: f.source.to_string() },
+        }
+    }
+}
+
+/// Parses a log handed over in pieces, eg. as a browser reads a file.
+/// Lines split between pieces are held back until the rest arrives.
+#[derive(Debug, Default)]
+pub struct ChunkParser {
+    partial: String,
+    format: LogFormat,
+    session: Session,
+    /// Bytes of the log parsed so far
+    offset: u64,
+}
+
+impl ChunkParser {
+    pub fn new() -> Self { Self::default() }
+
+    /// Parses every complete line seen so far
+    pub fn push(&mut self, chunk: &str) -> Vec<Result<Event, ParseFailure>> {
+        self.partial.push_str(chunk);
+        let Some(end) = self.partial.rfind('\n') else { return vec![]; };
+
+        let complete = self.partial.drain(..=end).collect::<String>();
+        self.parse(&complete)
+    }
+
+    /// Parses whatever's left, for logs which don't end in a newline
+    pub fn finish(&mut self) -> Vec<Result<Event, ParseFailure>> {
+        let rest = std::mem::take(&mut self.partial);
+        self.parse(&rest)
+    }
+
+    fn parse(&mut self, text: &str) -> Vec<Result<Event, ParseFailure>> {
+        let mut parser = EventParser::with_format(text.as_bytes(), self.format)
+            .starting_at(self.offset)
+            .with_session(std::mem::take(&mut self.session));
+        let events = parser.by_ref().collect_vec();
+        self.format = *parser.log_format();
+        self.session = parser.into_session();
+        self.offset += text.len() as u64;
+        events
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::components::events::EventType;
+    use crate::components::special::Special;
+    use crate::error::ParseError;
+    use crate::parser::{raw_field, split_fields, ChunkParser, EventParser, ParseOptions};
+
+    #[test]
+    fn split() {
+        assert_eq!(split_fields("a,\"b, c\",,d"), ["a", "b, c", "", "d"]);
+        assert_eq!(split_fields("a,"), ["a", ""]);
+        assert_eq!(split_fields("\"a\""), ["a"]);
+    }
+
+    #[test]
+    fn raw_fields() {
+        let raw = |line| split_fields(line).iter().map(|f| raw_field(line, f)).collect::<Vec<_>>();
+        assert_eq!(raw("a,\"b, c\",\"\",d"), ["a", "\"b, c\"", "\"\"", "d"]);
+        assert_eq!(raw("a,\"b"), ["a", "\"b"]);
+    }
+
+    #[test]
+    fn raw_line_on_failure() {
+        let log = "4/11 22:19:57.499  EMOTE,Creature-0-1465-2444-137-194909-00009853CD,\"Feather-Ruffling Duck\",0000000000000000,nil,\"Take control, of the Duck!\"\r\n\
+4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0xZZZ,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\r\n";
+
+        let events = EventParser::new(log.as_bytes()).collect::<Vec<_>>();
+        assert!(events[0].is_ok());
+
+        let failure = events[1].as_ref().unwrap_err();
+        assert_eq!(failure.line_no, Some(2));
+        assert_eq!(failure.raw, log.lines().nth(1).unwrap().trim_end());
+
+        let failure = EventParser::new(log.replace("\r\n", "\n").as_bytes()).nth(1).unwrap().unwrap_err();
+        assert_eq!(failure.line_no, Some(2));
+        assert_eq!(failure.raw, log.lines().nth(1).unwrap().trim_end());
+    }
+
+    #[test]
+    fn failure_location() {
+        let log = "4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\r\n\
+# Comments are skipped\r\n\
+4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0xZZZ,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\r\n";
+        let byte = log.rfind("4/6").unwrap() as u64;
+
+        let failure = EventParser::new(log.as_bytes()).with_file("WoWCombatLog.txt")
+            .find_map(Result::err).unwrap();
+        assert_eq!(failure.location(), format!("WoWCombatLog.txt:3 (byte {})", byte));
+
+        // Reading from part way through
+        let failure = EventParser::new(&log.as_bytes()[10..]).starting_at(10)
+            .filter_map(Result::err).last().unwrap();
+        assert_eq!(failure.line_no, None);
+        assert_eq!(failure.byte_offset, byte);
+    }
+
+    #[test]
+    fn garbled_line() {
+        let mut log = b"4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"S\xc3\xb8nike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n".to_vec();
+        let good = log.clone();
+        log.extend_from_slice(b"4/6 14:02:08.1\xff\xfe\x00\n");
+        log.extend_from_slice(&good);
+
+        let events = EventParser::new(&log[..]).collect::<Vec<_>>();
+        assert_eq!(events.len(), 3);
+        assert!(events[0].is_ok() && events[2].is_ok());
+
+        let failure = events[1].as_ref().unwrap_err();
+        assert_eq!(failure.line_no, Some(2));
+        assert_eq!(failure.byte_offset, good.len() as u64);
+        assert!(matches!(failure.source, ParseError::Unreadable { .. }));
+    }
+
+    #[test]
+    fn lenient() {
+        let log = "4/6 14:02:07.362  SPELL_SOMETHING_NEW,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,0000000000000000,nil,0x80000000,0x80000000,1234\n\
+4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0xZZZ,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n";
+
+        let strict = EventParser::new(log.as_bytes()).collect::<Vec<_>>();
+        assert!(matches!(strict[0].as_ref().unwrap_err().source, ParseError::UnknownEvent { .. }));
+
+        let events = EventParser::new(log.as_bytes())
+            .with_options(ParseOptions { lenient: true, ..Default::default() })
+            .collect::<Vec<_>>();
+        let event = events[0].as_ref().unwrap();
+        let EventType::Unknown { name, raw_fields } = &event.event_type else { panic!("{:?}", event) };
+        assert_eq!((&**name, raw_fields.len(), raw_fields[1].as_str()), ("SPELL_SOMETHING_NEW", 9, "\"Sønike-Ysondre\""));
+        assert_eq!(event.to_log_line(), log.lines().next().unwrap());
+        // Known events with bad fields still fail
+        assert!(events[1].is_err());
+    }
+
+    #[test]
+    fn strict() {
+        let strict = ParseOptions { strict: true, ..Default::default() };
+        let reference = include_str!("../resources/bench_reference.txt");
+        let failures = EventParser::new(reference.as_bytes()).with_options(strict)
+            .filter_map(Result::err)
+            .collect::<Vec<_>>();
+        assert!(failures.is_empty(), "{:?}", failures);
+
+        // A field added by a patch
+        let log = "4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1,42\n";
+        assert!(EventParser::new(log.as_bytes()).next().unwrap().is_ok());
+        let failure = EventParser::new(log.as_bytes()).with_options(strict).next().unwrap().unwrap_err();
+        assert!(matches!(failure.source, ParseError::FieldCount { expected: 11, got: 12, .. }), "{:?}", failure);
+    }
+
+    #[test]
+    fn recover() {
+        // Cut off mid damage suffix, then mid actor
+        let log = "4/6 14:02:07.362  SPELL_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,421971,\"Controlled Burn\",0x4,Creature-0-1469-2549-12530-209333-000011428A,0000000000000000,500000,1000000,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,483,25000\n\
+4/6 14:02:08.362  UNIT_DIED,0000000000000000,nil\n";
+
+        let failures = EventParser::new(log.as_bytes()).filter_map(Result::err).collect::<Vec<_>>();
+        assert!(matches!(failures[0].source, ParseError::FieldCount { expected: 39, got: 30, .. }), "{:?}", failures[0]);
+        assert!(matches!(failures[1].source, ParseError::FieldCount { expected: 10, got: 3, .. }), "{:?}", failures[1]);
+
+        let events = EventParser::new(log.as_bytes())
+            .with_options(ParseOptions { recover: true, ..Default::default() })
+            .collect::<Vec<_>>();
+        let EventType::Partial(partial) = &events[0].as_ref().unwrap().event_type else { panic!("{:?}", events[0]) };
+        assert_eq!(&*partial.name, "SPELL_DAMAGE");
+        assert_eq!(partial.source.as_ref().unwrap().name, "Sønike-Ysondre");
+        assert_eq!(partial.target.as_ref().unwrap().name, "Gnarlroot");
+        assert_eq!(partial.spell.as_ref().unwrap().spell_id, 421971);
+        assert_eq!(events[0].as_ref().unwrap().to_log_line(), log.lines().next().unwrap());
+        let EventType::Partial(partial) = &events[1].as_ref().unwrap().event_type else { panic!("{:?}", events[1]) };
+        assert!(partial.source.is_none() && partial.spell.is_none());
+    }
+
+    #[test]
+    fn truncated_affixes() {
+        let log = "4/6 14:00:01.000  CHALLENGE_MODE_START,\"The Dawnbreaker\",2662,505,10,\n\
+4/6 14:00:01.000  CHALLENGE_MODE_START,\"The Dawnbreaker\",2662,505,10,[\n\
+4/6 14:00:01.000  CHALLENGE_MODE_START,\"The Dawnbreaker\",2662,505,10,[10,109\n\
+4/6 14:00:01.000  CHALLENGE_MODE_START,\"The Dawnbreaker\",2662,505,10,[]\n";
+
+        let events = EventParser::new(log.as_bytes()).collect::<Vec<_>>();
+        for failure in events[..3].iter().map(|e| e.as_ref().unwrap_err()) {
+            assert!(matches!(&failure.source, ParseError::Malformed { reason, .. } if reason.contains("brackets")), "{:?}", failure);
+        }
+        let EventType::Special { details: Special::ChallengeModeStart { affix_ids, .. }, .. } = &events[3].as_ref().unwrap().event_type
+            else { panic!("{:?}", events[3]) };
+        assert!(affix_ids.is_empty());
+    }
+
+    #[test]
+    fn niche_events() {
+        let log = "4/6 14:02:07.362  STAGGER_PREVENTED,Player-1403-0A1B2C3D,124255,5210.500000\n\
+4/6 14:02:08.100  STAGGER_CLEAR,Player-1403-0A1B2C3D,48213.238281\n\
+4/6 14:02:09.512  SPELL_DISPEL,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x511,0x0,Player-1390-0C4E032E,\"Stillnixx-Hyjal\",0x512,0x0,527,\"Purify\",0x2,240443,\"Burst\",0x20,DEBUFF\n\
+4/6 14:02:10.004  SPELL_DISPEL,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x511,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,528,\"Dispel Magic\",0x2,421971,\"Controlled Burn\",0x4,BUFF\n";
+
+        let events = EventParser::new(log.as_bytes()).collect::<Vec<_>>();
+        assert!(events.iter().all(Result::is_ok), "{:?}", events);
+    }
+
+    #[test]
+    fn chunks() {
+        let log = "9/28 20:15:41.000  COMBAT_LOG_VERSION,9,ADVANCED_LOG_ENABLED,1,BUILD_VERSION,3.4.3,PROJECT_ID,11\n\
+9/28 20:15:41.735  SPELL_DAMAGE,Player-4395-01C5EEA6,\"Xyz-Whitemane\",0x512,0x0,Creature-0-4395-615-22-30452-00001A2B3C,\"Tenebron\",0x10a48,0x0,57591,\"Vengeance\",0x2,Creature-0-4395-615-22-30452-00001A2B3C,0000000000000000,3487744,3500000,0,0,0,-1,0,0,0,3262.87,532.15,0,1.6829,83,12256,12255,-1,2,0,0,0,nil,nil,nil\n\
+9/28 20:15:42.000  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1";
+
+        // Split mid-line, as a browser reading the file might hand it over
+        let split = log.find("Tenebron").unwrap();
+        let mut parser = ChunkParser::new();
+        let first = parser.push(&log[..split]);
+        assert_eq!(first.len(), 1);
+
+        let second = parser.push(&log[split..]);
+        assert_eq!(second.len(), 1);
+        // The classic layout from the header carries over between chunks
+        assert!(second[0].is_ok(), "{:?}", second);
+
+        let last = parser.finish();
+        assert!(matches!(&last[..], [Ok(_)]), "{:?}", last);
+    }
+}
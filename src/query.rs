@@ -0,0 +1,748 @@
+//! A small SQL-like language for ad-hoc questions about a log, eg.
+//! `SELECT source, SUM(amount) FROM damage WHERE target = 'Fyrakk' GROUP BY source ORDER BY SUM(amount) DESC`.
+//! Queries run over a single table, built column by column from the log's events.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+
+use crate::components::common::Actor;
+use crate::components::events::{Event, EventType};
+use crate::components::prefixes::Prefix;
+use crate::components::suffixes::Suffix;
+use crate::index::{self, LogIndex};
+use crate::summary::{Cell, Summary, Table};
+use crate::utils::wildcard_match;
+use wowlogs_parser::mmap::MmapParser;
+
+const DAMAGE: &[&str] = &["time", "event", "source", "source_guid", "target", "target_guid", "spell_id", "spell", "amount", "overkill", "absorbed", "critical"];
+const HEALING: &[&str] = &["time", "event", "source", "source_guid", "target", "target_guid", "spell_id", "spell", "amount", "overhealing", "absorbed", "critical"];
+const EVENTS: &[&str] = &["time", "event", "source", "source_guid", "target", "target_guid"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl Value {
+    fn number(&self) -> Option<f64> {
+        match self {
+            Self::Int(i) => Some(*i as f64),
+            Self::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    fn truthy(&self) -> bool {
+        match self {
+            Self::Null => false,
+            Self::Int(i) => *i != 0,
+            Self::Float(f) => *f != 0.,
+            Self::Text(t) => !t.is_empty(),
+        }
+    }
+
+    /// Nulls first, then numbers, then text
+    fn order(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => a.cmp(b),
+            (Self::Text(a), Self::Text(b)) => a.cmp(b),
+            (Self::Null, Self::Null) => Ordering::Equal,
+            (Self::Null, _) => Ordering::Less,
+            (_, Self::Null) => Ordering::Greater,
+            (Self::Text(_), _) => Ordering::Greater,
+            (_, Self::Text(_)) => Ordering::Less,
+            (a, b) => a.number().unwrap().total_cmp(&b.number().unwrap()),
+        }
+    }
+
+    fn cell(&self) -> Cell {
+        match self {
+            Self::Null => Cell::Empty,
+            Self::Int(i) => Cell::Int(*i),
+            Self::Float(f) => Cell::Float(*f, 2),
+            Self::Text(t) => Cell::Text(t.clone()),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => Ok(()),
+            Self::Int(i) => write!(f, "{}", i),
+            Self::Float(x) => write!(f, "{:.2}", x),
+            Self::Text(t) => write!(f, "{}", t),
+        }
+    }
+}
+
+/// Values stored a column at a time
+#[derive(Debug)]
+pub struct ColumnTable {
+    names: &'static [&'static str],
+    columns: Vec<Vec<Value>>,
+}
+
+impl ColumnTable {
+    fn new(names: &'static [&'static str]) -> Self {
+        Self { names, columns: names.iter().map(|_| vec![]).collect() }
+    }
+
+    fn push(&mut self, row: Vec<Value>) {
+        debug_assert_eq!(row.len(), self.names.len());
+        self.columns.iter_mut().zip(row).for_each(|(c, v)| c.push(v));
+    }
+
+    fn len(&self) -> usize {
+        self.columns.first().map_or(0, Vec::len)
+    }
+
+    fn column(&self, name: &str) -> Result<usize> {
+        self.names.iter().position(|n| n.eq_ignore_ascii_case(name))
+            .with_context(|| format!("Unknown column {:?}, expected one of: {}", name, self.names.join(", ")))
+    }
+
+    /// Builds one of the `damage`, `healing` or `events` tables
+    pub fn from_events(table: &str, events: impl Iterator<Item = Event>) -> Result<Self> {
+        let (names, kind): (&'static [&'static str], _) = match table.to_lowercase().as_str() {
+            "damage" => (DAMAGE, 0),
+            "healing" => (HEALING, 1),
+            "events" => (EVENTS, 2),
+            _ => bail!("Unknown table {:?}, expected damage, healing or events", table),
+        };
+        let mut out = Self::new(names);
+
+        let name = |a: &Option<Actor>| a.as_ref().map_or(Value::Null, |a| Value::Text(a.name.clone()));
+        let guid = |a: &Option<Actor>| a.as_ref().map_or(Value::Null, |a| Value::Text(a.guid.to_string()));
+
+        for event in events {
+            let time = Value::Text(event.timestamp.format("%m/%d %H:%M:%S%.3f").to_string());
+            let (event_name, source, target, prefix, suffix) = match &event.event_type {
+                EventType::Standard { name, source, target, prefix, suffix, .. } => (name.to_string(), source, target, Some(&**prefix), Some(suffix)),
+                EventType::EnvironmentalDamage { source, target, suffix, .. } => (event.event_type.name().to_string(), source, target, None, Some(suffix)),
+                EventType::Partial(partial) => (partial.name.to_string(), &partial.source, &partial.target, None, None),
+                EventType::Special { name, .. } | EventType::Unknown { name, .. } => (name.to_string(), &None, &None, None, None),
+            };
+            let actors = [time, Value::Text(event_name), name(source), guid(source), name(target), guid(target)];
+
+            let spell = || match (prefix.and_then(Prefix::spell_info), &event.event_type) {
+                (Some(s), _) => [Value::Int(s.spell_id as i64), Value::Text(s.spell_name.to_string())],
+                (None, EventType::EnvironmentalDamage { env_type, .. }) => [Value::Null, Value::Text(format!("{:?}", env_type))],
+                (None, _) => [Value::Null, Value::Text("Melee".to_string())],
+            };
+
+            match (kind, suffix) {
+                (0, Some(Suffix::Damage { amount, overkill, absorbed, critical, .. })) => out.push([
+                    &actors[..],
+                    &spell(),
+                    &[Value::Int(*amount), Value::Int(overkill.unwrap_or(0) as i64), Value::Int(*absorbed), Value::Int(*critical as i64)],
+                ].concat()),
+                (1, Some(Suffix::Heal { amount, overhealing, absorbed, critical, .. })) => out.push([
+                    &actors[..],
+                    &spell(),
+                    &[Value::Int(*amount as i64), Value::Int(*overhealing as i64), Value::Int(*absorbed as i64), Value::Int(*critical as i64)],
+                ].concat()),
+                (2, _) => out.push(actors.to_vec()),
+                _ => {}
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Aggregate {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Column(String),
+    Literal(Value),
+    /// `*` in the select list, every column
+    Star,
+    /// `COUNT(*)` has no argument
+    Aggregate(Aggregate, Option<Box<Expr>>),
+    Binary(Box<Expr>, &'static str, Box<Expr>),
+    Not(Box<Expr>),
+    Like(Box<Expr>, String),
+}
+
+impl Expr {
+    fn has_aggregate(&self) -> bool {
+        match self {
+            Self::Aggregate(..) => true,
+            Self::Binary(a, _, b) => a.has_aggregate() || b.has_aggregate(),
+            Self::Not(e) | Self::Like(e, _) => e.has_aggregate(),
+            _ => false,
+        }
+    }
+
+    fn check_columns(&self, table: &ColumnTable) -> Result<()> {
+        match self {
+            Self::Column(c) => table.column(c).map(|_| ()),
+            Self::Aggregate(_, Some(e)) | Self::Not(e) | Self::Like(e, _) => e.check_columns(table),
+            Self::Binary(a, _, b) => a.check_columns(table).and(b.check_columns(table)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Value over a group of rows. Columns outside of aggregates take the group's first row.
+    fn eval(&self, table: &ColumnTable, rows: &[usize]) -> Result<Value> {
+        Ok(match self {
+            Self::Column(c) => rows.first().map_or(Value::Null, |&r| table.columns[table.column(c).unwrap()][r].clone()),
+            Self::Literal(v) => v.clone(),
+            Self::Star => bail!("* can only be used on its own in the select list, or in COUNT(*)"),
+            Self::Aggregate(Aggregate::Count, None) => Value::Int(rows.len() as i64),
+            Self::Aggregate(agg, Some(arg)) => {
+                let values = rows.iter()
+                    .map(|&r| arg.eval(table, &[r]))
+                    .filter_ok(|v| *v != Value::Null)
+                    .collect::<Result<Vec<_>>>()?;
+                aggregate(*agg, values)?
+            }
+            Self::Aggregate(_, None) => bail!("Only COUNT can take *"),
+            Self::Not(e) => Value::Int(!e.eval(table, rows)?.truthy() as i64),
+            Self::Like(e, pattern) => match e.eval(table, rows)? {
+                Value::Null => Value::Null,
+                v => Value::Int(like(&pattern.to_lowercase(), &v.to_string().to_lowercase()) as i64),
+            },
+            Self::Binary(a, "AND", b) => Value::Int((a.eval(table, rows)?.truthy() && b.eval(table, rows)?.truthy()) as i64),
+            Self::Binary(a, "OR", b) => Value::Int((a.eval(table, rows)?.truthy() || b.eval(table, rows)?.truthy()) as i64),
+            Self::Binary(a, op, b) => binary(op, a.eval(table, rows)?, b.eval(table, rows)?)?,
+        })
+    }
+}
+
+fn aggregate(agg: Aggregate, values: Vec<Value>) -> Result<Value> {
+    if agg == Aggregate::Count { return Ok(Value::Int(values.len() as i64)); }
+    if values.is_empty() { return Ok(Value::Null); }
+
+    Ok(match agg {
+        Aggregate::Min => values.into_iter().min_by(Value::order).unwrap(),
+        Aggregate::Max => values.into_iter().max_by(Value::order).unwrap(),
+        Aggregate::Sum if values.iter().all(|v| matches!(v, Value::Int(_))) =>
+            Value::Int(values.iter()
+                .try_fold(0i64, |sum, v| if let Value::Int(i) = v { sum.checked_add(*i) } else { Some(sum) })
+                .context("SUM overflows")?),
+        Aggregate::Sum => Value::Float(values.iter().filter_map(Value::number).sum()),
+        Aggregate::Avg => {
+            let numbers = values.iter().filter_map(Value::number).collect_vec();
+            if numbers.is_empty() { Value::Null } else { Value::Float(numbers.iter().sum::<f64>() / numbers.len() as f64) }
+        }
+        Aggregate::Count => unreachable!(),
+    })
+}
+
+fn binary(op: &str, a: Value, b: Value) -> Result<Value> {
+    if a == Value::Null || b == Value::Null { return Ok(Value::Null); }
+
+    let compare = |f: fn(Ordering) -> bool| {
+        // Numbers compare with numbers, text with text
+        let comparable = a.number().is_some() == b.number().is_some();
+        Value::Int((comparable && f(a.order(&b))) as i64)
+    };
+
+    Ok(match op {
+        "=" => compare(|o| o == Ordering::Equal),
+        "!=" => compare(|o| o != Ordering::Equal),
+        "<" => compare(|o| o == Ordering::Less),
+        "<=" => compare(|o| o != Ordering::Greater),
+        ">" => compare(|o| o == Ordering::Greater),
+        ">=" => compare(|o| o != Ordering::Less),
+        _ => match (&a, &b) {
+            (Value::Int(x), Value::Int(y)) if op != "/" => {
+                let result = match op {
+                    "+" => x.checked_add(*y),
+                    "-" => x.checked_sub(*y),
+                    _ => x.checked_mul(*y),
+                };
+                Value::Int(result.with_context(|| format!("{} {} {} overflows", x, op, y))?)
+            }
+            _ => {
+                let (Some(x), Some(y)) = (a.number(), b.number()) else {
+                    bail!("Can't apply {} to {:?} and {:?}", op, a, b);
+                };
+                match op {
+                    "+" => Value::Float(x + y),
+                    "-" => Value::Float(x - y),
+                    "*" => Value::Float(x * y),
+                    _ if y == 0. => Value::Null,
+                    _ => Value::Float(x / y),
+                }
+            }
+        },
+    })
+}
+
+/// Matches a LIKE pattern, where `%` is any run of characters & `_` any one character
+fn like(pattern: &str, text: &str) -> bool {
+    wildcard_match(pattern, text, '%', '_')
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Number(String),
+    Text(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: &[&str] = &["<=", ">=", "!=", "<>", "=", "<", ">", ",", "(", ")", "*", "+", "-", "/"];
+
+/// Tokens with their start & end in the query
+fn tokenize(sql: &str) -> Result<Vec<(Token, usize, usize)>> {
+    let mut tokens = vec![];
+    let chars = sql.char_indices().collect_vec();
+    let mut i = 0;
+    while i < chars.len() {
+        let (start, c) = chars[i];
+        let end_of = |j: usize| chars.get(j).map_or(sql.len(), |&(b, _)| b);
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '\'' {
+            let close = (i + 1..chars.len()).find(|&j| chars[j].1 == '\'')
+                .with_context(|| format!("Unclosed string starting at {}", start))?;
+            tokens.push((Token::Text(sql[end_of(i + 1)..chars[close].0].to_string()), start, end_of(close + 1)));
+            i = close + 1;
+        } else if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|(_, d)| d.is_ascii_digit())) {
+            let end = (i..chars.len()).find(|&j| !(chars[j].1.is_ascii_digit() || chars[j].1 == '.')).unwrap_or(chars.len());
+            tokens.push((Token::Number(sql[start..end_of(end)].to_string()), start, end_of(end)));
+            i = end;
+        } else if c.is_alphabetic() || c == '_' {
+            let end = (i..chars.len()).find(|&j| !(chars[j].1.is_alphanumeric() || chars[j].1 == '_')).unwrap_or(chars.len());
+            tokens.push((Token::Word(sql[start..end_of(end)].to_string()), start, end_of(end)));
+            i = end;
+        } else {
+            let symbol = SYMBOLS.iter().find(|s| sql[start..].starts_with(**s))
+                .with_context(|| format!("Unexpected {:?} at {}", c, start))?;
+            tokens.push((Token::Symbol(if *symbol == "<>" { "!=" } else { symbol }), start, start + symbol.len()));
+            i += symbol.len();
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug)]
+pub struct Query {
+    /// Expressions & their column names
+    select: Vec<(Expr, String)>,
+    pub from: String,
+    filter: Option<Expr>,
+    group_by: Vec<Expr>,
+    /// Expressions & whether they're descending
+    order_by: Vec<(Expr, bool)>,
+    limit: Option<usize>,
+}
+
+struct Parser<'a> {
+    sql: &'a str,
+    tokens: Vec<(Token, usize, usize)>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.0)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword));
+        if found { self.pos += 1; }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if !self.keyword(keyword) { bail!("Expected {} {}", keyword, self.here()); }
+        Ok(())
+    }
+
+    fn symbol(&mut self, symbol: &str) -> bool {
+        let found = self.peek() == Some(&Token::Symbol(match SYMBOLS.iter().find(|s| **s == symbol) {
+            Some(s) => s,
+            None => return false,
+        }));
+        if found { self.pos += 1; }
+        found
+    }
+
+    fn here(&self) -> String {
+        match self.tokens.get(self.pos) {
+            Some((_, start, _)) => format!("at {:?}", &self.sql[*start..]),
+            None => "at the end of the query".to_string(),
+        }
+    }
+
+    fn word(&mut self) -> Result<String> {
+        match self.peek().cloned() {
+            Some(Token::Word(w)) => { self.pos += 1; Ok(w) }
+            _ => bail!("Expected a name {}", self.here()),
+        }
+    }
+
+    fn list<T>(&mut self, mut item: impl FnMut(&mut Self) -> Result<T>) -> Result<Vec<T>> {
+        let mut items = vec![item(self)?];
+        while self.symbol(",") {
+            items.push(item(self)?);
+        }
+        Ok(items)
+    }
+
+    fn query(&mut self) -> Result<Query> {
+        self.expect_keyword("SELECT")?;
+        let select = self.list(|p| {
+            if p.symbol("*") { return Ok((Expr::Star, "*".to_string())); }
+            let start = p.tokens.get(p.pos).map_or(p.sql.len(), |t| t.1);
+            let expr = p.or()?;
+            let end = p.tokens.get(p.pos - 1).map_or(p.sql.len(), |t| t.2);
+            let name = if p.keyword("AS") { p.word()? } else { p.sql[start..end].to_string() };
+            Ok((expr, name))
+        })?;
+
+        self.expect_keyword("FROM")?;
+        let from = self.word()?;
+        let filter = if self.keyword("WHERE") { Some(self.or()?) } else { None };
+        let group_by = if self.keyword("GROUP") {
+            self.expect_keyword("BY")?;
+            self.list(Self::or)?
+        } else { vec![] };
+        let order_by = if self.keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            self.list(|p| {
+                let expr = p.or()?;
+                let desc = p.keyword("DESC");
+                if !desc { p.keyword("ASC"); }
+                Ok((expr, desc))
+            })?
+        } else { vec![] };
+        let limit = if self.keyword("LIMIT") {
+            match self.peek().cloned() {
+                Some(Token::Number(n)) => { self.pos += 1; Some(n.parse().with_context(|| format!("Bad LIMIT {:?}", n))?) }
+                _ => bail!("Expected a number {}", self.here()),
+            }
+        } else { None };
+
+        if self.pos < self.tokens.len() {
+            bail!("Unexpected {}", self.here());
+        }
+        Ok(Query { select, from, filter, group_by, order_by, limit })
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.keyword("OR") {
+            expr = Expr::Binary(Box::new(expr), "OR", Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.not()?;
+        while self.keyword("AND") {
+            expr = Expr::Binary(Box::new(expr), "AND", Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        if self.keyword("NOT") { return Ok(Expr::Not(Box::new(self.not()?))); }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let expr = self.additive()?;
+        for op in ["=", "!=", "<=", ">=", "<", ">"] {
+            if self.symbol(op) {
+                let op = SYMBOLS.iter().find(|s| **s == op).unwrap();
+                return Ok(Expr::Binary(Box::new(expr), op, Box::new(self.additive()?)));
+            }
+        }
+
+        let negated = self.keyword("NOT");
+        if self.keyword("LIKE") {
+            let Some(Token::Text(pattern)) = self.peek().cloned() else { bail!("Expected a 'pattern' {}", self.here()); };
+            self.pos += 1;
+            let like = Expr::Like(Box::new(expr), pattern);
+            return Ok(if negated { Expr::Not(Box::new(like)) } else { like });
+        }
+        if negated { bail!("Expected LIKE {}", self.here()); }
+        Ok(expr)
+    }
+
+    fn additive(&mut self) -> Result<Expr> {
+        let mut expr = self.term()?;
+        loop {
+            let op = if self.symbol("+") { "+" } else if self.symbol("-") { "-" } else { return Ok(expr); };
+            expr = Expr::Binary(Box::new(expr), op, Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        loop {
+            let op = if self.symbol("*") { "*" } else if self.symbol("/") { "/" } else { return Ok(expr); };
+            expr = Expr::Binary(Box::new(expr), op, Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.symbol("-") {
+            return Ok(Expr::Binary(Box::new(Expr::Literal(Value::Int(0))), "-", Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        let Some(token) = self.peek().cloned() else { bail!("Expected a value at the end of the query"); };
+        self.pos += 1;
+        match token {
+            Token::Number(n) => Ok(Expr::Literal(match n.parse::<i64>() {
+                Ok(i) => Value::Int(i),
+                Err(_) => Value::Float(n.parse().with_context(|| format!("Bad number {:?}", n))?),
+            })),
+            Token::Text(t) => Ok(Expr::Literal(Value::Text(t))),
+            Token::Symbol("(") => {
+                let expr = self.or()?;
+                if !self.symbol(")") { bail!("Expected ) {}", self.here()); }
+                Ok(expr)
+            }
+            Token::Word(w) if w.eq_ignore_ascii_case("NULL") => Ok(Expr::Literal(Value::Null)),
+            Token::Word(w) if self.symbol("(") => {
+                let agg = match w.to_uppercase().as_str() {
+                    "COUNT" => Aggregate::Count,
+                    "SUM" => Aggregate::Sum,
+                    "AVG" => Aggregate::Avg,
+                    "MIN" => Aggregate::Min,
+                    "MAX" => Aggregate::Max,
+                    _ => bail!("Unknown function {:?}, expected COUNT, SUM, AVG, MIN or MAX", w),
+                };
+                let arg = if self.symbol("*") { None } else { Some(Box::new(self.or()?)) };
+                if !self.symbol(")") { bail!("Expected ) {}", self.here()); }
+                Ok(Expr::Aggregate(agg, arg))
+            }
+            Token::Word(w) => Ok(Expr::Column(w)),
+            Token::Symbol(s) => {
+                self.pos -= 1;
+                bail!("Unexpected {:?} {}", s, self.here())
+            }
+        }
+    }
+}
+
+impl Query {
+    pub fn parse(sql: &str) -> Result<Self> {
+        Parser { sql, tokens: tokenize(sql)?, pos: 0 }.query()
+            .with_context(|| format!("Bad query: {}", sql))
+    }
+
+    /// Runs the query, giving the results as a table
+    pub fn run(&self, table: &ColumnTable) -> Result<Summary> {
+        let select = self.select.iter()
+            .flat_map(|(expr, name)| match expr {
+                Expr::Star => table.names.iter().map(|n| (Expr::Column(n.to_string()), n.to_string())).collect_vec(),
+                _ => vec![(expr.clone(), name.clone())],
+            })
+            .collect_vec();
+
+        let exprs = select.iter().map(|(e, _)| e)
+            .chain(&self.filter)
+            .chain(&self.group_by)
+            .chain(self.order_by.iter().map(|(e, _)| e).filter(|e| !matches!(e, Expr::Column(c) if select.iter().any(|(_, n)| n == c))));
+        for expr in exprs {
+            expr.check_columns(table)?;
+        }
+        if self.filter.as_ref().is_some_and(Expr::has_aggregate) {
+            bail!("WHERE can't use aggregates");
+        }
+
+        let mut rows = vec![];
+        for row in 0..table.len() {
+            if self.filter.as_ref().map_or(Ok(true), |f| f.eval(table, &[row]).map(|v| v.truthy()))? {
+                rows.push(row);
+            }
+        }
+
+        // Each group of rows becomes one line of the results
+        let groups = if !self.group_by.is_empty() {
+            let mut groups: Vec<Vec<usize>> = vec![];
+            let mut keys = HashMap::new();
+            for row in rows {
+                let key = self.group_by.iter().map(|e| e.eval(table, &[row]).map(|v| format!("{:?}", v))).collect::<Result<Vec<_>>>()?;
+                let i = *keys.entry(key).or_insert_with(|| { groups.push(vec![]); groups.len() - 1 });
+                groups[i].push(row);
+            }
+            groups
+        } else if select.iter().any(|(e, _)| e.has_aggregate()) {
+            vec![rows]
+        } else {
+            rows.into_iter().map(|r| vec![r]).collect()
+        };
+
+        let mut lines = groups.iter()
+            .map(|rows| {
+                let values = select.iter().map(|(e, _)| e.eval(table, rows)).collect::<Result<Vec<_>>>()?;
+                let keys = self.order_by.iter()
+                    .map(|(e, _)| {
+                        // Ordering by a name from the select list uses that column
+                        let named = match e {
+                            Expr::Column(c) => select.iter().position(|(_, n)| n == c),
+                            _ => None,
+                        };
+                        named.map_or_else(|| e.eval(table, rows), |i| Ok(values[i].clone()))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok((keys, values))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        lines.sort_by(|(a, _), (b, _)| {
+            a.iter().zip(b).zip(&self.order_by)
+                .map(|((a, b), (_, desc))| if *desc { b.order(a) } else { a.order(b) })
+                .find(|o| o.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        lines.truncate(self.limit.unwrap_or(usize::MAX));
+
+        let widths = select.iter().enumerate()
+            .map(|(i, (_, name))| lines.iter().map(|(_, v)| v[i].to_string().chars().count()).max().unwrap_or(0).max(name.chars().count()))
+            .collect_vec();
+        let mut out = Table::new(&select.iter().zip(&widths).map(|((_, name), &w)| (name.as_str(), w)).collect_vec());
+        for (_, values) in lines {
+            out.push(values.iter().map(Value::cell).collect());
+        }
+
+        Ok(Summary::new("Query").with_table(out))
+    }
+}
+
+/// Answers a query about a log, or just the selected encounters of it, seeking to them using the log's index
+pub fn query(log: &Path, sql: &str, encounter: Option<&str>) -> Result<Summary> {
+    let query = Query::parse(sql)?;
+    let table = match encounter {
+        Some(selector) => {
+            let regions = LogIndex::load_or_build(log)?.select(selector);
+            if regions.is_empty() {
+                bail!("No encounters matching {:?}", selector);
+            }
+            let parsers = regions.iter().map(|r| index::read_region(log, r)).collect::<Result<Vec<_>>>()?;
+            ColumnTable::from_events(&query.from, parsers.into_iter().flatten().filter_map(Result::ok))?
+        }
+        None => ColumnTable::from_events(&query.from, MmapParser::open(log)?.filter_map(Result::ok))?,
+    };
+    query.run(&table)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::EventParser;
+    use crate::index::LogIndex;
+    use crate::query::{like, query, ColumnTable, Query};
+    use crate::summary::Cell;
+
+    const LOG: &str = "4/6 14:02:07.362  SWING_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4C,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,500,500,-1,1,0,0,0,nil,nil,nil\n\
+4/6 14:02:08.000  SPELL_DAMAGE,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,8921,\"Moonfire\",0x40,Creature-0-1469-2549-12530-209333-000011428A,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,72,1500,1500,-1,64,0,0,0,1,nil,nil\n\
+4/6 14:02:09.000  SWING_DAMAGE,Player-1335-0A264B4D,\"Other-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428B,\"Add\",0x10a48,0x0,Player-1335-0A264B4D,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,300,300,-1,1,0,0,0,nil,nil,nil\n\
+4/6 14:02:10.000  SWING_DAMAGE,Player-1335-0A264B4D,\"Other-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,Player-1335-0A264B4D,0000000000000000,100,100,0,0,0,0,0,0,0,0,0.00,0.00,2232,0.0000,70,700,700,-1,1,0,0,0,nil,nil,nil\n";
+
+    fn table(name: &str) -> ColumnTable {
+        ColumnTable::from_events(name, EventParser::new(LOG.as_bytes()).filter_map(Result::ok)).unwrap()
+    }
+
+    #[test]
+    fn group_by() {
+        let query = Query::parse("SELECT source, SUM(amount) AS total, COUNT(*) FROM damage WHERE target='Gnarlroot' GROUP BY source ORDER BY total DESC").unwrap();
+        let summary = query.run(&table(&query.from)).unwrap();
+        let table = &summary.tables[0];
+
+        assert_eq!(table.columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["source", "total", "COUNT(*)"]);
+        assert_eq!(table.rows, vec![
+            vec![Cell::Text("Sønike-Ysondre".to_string()), Cell::Int(2000), Cell::Int(2)],
+            vec![Cell::Text("Other-Ysondre".to_string()), Cell::Int(700), Cell::Int(1)],
+        ]);
+    }
+
+    #[test]
+    fn expressions() {
+        let run = |sql: &str| {
+            let query = Query::parse(sql).unwrap();
+            query.run(&table(&query.from)).unwrap().tables[0].rows.clone()
+        };
+
+        assert_eq!(run("select spell, amount from damage where critical = 1 or amount < 400 order by amount"), vec![
+            vec![Cell::Text("Melee".to_string()), Cell::Int(300)],
+            vec![Cell::Text("Moonfire".to_string()), Cell::Int(1500)],
+        ]);
+        assert_eq!(run("SELECT MAX(amount) - MIN(amount), AVG(amount) FROM damage WHERE source LIKE 'sønike%'"), vec![
+            vec![Cell::Int(1000), Cell::Float(1000., 2)],
+        ]);
+        assert_eq!(run("SELECT target FROM damage WHERE NOT target LIKE 'Gnarl%' LIMIT 5"), vec![vec![Cell::Text("Add".to_string())]]);
+        assert_eq!(run("SELECT COUNT(*) FROM events").len(), 1);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |sql: &str| match Query::parse(sql) {
+            Ok(query) => format!("{:#}", query.run(&table(&query.from)).unwrap_err()),
+            Err(e) => format!("{:#}", e),
+        };
+
+        assert!(error("SELECT source FROM damage WHERE target = 'Fyrakk").contains("Unclosed string"));
+        assert!(error("SELECT nonsense FROM damage").contains("Unknown column \"nonsense\""));
+        assert!(error("SELECT source FROM damage GROUP source").contains("Expected BY"));
+        assert!(error("SELECT MEDIAN(amount) FROM damage").contains("Unknown function"));
+        assert!(error("SELECT amount * 9223372036854775807 FROM damage").contains("overflows"));
+        assert!(error("SELECT -9223372036854775807 - amount FROM damage").contains("overflows"));
+        assert!(error("SELECT SUM(amount * 4611686018427387) FROM damage").contains("SUM overflows"));
+    }
+
+    #[test]
+    fn like_patterns() {
+        assert!(like("gnarl%", "gnarlroot"));
+        assert!(like("%root", "gnarlroot"));
+        assert!(like("g_a%o_t", "gnarlroot"));
+        assert!(like("%%", ""));
+        assert!(like("%a%b%", "xaxxbx"));
+        assert!(!like("%a%b", "xaxxbx"));
+        assert!(!like("gnarl", "gnarlroot"));
+        assert!(!like("_", ""));
+
+        // Would take exponential time with backtracking at every %
+        let text = "a".repeat(1000);
+        assert!(!like(&format!("{}b", "%a".repeat(50)), &text));
+    }
+
+    #[test]
+    fn encounter() {
+        let lines = LOG.lines().collect::<Vec<_>>();
+        let log = [
+            "4/6 14:02:00.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549",
+            lines[0], lines[1],
+            "4/6 14:02:08.500  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,1,8500",
+            lines[2], lines[3],
+        ].join("\n") + "\n";
+        let path = std::env::temp_dir().join("wowlogs_query_encounter.txt");
+        std::fs::write(&path, log).unwrap();
+        let _ = std::fs::remove_file(LogIndex::path_for(&path));
+
+        let sql = "SELECT SUM(amount) FROM damage";
+        let total = |encounter| query(&path, sql, encounter).unwrap().tables[0].rows[0][0].clone();
+        assert_eq!(total(None), Cell::Int(3000));
+        assert_eq!(total(Some("gnarlroot")), Cell::Int(2000));
+        assert!(query(&path, sql, Some("Fyrakk")).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use itertools::Itertools;
+
+use crate::parser::EventParser;
+
+/// A line from a failed lines file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailedLine {
+    /// Where the line originally came from, if recorded
+    pub location: Option<String>,
+    pub raw: String,
+}
+
+/// Outcome of re-parsing a failed lines file
+#[derive(Debug, Default)]
+pub struct RetryReport {
+    /// Event name -> lines of that event which now parse
+    pub fixed: HashMap<String, Vec<FailedLine>>,
+    /// Lines which still fail, with the new error
+    pub still_failing: Vec<(FailedLine, String)>,
+}
+
+impl RetryReport {
+    pub fn fixed_count(&self) -> usize {
+        self.fixed.values().map(Vec::len).sum()
+    }
+
+    pub fn display(&self) -> String {
+        let total = self.fixed_count() + self.still_failing.len();
+
+        let fixed = self.fixed.iter()
+            .sorted_by_key(|(name, lines)| (std::cmp::Reverse(lines.len()), *name))
+            .map(|(name, lines)| format!("{:>40}:{:>8}", name, lines.len()))
+            .join("\n");
+
+        let failing = self.still_failing.iter()
+            .map(|(line, error)| format!("{}: {}", line.location.as_deref().unwrap_or("?"), error))
+            .join("\n");
+
+        format!("{} of {} previously failing lines now parse\n{}\nStill failing\n{}",
+                self.fixed_count(), total, fixed, failing)
+    }
+
+    /// Writes the lines which still fail, in the same format as the failed lines file
+    pub fn write_still_failing(&self, path: &Path) -> Result<()> {
+        let s = self.still_failing.iter()
+            .map(|(line, _)| match &line.location {
+                Some(location) => format!("# {}\n{}\n", location, line.raw),
+                None => format!("{}\n", line.raw),
+            })
+            .join("");
+
+        std::fs::write(path, s)
+            .with_context(|| format!("Failed to write file: {:?}", path))
+    }
+}
+
+/// Reads the `# location` / raw line pairs written by FileLogger
+pub fn read_failed_lines(s: &str) -> Vec<FailedLine> {
+    let mut lines = vec![];
+    let mut location = None;
+
+    for line in s.lines().filter(|l| !l.trim().is_empty()) {
+        match line.strip_prefix("# ") {
+            Some(l) => location = Some(l.to_string()),
+            None => lines.push(FailedLine { location: location.take(), raw: line.to_string() }),
+        }
+    }
+
+    lines
+}
+
+/// Re-parses each previously failing line with the current parser
+pub fn retry(path: &Path) -> Result<RetryReport> {
+    let s = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to open file: {:?}", path))?;
+
+    let mut report = RetryReport::default();
+    for line in read_failed_lines(&s) {
+        match EventParser::new(line.raw.as_bytes()).next() {
+            Some(Ok(event)) => {
+                report.fixed.entry(event.event_type.name().to_string()).or_default().push(line);
+            }
+            Some(Err(e)) => {
+                let error = e.source.to_string();
+                report.still_failing.push((line, error));
+            }
+            None => report.still_failing.push((line, "Not a complete line".to_string())),
+        }
+    }
+
+    Ok(report)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::retry::{read_failed_lines, retry};
+
+    #[test]
+    fn retry_failed_lines() {
+        let path = std::env::temp_dir().join("wowlogs_retry_test.txt");
+        std::fs::write(&path, "# log.txt:12 (byte 3456)\n\
+4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+# log.txt:40 (byte 9000)\n\
+4/6 14:02:08.000  SWING_MYSTERY,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n").unwrap();
+
+        let report = retry(&path).unwrap();
+        assert_eq!(report.fixed_count(), 1);
+        assert_eq!(report.fixed["SWING_MISSED"][0].location.as_deref(), Some("log.txt:12 (byte 3456)"));
+        assert_eq!(report.still_failing.len(), 1);
+        assert_eq!(report.still_failing[0].0.location.as_deref(), Some("log.txt:40 (byte 9000)"));
+
+        let remaining = std::env::temp_dir().join("wowlogs_retry_remaining.txt");
+        report.write_still_failing(&remaining).unwrap();
+        let lines = read_failed_lines(&std::fs::read_to_string(&remaining).unwrap());
+        assert_eq!(lines, vec![report.still_failing[0].0.clone()]);
+    }
+}
@@ -0,0 +1,377 @@
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use itertools::Itertools;
+use serde::Serialize;
+use serde_reflection::{ContainerFormat, Format, Named, Registry, Tracer, TracerConfig, VariantFormat};
+
+use crate::components::combatant::Faction;
+use crate::components::enums::{AuraType, DamageKind, EnvironmentalType, MissType, PowerType, SpellSchool};
+use crate::components::events::EventType;
+use crate::components::guid::{CastType, CreatureType, GUID};
+use crate::components::names::{PrefixKind, SuffixKind};
+use crate::components::prefixes::Prefix;
+use crate::components::registry::KNOWN_EVENTS;
+use crate::components::special::Special;
+use crate::components::suffixes::Suffix;
+
+/// Types documented by their own section rather than in the shared types
+const EVENT_PARTS: [&str; 4] = ["EventType", "Prefix", "Suffix", "Special"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum SchemaFormat {
+    #[default]
+    Markdown,
+    Json,
+}
+
+/// A named field & its type
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Field {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: String,
+}
+
+/// A part of an event name & what it parses into
+#[derive(Debug, Clone, Serialize)]
+pub struct EventPart {
+    /// As it appears in the log, eg. SPELL_PERIODIC or DAMAGE
+    pub name: String,
+    pub variant: String,
+    /// Fields consumed from the line, where it's fixed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_fields: Option<usize>,
+    /// Whether advanced params come before the suffix fields
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub advanced_params: Option<bool>,
+    pub fields: Vec<Field>,
+}
+
+/// A known event name & how it's parsed
+#[derive(Debug, Clone, Serialize)]
+pub struct EventDoc {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<String>,
+    pub advanced_params: bool,
+    /// Special variants the event can parse into
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub special: Vec<String>,
+}
+
+/// A struct or enum used by the event fields
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeDoc {
+    pub name: String,
+    /// Struct fields, or enum variants with their payloads as the type
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<Field>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<Field>,
+}
+
+/// Every supported event & its fields, as implemented by the parser
+#[derive(Debug, Clone, Serialize)]
+pub struct Schema {
+    pub prefixes: Vec<EventPart>,
+    pub suffixes: Vec<EventPart>,
+    pub special: Vec<EventPart>,
+    pub events: Vec<EventDoc>,
+    pub types: Vec<TypeDoc>,
+}
+
+fn type_name(format: &Format) -> String {
+    match format {
+        Format::Variable(_) => "?".to_string(),
+        Format::TypeName(name) => name.clone(),
+        Format::Unit => "()".to_string(),
+        Format::Bool => "bool".to_string(),
+        Format::I8 => "i8".to_string(),
+        Format::I16 => "i16".to_string(),
+        Format::I32 => "i32".to_string(),
+        Format::I64 => "i64".to_string(),
+        Format::I128 => "i128".to_string(),
+        Format::U8 => "u8".to_string(),
+        Format::U16 => "u16".to_string(),
+        Format::U32 => "u32".to_string(),
+        Format::U64 => "u64".to_string(),
+        Format::U128 => "u128".to_string(),
+        Format::F32 => "f32".to_string(),
+        Format::F64 => "f64".to_string(),
+        Format::Char => "char".to_string(),
+        Format::Str => "String".to_string(),
+        Format::Bytes => "Vec<u8>".to_string(),
+        Format::Option(f) => format!("Option<{}>", type_name(f)),
+        Format::Seq(f) => format!("Vec<{}>", type_name(f)),
+        Format::Map { key, value } => format!("HashMap<{}, {}>", type_name(key), type_name(value)),
+        Format::Tuple(fs) => format!("({})", fs.iter().map(type_name).join(", ")),
+        Format::TupleArray { content, size } => format!("[{}; {}]", type_name(content), size),
+    }
+}
+
+fn named_fields(fields: &[Named<Format>]) -> Vec<Field> {
+    fields.iter()
+        .map(|f| Field { name: f.name.clone(), field_type: type_name(&f.value) })
+        .collect()
+}
+
+fn unnamed_fields(fields: &[Format]) -> Vec<Field> {
+    fields.iter().enumerate()
+        .map(|(i, f)| Field { name: i.to_string(), field_type: type_name(f) })
+        .collect()
+}
+
+fn variant_fields(variant: &VariantFormat) -> Vec<Field> {
+    match variant {
+        VariantFormat::Variable(_) | VariantFormat::Unit => vec![],
+        VariantFormat::NewType(f) => unnamed_fields(std::slice::from_ref(f)),
+        VariantFormat::Tuple(fs) => unnamed_fields(fs),
+        VariantFormat::Struct(fs) => named_fields(fs),
+    }
+}
+
+/// eg. DAMAGE_LANDED -> DamageLanded
+fn variant_name(log_name: &str) -> String {
+    log_name.split('_')
+        .map(|w| w[..1].to_string() + &w[1..].to_ascii_lowercase())
+        .join("")
+}
+
+/// Traces the parser's types through their serde implementations
+fn trace() -> Result<Registry> {
+    let mut tracer = Tracer::new(TracerConfig::default());
+
+    // Every enum has to be traced directly so that all of its variants are found
+    tracer.trace_simple_type::<EventType>().map_err(|e| anyhow!("{}", e))?;
+    tracer.trace_simple_type::<Prefix>().map_err(|e| anyhow!("{}", e))?;
+    tracer.trace_simple_type::<Suffix>().map_err(|e| anyhow!("{}", e))?;
+    tracer.trace_simple_type::<Special>().map_err(|e| anyhow!("{}", e))?;
+    tracer.trace_simple_type::<GUID>().map_err(|e| anyhow!("{}", e))?;
+    tracer.trace_simple_type::<CastType>().map_err(|e| anyhow!("{}", e))?;
+    tracer.trace_simple_type::<CreatureType>().map_err(|e| anyhow!("{}", e))?;
+    tracer.trace_simple_type::<SpellSchool>().map_err(|e| anyhow!("{}", e))?;
+    tracer.trace_simple_type::<PowerType>().map_err(|e| anyhow!("{}", e))?;
+    tracer.trace_simple_type::<MissType>().map_err(|e| anyhow!("{}", e))?;
+    tracer.trace_simple_type::<AuraType>().map_err(|e| anyhow!("{}", e))?;
+    tracer.trace_simple_type::<EnvironmentalType>().map_err(|e| anyhow!("{}", e))?;
+    tracer.trace_simple_type::<DamageKind>().map_err(|e| anyhow!("{}", e))?;
+    tracer.trace_simple_type::<Faction>().map_err(|e| anyhow!("{}", e))?;
+
+    tracer.registry().map_err(|e| anyhow!("{}", e))
+}
+
+fn variants<'a>(registry: &'a Registry, name: &str) -> Result<Vec<&'a Named<VariantFormat>>> {
+    match registry.get(name) {
+        Some(ContainerFormat::Enum(variants)) => Ok(variants.values().collect()),
+        _ => Err(anyhow!("{} is not a traced enum", name)),
+    }
+}
+
+fn variant<'a>(registry: &'a Registry, container: &str, variant: &str) -> Result<&'a VariantFormat> {
+    registry.get(container)
+        .and_then(|c| match c {
+            ContainerFormat::Enum(variants) => variants.values().find(|v| v.name == variant),
+            _ => None,
+        })
+        .map(|v| &v.value)
+        .ok_or_else(|| anyhow!("{}::{} not found", container, variant))
+}
+
+/// Builds the schema from the parser's own types & parse tables
+pub fn schema() -> Result<Schema> {
+    let registry = trace()?;
+
+    let prefixes = PrefixKind::ALL.iter()
+        .map(|prefix| {
+            let (variant_name, fields) = match prefix {
+                // Parsed into its own event type, of which the environment type is the prefix's part
+                PrefixKind::Environmental => ("EnvironmentalDamage".to_string(), variant_fields(variant(&registry, "EventType", "EnvironmentalDamage")?)
+                    .into_iter()
+                    .filter(|f| f.name == "env_type")
+                    .collect()),
+                _ => {
+                    let variant_name = variant_name(prefix.log_name());
+                    let fields = variant_fields(variant(&registry, "Prefix", &variant_name)?);
+                    (variant_name, fields)
+                }
+            };
+            Ok(EventPart {
+                name: prefix.log_name().to_string(),
+                fields,
+                variant: variant_name,
+                log_fields: Some(prefix.log_fields()),
+                advanced_params: None,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let suffixes = SuffixKind::ALL.iter()
+        .sorted_by_key(|suffix| suffix.log_name())
+        .map(|suffix| {
+            let variant_name = variant_name(suffix.log_name());
+            Ok(EventPart {
+                name: suffix.log_name().to_string(),
+                fields: variant_fields(variant(&registry, "Suffix", &variant_name)?),
+                variant: variant_name,
+                log_fields: None,
+                advanced_params: Some(suffix.has_advanced_params()),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let special = KNOWN_EVENTS.iter()
+        .flat_map(|e| e.special().iter().map(move |v| (e.name, v)))
+        .map(|(name, variant_name)| Ok(EventPart {
+            name: name.to_string(),
+            variant: variant_name.to_string(),
+            log_fields: None,
+            advanced_params: None,
+            fields: variant_fields(variant(&registry, "Special", variant_name)?),
+        }))
+        .collect::<Result<Vec<_>>>()?;
+
+    let events = KNOWN_EVENTS.iter()
+        .map(|e| EventDoc {
+            name: e.name.to_string(),
+            prefix: e.prefix().map(|p| p.log_name().to_string()),
+            suffix: e.suffix().map(|s| s.log_name().to_string()),
+            advanced_params: e.has_advanced_params(),
+            special: e.special().iter().map(|v| v.to_string()).collect(),
+        })
+        .collect();
+
+    let types = registry.iter()
+        .filter(|(name, _)| !EVENT_PARTS.contains(&name.as_str()))
+        .map(|(name, container)| {
+            let (fields, variants) = match container {
+                ContainerFormat::UnitStruct => (vec![], vec![]),
+                ContainerFormat::NewTypeStruct(f) => (unnamed_fields(std::slice::from_ref(f)), vec![]),
+                ContainerFormat::TupleStruct(fs) => (unnamed_fields(fs), vec![]),
+                ContainerFormat::Struct(fs) => (named_fields(fs), vec![]),
+                ContainerFormat::Enum(vs) => (vec![], vs.values()
+                    .map(|v| {
+                        let payload = variant_fields(&v.value).iter()
+                            .map(|f| if f.name.parse::<usize>().is_ok() { f.field_type.clone() } else { format!("{}: {}", f.name, f.field_type) })
+                            .join(", ");
+                        Field { name: v.name.clone(), field_type: payload }
+                    })
+                    .collect()),
+            };
+            TypeDoc { name: name.clone(), fields, variants }
+        })
+        .collect();
+
+    Ok(Schema { prefixes, suffixes, special, events, types })
+}
+
+fn fields_cell(fields: &[Field]) -> String {
+    fields.iter()
+        .map(|f| format!("`{}: {}`", f.name, f.field_type))
+        .join("<br>")
+}
+
+impl Schema {
+    pub fn to_markdown(&self) -> String {
+        let mut s = String::new();
+
+        s += "# Combat log events\n\n";
+        s += "Generated from the parser's own types with the `schema` subcommand.\n\n";
+        s += "Each line is `<timestamp>  <EVENT_NAME>,<fields...>`. ";
+        s += "Standard event names are a prefix followed by a suffix, eg. `SPELL_PERIODIC` + `DAMAGE`. ";
+        s += "Their fields are the source actor (4 fields), the target actor (4 fields), the prefix fields, ";
+        s += "the advanced params if the suffix has them & advanced logging is enabled, then the suffix fields. ";
+        s += "`ENVIRONMENTAL_DAMAGE` has its advanced params before the environment type, so is parsed into its own ";
+        s += "`EnvironmentalDamage` event type.\n\n";
+
+        s += "## Prefixes\n\n| Name | Variant | Log fields | Fields |\n|---|---|---|---|\n";
+        for p in &self.prefixes {
+            s += &format!("| `{}` | {} | {} | {} |\n", p.name, p.variant, p.log_fields.unwrap_or_default(), fields_cell(&p.fields));
+        }
+
+        s += "\n## Suffixes\n\n| Name | Variant | Advanced params | Fields |\n|---|---|---|---|\n";
+        for p in &self.suffixes {
+            let advanced = if p.advanced_params == Some(true) { "yes" } else { "no" };
+            s += &format!("| `{}` | {} | {} | {} |\n", p.name, p.variant, advanced, fields_cell(&p.fields));
+        }
+
+        s += "\n## Special events\n\n| Name | Variant | Fields |\n|---|---|---|\n";
+        for p in &self.special {
+            s += &format!("| `{}` | {} | {} |\n", p.name, p.variant, fields_cell(&p.fields));
+        }
+
+        s += "\n## Known events\n\n| Name | Prefix | Suffix | Advanced params | Special |\n|---|---|---|---|---|\n";
+        for e in &self.events {
+            let advanced = if e.advanced_params { "yes" } else { "no" };
+            s += &format!(
+                "| `{}` | {} | {} | {} | {} |\n",
+                e.name, e.prefix.as_deref().unwrap_or("-"), e.suffix.as_deref().unwrap_or("-"), advanced,
+                if e.special.is_empty() { "-".to_string() } else { e.special.join(", ") },
+            );
+        }
+
+        s += "\n## Types\n";
+        for t in &self.types {
+            s += &format!("\n### {}\n\n", t.name);
+            if !t.variants.is_empty() {
+                s += "| Variant | Payload |\n|---|---|\n";
+                t.variants.iter()
+                    .for_each(|v| s += &format!("| {} | {} |\n", v.name, if v.field_type.is_empty() { "-".to_string() } else { format!("`{}`", v.field_type) }));
+            } else {
+                s += "| Field | Type |\n|---|---|\n";
+                t.fields.iter()
+                    .for_each(|f| s += &format!("| {} | `{}` |\n", f.name, f.field_type));
+            }
+        }
+
+        s
+    }
+
+    pub fn render(&self, format: SchemaFormat) -> Result<String> {
+        Ok(match format {
+            SchemaFormat::Markdown => self.to_markdown(),
+            SchemaFormat::Json => serde_json::to_string_pretty(self)?,
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::components::registry::KNOWN_EVENTS;
+    use crate::schema::{Field, schema, SchemaFormat, trace, variants};
+
+    #[test]
+    fn every_variant_documented() {
+        let schema = schema().unwrap();
+        let registry = trace().unwrap();
+
+        for (container, parts) in [("Prefix", &schema.prefixes), ("Suffix", &schema.suffixes), ("Special", &schema.special)] {
+            for v in variants(&registry, container).unwrap() {
+                if v.name == "NoneSentinel" { continue; }
+                assert!(parts.iter().any(|p| p.variant == v.name), "{}::{} is not documented", container, v.name);
+            }
+        }
+        assert_eq!(schema.special.len(), KNOWN_EVENTS.iter().map(|e| e.special().len()).sum::<usize>());
+    }
+
+    #[test]
+    fn fields() {
+        let schema = schema().unwrap();
+
+        let damage = schema.suffixes.iter().find(|p| p.name == "DAMAGE").unwrap();
+        assert_eq!(damage.variant, "Damage");
+        assert_eq!(damage.advanced_params, Some(true));
+        assert_eq!(damage.fields[2], Field { name: "overkill".to_string(), field_type: "Option<u64>".to_string() });
+
+        let periodic = schema.prefixes.iter().find(|p| p.name == "SPELL_PERIODIC").unwrap();
+        assert_eq!(periodic.log_fields, Some(3));
+        assert_eq!(periodic.fields[0].field_type, "SpellInfo");
+
+        assert!(schema.types.iter().any(|t| t.name == "SpellInfo"));
+        assert!(schema.render(SchemaFormat::Markdown).unwrap().contains("| `DAMAGE` | Damage | yes |"));
+        assert!(schema.render(SchemaFormat::Markdown).unwrap().contains("| `DAMAGE_SHIELD` | SPELL | DAMAGE | yes | - |"));
+        assert!(schema.render(SchemaFormat::Json).is_ok());
+    }
+}
@@ -0,0 +1,109 @@
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::consumers::actors::ActorIds;
+use crate::index::{self, LogIndex};
+
+/// Copies a byte range of the log to the output
+pub fn copy_range(log: &mut File, range: Range<u64>, out: &mut impl Write) -> Result<()> {
+    log.seek(SeekFrom::Start(range.start))?;
+    std::io::copy(&mut Read::by_ref(log).take(range.end - range.start), out)?;
+    Ok(())
+}
+
+/// Keeps boss names usable as file names on every platform
+fn file_safe(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect::<String>()
+        .split('_')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Writes each pull to its own valid combat log in `dir`, named by pull number, boss & start time,
+/// eg. `01_Gnarlroot_0406-140105.txt`. The most recent COMBAT_LOG_VERSION header is copied to the top of each.
+/// With `json`, the parsed events are also written next to each one as JSON lines.
+/// Their short actor IDs are shared between the pulls, so they can be joined across files.
+pub fn split_encounters(log: &Path, dir: &Path, json: bool) -> Result<Vec<PathBuf>> {
+    let index = LogIndex::load_or_build(log)?;
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create directory: {:?}", dir))?;
+    let mut file = File::open(log)
+        .with_context(|| format!("Failed to open file: {:?}", log))?;
+
+    let mut ids = ActorIds::new();
+    let mut written = vec![];
+    for (i, region) in index.encounters().iter().enumerate() {
+        let events = index::read_region(log, region)?.collect::<Vec<_>>();
+        let started = events.iter()
+            .find_map(|e| e.as_ref().ok())
+            .map_or("unknown".to_string(), |e| e.timestamp.format("%m%d-%H%M%S").to_string());
+        let path = dir.join(format!("{:02}_{}_{}.txt", i + 1, file_safe(&region.encounter_name), started));
+
+        let mut out = BufWriter::new(File::create(&path)
+            .with_context(|| format!("Failed to create file: {:?}", path))?);
+        if let Some(header) = index.header_before(region.range.start) {
+            copy_range(&mut file, header.start..header.end, &mut out)?;
+        }
+        copy_range(&mut file, region.range.clone(), &mut out)?;
+        out.flush()?;
+
+        if json {
+            let json_path = path.with_extension("jsonl");
+            let mut out = BufWriter::new(File::create(&json_path)
+                .with_context(|| format!("Failed to create file: {:?}", json_path))?);
+            for event in &events {
+                writeln!(out, "{}", serde_json::to_string(&ids.identify(event))?)?;
+            }
+            out.flush()?;
+        }
+
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::EventParser;
+    use crate::split::split_encounters;
+
+    #[test]
+    fn split() {
+        let log = "4/6 14:00:00.000  COMBAT_LOG_VERSION,20,ADVANCED_LOG_ENABLED,1,BUILD_VERSION,10.2.6,PROJECT_ID,1\n\
+4/6 14:00:01.000  ZONE_CHANGE,2549,\"Amirdrassil, the Dream's Hope\",16\n\
+4/6 14:01:05.000  ENCOUNTER_START,2820,\"Gnarlroot\",14,19,2549\n\
+4/6 14:02:07.362  SWING_MISSED,Player-1335-0A264B4C,\"Sønike-Ysondre\",0x514,0x0,Creature-0-1469-2549-12530-209333-000011428A,\"Gnarlroot\",0x10a48,0x0,MISS,1\n\
+4/6 14:12:00.000  ENCOUNTER_END,2820,\"Gnarlroot\",14,19,1,162742\n\
+4/6 14:20:00.000  ENCOUNTER_START,2709,\"Igira the Cruel\",14,19,2549\n\
+4/6 14:25:00.000  ENCOUNTER_END,2709,\"Igira the Cruel\",14,19,0,300000\n";
+
+        let dir = std::env::temp_dir().join("wowlogs_split_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("WoWCombatLog.txt");
+        std::fs::write(&path, log).unwrap();
+
+        let written = split_encounters(&path, &dir.join("pulls"), true).unwrap();
+        let names = written.iter().map(|p| p.file_name().unwrap().to_str().unwrap()).collect::<Vec<_>>();
+        assert_eq!(names, ["01_Gnarlroot_0406-140105.txt", "02_Igira_the_Cruel_0406-142000.txt"]);
+
+        let first = std::fs::read_to_string(&written[0]).unwrap();
+        let lines = log.lines().collect::<Vec<_>>();
+        assert_eq!(first, [lines[0], lines[2], lines[3], lines[4], ""].join("\n"));
+        assert!(EventParser::new(first.as_bytes()).all(|e| e.is_ok()));
+
+        let json = std::fs::read_to_string(written[0].with_extension("jsonl")).unwrap();
+        assert_eq!(json.lines().count(), 3);
+        let swing: serde_json::Value = serde_json::from_str(json.lines().nth(1).unwrap()).unwrap();
+        assert_eq!((&swing["source_id"], &swing["target_id"]), (&serde_json::json!("A1"), &serde_json::json!("A2")));
+    }
+}
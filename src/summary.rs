@@ -0,0 +1,440 @@
+use clap::ValueEnum;
+use itertools::Itertools;
+use serde_json::{json, Map, Value};
+
+use crate::consumers::EventHandler;
+
+/// How handler summaries are written out
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum SummaryFormat {
+    /// Fixed width text tables
+    #[default]
+    Plain,
+    Markdown,
+    Json,
+    /// A standalone HTML page
+    Html,
+    /// Markdown for Discord: tables in code blocks, split into messages under Discord's length limit
+    Discord,
+}
+
+/// Maximum characters in a Discord message
+const DISCORD_MESSAGE_LIMIT: usize = 2000;
+
+/// A single typed value in a table
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Empty,
+    Text(String),
+    Int(i64),
+    /// Value & number of decimal places to show
+    Float(f64, usize),
+    Percent(f64),
+    /// Seconds, shown as m:ss
+    Duration(f64),
+}
+
+impl Cell {
+    fn text(&self) -> String {
+        match self {
+            Cell::Empty => String::new(),
+            Cell::Text(s) => s.clone(),
+            Cell::Int(n) => n.to_string(),
+            Cell::Float(x, decimals) => format!("{:.*}", decimals, x),
+            Cell::Percent(x) => format!("{:.1}%", x),
+            Cell::Duration(secs) => {
+                let secs = secs.round() as i64;
+                format!("{}:{:02}", secs / 60, secs % 60)
+            }
+        }
+    }
+
+    fn json(&self) -> Value {
+        match self {
+            Cell::Empty => Value::Null,
+            Cell::Text(s) => json!(s),
+            Cell::Int(n) => json!(n),
+            Cell::Float(x, _) | Cell::Percent(x) | Cell::Duration(x) => json!(x),
+        }
+    }
+
+    fn numeric(&self) -> bool {
+        !matches!(self, Cell::Text(_) | Cell::Empty)
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(s: &str) -> Self { Cell::Text(s.to_string()) }
+}
+
+impl From<String> for Cell {
+    fn from(s: String) -> Self { Cell::Text(s) }
+}
+
+impl From<i64> for Cell {
+    fn from(n: i64) -> Self { Cell::Int(n) }
+}
+
+impl From<u64> for Cell {
+    fn from(n: u64) -> Self { Cell::Int(n as i64) }
+}
+
+impl From<usize> for Cell {
+    fn from(n: usize) -> Self { Cell::Int(n as i64) }
+}
+
+impl<T: Into<Cell>> From<Option<T>> for Cell {
+    fn from(x: Option<T>) -> Self { x.map_or(Cell::Empty, Into::into) }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    /// Width in plain text
+    pub width: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub title: Option<String>,
+    pub columns: Vec<Column>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+    /// Columns as (name, plain text width)
+    pub fn new(columns: &[(&str, usize)]) -> Self {
+        Self {
+            title: None,
+            columns: columns.iter().map(|&(name, width)| Column { name: name.to_string(), width }).collect(),
+            rows: vec![],
+        }
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn push(&mut self, row: Vec<Cell>) {
+        debug_assert_eq!(row.len(), self.columns.len());
+        self.rows.push(row);
+    }
+
+    /// Cell in the named column
+    pub fn get(&self, row: usize, column: &str) -> Option<&Cell> {
+        let i = self.columns.iter().position(|c| c.name == column)?;
+        self.rows.get(row)?.get(i)
+    }
+
+    /// First row with the given text in its first column
+    pub fn find(&self, key: &str) -> Option<&[Cell]> {
+        self.rows.iter()
+            .find(|r| matches!(r.first(), Some(Cell::Text(s)) if s == key))
+            .map(|r| r.as_slice())
+    }
+
+    /// Header & rows as `first: a|b|c`, headers space separated
+    fn plain_lines(&self) -> Vec<String> {
+        let header = self.columns.iter()
+            .map(|c| format!("{:>w$}", c.name, w = c.width))
+            .join(" ");
+
+        let rows = self.rows.iter()
+            .map(|row| {
+                let mut cells = row.iter().zip(&self.columns)
+                    .map(|(cell, c)| format!("{:>w$}", cell.text(), w = c.width));
+                let first = cells.next().unwrap_or_default();
+                format!("{}:{}", first, cells.join("|"))
+            });
+
+        [header].into_iter().chain(rows).collect()
+    }
+
+    fn plain(&self) -> String {
+        self.title.iter().cloned()
+            .chain(self.plain_lines())
+            .join("\n")
+    }
+
+    fn markdown(&self) -> String {
+        let escape = |s: String| s.replace('|', "\\|");
+        let header = self.columns.iter().map(|c| escape(c.name.clone())).join(" | ");
+        let align = self.columns.iter().enumerate()
+            .map(|(i, _)| match self.rows.first().and_then(|r| r.get(i)) {
+                Some(cell) if cell.numeric() => "---:",
+                _ => "---",
+            })
+            .join(" | ");
+        let rows = self.rows.iter()
+            .map(|row| format!("| {} |", row.iter().map(|c| escape(c.text())).join(" | ")));
+
+        self.title.iter().map(|t| format!("### {}\n", t))
+            .chain([format!("| {} |", header), format!("| {} |", align)])
+            .chain(rows)
+            .join("\n")
+    }
+
+    fn html(&self) -> String {
+        let header = self.columns.iter()
+            .map(|c| format!("<th>{}</th>", escape_html(&c.name)))
+            .join("");
+        let rows = self.rows.iter()
+            .map(|row| {
+                let cells = row.iter()
+                    .map(|c| match c.numeric() {
+                        true => format!("<td class=\"num\">{}</td>", escape_html(&c.text())),
+                        false => format!("<td>{}</td>", escape_html(&c.text())),
+                    })
+                    .join("");
+                format!("<tr>{}</tr>", cells)
+            })
+            .join("\n");
+
+        let title = self.title.as_ref().map(|t| format!("<h3>{}</h3>\n", escape_html(t))).unwrap_or_default();
+        format!("{}<table>\n<tr>{}</tr>\n{}\n</table>", title, header, rows)
+    }
+
+    /// Rows as objects keyed by column name
+    fn json(&self) -> Value {
+        let rows = self.rows.iter()
+            .map(|row| self.columns.iter().zip(row)
+                .map(|(c, cell)| (c.name.clone(), cell.json()))
+                .collect::<Map<_, _>>())
+            .collect_vec();
+
+        json!({ "title": self.title, "rows": rows })
+    }
+}
+
+/// A handler's results as typed tables, rendered by the formatters here rather than by each handler
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub title: String,
+    pub tables: Vec<Table>,
+    /// Free text lines, eg. from handlers that only report a status
+    pub notes: Vec<String>,
+}
+
+impl Summary {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self { title: title.into(), tables: vec![], notes: vec![] }
+    }
+
+    pub fn with_table(mut self, table: Table) -> Self {
+        self.tables.push(table);
+        self
+    }
+
+    /// Wraps pre-formatted text from a handler without a summary
+    pub fn from_text(text: &str) -> Self {
+        Self { title: String::new(), tables: vec![], notes: text.lines().map(str::to_string).collect() }
+    }
+
+    pub fn table(&self, title: &str) -> Option<&Table> {
+        self.tables.iter().find(|t| t.title.as_deref() == Some(title))
+    }
+
+    pub fn render(&self, format: SummaryFormat) -> String {
+        match format {
+            SummaryFormat::Plain => {
+                Some(self.title.clone()).filter(|t| !t.is_empty()).into_iter()
+                    .chain(self.tables.iter().map(Table::plain))
+                    .chain(self.notes.iter().cloned())
+                    .join("\n")
+            }
+            SummaryFormat::Markdown => {
+                Some(format!("## {}", self.title)).filter(|_| !self.title.is_empty()).into_iter()
+                    .chain(self.tables.iter().map(Table::markdown))
+                    .chain(self.notes.iter().cloned())
+                    .join("\n\n")
+            }
+            SummaryFormat::Json => self.json().to_string(),
+            SummaryFormat::Html => {
+                Some(format!("<h2>{}</h2>", escape_html(&self.title))).filter(|_| !self.title.is_empty()).into_iter()
+                    .chain(self.tables.iter().map(Table::html))
+                    .chain(self.notes.iter().map(|n| format!("<p>{}</p>", escape_html(n))))
+                    .join("\n")
+            }
+            SummaryFormat::Discord => self.discord_messages().join("\n\n"),
+        }
+    }
+
+    /// One message per table, eg. per pull, each under Discord's message limit.
+    /// Tables too long for one message carry on in the next, with the header repeated.
+    pub fn discord_messages(&self) -> Vec<String> {
+        let mut messages = vec![];
+
+        for table in &self.tables {
+            let heading = [Some(self.title.as_str()), table.title.as_deref()].into_iter()
+                .flatten()
+                .filter(|t| !t.is_empty())
+                .join(" - ");
+            let heading = format!("**{}**\n```\n", heading);
+            let lines = table.plain_lines();
+            let (header, rows) = lines.split_first().expect("Tables always have a header");
+
+            // Room for the heading, header & closing fence
+            let budget = DISCORD_MESSAGE_LIMIT.saturating_sub(heading.len() + header.len() + "\n\n```".len());
+            let mut message = format!("{}{}", heading, header);
+            let mut used = 0;
+            for row in rows {
+                let row = truncate(row, budget);
+                if used > 0 && used + row.len() + 1 > budget {
+                    messages.push(format!("{}\n```", message));
+                    message = format!("{}{}", heading, header);
+                    used = 0;
+                }
+                message.push('\n');
+                message.push_str(row);
+                used += row.len() + 1;
+            }
+            messages.push(format!("{}\n```", message));
+        }
+
+        if !self.notes.is_empty() {
+            let mut message = String::new();
+            for note in &self.notes {
+                let note = truncate(note, DISCORD_MESSAGE_LIMIT);
+                if !message.is_empty() && message.len() + note.len() + 1 > DISCORD_MESSAGE_LIMIT {
+                    messages.push(std::mem::take(&mut message));
+                }
+                if !message.is_empty() { message.push('\n'); }
+                message.push_str(note);
+            }
+            messages.push(message);
+        }
+
+        messages
+    }
+
+    fn json(&self) -> Value {
+        json!({
+            "title": self.title,
+            "tables": self.tables.iter().map(Table::json).collect_vec(),
+            "notes": self.notes,
+        })
+    }
+}
+
+/// Cuts a string down to at most `max` bytes, on a character boundary
+fn truncate(s: &str, max: usize) -> &str {
+    if s.len() <= max { return s; }
+    let end = (0..=max).rev().find(|&i| s.is_char_boundary(i)).unwrap_or_default();
+    &s[..end]
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders several summaries into one document
+pub fn render_all(summaries: &[Summary], format: SummaryFormat) -> String {
+    match format {
+        SummaryFormat::Plain => summaries.iter().map(|s| s.render(format)).join("\n---\n"),
+        SummaryFormat::Markdown | SummaryFormat::Discord => summaries.iter().map(|s| s.render(format)).join("\n\n"),
+        SummaryFormat::Json => Value::Array(summaries.iter().map(Summary::json).collect()).to_string(),
+        SummaryFormat::Html => format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<style>td.num {{ text-align: right; }}</style>\n</head>\n<body>\n{}\n</body>\n</html>\n",
+            summaries.iter().map(|s| s.render(format)).join("\n<hr>\n")
+        ),
+    }
+}
+
+fn summaries(handlers: &[Box<dyn EventHandler>]) -> Vec<Summary> {
+    handlers.iter()
+        .filter_map(|h| h.summary().or_else(|| h.display().map(|d| Summary::from_text(&d))))
+        .collect_vec()
+}
+
+/// Every handler's results in the given format. Plain text uses each handler's own display
+pub fn render_handlers(handlers: &[Box<dyn EventHandler>], format: SummaryFormat) -> String {
+    match format {
+        SummaryFormat::Plain => handlers.iter().filter_map(|h| h.display()).join("\n---\n"),
+        _ => render_all(&summaries(handlers), format),
+    }
+}
+
+/// Results for a batch of logs: each log's own, then the whole batch's, with each titled by what it covers
+pub fn render_batch(logs: &[(String, Vec<Box<dyn EventHandler>>)], all: &[Box<dyn EventHandler>], format: SummaryFormat) -> String {
+    let sections = logs.iter()
+        .map(|(name, handlers)| (name.clone(), &handlers[..]))
+        .chain([(format!("All {} logs", logs.len()), all)]);
+
+    match format {
+        SummaryFormat::Plain => sections
+            .map(|(name, handlers)| format!("== {} ==\n{}", name, render_handlers(handlers, format)))
+            .join("\n\n"),
+        _ => {
+            let summaries = sections
+                .flat_map(|(name, handlers)| summaries(handlers).into_iter().map(move |mut s| {
+                    s.title = if s.title.is_empty() { name.clone() } else { format!("{} ({})", s.title, name) };
+                    s
+                }))
+                .collect_vec();
+            render_all(&summaries, format)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::summary::{Cell, render_all, Summary, SummaryFormat, Table};
+
+    fn summary() -> Summary {
+        let mut table = Table::new(&[("Player", 10), ("Damage", 8), ("Active", 7)]).with_title("Gnarlroot");
+        table.push(vec!["Sønike".into(), Cell::Int(1200), Cell::Percent(95.)]);
+        table.push(vec!["A|b".into(), Cell::Float(1.25, 1), Cell::Empty]);
+        Summary::new("Damage").with_table(table)
+    }
+
+    #[test]
+    fn plain() {
+        assert_eq!(summary().render(SummaryFormat::Plain), format!(
+            "Damage\nGnarlroot\n{:>10} {:>8} {:>7}\n{:>10}:{:>8}|{:>7}\n{:>10}:{:>8}|{:>7}",
+            "Player", "Damage", "Active", "Sønike", 1200, "95.0%", "A|b", "1.2", ""));
+    }
+
+    #[test]
+    fn markdown() {
+        assert_eq!(summary().render(SummaryFormat::Markdown),
+                   "## Damage\n\n### Gnarlroot\n\n| Player | Damage | Active |\n| --- | ---: | ---: |\n| Sønike | 1200 | 95.0% |\n| A\\|b | 1.2 |  |");
+    }
+
+    #[test]
+    fn discord() {
+        let messages = summary().discord_messages();
+        assert_eq!(messages, vec![format!(
+            "**Damage - Gnarlroot**\n```\n{:>10} {:>8} {:>7}\n{:>10}:{:>8}|{:>7}\n{:>10}:{:>8}|{:>7}\n```",
+            "Player", "Damage", "Active", "Sønike", 1200, "95.0%", "A|b", "1.2", "")]);
+
+        // Long tables carry on in more messages
+        let mut table = Table::new(&[("Player", 30), ("Damage", 10)]);
+        (0..200).for_each(|i| table.push(vec![format!("Row {}", i).into(), Cell::Int(i)]));
+        let messages = Summary::new("Damage").with_table(table).discord_messages();
+        assert!(messages.len() > 1);
+        assert!(messages.iter().all(|m| m.len() <= 2000 && m.starts_with("**Damage**\n```\n") && m.ends_with("\n```")));
+        assert_eq!(messages.iter().map(|m| m.matches("Row ").count()).sum::<usize>(), 200);
+    }
+
+    #[test]
+    fn json() {
+        let json: serde_json::Value = serde_json::from_str(&render_all(&[summary()], SummaryFormat::Json)).unwrap();
+        let rows = &json[0]["tables"][0]["rows"];
+        assert_eq!(rows[0]["Damage"], 1200);
+        assert_eq!(rows[0]["Active"], 95.);
+        assert_eq!(rows[1]["Active"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn html() {
+        let html = render_all(&[summary()], SummaryFormat::Html);
+        assert!(html.contains("<h3>Gnarlroot</h3>"));
+        assert!(html.contains("<td class=\"num\">1200</td>"));
+        assert!(html.contains("<td>A|b</td>"));
+    }
+}
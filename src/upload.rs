@@ -0,0 +1,152 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::net::IpAddr;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use ureq::http::Uri;
+
+use crate::consumers::hooks::post;
+
+/// Splits a log into chunks of whole lines, each at least `chunk_bytes` long apart from the last
+struct Chunks<R> {
+    reader: R,
+    chunk_bytes: usize,
+}
+
+impl<R: BufRead> Iterator for Chunks<R> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = vec![];
+        while chunk.len() < self.chunk_bytes {
+            match self.reader.read_until(b'\n', &mut chunk) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+        (!chunk.is_empty()).then_some(Ok(chunk))
+    }
+}
+
+fn gzip(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(bytes)?;
+    Ok(encoder.finish()?)
+}
+
+/// Whether a token can be sent to the URL without it going over the network in plain text
+fn token_safe(url: &str) -> Result<bool> {
+    let uri: Uri = url.parse().with_context(|| format!("Bad URL: {:?}", url))?;
+    let host = uri.host().unwrap_or_default().trim_start_matches('[').trim_end_matches(']');
+    let local = host == "localhost" || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback());
+    Ok(uri.scheme_str() == Some("https") || local)
+}
+
+/// POSTs a log to a report service as gzipped chunks of whole lines, in order.
+/// Each chunk says which log it's from, its index & whether it's the last, so the service can put them back together.
+/// Returns the number of chunks sent.
+pub fn upload(log: &Path, url: &str, token: Option<&str>, chunk_bytes: usize) -> Result<usize> {
+    if token.is_some() && !token_safe(url)? {
+        bail!("Refusing to send the token to {:?}, use https://", url);
+    }
+
+    let file = File::open(log)
+        .with_context(|| format!("Failed to open file: {:?}", log))?;
+    let name = log.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+    let mut chunks = Chunks { reader: BufReader::new(file), chunk_bytes }.peekable();
+
+    let mut sent = 0;
+    while let Some(chunk) = chunks.next() {
+        let body = gzip(&chunk?)?;
+
+        let mut headers = vec![
+            ("Content-Type", "text/plain; charset=utf-8".to_string()),
+            ("Content-Encoding", "gzip".to_string()),
+            ("X-Log-Name", name.clone()),
+            ("X-Chunk-Index", sent.to_string()),
+            ("X-Chunk-Final", (chunks.peek().is_none() as u8).to_string()),
+        ];
+        if let Some(token) = token {
+            headers.push(("Authorization", format!("Bearer {}", token)));
+        }
+
+        post(url, &headers, &body).with_context(|| format!("Failed to upload chunk {}", sent))?;
+        sent += 1;
+    }
+
+    Ok(sent)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use flate2::read::GzDecoder;
+
+    use crate::upload::{token_safe, upload};
+
+    #[test]
+    fn chunks_reassemble() {
+        let log = (0..100).map(|i| format!("4/6 14:01:{:02}.000  LINE,{}\n", i % 60, i)).collect::<String>();
+        let path = std::env::temp_dir().join("wowlogs_upload.txt");
+        std::fs::write(&path, &log).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/upload", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            let mut received = vec![];
+            loop {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut headers = vec![];
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" { break; }
+                    headers.push(line.trim_end().to_string());
+                }
+                let header = |name: &str| headers.iter()
+                    .find_map(|h| h.split_once(": ").filter(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.to_string()))
+                    .unwrap();
+
+                let mut body = vec![0; header("Content-Length").parse().unwrap()];
+                reader.read_exact(&mut body).unwrap();
+                let mut text = String::new();
+                GzDecoder::new(&body[..]).read_to_string(&mut text).unwrap();
+                reader.get_mut().write_all(b"HTTP/1.1 204 No Content\r\n\r\n").unwrap();
+
+                assert_eq!(header("Authorization"), "Bearer secret");
+                assert_eq!(header("X-Chunk-Index"), received.len().to_string());
+                received.push(text);
+                if header("X-Chunk-Final") == "1" { return received; }
+            }
+        });
+
+        let sent = upload(&path, &url, Some("secret"), 1000).unwrap();
+        let received = server.join().unwrap();
+        assert_eq!(sent, received.len());
+        assert!(sent > 1);
+        assert!(received.iter().all(|c| c.ends_with('\n')));
+        assert_eq!(received.concat(), log);
+    }
+
+    #[test]
+    fn token_only_over_tls() {
+        assert!(token_safe("https://logs.example.com/upload").unwrap());
+        assert!(token_safe("http://localhost:8000/upload").unwrap());
+        assert!(token_safe("http://127.0.0.1:8000/upload").unwrap());
+        assert!(token_safe("http://[::1]:8000/upload").unwrap());
+        assert!(!token_safe("http://logs.example.com/upload").unwrap());
+
+        let err = upload(std::path::Path::new("missing.txt"), "http://logs.example.com/upload", Some("secret"), 1000).unwrap_err();
+        assert!(err.to_string().contains("https"), "{}", err);
+    }
+}
@@ -1,14 +1,17 @@
 use std::any::type_name;
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::str::FromStr;
+use std::sync::Arc;
 
-use anyhow::{anyhow, Result};
 use num_traits::Num;
 use regex::Regex;
 
+use crate::error::{ComponentError, malformed, Result};
+
 pub fn parse_num<T: FromStr>(x: &str) -> Result<T>
 {
-    // https://github.com/dtolnay/anyhow/issues/318
-    T::from_str(x).map_err(|_| anyhow!("Failed to parse {}: {:?}", type_name::<T>(), x))
+    T::from_str(x).map_err(|_| ComponentError::bad_field(x, type_name::<T>()))
 }
 
 /// Either nil-1 or 0-1 variants
@@ -17,13 +20,79 @@ pub fn parse_bool(x: &str) -> Result<bool> {
         // https://warcraft.wiki.gg/wiki/COMBAT_LOG_EVENT#Death_Events
         "nil" | "0" => Ok(false),
         "1" => Ok(true),
-        _ => Err(anyhow!("Failed to parse bool: {:?}", x))
+        _ => Err(ComponentError::bad_field(x, "bool"))
+    }
+}
+
+/// Inverse of parse_bool, for fields logged as nil-1
+pub fn nil_bool(b: bool) -> String {
+    if b { "1" } else { "nil" }.to_string()
+}
+
+/// Inverse of parse_bool, for fields logged as 0-1
+pub fn num_bool(b: bool) -> String {
+    (b as u8).to_string()
+}
+
+/// Strings are quoted in the log, apart from nil, the game's null
+pub fn quote(s: &str) -> String {
+    match s {
+        "nil" => s.to_string(),
+        _ => format!("\"{}\"", s),
     }
 }
 
+thread_local! {
+    static INTERNED: RefCell<HashSet<Arc<str>>> = RefCell::new(HashSet::new());
+}
+
+/// Shares one allocation between every copy of a string, for the names repeated on millions of lines
+/// (event & spell names). There are only a few thousand distinct ones in a log, so they're never freed.
+pub fn intern(s: &str) -> Arc<str> {
+    INTERNED.with_borrow_mut(|interned| match interned.get(s) {
+        Some(i) => i.clone(),
+        None => {
+            let i: Arc<str> = Arc::from(s);
+            interned.insert(i.clone());
+            i
+        }
+    })
+}
+
 pub fn parse_hex<T: FromStr + Num>(x: &str) -> Result<T> {
     T::from_str_radix(x.trim_start_matches("0x"), 16)
-        .map_err(|_| anyhow!("Error parsing hex: {:?}", x))
+        .map_err(|_| ComponentError::bad_field(x, &format!("hex {}", type_name::<T>())))
+}
+
+/// Parsing a line's fields by index, so a bad field's error says which one it was
+pub(crate) trait Fields {
+    fn field(&self, i: usize) -> Result<&str>;
+
+    fn num<T: FromStr>(&self, i: usize) -> Result<T> {
+        parse_num(self.field(i)?).map_err(|e| e.in_field(i))
+    }
+
+    fn hex<T: FromStr + Num>(&self, i: usize) -> Result<T> {
+        parse_hex(self.field(i)?).map_err(|e| e.in_field(i))
+    }
+
+    fn bool(&self, i: usize) -> Result<bool> {
+        parse_bool(self.field(i)?).map_err(|e| e.in_field(i))
+    }
+
+    /// Parses the field at `i` with a component's own parser
+    fn parse_with<T>(&self, i: usize, parse: impl FnOnce(&str) -> Result<T>) -> Result<T> {
+        parse(self.field(i)?).map_err(|e| e.in_field(i))
+    }
+}
+
+impl Fields for [&str] {
+    fn field(&self, i: usize) -> Result<&str> {
+        match self.get(i) {
+            Some(field) => Ok(field),
+            None => malformed!("Missing field {} of {}", i, self.len()),
+        }
+    }
 }
 
 /// Extracts and replaces the given regex, returning it
@@ -35,4 +104,68 @@ pub fn match_replace_all(re: &Regex, s: &str) -> (Vec<String>, String) {
     let s = re.replace_all(s, "").to_string();
 
     (matches, s)
-}
\ No newline at end of file
+}
+
+/// Matches `text` against a pattern where `many` is any run of characters & `one` any one character,
+/// eg. `*` & `?` in file names. On a mismatch, only the last `many` is retried one character further on,
+/// so it takes O(pattern * text) at worst rather than backtracking at every one.
+pub fn wildcard_match(pattern: &str, text: &str, many: char, one: char) -> bool {
+    let p = pattern.chars().collect::<Vec<_>>();
+    let t = text.chars().collect::<Vec<_>>();
+    let (mut pi, mut ti) = (0, 0);
+    // The last `many` seen, & the text position it's currently matched up to
+    let mut retry = None;
+
+    while ti < t.len() {
+        match p.get(pi) {
+            Some(&c) if c == many => {
+                retry = Some((pi, ti));
+                pi += 1;
+            }
+            Some(&c) if c == one || c == t[ti] => {
+                pi += 1;
+                ti += 1;
+            }
+            _ => match retry {
+                Some((star, matched)) => {
+                    retry = Some((star, matched + 1));
+                    pi = star + 1;
+                    ti = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    p[pi..].iter().all(|&c| c == many)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::utils::{intern, wildcard_match};
+
+    #[test]
+    fn interned_once() {
+        let a = intern("SPELL_DAMAGE");
+        let b = intern(&String::from("SPELL_DAMAGE"));
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(&*a, "SPELL_DAMAGE");
+        assert!(!Arc::ptr_eq(&a, &intern("SWING_DAMAGE")));
+    }
+    #[test]
+    fn wildcards() {
+        let glob = |pattern, name| wildcard_match(pattern, name, '*', '?');
+        assert!(glob("WoWCombatLog*.txt", "WoWCombatLog-041124_213746.txt"));
+        assert!(glob("WoWCombatLog-0?1124*", "WoWCombatLog-041124_213746.txt"));
+        assert!(glob("**", ""));
+        assert!(!glob("WoWCombatLog*.txt", "WoWCombatLog-041124_213746.txt.gz"));
+        assert!(!glob("?", ""));
+
+        // Would take exponential time with backtracking at every *
+        let name = "a".repeat(1000);
+        assert!(!glob(&format!("{}b", "*a".repeat(50)), &name));
+    }
+}
@@ -0,0 +1,49 @@
+//! Browser bindings. Events are handed to JavaScript as JSON, in the same shape as the CLI's outputs.
+
+use wasm_bindgen::prelude::*;
+
+use crate::components::events::Event;
+use crate::error::ParseFailure;
+use crate::parser::{ChunkParser, EventParser, ParseResult};
+
+fn to_json(events: &[Result<Event, ParseFailure>]) -> Result<String, JsError> {
+    let parsed = events.iter().map(ParseResult::from).collect::<Vec<_>>();
+
+    serde_json::to_string(&parsed).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// Parses a single line of a current retail log into an event, as JSON
+#[wasm_bindgen(js_name = parseLine)]
+pub fn parse_line(line: &str) -> Result<String, JsError> {
+    let event = EventParser::new(line.as_bytes())
+        .next()
+        .ok_or_else(|| JsError::new("Empty line"))?
+        .map_err(|e| JsError::new(&e.to_string()))?;
+
+    serde_json::to_string(&event).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// Parses a log a chunk at a time, eg. from a `File.stream()` reader
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct LogParser {
+    inner: ChunkParser,
+}
+
+#[wasm_bindgen]
+impl LogParser {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self { Self::default() }
+
+    /// JSON array of `{"event": ...}` & `{"error": ...}` objects for the complete lines in the chunk.
+    /// A line cut off at the end of the chunk is parsed along with the next one.
+    #[wasm_bindgen(js_name = parseChunk)]
+    pub fn parse_chunk(&mut self, chunk: &str) -> Result<String, JsError> {
+        to_json(&self.inner.push(chunk))
+    }
+
+    /// Parses the last line, if the log didn't end with a newline
+    pub fn finish(&mut self) -> Result<String, JsError> {
+        to_json(&self.inner.finish())
+    }
+}
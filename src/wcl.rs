@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDateTime};
+use serde::Deserialize;
+
+/// Warcraft Logs reports melee swings as this ability ID
+const MELEE_ABILITY_ID: u64 = 1;
+
+const NIL_ACTOR: &str = "0000000000000000,nil,0x80000000,0x80000000";
+
+#[derive(Debug, Deserialize)]
+struct WclEvent {
+    timestamp: i64,
+    #[serde(rename = "type")]
+    event_type: String,
+    #[serde(rename = "sourceID", default = "no_actor")]
+    source_id: i64,
+    #[serde(rename = "targetID", default = "no_actor")]
+    target_id: i64,
+    #[serde(rename = "abilityGameID", default)]
+    ability_id: u64,
+    #[serde(rename = "hitType", default)]
+    hit_type: u64,
+    #[serde(default)]
+    amount: i64,
+    #[serde(rename = "unmitigatedAmount")]
+    unmitigated_amount: Option<i64>,
+    #[serde(default)]
+    overkill: u64,
+    #[serde(default)]
+    overheal: u64,
+    #[serde(default)]
+    absorbed: i64,
+    #[serde(default)]
+    blocked: u64,
+    #[serde(default)]
+    tick: bool,
+    #[serde(rename = "encounterID", default)]
+    encounter_id: u64,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    difficulty: u64,
+    #[serde(default)]
+    size: u64,
+    #[serde(default)]
+    kill: bool,
+    #[serde(rename = "fightTime", default)]
+    fight_time: u64,
+}
+
+fn no_actor() -> i64 { -1 }
+
+#[derive(Debug, Deserialize)]
+struct WclActor {
+    id: i64,
+    name: String,
+    #[serde(rename = "type")]
+    actor_type: String,
+    #[serde(rename = "gameID", default)]
+    game_id: u64,
+    #[serde(rename = "petOwner")]
+    pet_owner: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WclAbility {
+    #[serde(rename = "gameID")]
+    game_id: u64,
+    name: String,
+    #[serde(rename = "type", default = "physical")]
+    school: u64,
+}
+
+fn physical() -> u64 { 1 }
+
+#[derive(Debug, Default, Deserialize)]
+struct MasterData {
+    #[serde(default)]
+    actors: Vec<WclActor>,
+    #[serde(default)]
+    abilities: Vec<WclAbility>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Events {
+    List(Vec<WclEvent>),
+    Paged { data: Vec<WclEvent> },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Export {
+    Events(Events),
+    Report {
+        #[serde(rename = "startTime")]
+        start_time: Option<i64>,
+        #[serde(rename = "masterData", default)]
+        master_data: Box<MasterData>,
+        events: Events,
+    },
+}
+
+/// Combat log lines synthesised from a Warcraft Logs export
+#[derive(Debug)]
+pub struct Conversion {
+    pub lines: Vec<String>,
+    /// WCL event types we don't have a combat log equivalent for
+    pub skipped: HashMap<String, usize>,
+}
+
+struct Converter {
+    start: NaiveDateTime,
+    actors: HashMap<i64, WclActor>,
+    abilities: HashMap<u64, WclAbility>,
+}
+
+impl Converter {
+    fn timestamp(&self, offset_ms: i64) -> String {
+        (self.start + Duration::milliseconds(offset_ms)).format("%-m/%-d %H:%M:%S%.3f").to_string()
+    }
+
+    fn actor(&self, id: i64) -> String {
+        let Some(actor) = self.actors.get(&id) else { return NIL_ACTOR.to_string(); };
+
+        let (guid, flags) = match actor.actor_type.as_str() {
+            "Player" => (format!("Player-0-{:08X}", actor.id), "0x512"),
+            "Pet" => (format!("Pet-0-0-0-0-{}-{:010X}", actor.game_id, actor.id), "0x1112"),
+            _ => (format!("Creature-0-0-0-0-{}-{:010X}", actor.game_id, actor.id), "0xa48"),
+        };
+
+        format!("{},\"{}\",{},0x0", guid, actor.name, flags)
+    }
+
+    fn spell(&self, id: u64) -> String {
+        match self.abilities.get(&id) {
+            Some(a) => format!("{},\"{}\",0x{:x}", a.game_id, a.name, a.school),
+            None => format!("{},\"Unknown\",0x1", id),
+        }
+    }
+
+    fn school(&self, id: u64) -> u64 {
+        self.abilities.get(&id).map_or(1, |a| a.school)
+    }
+
+    /// Summons for every known pet, so owners can be resolved by the consumers
+    fn summons(&self) -> Vec<String> {
+        self.actors.values()
+            .filter_map(|a| a.pet_owner.map(|owner| (owner, a.id)))
+            .map(|(owner, pet)| format!("{}  SPELL_SUMMON,{},{},0,\"Summon\",0x1",
+                                        self.timestamp(0), self.actor(owner), self.actor(pet)))
+            .collect()
+    }
+
+    fn convert(&self, e: &WclEvent) -> Option<String> {
+        let actors = format!("{},{}", self.actor(e.source_id), self.actor(e.target_id));
+        let periodic = if e.tick { "PERIODIC_" } else { "" };
+
+        let body = match e.event_type.as_str() {
+            "damage" => {
+                let overkill = if e.overkill == 0 { "-1".to_string() } else { e.overkill.to_string() };
+                let suffix = format!("{},{},{},0x{:x},0,{},{},{},0,nil,nil",
+                                     e.amount, e.unmitigated_amount.unwrap_or(e.amount), overkill, self.school(e.ability_id),
+                                     e.blocked, e.absorbed, (e.hit_type == 2) as u8);
+                if e.ability_id == MELEE_ABILITY_ID {
+                    format!("SWING_DAMAGE,{},{}", actors, suffix)
+                } else {
+                    format!("SPELL_{}DAMAGE,{},{},{}", periodic, actors, self.spell(e.ability_id), suffix)
+                }
+            }
+            "heal" => format!("SPELL_{}HEAL,{},{},{},{},{},{},{}",
+                              periodic, actors, self.spell(e.ability_id),
+                              e.amount + e.overheal as i64, e.amount + e.overheal as i64, e.overheal, e.absorbed, (e.hit_type == 2) as u8),
+            "begincast" => format!("SPELL_CAST_START,{},{}", actors, self.spell(e.ability_id)),
+            "cast" => format!("SPELL_CAST_SUCCESS,{},{}", actors, self.spell(e.ability_id)),
+            "applybuff" => format!("SPELL_AURA_APPLIED,{},{},BUFF", actors, self.spell(e.ability_id)),
+            "applydebuff" => format!("SPELL_AURA_APPLIED,{},{},DEBUFF", actors, self.spell(e.ability_id)),
+            "removebuff" => format!("SPELL_AURA_REMOVED,{},{},BUFF", actors, self.spell(e.ability_id)),
+            "removedebuff" => format!("SPELL_AURA_REMOVED,{},{},DEBUFF", actors, self.spell(e.ability_id)),
+            "death" => format!("UNIT_DIED,{},{},0", NIL_ACTOR, self.actor(e.target_id)),
+            "encounterstart" => format!("ENCOUNTER_START,{},\"{}\",{},{},0",
+                                        e.encounter_id, e.name, e.difficulty, e.size),
+            "encounterend" => format!("ENCOUNTER_END,{},\"{}\",{},{},{},{}",
+                                      e.encounter_id, e.name, e.difficulty, e.size, e.kill as u8, e.fight_time),
+            _ => return None
+        };
+
+        Some(format!("{}  {}", self.timestamp(e.timestamp), body))
+    }
+}
+
+/// Converts events returned by the Warcraft Logs API into synthetic combat log lines.
+/// Accepts either a bare list of events, or an object with `events` alongside the report's `startTime` & `masterData`.
+/// The log is written with advanced logging disabled as WCL doesn't export those fields.
+pub fn convert(json: &str) -> Result<Conversion> {
+    let export: Export = serde_json::from_str(json)
+        .context("Failed to read Warcraft Logs export")?;
+
+    let (start_time, master_data, events) = match export {
+        Export::Events(events) => (None, MasterData::default(), events),
+        Export::Report { start_time, master_data, events } => (start_time, *master_data, events),
+    };
+    let events = match events {
+        Events::List(e) | Events::Paged { data: e } => e
+    };
+
+    let converter = Converter {
+        start: start_time
+            .and_then(DateTime::from_timestamp_millis)
+            .map_or_else(|| NaiveDateTime::parse_from_str("2024/01/01 00:00:00.000", "%Y/%m/%d %H:%M:%S%.3f").unwrap(),
+                         |t| t.naive_utc()),
+        actors: master_data.actors.into_iter().map(|a| (a.id, a)).collect(),
+        abilities: master_data.abilities.into_iter().map(|a| (a.game_id, a)).collect(),
+    };
+
+    let mut lines = vec![format!("{}  COMBAT_LOG_VERSION,20,ADVANCED_LOG_ENABLED,0,BUILD_VERSION,10.2.6,PROJECT_ID,1", converter.timestamp(0))];
+    lines.extend(converter.summons());
+
+    let mut skipped = HashMap::new();
+    for event in &events {
+        match converter.convert(event) {
+            Some(line) => lines.push(line),
+            None => *skipped.entry(event.event_type.clone()).or_default() += 1,
+        }
+    }
+
+    Ok(Conversion { lines, skipped })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::consumers::{DamageTracker, dispatch, EventHandler};
+    use crate::parser::EventParser;
+    use crate::wcl::convert;
+
+    const EXPORT: &str = r#"{
+        "startTime": 1707854052865,
+        "masterData": {
+            "actors": [
+                {"id": 1, "name": "Sangrenar", "type": "Player", "gameID": 0},
+                {"id": 2, "name": "Fyrakk", "type": "NPC", "gameID": 204931},
+                {"id": 3, "name": "Dreadstalker", "type": "Pet", "gameID": 98035, "petOwner": 1}
+            ],
+            "abilities": [
+                {"gameID": 203796, "name": "Demon Blades", "type": 32},
+                {"gameID": 8936, "name": "Regrowth", "type": 8}
+            ]
+        },
+        "events": {"data": [
+            {"timestamp": 0, "type": "encounterstart", "encounterID": 2677, "name": "Fyrakk", "difficulty": 16, "size": 20},
+            {"timestamp": 10, "type": "begincast", "sourceID": 1, "targetID": -1, "abilityGameID": 8936},
+            {"timestamp": 1510, "type": "cast", "sourceID": 1, "targetID": 1, "abilityGameID": 8936},
+            {"timestamp": 1600, "type": "heal", "sourceID": 1, "targetID": 1, "abilityGameID": 8936, "hitType": 2, "amount": 750, "overheal": 250},
+            {"timestamp": 2000, "type": "damage", "sourceID": 1, "targetID": 2, "abilityGameID": 203796, "hitType": 1, "amount": 16857, "unmitigatedAmount": 6079},
+            {"timestamp": 2100, "type": "damage", "sourceID": 3, "targetID": 2, "abilityGameID": 1, "hitType": 1, "amount": 500, "absorbed": 10},
+            {"timestamp": 3000, "type": "applydebuff", "sourceID": 2, "targetID": 1, "abilityGameID": 8936},
+            {"timestamp": 4000, "type": "death", "sourceID": -1, "targetID": 1},
+            {"timestamp": 4000, "type": "combatantinfo", "sourceID": 1},
+            {"timestamp": 5000, "type": "encounterend", "encounterID": 2677, "name": "Fyrakk", "difficulty": 16, "size": 20, "kill": false, "fightTime": 5000}
+        ]}
+    }"#;
+
+    #[test]
+    fn convert_export() {
+        let conversion = convert(EXPORT).unwrap();
+        assert_eq!(conversion.skipped["combatantinfo"], 1);
+
+        let log = conversion.lines.join("\n");
+        let events = EventParser::new(log.as_bytes()).collect::<Vec<_>>();
+        events.iter().for_each(|e| assert!(e.is_ok(), "{:?}", e));
+        assert_eq!(events.len(), conversion.lines.len());
+    }
+
+    #[test]
+    fn pets_attributed() {
+        let log = convert(EXPORT).unwrap().lines.join("\n");
+
+        let mut handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(DamageTracker::new())];
+        EventParser::new(log.as_bytes()).for_each(|e| dispatch(&mut handlers, &e));
+
+        assert!(handlers[0].display().unwrap().contains(&format!("{:>10}", 16857 + 500)));
+    }
+
+    #[test]
+    fn bare_events() {
+        let conversion = convert(r#"[{"timestamp": 0, "type": "cast", "sourceID": -1, "targetID": -1, "abilityGameID": 1850}]"#).unwrap();
+        assert_eq!(conversion.lines.len(), 2);
+    }
+}